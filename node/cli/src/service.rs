@@ -29,7 +29,7 @@ use fc_consensus::FrontierBlockImport;
 use sc_client_api::BlockBackend;
 use fc_rpc_core::types::{FeeHistoryCache, FilterPool};
 use futures::prelude::*;
-use sc_consensus_aura::{self, CompatibilityMode, ImportQueueParams, SlotProportion, StartAuraParams};
+use sc_consensus_aura::{self, CompatibilityMode, ImportQueueParamsBuilder, StartAuraParamsBuilder};
 use sc_network::{Event, NetworkService};
 use sc_service::{config::{Configuration, /*PrometheusConfig*/}, error::Error as ServiceError, RpcHandlers,BasePath, ChainSpec, TaskManager};
 use sc_telemetry::{Telemetry, TelemetryWorker, TelemetryWorkerHandle};
@@ -151,7 +151,7 @@ pub fn new_partial(
 		FullClient,
 		FullBackend,
 		FullSelectChain,
-		sc_consensus::DefaultImportQueue<Block, FullClient>,
+		sc_consensus_aura::AuraImportQueue<Block, FullClient>,
 		sc_transaction_pool::FullPool<Block, FullClient>,
 		(
 			ConsensusResult,
@@ -233,43 +233,52 @@ pub fn new_partial(
 	let slot_duration = sc_consensus_aura::slot_duration(&*client)?;//.as_duration();
 	let target_gas_price = U256::from(cli.run.target_gas_price);
 
+	#[cfg(feature = "frontier-block-import")]
+	let aura_block_import = frontier_block_import.clone();
+	#[cfg(not(feature = "frontier-block-import"))]
+	let aura_block_import = grandpa_block_import.clone();
+	#[cfg(feature = "beresheet-runtime")]
+	let compatibility_mode = CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(8888u32) };
+	#[cfg(not(feature = "beresheet-runtime"))]
+	let compatibility_mode = CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(14_555_555u32) };
+
+	let spawner = task_manager.spawn_essential_handle();
+	let mut import_queue_params = ImportQueueParamsBuilder::new(
+		aura_block_import,
+		client.clone(),
+		move |_, ()| async move {
+			let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+
+			let slot =
+				sp_consensus_aura::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
+					*timestamp,
+					slot_duration,
+				);
+
+//			let uncles =
+//				sp_authorship::InherentDataProvider::<<Block as BlockT>::Header>::check_inherents();
+
+			let dynamic_fee =
+				pallet_dynamic_fee::InherentDataProvider(U256::from(target_gas_price));
+
+			Ok((timestamp, slot, /*uncles,*/ dynamic_fee))
+		},
+		&spawner,
+		sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()),
+	)
+	.justification_import(Box::new(justification_import))
+	.compatibility_mode(compatibility_mode);
+	if let Some(registry) = config.prometheus_registry() {
+		import_queue_params = import_queue_params.registry(registry);
+	}
+	if let Some(telemetry) = telemetry.as_ref() {
+		import_queue_params = import_queue_params.telemetry(telemetry.handle());
+	}
+
 	let import_queue =
 		sc_consensus_aura::import_queue::<sp_consensus_aura::ed25519::AuthorityPair, _, _, _, _, _, _>(
-	    ImportQueueParams {
-		    #[cfg(feature = "frontier-block-import")]
-		    block_import: frontier_block_import.clone(),
-		    #[cfg(not(feature = "frontier-block-import"))]
-		    block_import: grandpa_block_import.clone(),
-		    justification_import: Some(Box::new(justification_import)),
-		    client: client.clone(),
-		    create_inherent_data_providers: move |_, ()| async move {
-			    let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
-
-			    let slot =
-				    sp_consensus_aura::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
-					    *timestamp,
-					    slot_duration,
-				    );
-
-    //			let uncles =
-    //				sp_authorship::InherentDataProvider::<<Block as BlockT>::Header>::check_inherents();
-
-			    let dynamic_fee =
-				    pallet_dynamic_fee::InherentDataProvider(U256::from(target_gas_price));
-
-			    Ok((timestamp, slot, /*uncles,*/ dynamic_fee))
-		    },
-		    spawner: &task_manager.spawn_essential_handle(),
-		    registry: config.prometheus_registry(),
-		    can_author_with: sp_consensus::CanAuthorWithNativeVersion::new(client.executor().clone()),
-		    check_for_equivocation: Default::default(),
-		    telemetry: telemetry.as_ref().map(|x| x.handle()),
-		    #[cfg(feature = "beresheet-runtime")]
-			compatibility_mode: CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(8888u32) },
-		    #[cfg(not(feature = "beresheet-runtime"))]
-			compatibility_mode: CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(14_555_555u32) },
-		}
-	)?;
+			import_queue_params.build(),
+		)?;
 
     #[cfg(feature = "frontier-block-import")]
     let import_setup = (frontier_block_import.clone(), grandpa_link);
@@ -519,46 +528,54 @@ pub fn new_full_base(mut config: Configuration,
 		let raw_slot_duration: sc_consensus_aura::SlotDuration = slot_duration.clone();
 		let target_gas_price = U256::from(cli.run.target_gas_price);
 
-		let aura = sc_consensus_aura::start_aura::<sp_consensus_aura::ed25519::AuthorityPair, _, _, _, _, _, _, _, _, _, _, _>(
-			StartAuraParams {
-				slot_duration: slot_duration,
-				client: client.clone(),
-				select_chain: select_chain,
-				block_import: block_import,
-				proposer_factory: proposer_factory,
-				sync_oracle: network.clone(),
-				justification_sync_link: network.clone(),
-				create_inherent_data_providers: move |_, ()| async move {
-					let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
-
-					let slot =
-						sp_consensus_aura::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
-							*timestamp,
-							raw_slot_duration,
-						);
-
-//					let uncles =
-//						sp_authorship::InherentDataProvider::<<Block as BlockT>::Header>::check_inherents();
-
-					let dynamic_fee =
-						pallet_dynamic_fee::InherentDataProvider(U256::from(target_gas_price));
-
-					Ok((timestamp, slot, /*uncles,*/ dynamic_fee))
-				},
+		#[cfg(feature = "beresheet-runtime")]
+		let compatibility_mode = CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(8888u32) };
+		#[cfg(not(feature = "beresheet-runtime"))]
+		let compatibility_mode = CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(14_555_555u32) };
+
+		let mut aura_params = StartAuraParamsBuilder::new(
+			slot_duration,
+			client.clone(),
+			select_chain,
+			block_import,
+			proposer_factory,
+			network.clone(),
+			network.clone(),
+			move |_, ()| async move {
+				let timestamp = sp_timestamp::InherentDataProvider::from_system_time();
+
+				let slot =
+					sp_consensus_aura::inherents::InherentDataProvider::from_timestamp_and_slot_duration(
+						*timestamp,
+						raw_slot_duration,
+					);
+
+//				let uncles =
+//					sp_authorship::InherentDataProvider::<<Block as BlockT>::Header>::check_inherents();
 
-				force_authoring: force_authoring,
-				backoff_authoring_blocks: backoff_authoring_blocks,
-				keystore: keystore_container.sync_keystore(),
-				can_author_with: can_author_with,
-				block_proposal_slot_portion: SlotProportion::new(2f32 / 3f32),
-				max_block_proposal_slot_portion: None,
-				telemetry: telemetry.as_ref().map(|x| x.handle()),
-				#[cfg(feature = "beresheet-runtime")]
-				compatibility_mode: CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(8888u32) },
-				#[cfg(not(feature = "beresheet-runtime"))]
-				compatibility_mode: CompatibilityMode::UseInitializeBlock { until: BlockNumber::from(14_555_555u32) },
+				let dynamic_fee =
+					pallet_dynamic_fee::InherentDataProvider(U256::from(target_gas_price));
+
+				Ok((timestamp, slot, /*uncles,*/ dynamic_fee))
 			},
-		)?;
+			keystore_container.sync_keystore(),
+			can_author_with,
+		)
+		.force_authoring(force_authoring)
+		.compatibility_mode(compatibility_mode);
+		if let Some(backoff_authoring_blocks) = backoff_authoring_blocks {
+			aura_params = aura_params.backoff_authoring_blocks(backoff_authoring_blocks);
+		}
+		if let Some(telemetry) = telemetry.as_ref() {
+			aura_params = aura_params.telemetry(telemetry.handle());
+		}
+		if let Some(prometheus_registry) = prometheus_registry.clone() {
+			aura_params = aura_params.prometheus_registry(prometheus_registry);
+		}
+
+		let (aura, _aura_control, _slot_tracker) = sc_consensus_aura::start_aura::<sp_consensus_aura::ed25519::AuthorityPair, _, _, _, _, _, _, _, _, _, _, _, _>(
+			aura_params.build(),
+		).map_err(sp_consensus::Error::from)?;
 
 		// the AURA authoring task is considered essential, i.e. if it
 		// fails we take down the service with it.
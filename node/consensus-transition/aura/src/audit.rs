@@ -0,0 +1,301 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Audit of the blocks of a chain against the Aura authoring schedule.
+
+use crate::{
+	authorities, find_pre_digest_with_engine_id, seal_message, slot_author_weighted,
+	strip_seal_with_engine_id, AuraApiAuthorities, AuthorityId, AuthorityWeights,
+	CompatibilityMode,
+};
+use codec::Codec;
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus::Error as ConsensusError;
+use sp_consensus_aura::AuraApi;
+use sp_consensus_slots::Slot;
+use sp_core::crypto::Pair;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header, NumberFor, Zero},
+	ConsensusEngineId,
+};
+use std::fmt::Debug;
+
+/// A block whose seal does not match the authoring schedule, found by [`audit_authoring`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthoringDiscrepancy<H, N, A> {
+	/// The block has no valid Aura pre-runtime digest or seal.
+	Undecodable {
+		/// Hash of the block.
+		hash: H,
+		/// Number of the block.
+		number: N,
+		/// Why the digests could not be decoded.
+		reason: String,
+	},
+	/// The seal is not signed by the expected author of the block's slot.
+	WrongAuthor {
+		/// Hash of the block.
+		hash: H,
+		/// Number of the block.
+		number: N,
+		/// Slot of the block.
+		slot: Slot,
+		/// The author the schedule assigns to the slot, if the authority set is not empty.
+		expected: Option<A>,
+		/// The authority that signed the seal, if it is part of the authority set at all.
+		signer: Option<A>,
+	},
+}
+
+/// Replay the authoring schedule over the blocks `from..=to` of the canonical chain.
+///
+/// For each block the expected author of its slot is computed from the authority set at its
+/// parent and compared with the signer of its seal. On a correct chain no discrepancy is ever
+/// returned, so this gives a definitive audit that authoring followed the round-robin
+/// schedule, e.g. after investigating a consensus bug. The genesis block is skipped, as it is
+/// not sealed.
+///
+/// Use the same `compatibility_mode` and `weights` as the import queue, otherwise blocks around
+/// authority set changes, respectively all blocks of weighted authors, are reported. The
+/// pre-runtime digests and seals are read under `engine_id`, see
+/// [`BuildAuraWorkerParams::engine_id`](crate::BuildAuraWorkerParams::engine_id).
+pub fn audit_authoring<P, B, C>(
+	client: &C,
+	from: NumberFor<B>,
+	to: NumberFor<B>,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+	weights: Option<&AuthorityWeights>,
+	engine_id: ConsensusEngineId,
+) -> Result<Vec<AuthoringDiscrepancy<B::Hash, NumberFor<B>, AuthorityId<P>>>, ConsensusError>
+where
+	P: Pair,
+	P::Public: Codec + Debug,
+	P::Signature: Codec,
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+{
+	let mut discrepancies = Vec::new();
+	let mut number = from;
+
+	while number <= to {
+		let header = client
+			.header(BlockId::Number(number))
+			.map_err(|e| ConsensusError::ChainLookup(e.to_string()))?
+			.ok_or_else(|| ConsensusError::ChainLookup(format!("Unknown block #{:?}", number)))?;
+
+		if !header.number().is_zero() {
+			let authorities = authorities(
+				client,
+				&AuraApiAuthorities,
+				*header.parent_hash(),
+				number,
+				compatibility_mode,
+			)?;
+			discrepancies.extend(audit_header::<P, B>(
+				&header,
+				&authorities,
+				weights,
+				compatibility_mode,
+				engine_id,
+			));
+		}
+
+		number += 1u32.into();
+	}
+
+	Ok(discrepancies)
+}
+
+/// Check that `header` is sealed by the expected author among `authorities`.
+fn audit_header<P, B>(
+	header: &B::Header,
+	authorities: &[AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+	engine_id: ConsensusEngineId,
+) -> Option<AuthoringDiscrepancy<B::Hash, NumberFor<B>, AuthorityId<P>>>
+where
+	P: Pair,
+	P::Signature: Codec,
+	B: BlockT,
+{
+	let hash = header.hash();
+	let number = *header.number();
+
+	let (pre_header, signature) =
+		match strip_seal_with_engine_id::<B, P::Signature>(header, engine_id) {
+			(pre_header, Some(signature)) => (pre_header, signature),
+			(_, None) => {
+				let reason = "no Aura seal".into();
+				return Some(AuthoringDiscrepancy::Undecodable { hash, number, reason })
+			},
+		};
+	let slot = match find_pre_digest_with_engine_id::<B>(&pre_header, engine_id) {
+		Ok(slot) => slot,
+		Err(e) => {
+			let reason = e.to_string();
+			return Some(AuthoringDiscrepancy::Undecodable { hash, number, reason })
+		},
+	};
+
+	let message = seal_message::<B>(number, slot, &pre_header.hash(), compatibility_mode);
+	let expected = slot_author_weighted::<P>(slot, authorities, weights);
+	if expected.map_or(false, |author| P::verify(&signature, &message, author)) {
+		return None
+	}
+
+	Some(AuthoringDiscrepancy::WrongAuthor {
+		hash,
+		number,
+		slot,
+		expected: expected.cloned(),
+		signer: authorities
+			.iter()
+			.find(|authority| P::verify(&signature, &message, authority))
+			.cloned(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::Encode;
+	use sp_consensus_aura::{
+		digests::CompatibleDigestItem,
+		sr25519::{AuthorityPair, AuthoritySignature},
+		AURA_ENGINE_ID,
+	};
+	use sp_runtime::{Digest, DigestItem};
+	use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+	fn pair(seed: &str) -> AuthorityPair {
+		AuthorityPair::from_string(seed, None).expect("Valid seed")
+	}
+
+	const MODE: CompatibilityMode<u64> = CompatibilityMode::None;
+
+	fn audit(
+		header: &TestHeader,
+		authorities: &[AuthorityId<AuthorityPair>],
+		mode: &CompatibilityMode<u64>,
+	) -> Option<AuthoringDiscrepancy<sp_core::H256, u64, AuthorityId<AuthorityPair>>> {
+		audit_header::<AuthorityPair, Block>(header, authorities, None, mode, AURA_ENGINE_ID)
+	}
+
+	fn sealed_header(pair: &AuthorityPair, slot: u64) -> TestHeader {
+		let mut header = TestHeader::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest {
+				logs: vec![<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(
+					slot.into(),
+				)],
+			},
+		);
+		let signature = pair.sign(header.hash().as_ref());
+		header
+			.digest_mut()
+			.push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature));
+		header
+	}
+
+	#[test]
+	fn block_of_scheduled_author_passes_audit() {
+		let (alice, bob) = (pair("//Alice"), pair("//Bob"));
+		let authorities = vec![alice.public(), bob.public()];
+
+		for (author, slot) in [(&alice, 4), (&bob, 5), (&alice, 6)] {
+			let header = sealed_header(author, slot);
+			assert_eq!(audit(&header, &authorities, &MODE), None);
+		}
+	}
+
+	#[test]
+	fn block_of_other_author_is_flagged() {
+		let (alice, bob, charlie) = (pair("//Alice"), pair("//Bob"), pair("//Charlie"));
+		let authorities = vec![alice.public(), bob.public()];
+
+		let header = sealed_header(&bob, 4);
+		assert_eq!(
+			audit(&header, &authorities, &MODE),
+			Some(AuthoringDiscrepancy::WrongAuthor {
+				hash: header.hash(),
+				number: 1,
+				slot: 4.into(),
+				expected: Some(alice.public()),
+				signer: Some(bob.public()),
+			}),
+		);
+
+		let header = sealed_header(&charlie, 4);
+		assert!(matches!(
+			audit(&header, &authorities, &MODE),
+			Some(AuthoringDiscrepancy::WrongAuthor { signer: None, .. }),
+		));
+	}
+
+	#[test]
+	fn block_in_legacy_seal_format_is_audited_in_that_format() {
+		let alice = pair("//Alice");
+		let mut header = sealed_header(&alice, 4);
+		header.digest_mut().pop();
+		let signature = alice.sign(&(Slot::from(4), header.hash()).encode());
+		header
+			.digest_mut()
+			.push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature));
+
+		let legacy = CompatibilityMode::LegacySealFormat { until: 2 };
+		let authorities = vec![alice.public()];
+		assert_eq!(audit(&header, &authorities, &legacy), None);
+		assert!(matches!(
+			audit(&header, &authorities, &MODE),
+			Some(AuthoringDiscrepancy::WrongAuthor { .. }),
+		));
+
+		let switched = CompatibilityMode::LegacySealFormat { until: 1 };
+		assert!(audit(&header, &authorities, &switched).is_some());
+	}
+
+	#[test]
+	fn unsealed_block_is_flagged() {
+		let alice = pair("//Alice");
+		let mut header = sealed_header(&alice, 4);
+		header.digest_mut().pop();
+
+		assert!(matches!(
+			audit(&header, &[alice.public()], &MODE),
+			Some(AuthoringDiscrepancy::Undecodable { .. }),
+		));
+	}
+
+	#[test]
+	fn blocks_are_audited_under_the_given_engine_id() {
+		let alice = pair("//Alice");
+		let header = sealed_header(&alice, 4);
+
+		assert!(matches!(
+			audit_header::<AuthorityPair, Block>(&header, &[alice.public()], None, &MODE, *b"fork"),
+			Some(AuthoringDiscrepancy::Undecodable { .. }),
+		));
+	}
+}
@@ -0,0 +1,270 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Notifications of the blocks authored by this node.
+
+use codec::Encode;
+use futures::{prelude::*, task::AtomicWaker};
+use log::debug;
+use parking_lot::Mutex;
+use sc_consensus_slots::{SlotInfo, SlotResult, SlotWorker};
+use sp_consensus_slots::Slot;
+use sp_runtime::traits::Block as BlockT;
+use std::{
+	collections::VecDeque,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+};
+
+/// A block authored by this node, as sent through [`AuthoredBlocks`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthoredBlockInfo<H> {
+	/// The hash of the sealed block.
+	pub hash: H,
+	/// The slot the block was authored in.
+	pub slot: Slot,
+	/// The index of the author in the authority set, if known.
+	pub authority_index: Option<usize>,
+	/// The Unix timestamp in milliseconds at which the block was sealed.
+	pub timestamp: u64,
+	/// The SCALE encoded `Proposer::Proof` of the block.
+	///
+	/// Only present if the proposer produces a proof, i.e. records one, and the block got
+	/// imported. Proposers not recording a proof have the proof `()`, which encodes to nothing
+	/// and is left out.
+	pub proof: Option<Vec<u8>>,
+}
+
+/// Handle the worker sends the blocks it authored through.
+///
+/// See [`BuildAuraWorkerParams::authored_blocks`](crate::BuildAuraWorkerParams).
+pub struct AuthoredBlocks<H> {
+	shared: Arc<Shared<H>>,
+}
+
+/// Stream of the blocks sent through [`AuthoredBlocks`].
+///
+/// The stream ends once all handles are dropped and the buffered blocks are taken.
+pub struct AuthoredBlocksStream<H> {
+	shared: Arc<Shared<H>>,
+}
+
+struct Shared<H> {
+	queue: Mutex<VecDeque<AuthoredBlockInfo<H>>>,
+	/// The sealed block waiting for its proof, see [`AuthoredBlocks::stage`].
+	staged: Mutex<Option<AuthoredBlockInfo<H>>>,
+	capacity: usize,
+	waker: AtomicWaker,
+	senders: Mutex<usize>,
+}
+
+impl<H> AuthoredBlocks<H> {
+	/// Create a handle along with the stream of the blocks sent through it.
+	///
+	/// The stream buffers at most `capacity` blocks. Sending never waits for the stream to be
+	/// polled: once the buffer is full, the oldest block is dropped to make room.
+	pub fn new(capacity: usize) -> (Self, AuthoredBlocksStream<H>) {
+		let shared = Arc::new(Shared {
+			queue: Mutex::new(VecDeque::with_capacity(capacity)),
+			staged: Mutex::new(None),
+			capacity: capacity.max(1),
+			waker: AtomicWaker::new(),
+			senders: Mutex::new(1),
+		});
+		(Self { shared: shared.clone() }, AuthoredBlocksStream { shared })
+	}
+
+	/// Send `block` to the stream, dropping the oldest buffered block if the buffer is full.
+	pub(crate) fn note(&self, block: AuthoredBlockInfo<H>) {
+		let mut queue = self.shared.queue.lock();
+		if queue.len() >= self.shared.capacity {
+			queue.pop_front();
+			debug!(target: "aura", "Dropped the oldest authored block, the consumer is too slow.");
+		}
+		queue.push_back(block);
+		drop(queue);
+
+		self.shared.waker.wake();
+	}
+
+	/// Hold back the sealed `block` until its proof is known, see [`Self::send_staged`].
+	pub(crate) fn stage(&self, block: AuthoredBlockInfo<H>) {
+		*self.shared.staged.lock() = Some(block);
+	}
+
+	/// Send the staged block, if any, along with its encoded `proof`.
+	pub(crate) fn send_staged(&self, proof: Option<Vec<u8>>) {
+		let staged = self.shared.staged.lock().take();
+		if let Some(block) = staged {
+			self.note(AuthoredBlockInfo { proof, ..block });
+		}
+	}
+}
+
+impl<H> Clone for AuthoredBlocks<H> {
+	fn clone(&self) -> Self {
+		*self.shared.senders.lock() += 1;
+		Self { shared: self.shared.clone() }
+	}
+}
+
+impl<H> Drop for AuthoredBlocks<H> {
+	fn drop(&mut self) {
+		let mut senders = self.shared.senders.lock();
+		*senders -= 1;
+		if *senders == 0 {
+			self.shared.waker.wake();
+		}
+	}
+}
+
+impl<H> Stream for AuthoredBlocksStream<H> {
+	type Item = AuthoredBlockInfo<H>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		self.shared.waker.register(cx.waker());
+
+		if let Some(block) = self.shared.queue.lock().pop_front() {
+			return Poll::Ready(Some(block))
+		}
+		if *self.shared.senders.lock() == 0 {
+			return Poll::Ready(None)
+		}
+
+		Poll::Pending
+	}
+}
+
+/// Slot worker sending the blocks staged by `worker` through `authored_blocks` once the slot is
+/// over, along with the proof of the proposal.
+pub(crate) struct ProofNotifyingWorker<W, H> {
+	pub worker: W,
+	pub authored_blocks: Option<AuthoredBlocks<H>>,
+}
+
+#[async_trait::async_trait]
+impl<B, Proof, W> SlotWorker<B, Proof> for ProofNotifyingWorker<W, B::Hash>
+where
+	B: BlockT,
+	Proof: Encode + Send + 'static,
+	W: SlotWorker<B, Proof> + Send,
+{
+	async fn on_slot(&mut self, slot_info: SlotInfo<B>) -> Option<SlotResult<B, Proof>> {
+		let result = self.worker.on_slot(slot_info).await;
+		if let Some(authored_blocks) = &self.authored_blocks {
+			let proof = result
+				.as_ref()
+				.map(|result| result.storage_proof.encode())
+				.filter(|proof| !proof.is_empty());
+			authored_blocks.send_staged(proof);
+		}
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use sp_core::H256;
+	use std::time::{Duration, Instant};
+	use substrate_test_runtime_client::runtime::{Block, Header};
+
+	fn block(slot: u64) -> AuthoredBlockInfo<u64> {
+		AuthoredBlockInfo {
+			hash: slot,
+			slot: slot.into(),
+			authority_index: Some(0),
+			timestamp: 0,
+			proof: None,
+		}
+	}
+
+	#[test]
+	fn slow_consumers_miss_the_oldest_blocks() {
+		let (handle, stream) = AuthoredBlocks::new(2);
+		for slot in 1..=4 {
+			handle.note(block(slot));
+		}
+		drop(handle);
+
+		let slots = block_on(stream.map(|block| *block.slot).collect::<Vec<_>>());
+		assert_eq!(slots, vec![3, 4]);
+	}
+
+	/// Slot worker staging a block in every slot, which gets imported with `proof` if given.
+	struct StagingWorker<P> {
+		authored_blocks: AuthoredBlocks<H256>,
+		proof: Option<P>,
+	}
+
+	#[async_trait::async_trait]
+	impl<P: Clone + Send + 'static> SlotWorker<Block, P> for StagingWorker<P> {
+		async fn on_slot(&mut self, slot_info: SlotInfo<Block>) -> Option<SlotResult<Block, P>> {
+			let hash = H256::from_low_u64_be(*slot_info.slot);
+			self.authored_blocks.stage(AuthoredBlockInfo {
+				hash,
+				slot: slot_info.slot,
+				authority_index: Some(0),
+				timestamp: 0,
+				proof: None,
+			});
+			let block = Block { header: slot_info.chain_head, extrinsics: Vec::new() };
+			self.proof.clone().map(|storage_proof| SlotResult { block, storage_proof })
+		}
+	}
+
+	/// The proofs sent along with the block authored in one slot by a worker importing it with
+	/// `proof`.
+	fn sent_proof<P: Clone + Encode + Send + 'static>(proof: Option<P>) -> Option<Vec<u8>> {
+		let (handle, mut stream) = AuthoredBlocks::new(1);
+		let mut worker = ProofNotifyingWorker {
+			worker: StagingWorker { authored_blocks: handle.clone(), proof },
+			authored_blocks: Some(handle),
+		};
+		let slot_info = SlotInfo::<Block> {
+			slot: 1.into(),
+			timestamp: Default::default(),
+			ends_at: Instant::now() + Duration::from_secs(3600),
+			inherent_data: Default::default(),
+			duration: Duration::from_millis(1000),
+			chain_head: Header::new(
+				0,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			),
+			block_size_limit: None,
+		};
+		block_on(worker.on_slot(slot_info));
+
+		let block = block_on(stream.next()).expect("The staged block is sent");
+		assert_eq!(block.hash, H256::from_low_u64_be(1));
+		block.proof
+	}
+
+	#[test]
+	fn authored_blocks_are_sent_with_the_proof_of_their_proposal() {
+		assert_eq!(sent_proof(Some(vec![1u8, 2, 3])), Some(vec![1, 2, 3].encode()));
+		// proposers not recording a proof, and blocks failing to import
+		assert_eq!(sent_proof(Some(())), None);
+		assert_eq!(sent_proof::<Vec<u8>>(None), None);
+	}
+}
@@ -0,0 +1,173 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Retrying a failed fetch of the authority set within the slot.
+//!
+//! See [`BuildAuraWorkerParams::authorities_retry`](crate::BuildAuraWorkerParams).
+
+use crate::AuraMetrics;
+use log::debug;
+use sc_consensus_slots::{SlotInfo, SlotResult, SlotWorker};
+use sp_runtime::traits::{Block as BlockT, Header};
+use std::{
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant},
+};
+
+/// Delay before the authorities are fetched again after a failure.
+const AUTHORITIES_RETRY_DELAY: Duration = Duration::from_millis(50);
+
+/// Raised by the worker when fetching the authority set of a slot failed.
+#[derive(Clone, Default)]
+pub(crate) struct AuthoritiesFetchFailed(Arc<AtomicBool>);
+
+impl AuthoritiesFetchFailed {
+	/// Note that fetching the authority set failed.
+	pub(crate) fn raise(&self) {
+		self.0.store(true, Ordering::Relaxed);
+	}
+
+	/// Whether fetching the authority set failed since the last call.
+	fn take(&self) -> bool {
+		self.0.swap(false, Ordering::Relaxed)
+	}
+}
+
+/// Slot worker running a slot once more after a short delay, if `worker` failed to fetch its
+/// authority set.
+///
+/// The delay is awaited, so the executor is not blocked while waiting for the runtime to
+/// recover.
+pub(crate) struct AuthoritiesRetryingWorker<W> {
+	pub worker: W,
+	pub fetch_failed: Option<AuthoritiesFetchFailed>,
+	pub metrics: Option<AuraMetrics>,
+}
+
+#[async_trait::async_trait]
+impl<B, Proof, W> SlotWorker<B, Proof> for AuthoritiesRetryingWorker<W>
+where
+	B: BlockT,
+	Proof: Send + 'static,
+	W: SlotWorker<B, Proof> + Send,
+{
+	async fn on_slot(&mut self, slot_info: SlotInfo<B>) -> Option<SlotResult<B, Proof>> {
+		let fetch_failed = match self.fetch_failed.clone() {
+			Some(fetch_failed) => fetch_failed,
+			None => return self.worker.on_slot(slot_info).await,
+		};
+
+		let retry_info = SlotInfo {
+			slot: slot_info.slot,
+			timestamp: slot_info.timestamp,
+			ends_at: slot_info.ends_at,
+			inherent_data: slot_info.inherent_data.clone(),
+			duration: slot_info.duration,
+			chain_head: slot_info.chain_head.clone(),
+			block_size_limit: slot_info.block_size_limit,
+		};
+		fetch_failed.take();
+		let result = self.worker.on_slot(slot_info).await;
+		if result.is_some() || !fetch_failed.take() {
+			return result
+		}
+		if retry_info.ends_at <= Instant::now() + AUTHORITIES_RETRY_DELAY {
+			return None
+		}
+
+		debug!(
+			target: "aura",
+			"Fetching the authorities at {:?} failed, retrying in {:?}.",
+			retry_info.chain_head.hash(),
+			AUTHORITIES_RETRY_DELAY,
+		);
+		if let Some(metrics) = self.metrics.as_ref() {
+			metrics.authorities_retries.inc();
+		}
+		futures_timer::Delay::new(AUTHORITIES_RETRY_DELAY).await;
+
+		let result = self.worker.on_slot(retry_info).await;
+		fetch_failed.take();
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+	/// Slot worker failing to fetch the authorities in the first `failures` slots it runs.
+	struct FailingWorker {
+		failures: usize,
+		runs: usize,
+		fetch_failed: AuthoritiesFetchFailed,
+	}
+
+	#[async_trait::async_trait]
+	impl SlotWorker<Block, ()> for FailingWorker {
+		async fn on_slot(&mut self, _: SlotInfo<Block>) -> Option<SlotResult<Block, ()>> {
+			self.runs += 1;
+			if self.runs <= self.failures {
+				self.fetch_failed.raise();
+			}
+			None
+		}
+	}
+
+	/// Run a slot on a worker failing `failures` times, returning how often it ran.
+	fn runs(failures: usize, retry: bool) -> usize {
+		let fetch_failed = AuthoritiesFetchFailed::default();
+		let worker = FailingWorker { failures, runs: 0, fetch_failed: fetch_failed.clone() };
+		let mut worker = AuthoritiesRetryingWorker {
+			worker,
+			fetch_failed: retry.then(|| fetch_failed),
+			metrics: None,
+		};
+		let slot_info = SlotInfo::<Block> {
+			slot: 1.into(),
+			timestamp: Default::default(),
+			ends_at: Instant::now() + Duration::from_secs(3600),
+			inherent_data: Default::default(),
+			duration: Duration::from_millis(1000),
+			chain_head: TestHeader::new(
+				0,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			),
+			block_size_limit: None,
+		};
+		assert!(block_on(worker.on_slot(slot_info)).is_none());
+		worker.worker.runs
+	}
+
+	#[test]
+	fn slots_are_retried_once_after_failing_to_fetch_the_authorities() {
+		assert_eq!(runs(0, true), 1);
+		assert_eq!(runs(1, true), 2);
+		// a persistent failure still fails the slot after the retry
+		assert_eq!(runs(2, true), 2);
+		assert_eq!(runs(1, false), 1);
+	}
+}
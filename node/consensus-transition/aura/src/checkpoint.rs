@@ -0,0 +1,108 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Checkpoint of the last authority set fetched by the worker, kept in the aux store.
+//!
+//! After a restart the worker seeds its [`AuthoritiesCache`](crate::AuthoritiesCache) from the
+//! checkpoint, once the runtime confirmed it, instead of starting with an empty cache.
+
+use codec::{Decode, Encode};
+use sc_client_api::backend::AuxStore;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+
+/// The aux store key of the checkpoint.
+const AUTHORITIES_CHECKPOINT_KEY: &[u8] = b"aura_authorities_checkpoint";
+
+/// The authority set at a block, as persisted by [`write_authorities_checkpoint`].
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct AuthoritiesCheckpoint<H, N, A> {
+	/// The hash of the block the set was fetched at.
+	pub hash: H,
+	/// The number of the block the set was fetched at.
+	pub number: N,
+	/// The authorities at the block.
+	pub authorities: Vec<A>,
+}
+
+/// Persist `checkpoint`, replacing the previous one.
+pub fn write_authorities_checkpoint<C, H, N, A>(
+	client: &C,
+	checkpoint: &AuthoritiesCheckpoint<H, N, A>,
+) -> ClientResult<()>
+where
+	C: AuxStore,
+	H: Encode,
+	N: Encode,
+	A: Encode,
+{
+	client.insert_aux(&[(AUTHORITIES_CHECKPOINT_KEY, &checkpoint.encode()[..])], &[])
+}
+
+/// Load the checkpoint persisted by [`write_authorities_checkpoint`], if any.
+pub fn load_authorities_checkpoint<C, H, N, A>(
+	client: &C,
+) -> ClientResult<Option<AuthoritiesCheckpoint<H, N, A>>>
+where
+	C: AuxStore,
+	H: Decode,
+	N: Decode,
+	A: Decode,
+{
+	match client.get_aux(AUTHORITIES_CHECKPOINT_KEY)? {
+		Some(encoded) => AuthoritiesCheckpoint::decode(&mut &encoded[..]).map(Some).map_err(|e| {
+			ClientError::Backend(format!("Failed to decode the authorities checkpoint: {}", e))
+		}),
+		None => Ok(None),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_consensus_aura::sr25519::AuthorityId;
+	use sp_core::H256;
+	use sp_keyring::sr25519::Keyring;
+
+	#[test]
+	fn checkpoint_is_read_back() {
+		let client = substrate_test_runtime_client::new();
+		assert_eq!(
+			load_authorities_checkpoint::<_, H256, u64, AuthorityId>(&client)
+				.expect("Readable aux store"),
+			None,
+		);
+
+		let checkpoint = AuthoritiesCheckpoint::<_, _, AuthorityId> {
+			hash: H256::repeat_byte(1),
+			number: 7u64,
+			authorities: vec![Keyring::Alice.public().into(), Keyring::Bob.public().into()],
+		};
+		write_authorities_checkpoint(&client, &checkpoint).expect("Writable aux store");
+		assert_eq!(
+			load_authorities_checkpoint(&client).expect("Readable aux store"),
+			Some(checkpoint.clone()),
+		);
+
+		let replaced = AuthoritiesCheckpoint { hash: H256::repeat_byte(2), ..checkpoint };
+		write_authorities_checkpoint(&client, &replaced).expect("Writable aux store");
+		assert_eq!(
+			load_authorities_checkpoint(&client).expect("Readable aux store"),
+			Some(replaced),
+		);
+	}
+}
@@ -0,0 +1,177 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Proposing blocks without sealing or importing them, behind the `dry-run` feature.
+//!
+//! This is meant for capacity planning, i.e. to estimate how large and how slow to propose the
+//! blocks authored under the current transaction pool would be.
+
+use codec::Encode;
+use log::debug;
+use sp_consensus::{Environment, Proposer};
+use sp_consensus_slots::Slot;
+use sp_inherents::InherentData;
+use sp_runtime::{
+	traits::{Block as BlockT, Header},
+	ConsensusEngineId, Digest, DigestItem,
+};
+use std::time::{Duration, Instant};
+
+/// The outcome of a proposal of [`dry_run_proposal`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DryRunProposal {
+	/// The size of the encoded block, without a seal.
+	pub block_size: usize,
+	/// The number of extrinsics in the block, including the inherents.
+	pub extrinsics: usize,
+	/// How long initializing the proposer and proposing took.
+	pub duration: Duration,
+}
+
+/// Propose a block on top of `parent` in `slot` like the worker does, and discard it.
+///
+/// The proposer is created by `proposer_factory` and handed `inherent_data`, which should be
+/// created for `slot`, and the same limits the worker would hand it, i.e. `max_duration` and
+/// `block_size_limit`. The block carries the pre-runtime digest of `slot` under `engine_id`, so
+/// the runtime accepts it, but it is neither sealed nor imported.
+pub async fn dry_run_proposal<B, PF, Error>(
+	proposer_factory: &mut PF,
+	parent: &B::Header,
+	slot: Slot,
+	engine_id: ConsensusEngineId,
+	inherent_data: InherentData,
+	max_duration: Duration,
+	block_size_limit: Option<usize>,
+) -> Result<DryRunProposal, Error>
+where
+	B: BlockT,
+	PF: Environment<B, Error = Error>,
+	PF::Proposer: Proposer<B, Error = Error>,
+	Error: std::error::Error,
+{
+	let started = Instant::now();
+	let proposer = proposer_factory.init(parent).await?;
+	let digest = Digest { logs: vec![DigestItem::PreRuntime(engine_id, slot.encode())] };
+	let proposal = proposer.propose(inherent_data, digest, max_duration, block_size_limit).await?;
+
+	let dry_run = DryRunProposal {
+		block_size: proposal.block.encoded_size(),
+		extrinsics: proposal.block.extrinsics().len(),
+		duration: started.elapsed(),
+	};
+	debug!(
+		target: "aura",
+		"Dry run proposal on top of #{:?} in slot {}: {:?}",
+		parent.number(),
+		slot,
+		dry_run,
+	);
+	Ok(dry_run)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{executor::block_on, future};
+	use sp_consensus::{DisableProofRecording, Proposal};
+	use sp_runtime::traits::Header as _;
+	use substrate_test_runtime_client::runtime::{Block, Extrinsic, Header};
+
+	/// Creates proposers of blocks holding the given extrinsics.
+	struct TestEnvironment(Vec<Extrinsic>);
+
+	struct TestProposer {
+		extrinsics: Vec<Extrinsic>,
+		parent: Header,
+	}
+
+	impl Environment<Block> for TestEnvironment {
+		type Proposer = TestProposer;
+		type CreateProposer = future::Ready<Result<TestProposer, sp_consensus::Error>>;
+		type Error = sp_consensus::Error;
+
+		fn init(&mut self, parent: &Header) -> Self::CreateProposer {
+			future::ready(Ok(TestProposer { extrinsics: self.0.clone(), parent: parent.clone() }))
+		}
+	}
+
+	impl Proposer<Block> for TestProposer {
+		type Error = sp_consensus::Error;
+		type Transaction = ();
+		type Proposal = future::Ready<Result<Proposal<Block, (), ()>, sp_consensus::Error>>;
+		type ProofRecording = DisableProofRecording;
+		type Proof = ();
+
+		fn propose(
+			self,
+			_: InherentData,
+			digest: Digest,
+			_: Duration,
+			_: Option<usize>,
+		) -> Self::Proposal {
+			let header = Header::new(
+				self.parent.number + 1,
+				Default::default(),
+				Default::default(),
+				self.parent.hash(),
+				digest,
+			);
+			future::ready(Ok(Proposal {
+				block: Block { header, extrinsics: self.extrinsics },
+				proof: (),
+				storage_changes: Default::default(),
+			}))
+		}
+	}
+
+	#[test]
+	fn dry_run_reports_the_discarded_proposal() {
+		let extrinsics = vec![Extrinsic::IncludeData(vec![1; 100]), Extrinsic::IncludeData(vec![])];
+		let mut proposer_factory = TestEnvironment(extrinsics.clone());
+		let parent = Header::new(
+			7,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+
+		let dry_run = block_on(dry_run_proposal::<Block, _, _>(
+			&mut proposer_factory,
+			&parent,
+			42.into(),
+			crate::AURA_ENGINE_ID,
+			InherentData::new(),
+			Duration::from_secs(1),
+			None,
+		))
+		.expect("Proposes");
+
+		let header = Header::new(
+			8,
+			Default::default(),
+			Default::default(),
+			parent.hash(),
+			Digest {
+				logs: vec![DigestItem::PreRuntime(crate::AURA_ENGINE_ID, Slot::from(42).encode())],
+			},
+		);
+		assert_eq!(dry_run.extrinsics, 2);
+		assert_eq!(dry_run.block_size, Block { header, extrinsics }.encoded_size());
+	}
+}
@@ -0,0 +1,182 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Restarting the slot timer when the runtime changes the slot duration.
+
+use futures::{channel::mpsc, future::Either, prelude::*};
+use log::info;
+use parking_lot::Mutex;
+use sc_consensus_slots::{InherentDataProviderExt, SlotInfo, SlotResult, SlotWorker};
+use sp_consensus::{CanAuthorWith, SelectChain, SyncOracle};
+use sp_consensus_aura::SlotDuration;
+use sp_inherents::CreateInherentDataProviders;
+use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use std::sync::Arc;
+
+/// Handle the worker reports changes of the runtime's slot duration through.
+///
+/// See [`BuildAuraWorkerParams::slot_duration_changes`](crate::BuildAuraWorkerParams).
+#[derive(Clone)]
+pub struct SlotDurationChanges {
+	current: Arc<Mutex<SlotDuration>>,
+	sender: mpsc::UnboundedSender<SlotDuration>,
+}
+
+impl SlotDurationChanges {
+	/// Create a handle starting at `slot_duration`, along with the stream of the slot durations
+	/// it changes to.
+	pub fn new(slot_duration: SlotDuration) -> (Self, mpsc::UnboundedReceiver<SlotDuration>) {
+		let (sender, receiver) = mpsc::unbounded();
+		(Self { current: Arc::new(Mutex::new(slot_duration)), sender }, receiver)
+	}
+
+	/// The current slot duration.
+	pub fn current(&self) -> SlotDuration {
+		*self.current.lock()
+	}
+
+	/// Note the slot duration read from the runtime, sending it if it changed.
+	///
+	/// Returns whether the slot duration changed.
+	pub(crate) fn note(&self, slot_duration: SlotDuration) -> bool {
+		let mut current = self.current.lock();
+		if current.as_millis() == slot_duration.as_millis() {
+			return false
+		}
+
+		*current = slot_duration;
+		// the receiver is gone if the worker is not run with a dynamic slot duration
+		let _ = self.sender.unbounded_send(slot_duration);
+		true
+	}
+}
+
+/// Run the slot worker, restarting the slot timer whenever the slot duration changes to one
+/// received through `changes`.
+///
+/// The slot in which a change is received is aborted, so no block is authored in it.
+pub(crate) async fn run_with_dynamic_slot_duration<B, C, W, SO, CIDP, CAW, Proof>(
+	mut slot_duration: SlotDuration,
+	mut changes: mpsc::UnboundedReceiver<SlotDuration>,
+	select_chain: C,
+	worker: W,
+	sync_oracle: SO,
+	create_inherent_data_providers: CIDP,
+	can_author_with: CAW,
+) where
+	B: BlockT,
+	C: SelectChain<B>,
+	W: SlotWorker<B, Proof> + Send,
+	Proof: Send,
+	SO: SyncOracle + Send + Clone,
+	CIDP: CreateInherentDataProviders<B, ()> + Send,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send,
+	CAW: CanAuthorWith<B> + Send,
+{
+	let worker = Arc::new(futures::lock::Mutex::new(worker));
+	let create_inherent_data_providers = Arc::new(create_inherent_data_providers);
+	let can_author_with = Arc::new(Mutex::new(can_author_with));
+
+	loop {
+		let slots = Box::pin(sc_consensus_slots::start_slot_worker(
+			slot_duration,
+			select_chain.clone(),
+			SharedSlotWorker(worker.clone()),
+			sync_oracle.clone(),
+			SharedInherentDataProviders(create_inherent_data_providers.clone()),
+			SharedCanAuthorWith(can_author_with.clone()),
+		));
+
+		match future::select(slots, changes.next()).await {
+			Either::Left(((), _)) => return,
+			Either::Right((Some(new_slot_duration), _)) => {
+				info!(
+					target: "aura",
+					"Slot duration changed from {}ms to {}ms, restarting the slot timer.",
+					slot_duration.as_millis(),
+					new_slot_duration.as_millis(),
+				);
+				slot_duration = new_slot_duration;
+			},
+			Either::Right((None, slots)) => return slots.await,
+		}
+	}
+}
+
+/// Slot worker shared between the slot timers started over the lifetime of the worker.
+struct SharedSlotWorker<W>(Arc<futures::lock::Mutex<W>>);
+
+#[async_trait::async_trait]
+impl<B, W, Proof> SlotWorker<B, Proof> for SharedSlotWorker<W>
+where
+	B: BlockT,
+	W: SlotWorker<B, Proof> + Send,
+	Proof: Send,
+{
+	async fn on_slot(&mut self, slot_info: SlotInfo<B>) -> Option<SlotResult<B, Proof>> {
+		self.0.lock().await.on_slot(slot_info).await
+	}
+}
+
+/// Inherent data providers shared between the slot timers started over the lifetime of the
+/// worker.
+struct SharedInherentDataProviders<CIDP>(Arc<CIDP>);
+
+#[async_trait::async_trait]
+impl<B, CIDP> CreateInherentDataProviders<B, ()> for SharedInherentDataProviders<CIDP>
+where
+	B: BlockT,
+	CIDP: CreateInherentDataProviders<B, ()>,
+{
+	type InherentDataProviders = CIDP::InherentDataProviders;
+
+	async fn create_inherent_data_providers(
+		&self,
+		parent: B::Hash,
+		extra_args: (),
+	) -> Result<Self::InherentDataProviders, Box<dyn std::error::Error + Send + Sync>> {
+		self.0.create_inherent_data_providers(parent, extra_args).await
+	}
+}
+
+/// Authoring check shared between the slot timers started over the lifetime of the worker.
+struct SharedCanAuthorWith<CAW>(Arc<Mutex<CAW>>);
+
+impl<B: BlockT, CAW: CanAuthorWith<B>> CanAuthorWith<B> for SharedCanAuthorWith<CAW> {
+	fn can_author_with(&self, at: &BlockId<B>) -> Result<(), String> {
+		self.0.lock().can_author_with(at)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn only_changed_slot_durations_are_sent() {
+		let (changes, mut receiver) = SlotDurationChanges::new(SlotDuration::from_millis(6_000));
+
+		assert!(!changes.note(SlotDuration::from_millis(6_000)));
+		assert!(changes.note(SlotDuration::from_millis(3_000)));
+		assert!(!changes.clone().note(SlotDuration::from_millis(3_000)));
+		assert_eq!(changes.current().as_millis(), 3_000);
+
+		assert_eq!(receiver.try_next().unwrap().map(|d| d.as_millis()), Some(3_000));
+		assert!(receiver.try_next().is_err());
+	}
+}
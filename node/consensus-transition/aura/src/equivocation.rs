@@ -0,0 +1,276 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Proofs of Aura authors equivocating, i.e. sealing two different blocks for the same slot.
+
+use crate::{find_pre_digest_with_engine_id, strip_seal_with_engine_id, AuthorityId};
+use codec::Codec;
+use sp_consensus_slots::Slot;
+use sp_core::crypto::Pair;
+use sp_consensus_aura::AURA_ENGINE_ID;
+use sp_runtime::{
+	traits::{Block as BlockT, Header},
+	ConsensusEngineId,
+};
+
+/// Proof that the authority `offender` sealed two different headers for the same `slot`.
+///
+/// This is the SCALE encoded format a reporter submits to the runtime, e.g. to a slashing
+/// pallet. The proofs returned by [`sc_consensus_slots::check_equivocation`] during import are
+/// of the same type.
+pub type EquivocationProof<B, P> =
+	sp_consensus_slots::EquivocationProof<<B as BlockT>::Header, AuthorityId<P>>;
+
+/// Construct the proof that `offender` equivocated by sealing both `first_header` and
+/// `second_header`.
+///
+/// Returns `None` if the headers do not carry the same Aura slot or are the same header. The
+/// seals are not checked, use [`verify_equivocation_proof`] for that.
+pub fn make_equivocation_proof<B, P>(
+	offender: AuthorityId<P>,
+	first_header: B::Header,
+	second_header: B::Header,
+) -> Option<EquivocationProof<B, P>>
+where
+	B: BlockT,
+	P: Pair,
+	P::Signature: Codec,
+{
+	make_equivocation_proof_with_engine_id::<B, P>(
+		offender,
+		first_header,
+		second_header,
+		AURA_ENGINE_ID,
+	)
+}
+
+/// Construct the proof that `offender` equivocated by sealing both `first_header` and
+/// `second_header`, reading the slots from the pre-runtime digests issued under `engine_id`.
+///
+/// See [`make_equivocation_proof`] and
+/// [`BuildAuraWorkerParams::engine_id`](crate::BuildAuraWorkerParams::engine_id).
+pub fn make_equivocation_proof_with_engine_id<B, P>(
+	offender: AuthorityId<P>,
+	first_header: B::Header,
+	second_header: B::Header,
+	engine_id: ConsensusEngineId,
+) -> Option<EquivocationProof<B, P>>
+where
+	B: BlockT,
+	P: Pair,
+	P::Signature: Codec,
+{
+	let slot = find_pre_digest_with_engine_id::<B>(&first_header, engine_id).ok()?;
+	if find_pre_digest_with_engine_id::<B>(&second_header, engine_id).ok()? != slot ||
+		first_header.hash() == second_header.hash()
+	{
+		return None
+	}
+
+	Some(EquivocationProof::<B, P> { offender, slot, first_header, second_header })
+}
+
+/// Check an equivocation proof.
+///
+/// The proof is valid if both headers are sealed by the offender, carry the slot of the proof
+/// and are different. Whether the offender was the expected author of the slot depends on the
+/// authority set at that time and is left to the party holding it, usually the runtime.
+/// Headers sealed in the format of [`crate::CompatibilityMode::LegacySealFormat`] do not verify.
+pub fn verify_equivocation_proof<B, P>(proof: &EquivocationProof<B, P>) -> bool
+where
+	B: BlockT,
+	P: Pair,
+	P::Signature: Codec,
+{
+	verify_equivocation_proof_with_engine_id::<B, P>(proof, AURA_ENGINE_ID)
+}
+
+/// Check an equivocation proof whose headers are sealed under `engine_id`.
+///
+/// See [`verify_equivocation_proof`] and
+/// [`BuildAuraWorkerParams::engine_id`](crate::BuildAuraWorkerParams::engine_id).
+pub fn verify_equivocation_proof_with_engine_id<B, P>(
+	proof: &EquivocationProof<B, P>,
+	engine_id: ConsensusEngineId,
+) -> bool
+where
+	B: BlockT,
+	P: Pair,
+	P::Signature: Codec,
+{
+	let sealed_by_offender =
+		|header| check_sealed_by::<B, P>(header, proof.slot, &proof.offender, engine_id);
+
+	proof.first_header.hash() != proof.second_header.hash() &&
+		sealed_by_offender(&proof.first_header) &&
+		sealed_by_offender(&proof.second_header)
+}
+
+/// Check that `header` carries `slot` and is sealed by `author` under `engine_id`.
+fn check_sealed_by<B, P>(
+	header: &B::Header,
+	slot: Slot,
+	author: &AuthorityId<P>,
+	engine_id: ConsensusEngineId,
+) -> bool
+where
+	B: BlockT,
+	P: Pair,
+	P::Signature: Codec,
+{
+	let (pre_header, signature) =
+		match strip_seal_with_engine_id::<B, P::Signature>(header, engine_id) {
+			(pre_header, Some(signature)) => (pre_header, signature),
+			(_, None) => return false,
+		};
+
+	if find_pre_digest_with_engine_id::<B>(&pre_header, engine_id).ok() != Some(slot) {
+		return false
+	}
+
+	P::verify(&signature, pre_header.hash().as_ref(), author)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use codec::{Decode, Encode};
+	use sp_consensus_aura::{
+		digests::CompatibleDigestItem,
+		sr25519::{AuthorityPair, AuthoritySignature},
+	};
+	use sp_runtime::{Digest, DigestItem};
+	use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+	fn sealed_header(pair: &AuthorityPair, slot: u64, state_root: u8) -> TestHeader {
+		let pre_digest =
+			<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(slot.into());
+		let mut header = TestHeader::new(
+			1,
+			Default::default(),
+			[state_root; 32].into(),
+			Default::default(),
+			Digest { logs: vec![pre_digest] },
+		);
+		let signature = pair.sign(header.hash().as_ref());
+		header
+			.digest_mut()
+			.push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature));
+		header
+	}
+
+	fn pair(seed: &str) -> AuthorityPair {
+		AuthorityPair::from_string(seed, None).expect("Valid seed")
+	}
+
+	#[test]
+	fn proof_of_two_headers_in_one_slot_verifies() {
+		let alice = pair("//Alice");
+		let proof = make_equivocation_proof::<Block, AuthorityPair>(
+			alice.public(),
+			sealed_header(&alice, 7, 1),
+			sealed_header(&alice, 7, 2),
+		)
+		.expect("Headers equivocate");
+
+		assert_eq!(proof.slot, Slot::from(7));
+		assert!(verify_equivocation_proof::<Block, AuthorityPair>(&proof));
+
+		let decoded = EquivocationProof::<Block, AuthorityPair>::decode(&mut &proof.encode()[..])
+			.expect("Proof decodes");
+		assert_eq!(decoded, proof);
+	}
+
+	#[test]
+	fn headers_of_different_slots_or_identical_headers_are_no_equivocation() {
+		let alice = pair("//Alice");
+
+		assert!(make_equivocation_proof::<Block, AuthorityPair>(
+			alice.public(),
+			sealed_header(&alice, 7, 1),
+			sealed_header(&alice, 8, 2),
+		)
+		.is_none());
+		assert!(make_equivocation_proof::<Block, AuthorityPair>(
+			alice.public(),
+			sealed_header(&alice, 7, 1),
+			sealed_header(&alice, 7, 1),
+		)
+		.is_none());
+	}
+
+	#[test]
+	fn proof_with_foreign_seal_or_wrong_slot_is_rejected() {
+		let alice = pair("//Alice");
+		let bob = pair("//Bob");
+
+		let foreign_seal = EquivocationProof::<Block, AuthorityPair> {
+			offender: alice.public(),
+			slot: 7.into(),
+			first_header: sealed_header(&alice, 7, 1),
+			second_header: sealed_header(&bob, 7, 2),
+		};
+		assert!(!verify_equivocation_proof::<Block, AuthorityPair>(&foreign_seal));
+
+		let wrong_slot = EquivocationProof::<Block, AuthorityPair> {
+			offender: alice.public(),
+			slot: 8.into(),
+			first_header: sealed_header(&alice, 7, 1),
+			second_header: sealed_header(&alice, 7, 2),
+		};
+		assert!(!verify_equivocation_proof::<Block, AuthorityPair>(&wrong_slot));
+	}
+
+	#[test]
+	fn proofs_of_headers_sealed_under_another_engine_id_verify_under_it() {
+		const FORK_ENGINE_ID: ConsensusEngineId = *b"fork";
+		let alice = pair("//Alice");
+		let sealed_under_fork = |state_root: u8| {
+			let pre_digest = DigestItem::PreRuntime(FORK_ENGINE_ID, Slot::from(7).encode());
+			let mut header = TestHeader::new(
+				1,
+				Default::default(),
+				[state_root; 32].into(),
+				Default::default(),
+				Digest { logs: vec![pre_digest] },
+			);
+			let signature = alice.sign(header.hash().as_ref());
+			header.digest_mut().push(DigestItem::Seal(FORK_ENGINE_ID, signature.encode()));
+			header
+		};
+
+		assert!(make_equivocation_proof::<Block, AuthorityPair>(
+			alice.public(),
+			sealed_under_fork(1),
+			sealed_under_fork(2),
+		)
+		.is_none());
+		let proof = make_equivocation_proof_with_engine_id::<Block, AuthorityPair>(
+			alice.public(),
+			sealed_under_fork(1),
+			sealed_under_fork(2),
+			FORK_ENGINE_ID,
+		)
+		.expect("Headers equivocate");
+		assert!(verify_equivocation_proof_with_engine_id::<Block, AuthorityPair>(
+			&proof,
+			FORK_ENGINE_ID,
+		));
+		assert!(!verify_equivocation_proof::<Block, AuthorityPair>(&proof));
+	}
+}
@@ -0,0 +1,180 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! The headers of recent slots, kept in the aux store to detect equivocations.
+//!
+//! This is [`sc_consensus_slots::check_equivocation`] with a configurable window, see
+//! [`BuildVerifierParams::equivocation_window_slots`](crate::BuildVerifierParams). The headers
+//! are stored under the same keys and in the same format, so a node switching between the two
+//! keeps the headers seen so far.
+
+use codec::{Decode, Encode};
+use sc_client_api::backend::AuxStore;
+use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_consensus_slots::{EquivocationProof, Slot};
+use sp_runtime::traits::Header;
+
+/// The aux store key prefix of the headers seen in a slot.
+const SLOT_HEADER_MAP_KEY: &[u8] = b"slot_header_map";
+/// The aux store key of the oldest slot whose headers are kept.
+const SLOT_HEADER_START: &[u8] = b"slot_header_start";
+
+/// The aux store key of the headers seen in `slot`.
+fn slot_header_key(slot: Slot) -> Vec<u8> {
+	let mut key = SLOT_HEADER_MAP_KEY.to_vec();
+	slot.using_encoded(|encoded| key.extend(encoded));
+	key
+}
+
+fn load_decode<C: AuxStore, T: Decode>(backend: &C, key: &[u8]) -> ClientResult<Option<T>> {
+	match backend.get_aux(key)? {
+		Some(encoded) => T::decode(&mut &encoded[..]).map(Some).map_err(|e| {
+			ClientError::Backend(format!("Failed to decode the headers of a slot: {}", e))
+		}),
+		None => Ok(None),
+	}
+}
+
+/// Record `header` sealed by `signer` at `slot`, and return a proof of equivocation if `signer`
+/// sealed another header at `slot` before.
+///
+/// Headers of slots more than `window` slots before `slot_now` are neither checked nor
+/// recorded. The headers of such slots are pruned once the oldest kept slot falls `2 * window`
+/// slots behind, so at most that many slots are kept.
+pub(crate) fn check_equivocation<C, H, P>(
+	backend: &C,
+	slot_now: Slot,
+	slot: Slot,
+	header: &H,
+	signer: &P,
+	window: u64,
+) -> ClientResult<Option<EquivocationProof<H, P>>>
+where
+	C: AuxStore,
+	H: Header,
+	P: Clone + Encode + Decode + PartialEq,
+{
+	if (*slot_now).saturating_sub(*slot) > window {
+		return Ok(None)
+	}
+
+	let slot_key = slot_header_key(slot);
+	let mut headers_with_sig =
+		load_decode::<_, Vec<(H, P)>>(backend, &slot_key)?.unwrap_or_default();
+	let first_saved_slot = load_decode::<_, Slot>(backend, SLOT_HEADER_START)?.unwrap_or(slot);
+	if slot_now < first_saved_slot {
+		// the slots are assumed to be visited in order
+		return Ok(None)
+	}
+
+	if let Some((first_header, _)) = headers_with_sig.iter().find(|(_, seen)| seen == signer) {
+		if first_header.hash() == header.hash() {
+			// the header was checked before
+			return Ok(None)
+		}
+		return Ok(Some(EquivocationProof {
+			slot,
+			offender: signer.clone(),
+			first_header: first_header.clone(),
+			second_header: header.clone(),
+		}))
+	}
+
+	let mut keys_to_delete = Vec::new();
+	let mut new_first_saved_slot = first_saved_slot;
+	if (*slot_now).saturating_sub(*first_saved_slot) >= window.saturating_mul(2) {
+		new_first_saved_slot = Slot::from((*slot_now).saturating_sub(window));
+		keys_to_delete.extend(
+			(*first_saved_slot..*new_first_saved_slot).map(|slot| slot_header_key(slot.into())),
+		);
+	}
+
+	headers_with_sig.push((header.clone(), signer.clone()));
+	backend.insert_aux(
+		&[
+			(&slot_key[..], &headers_with_sig.encode()[..]),
+			(SLOT_HEADER_START, &new_first_saved_slot.encode()[..]),
+		],
+		&keys_to_delete.iter().map(|key| &key[..]).collect::<Vec<_>>(),
+	)?;
+
+	Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_test_runtime_client::runtime::Header as TestHeader;
+
+	fn header(slot: u64, state_root: u8) -> TestHeader {
+		TestHeader::new(
+			slot,
+			Default::default(),
+			[state_root; 32].into(),
+			Default::default(),
+			Default::default(),
+		)
+	}
+
+	#[test]
+	fn old_slots_are_pruned_while_recent_equivocations_are_caught() {
+		let client = substrate_test_runtime_client::new();
+		let check = |slot_now: u64, slot: u64, header: &TestHeader, signer: u8| {
+			check_equivocation(&client, slot_now.into(), slot.into(), header, &signer, 10)
+				.expect("Accessible aux store")
+		};
+		let kept = |slot: u64| {
+			load_decode::<_, Vec<(TestHeader, u8)>>(&client, &slot_header_key(slot.into()))
+				.expect("Accessible aux store")
+				.is_some()
+		};
+
+		for slot in 0..50u64 {
+			assert!(check(slot, slot, &header(slot, 0), 0).is_none());
+		}
+		assert!(!kept(29));
+		assert!((30..50).all(kept));
+
+		// slots that left the window are neither checked nor recorded
+		assert!(check(49, 30, &header(30, 1), 0).is_none());
+		assert!(check(49, 38, &header(38, 1), 0).is_none());
+
+		let proof = check(49, 45, &header(45, 1), 0).expect("Equivocates");
+		assert_eq!(proof.slot, 45.into());
+		assert_eq!(proof.offender, 0);
+		assert_eq!(proof.first_header, header(45, 0));
+		assert_eq!(proof.second_header, header(45, 1));
+
+		// neither a header seen before nor a header of another author is an equivocation
+		assert!(check(49, 45, &header(45, 0), 0).is_none());
+		assert!(check(49, 46, &header(46, 1), 1).is_none());
+	}
+
+	#[test]
+	fn equivocations_are_caught_across_restarts() {
+		let client = substrate_test_runtime_client::new();
+		assert!(check_equivocation(&client, 5.into(), 5.into(), &header(5, 0), &0u8, 10)
+			.expect("Accessible aux store")
+			.is_none());
+
+		// nothing but the aux store is kept between the checks
+		assert!(check_equivocation(&client, 6.into(), 5.into(), &header(5, 1), &0u8, 10)
+			.expect("Accessible aux store")
+			.is_some());
+	}
+}
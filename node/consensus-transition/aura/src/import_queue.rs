@@ -19,93 +19,417 @@
 //! Module implementing the logic for verifying and importing AuRa blocks.
 
 use crate::{
-	aura_err, authorities, find_pre_digest, slot_author, AuthorityId, CompatibilityMode, Error,
+	aura_err, equivocation_window::check_equivocation, expected_slot_author, fetch_authorities,
+	find_authority_index, find_pre_digest_with_engine_id, is_secondary_slot, seal_message,
+	seal_preverification::{
+		AuraImportQueue, PreverifiedSeals, SealPreverifier, SealPreverifyingQueue,
+	},
+	slot_to_timestamp_with_offset, take_pre_seal_digests, timestamp_to_slot_with_offset,
+	AuraApiAuthorities, AuthorityId, AuthorityWeights, CompatibilityMode, Error,
+	EquivocationProof, OfflineAuthorities, StageBehavior, VerifierMetrics,
 };
 use codec::{Codec, Decode, Encode};
-use log::{debug, info, trace};
+use futures::channel::mpsc;
+use log::{debug, info, trace, warn};
+use parking_lot::Mutex;
 use prometheus_endpoint::Registry;
 use sc_client_api::{backend::AuxStore, BlockOf, UsageProvider};
 use sc_consensus::{
-	block_import::{BlockImport, BlockImportParams, ForkChoiceStrategy},
-	import_queue::{BasicQueue, BoxJustificationImport, DefaultImportQueue, Verifier},
+	block_import::{BlockImport, BlockImportParams, ForkChoiceStrategy, StateAction},
+	import_queue::{BasicQueue, BoxJustificationImport, Verifier},
 };
-use sc_consensus_slots::{check_equivocation, CheckedHeader, InherentDataProviderExt};
+use sc_consensus_slots::{CheckedHeader, InherentDataProviderExt};
 use sc_telemetry::{telemetry, TelemetryHandle, CONSENSUS_DEBUG, CONSENSUS_TRACE};
 use sp_api::{ApiExt, ProvideRuntimeApi};
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
 use sp_blockchain::{well_known_cache_keys::Id as CacheKeyId, HeaderBackend};
 use sp_consensus::{CanAuthorWith, Error as ConsensusError};
-use sp_consensus_aura::{digests::CompatibleDigestItem, inherents::AuraInherentData, AuraApi};
+use sp_consensus_aura::{
+	inherents::AuraInherentData, AuraApi, ConsensusLog, SlotDuration, AURA_ENGINE_ID,
+};
 use sp_consensus_slots::Slot;
 use sp_core::{crypto::Pair, ExecutionContext};
-use sp_inherents::{CreateInherentDataProviders, InherentDataProvider as _};
+use sp_inherents::{CreateInherentDataProviders, InherentData, InherentDataProvider as _};
 use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Header, NumberFor},
-	DigestItem,
+	ConsensusEngineId, DigestItem,
+};
+use std::{
+	collections::{HashMap, HashSet, VecDeque},
+	fmt::Debug,
+	hash::Hash,
+	marker::PhantomData,
+	sync::Arc,
 };
-use std::{fmt::Debug, hash::Hash, marker::PhantomData, sync::Arc};
 
-/// check a header has been signed by the right key. If the slot is too far in the future, an error
-/// will be returned. If it's successful, returns the pre-header and the digest item
-/// containing the seal.
+/// Maximum number of authority sets tracked from consensus logs.
+const MAX_TRACKED_AUTHORITY_SETS: usize = 1024;
+
+/// Maximum number of reported equivocations remembered to deduplicate reports.
+const MAX_REPORTED_EQUIVOCATIONS: usize = 1024;
+
+/// Maximum number of blocks whose chains are tracked to enforce monotonic slots.
+const MAX_TRACKED_CHAIN_SLOTS: usize = 1024;
+
+/// The individual steps of verifying a sealed Aura header.
 ///
-/// This digest item will always return `Some` when used with `as_aura_seal`.
-fn check_header<C, B: BlockT, P: Pair>(
-	client: &C,
-	slot_now: Slot,
-	mut header: B::Header,
+/// [`AuraVerifier`] implements this trait, so verifiers layered on top of it can reuse the seal
+/// logic step by step instead of going through the monolithic [`Verifier::verify`].
+pub trait SealVerification<B: BlockT> {
+	/// The public key of an authority.
+	type AuthorityId;
+	/// The signature contained in the seal.
+	type Signature;
+
+	/// Extract the slot from the Aura pre-runtime digest of `header`.
+	fn verify_pre_digest(&self, header: &B::Header) -> Result<Slot, Error<B>>;
+
+	/// Remove the seal from `header`, the block with the given `hash`, and decode its signature.
+	///
+	/// Returns the signature together with the removed seal digest item.
+	fn verify_seal(
+		&self,
+		header: &mut B::Header,
+		hash: B::Hash,
+	) -> Result<(Self::Signature, DigestItem), Error<B>>;
+
+	/// The message signed by the seal of the block with the pre-seal `header` and `slot`.
+	///
+	/// See [`crate::seal_message`].
+	fn seal_message(&self, header: &B::Header, slot: Slot) -> Vec<u8>;
+
+	/// Check that `signature` over `message` was made by the expected author of `slot`.
+	///
+	/// Returns the expected author on success.
+	fn verify_author<'a>(
+		&self,
+		slot: Slot,
+		message: &[u8],
+		signature: &Self::Signature,
+		authorities: &'a [Self::AuthorityId],
+		hash: B::Hash,
+	) -> Result<&'a Self::AuthorityId, Error<B>>;
+}
+
+/// Remove the seal issued under `engine_id` from `header`, the block with the given `hash`, and
+/// decode its signature.
+///
+/// The seal has to be the last digest item. Any other digest item, known or not, stays in the
+/// header and is covered by the seal, so unknown items, e.g. of other protocols, are accepted
+/// as long as they are added before sealing.
+fn take_seal<B: BlockT, S: Codec>(
+	header: &mut B::Header,
+	hash: B::Hash,
+	engine_id: ConsensusEngineId,
+) -> Result<(S, DigestItem), Error<B>> {
+	let seal = header.digest_mut().pop().ok_or(Error::HeaderUnsealed(hash))?;
+
+	let sig = seal.seal_try_to(&engine_id).ok_or_else(|| aura_err(Error::HeaderBadSeal(hash)))?;
+
+	Ok((sig, seal))
+}
+
+/// Check that `signature` over `message` was made by the expected author of `slot`, or by its
+/// secondary author if `secondary`.
+fn check_author<'a, B: BlockT, P: Pair>(
+	slot: Slot,
+	message: &[u8],
+	signature: &P::Signature,
+	authorities: &'a [AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+	offline: Option<&OfflineAuthorities<AuthorityId<P>>>,
+	secondary: bool,
+	hash: B::Hash,
+) -> Result<&'a AuthorityId<P>, Error<B>> {
+	let expected_author = expected_slot_author::<P>(slot, authorities, weights, offline, secondary)
+		.ok_or(Error::SlotAuthorNotFound)?;
+
+	if P::verify(signature, message, expected_author) {
+		Ok(expected_author)
+	} else {
+		Err(Error::BadSignature(hash))
+	}
+}
+
+/// Check the authority `index` a block may carry against `authorities`, and against the
+/// `author` of its slot once known.
+fn check_authority_index<B: BlockT, A: PartialEq>(
+	index: Option<u32>,
+	authorities: &[A],
+	author: Option<&A>,
 	hash: B::Hash,
+) -> Result<(), Error<B>> {
+	let index = match index {
+		Some(index) => index,
+		None => return Ok(()),
+	};
+
+	match authorities.get(index as usize) {
+		Some(indexed) if author.map_or(true, |author| author == indexed) => Ok(()),
+		_ => Err(aura_err(Error::InvalidAuthorityIndex { hash, index })),
+	}
+}
+
+/// Check the seal of the sealed `header` against `authorities`.
+///
+/// These are the seal checks [`AuraVerifier`] runs on import, leaving out the checks that
+/// depend on the time of import or on other blocks. Blocks marked as authored in a secondary
+/// slot are checked against the secondary author. Returns the slot of the header.
+pub(crate) fn check_seal<B, P>(
+	mut header: B::Header,
 	authorities: &[AuthorityId<P>],
-	check_for_equivocation: CheckForEquivocation,
-) -> Result<CheckedHeader<B::Header, (Slot, DigestItem)>, Error<B>>
+	weights: Option<&AuthorityWeights>,
+	offline: Option<&OfflineAuthorities<AuthorityId<P>>>,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+	engine_id: ConsensusEngineId,
+) -> Result<Slot, Error<B>>
 where
+	B: BlockT,
+	P: Pair,
 	P::Signature: Codec,
-	C: sc_client_api::backend::AuxStore,
-	P::Public: Encode + Decode + PartialEq + Clone,
 {
-	let seal = header.digest_mut().pop().ok_or(Error::HeaderUnsealed(hash))?;
+	let hash = header.hash();
+	let (sig, _) = take_seal::<B, P::Signature>(&mut header, hash, engine_id)?;
+	let slot = find_pre_digest_with_engine_id::<B>(&header, engine_id)?;
+	let authority_index = find_authority_index::<B>(&header)?;
+	check_authority_index::<B, _>(authority_index, authorities, None, hash)?;
+	let message = seal_message::<B>(*header.number(), slot, &header.hash(), compatibility_mode);
+	let secondary = is_secondary_slot::<B>(&header);
+	let author =
+		check_author::<B, P>(slot, &message, &sig, authorities, weights, offline, secondary, hash)?;
+	check_authority_index::<B, _>(authority_index, authorities, Some(author), hash)?;
 
-	let sig = seal.as_aura_seal().ok_or_else(|| aura_err(Error::HeaderBadSeal(hash)))?;
+	Ok(slot)
+}
 
-	let slot = find_pre_digest::<B, P::Signature>(&header)?;
+/// Find the authority set announced by a `ConsensusLog::AuthoritiesChange` digest.
+pub(crate) fn find_authorities_change<B: BlockT, A: Decode>(header: &B::Header) -> Option<Vec<A>> {
+	header.digest().logs().iter().find_map(|log| {
+		match log.consensus_try_to::<ConsensusLog<A>>(&AURA_ENGINE_ID) {
+			Some(ConsensusLog::AuthoritiesChange(authorities)) => Some(authorities),
+			_ => None,
+		}
+	})
+}
 
-	if slot > slot_now {
-		header.digest_mut().push(seal);
-		Ok(CheckedHeader::Deferred(header, slot))
-	} else {
-		// check the signature is valid under the expected authority and
-		// chain state.
-		let expected_author =
-			slot_author::<P>(slot, authorities).ok_or(Error::SlotAuthorNotFound)?;
-
-		let pre_hash = header.hash();
-
-		if P::verify(&sig, pre_hash.as_ref(), expected_author) {
-			if check_for_equivocation.check_for_equivocation() {
-				if let Some(equivocation_proof) =
-					check_equivocation(client, slot_now, slot, &header, expected_author)
-						.map_err(Error::Client)?
-				{
-					info!(
-						target: "aura",
-						"Slot author is equivocating at slot {} with headers {:?} and {:?}",
-						slot,
-						equivocation_proof.first_header.hash(),
-						equivocation_proof.second_header.hash(),
-					);
-				}
+/// Authority sets maintained from the `ConsensusLog::AuthoritiesChange` digests of imported
+/// blocks.
+///
+/// Each entry maps the hash of a block to the authorities that seal its children.
+struct ConsensusLogAuthorities<H, A> {
+	sets: HashMap<H, Vec<A>>,
+	insertion_order: VecDeque<H>,
+}
+
+impl<H: Hash + Eq + Clone, A: Clone> ConsensusLogAuthorities<H, A> {
+	fn new() -> Self {
+		Self { sets: HashMap::new(), insertion_order: VecDeque::new() }
+	}
+
+	/// The authorities that seal the children of `parent_hash`, if known.
+	fn get(&self, parent_hash: &H) -> Option<&Vec<A>> {
+		self.sets.get(parent_hash)
+	}
+
+	/// Track the authorities for the children of the block `hash`.
+	///
+	/// `authorities` is the set that sealed the block itself, which is inherited unless the
+	/// block announces a change.
+	fn note_block(&mut self, hash: H, authorities: Vec<A>, change: Option<Vec<A>>) {
+		if self.sets.insert(hash.clone(), change.unwrap_or(authorities)).is_none() {
+			self.insertion_order.push_back(hash);
+		}
+
+		while self.insertion_order.len() > MAX_TRACKED_AUTHORITY_SETS {
+			if let Some(oldest) = self.insertion_order.pop_front() {
+				self.sets.remove(&oldest);
 			}
+		}
+	}
+}
 
-			Ok(CheckedHeader::Checked(header, (slot, seal)))
-		} else {
-			Err(Error::BadSignature(hash))
+/// Sends detected equivocations to the channel given in
+/// [`BuildVerifierParams::equivocation_sender`], each offence only once.
+struct EquivocationReporter<H, A> {
+	sender: mpsc::Sender<sp_consensus_slots::EquivocationProof<H, A>>,
+	reported: HashSet<(A, Slot)>,
+	report_order: VecDeque<(A, Slot)>,
+}
+
+impl<H, A: Hash + Eq + Clone> EquivocationReporter<H, A> {
+	fn new(sender: mpsc::Sender<sp_consensus_slots::EquivocationProof<H, A>>) -> Self {
+		Self { sender, reported: HashSet::new(), report_order: VecDeque::new() }
+	}
+
+	/// Send `proof`, unless the equivocation of its offender in its slot was sent before.
+	///
+	/// Returns `false` if the proof was dropped because the channel is full or closed.
+	fn report(&mut self, proof: sp_consensus_slots::EquivocationProof<H, A>) -> bool {
+		let offence = (proof.offender.clone(), proof.slot);
+		if self.reported.contains(&offence) {
+			return true
+		}
+
+		if self.sender.try_send(proof).is_err() {
+			return false
+		}
+
+		self.reported.insert(offence.clone());
+		self.report_order.push_back(offence);
+		while self.report_order.len() > MAX_REPORTED_EQUIVOCATIONS {
+			if let Some(oldest) = self.report_order.pop_front() {
+				self.reported.remove(&oldest);
+			}
+		}
+
+		true
+	}
+}
+
+/// Calls the hook given in [`BuildVerifierParams::on_equivocation`] with the headers of
+/// detected equivocations, each pair of headers only once.
+struct EquivocationHook<H: Header> {
+	callback: Box<dyn Fn(&H, &H) + Send + Sync>,
+	notified: HashSet<(H::Hash, H::Hash)>,
+	notify_order: VecDeque<(H::Hash, H::Hash)>,
+}
+
+impl<H: Header> EquivocationHook<H> {
+	fn new(callback: Box<dyn Fn(&H, &H) + Send + Sync>) -> Self {
+		Self { callback, notified: HashSet::new(), notify_order: VecDeque::new() }
+	}
+
+	/// Call the hook with `first` and `second`, unless it was called with the pair before.
+	fn notify(&mut self, first: &H, second: &H) {
+		let pair = (first.hash(), second.hash());
+		if !self.notified.insert(pair) {
+			return
+		}
+
+		(self.callback)(first, second);
+
+		self.notify_order.push_back(pair);
+		while self.notify_order.len() > MAX_REPORTED_EQUIVOCATIONS {
+			if let Some(oldest) = self.notify_order.pop_front() {
+				self.notified.remove(&oldest);
+			}
+		}
+	}
+}
+
+/// The highest slot of each author on the chains of recently verified blocks, see
+/// [`BuildVerifierParams::enforce_monotonic_slots`].
+///
+/// Each entry maps the hash of a block to the highest slot of each author on the chain up to and
+/// including that block, so a block is only ever compared against its own ancestors.
+struct MonotonicSlots<H, A> {
+	chains: HashMap<H, HashMap<A, Slot>>,
+	insertion_order: VecDeque<H>,
+}
+
+impl<H: Hash + Eq + Clone, A: Hash + Eq + Clone> MonotonicSlots<H, A> {
+	fn new() -> Self {
+		Self { chains: HashMap::new(), insertion_order: VecDeque::new() }
+	}
+
+	/// The highest slot `author` sealed on the chain ending at `parent_hash`, if `slot` does not
+	/// exceed it.
+	///
+	/// Chains ending at blocks that are not tracked, e.g. the first block verified after a
+	/// restart, are not checked.
+	fn violation(&self, parent_hash: &H, author: &A, slot: Slot) -> Option<Slot> {
+		let previous_slot = *self.chains.get(parent_hash)?.get(author)?;
+		(slot <= previous_slot).then(|| previous_slot)
+	}
+
+	/// Track the chain ending at the block `hash`, a child of `parent_hash` sealed by `author`
+	/// at `slot`.
+	fn note_block(&mut self, hash: H, parent_hash: &H, author: A, slot: Slot) {
+		let mut highest = self.chains.get(parent_hash).cloned().unwrap_or_default();
+		let author_slot = highest.entry(author).or_insert(slot);
+		*author_slot = (*author_slot).max(slot);
+
+		if self.chains.insert(hash.clone(), highest).is_none() {
+			self.insertion_order.push_back(hash);
 		}
+
+		while self.insertion_order.len() > MAX_TRACKED_CHAIN_SLOTS {
+			if let Some(oldest) = self.insertion_order.pop_front() {
+				self.chains.remove(&oldest);
+			}
+		}
+	}
+}
+
+/// The timestamp at which `slot` starts, with slot 0 starting at `genesis_timestamp`.
+fn slot_start_timestamp(
+	slot: Slot,
+	slot_duration: SlotDuration,
+	genesis_timestamp: u64,
+) -> sp_timestamp::Timestamp {
+	sp_timestamp::Timestamp::new(slot_to_timestamp_with_offset(
+		slot,
+		slot_duration,
+		genesis_timestamp,
+	))
+}
+
+/// The slot at `timestamp` with the given `slot_duration`, with slot 0 starting at
+/// `genesis_timestamp`.
+fn slot_at_timestamp(
+	timestamp: sp_timestamp::Timestamp,
+	slot_duration: SlotDuration,
+	genesis_timestamp: u64,
+) -> Slot {
+	timestamp_to_slot_with_offset(*timestamp, slot_duration, genesis_timestamp)
+}
+
+/// The slot duration that `schedule` assigns to block `number`.
+///
+/// This is the duration of the entry with the highest starting block not above `number`.
+fn scheduled_slot_duration<N: PartialOrd>(
+	schedule: &[(N, SlotDuration)],
+	number: &N,
+) -> Option<SlotDuration> {
+	schedule
+		.iter()
+		.filter(|(from, _)| from <= number)
+		.fold(None, |best: Option<&(N, SlotDuration)>, entry| match best {
+			Some(best) if best.0 >= entry.0 => Some(best),
+			_ => Some(entry),
+		})
+		.map(|(_, slot_duration)| *slot_duration)
+}
+
+/// Whether `number` lies within the inclusive authority-set handover `window`.
+fn in_handover_window<N: PartialOrd>(window: Option<&(N, N)>, number: &N) -> bool {
+	window.map_or(false, |(start, end)| start <= number && number <= end)
+}
+
+/// Record that `author` produced block `number` at `slot`.
+///
+/// Only the most recent slot of each author is kept, and authors that are not part of
+/// `authorities` anymore are dropped.
+fn note_last_seen<A: Hash + Eq, N>(
+	last_seen: &mut HashMap<A, (Slot, N)>,
+	author: A,
+	slot: Slot,
+	number: N,
+	authorities: &[A],
+) {
+	last_seen.retain(|author, _| authorities.contains(author));
+
+	match last_seen.get(&author) {
+		Some((seen_slot, _)) if *seen_slot > slot => {},
+		_ => {
+			last_seen.insert(author, (slot, number));
+		},
 	}
 }
 
 /// A verifier for Aura blocks.
-pub struct AuraVerifier<C, P, CAW, CIDP, N> {
+pub struct AuraVerifier<C, P: Pair, CAW, CIDP, N, H: Header> {
 	client: Arc<C>,
 	phantom: PhantomData<P>,
 	create_inherent_data_providers: CIDP,
@@ -113,9 +437,27 @@ pub struct AuraVerifier<C, P, CAW, CIDP, N> {
 	check_for_equivocation: CheckForEquivocation,
 	telemetry: Option<TelemetryHandle>,
 	compatibility_mode: CompatibilityMode<N>,
+	consensus_log_authorities: Option<ConsensusLogAuthorities<H::Hash, AuthorityId<P>>>,
+	metrics: Option<VerifierMetrics>,
+	handover_window: Option<(N, N)>,
+	check_timestamp_slot_consistency: bool,
+	last_seen: HashMap<AuthorityId<P>, (Slot, N)>,
+	slot_duration_schedule: Vec<(N, SlotDuration)>,
+	equivocation_reporter: Option<Mutex<EquivocationReporter<H, AuthorityId<P>>>>,
+	authority_weights: Option<AuthorityWeights>,
+	equivocation_hook: Option<Mutex<EquivocationHook<H>>>,
+	equivocation_window_slots: u64,
+	offline_authorities: Option<OfflineAuthorities<AuthorityId<P>>>,
+	max_future_slots: u64,
+	engine_id: ConsensusEngineId,
+	preverified_seals: Option<PreverifiedSeals<H::Hash, AuthorityId<P>>>,
+	accept_secondary_slots: bool,
+	monotonic_slots: Option<MonotonicSlots<H::Hash, AuthorityId<P>>>,
+	verify_seals_only: bool,
+	genesis_timestamp: u64,
 }
 
-impl<C, P, CAW, CIDP, N> AuraVerifier<C, P, CAW, CIDP, N> {
+impl<C, P: Pair, CAW, CIDP, N, H: Header> AuraVerifier<C, P, CAW, CIDP, N, H> {
 	pub(crate) fn new(
 		client: Arc<C>,
 		create_inherent_data_providers: CIDP,
@@ -123,6 +465,24 @@ impl<C, P, CAW, CIDP, N> AuraVerifier<C, P, CAW, CIDP, N> {
 		check_for_equivocation: CheckForEquivocation,
 		telemetry: Option<TelemetryHandle>,
 		compatibility_mode: CompatibilityMode<N>,
+		use_consensus_log_authorities: bool,
+		metrics: Option<VerifierMetrics>,
+		handover_window: Option<(N, N)>,
+		check_timestamp_slot_consistency: bool,
+		slot_duration_schedule: Vec<(N, SlotDuration)>,
+		equivocation_sender: Option<
+			mpsc::Sender<sp_consensus_slots::EquivocationProof<H, AuthorityId<P>>>,
+		>,
+		authority_weights: Option<AuthorityWeights>,
+		on_equivocation: Option<Box<dyn Fn(&H, &H) + Send + Sync>>,
+		accept_secondary_slots: bool,
+		equivocation_window_slots: u64,
+		offline_authorities: Option<OfflineAuthorities<AuthorityId<P>>>,
+		max_future_slots: u64,
+		engine_id: ConsensusEngineId,
+		enforce_monotonic_slots: bool,
+		verify_seals_only: bool,
+		genesis_timestamp: u64,
 	) -> Self {
 		Self {
 			client,
@@ -131,17 +491,163 @@ impl<C, P, CAW, CIDP, N> AuraVerifier<C, P, CAW, CIDP, N> {
 			check_for_equivocation,
 			telemetry,
 			compatibility_mode,
+			// blocks imported without executing them have no state to read the authorities from.
+			consensus_log_authorities: (use_consensus_log_authorities || verify_seals_only)
+				.then(ConsensusLogAuthorities::new),
+			metrics,
+			handover_window,
+			check_timestamp_slot_consistency,
+			last_seen: HashMap::new(),
+			slot_duration_schedule,
+			equivocation_reporter: equivocation_sender
+				.map(|sender| Mutex::new(EquivocationReporter::new(sender))),
+			authority_weights,
+			equivocation_hook: on_equivocation.map(|hook| Mutex::new(EquivocationHook::new(hook))),
+			equivocation_window_slots,
+			offline_authorities,
+			max_future_slots,
+			engine_id,
+			preverified_seals: None,
+			accept_secondary_slots,
+			monotonic_slots: enforce_monotonic_slots.then(MonotonicSlots::new),
+			verify_seals_only,
+			genesis_timestamp,
 			phantom: PhantomData,
 		}
 	}
+
+	/// Accept the seals preverified by a [`SealPreverifier`] instead of checking them again.
+	pub(crate) fn with_preverified_seals(
+		self,
+		preverified_seals: PreverifiedSeals<H::Hash, AuthorityId<P>>,
+	) -> Self {
+		Self { preverified_seals: Some(preverified_seals), ..self }
+	}
+
+	/// The slot and number of the most recent block verified for each authority.
+	///
+	/// Only authorities of the set that sealed the last verified block are included.
+	pub fn last_seen(&self) -> HashMap<AuthorityId<P>, (Slot, N)>
+	where
+		P::Public: Clone,
+		N: Clone,
+	{
+		self.last_seen.clone()
+	}
+
+	/// Count the rejection of a block for `error` in the metrics, passing the error on.
+	fn note_rejection<B: BlockT>(&self, error: Error<B>) -> Error<B> {
+		if let Some(metrics) = self.metrics.as_ref() {
+			metrics.note_rejected(error.reason());
+		}
+		error
+	}
 }
 
-impl<C, P, CAW, CIDP, N> AuraVerifier<C, P, CAW, CIDP, N>
+impl<C, P: Pair, CAW, CIDP, N, H: Header> AuraVerifier<C, P, CAW, CIDP, N, H>
 where
 	P: Send + Sync + 'static,
 	CAW: Send + Sync + 'static,
 	CIDP: Send,
 {
+	/// check a header has been signed by the right key. If the slot is too far in the future, an
+	/// error will be returned. If it's successful, returns the pre-header and the digest item
+	/// containing the seal.
+	///
+	/// This digest item will always return `Some` when used with `seal_try_to` under the
+	/// verifier's engine ID.
+	fn check_header<B: BlockT<Header = H>>(
+		&self,
+		slot_now: Slot,
+		mut header: B::Header,
+		hash: B::Hash,
+		authorities: &[AuthorityId<P>],
+	) -> Result<CheckedHeader<B::Header, (Slot, DigestItem)>, Error<B>>
+	where
+		C: AuxStore,
+		P::Public: Encode + Decode + Hash + Eq + Clone,
+		Self: SealVerification<B, AuthorityId = AuthorityId<P>, Signature = P::Signature>,
+	{
+		let (sig, seal) = self.verify_seal(&mut header, hash)?;
+
+		let slot = self.verify_pre_digest(&header)?;
+		let authority_index = find_authority_index::<B>(&header)?;
+		check_authority_index::<B, _>(authority_index, authorities, None, hash)?;
+
+		if *slot > (*slot_now).saturating_add(self.max_future_slots) {
+			Err(aura_err(Error::BlockTooFarInFuture { block_slot: slot, now_slot: slot_now }))
+		} else if slot > slot_now {
+			header.digest_mut().push(seal);
+			Ok(CheckedHeader::Deferred(header, slot))
+		} else {
+			// check the signature is valid under the expected authority and
+			// chain state.
+			let message = self.seal_message(&header, slot);
+			let expected_author = if is_secondary_slot::<B>(&header) {
+				if !self.accept_secondary_slots {
+					return Err(aura_err(Error::UnexpectedSecondarySlot(hash)))
+				}
+				check_author::<B, P>(
+					slot,
+					&message,
+					&sig,
+					authorities,
+					self.authority_weights.as_ref(),
+					None,
+					true,
+					hash,
+				)?
+			} else {
+				self.verify_author(slot, &message, &sig, authorities, hash)?
+			};
+			let author = Some(expected_author);
+			check_authority_index::<B, _>(authority_index, authorities, author, hash)?;
+
+			if self.check_for_equivocation.check_for_equivocation() {
+				if let Some(equivocation_proof) = check_equivocation(
+					&*self.client,
+					slot_now,
+					slot,
+					&header,
+					expected_author,
+					self.equivocation_window_slots,
+				)
+				.map_err(Error::Client)?
+				{
+					info!(
+						target: "aura",
+						"Slot author is equivocating at slot {} with headers {:?} and {:?}",
+						slot,
+						equivocation_proof.first_header.hash(),
+						equivocation_proof.second_header.hash(),
+					);
+
+					if let Some(hook) = self.equivocation_hook.as_ref() {
+						hook.lock().notify(
+							&equivocation_proof.first_header,
+							&equivocation_proof.second_header,
+						);
+					}
+
+					if let Some(reporter) = self.equivocation_reporter.as_ref() {
+						if !reporter.lock().report(equivocation_proof) {
+							warn!(
+								target: "aura",
+								"Dropped report of equivocation at slot {}, the channel is full or closed.",
+								slot,
+							);
+							if let Some(metrics) = self.metrics.as_ref() {
+								metrics.dropped_equivocation_reports.inc();
+							}
+						}
+					}
+				}
+			}
+
+			Ok(CheckedHeader::Checked(header, (slot, seal)))
+		}
+	}
+
 	async fn check_inherents<B: BlockT>(
 		&self,
 		block: B,
@@ -183,10 +689,119 @@ where
 
 		Ok(())
 	}
+
+	/// Check that the timestamp set by `block` is consistent with its `slot`.
+	///
+	/// The timestamp inherent data is replaced by the start of the slot and the inherents are
+	/// checked again, so the runtime validates the block's timestamp against the slot instead of
+	/// the local clock, within the runtime's own drift tolerance.
+	fn check_timestamp_consistency<B: BlockT>(
+		&self,
+		block: B,
+		hash: B::Hash,
+		block_id: BlockId<B>,
+		mut inherent_data: InherentData,
+		slot: Slot,
+		execution_context: ExecutionContext,
+	) -> Result<(), Error<B>>
+	where
+		C: ProvideRuntimeApi<B>,
+		C::Api: BlockBuilderApi<B> + AuraApi<B, AuthorityId<P>>,
+		P: Pair,
+		P::Public: Codec,
+	{
+		let runtime_api = self.client.runtime_api();
+		let slot_duration =
+			match scheduled_slot_duration(&self.slot_duration_schedule, block.header().number()) {
+				Some(slot_duration) => slot_duration,
+				None => runtime_api.slot_duration(&block_id).map_err(|e| Error::Client(e.into()))?,
+			};
+
+		inherent_data.replace_data(
+			sp_timestamp::INHERENT_IDENTIFIER,
+			&slot_start_timestamp(slot, slot_duration, self.genesis_timestamp),
+		);
+
+		let inherent_res = runtime_api
+			.check_inherents_with_context(&block_id, execution_context, block, inherent_data)
+			.map_err(|e| Error::Client(e.into()))?;
+
+		if inherent_res
+			.into_errors()
+			.any(|(identifier, _)| identifier == sp_timestamp::INHERENT_IDENTIFIER)
+		{
+			return Err(aura_err(Error::TimestampSlotMismatch(hash, slot)))
+		}
+
+		Ok(())
+	}
+}
+
+impl<B, C, P, CAW, CIDP> SealVerification<B>
+	for AuraVerifier<C, P, CAW, CIDP, NumberFor<B>, B::Header>
+where
+	B: BlockT,
+	P: Pair,
+	P::Signature: Codec,
+{
+	type AuthorityId = AuthorityId<P>;
+	type Signature = P::Signature;
+
+	fn verify_pre_digest(&self, header: &B::Header) -> Result<Slot, Error<B>> {
+		find_pre_digest_with_engine_id::<B>(header, self.engine_id)
+	}
+
+	fn verify_seal(
+		&self,
+		header: &mut B::Header,
+		hash: B::Hash,
+	) -> Result<(P::Signature, DigestItem), Error<B>> {
+		take_seal::<B, P::Signature>(header, hash, self.engine_id)
+	}
+
+	fn seal_message(&self, header: &B::Header, slot: Slot) -> Vec<u8> {
+		seal_message::<B>(*header.number(), slot, &header.hash(), &self.compatibility_mode)
+	}
+
+	fn verify_author<'a>(
+		&self,
+		slot: Slot,
+		message: &[u8],
+		signature: &P::Signature,
+		authorities: &'a [AuthorityId<P>],
+		hash: B::Hash,
+	) -> Result<&'a AuthorityId<P>, Error<B>> {
+		// the seal of a preverified block is known to be signed by `signer`.
+		if let Some(signer) = self.preverified_seals.as_ref().and_then(|seals| seals.take(&hash)) {
+			let expected_author = expected_slot_author::<P>(
+				slot,
+				authorities,
+				self.authority_weights.as_ref(),
+				self.offline_authorities.as_ref(),
+				false,
+			)
+			.ok_or(Error::SlotAuthorNotFound)?;
+			if *expected_author == signer {
+				return Ok(expected_author)
+			}
+		}
+
+		check_author::<B, P>(
+			slot,
+			message,
+			signature,
+			authorities,
+			self.authority_weights.as_ref(),
+			self.offline_authorities.as_ref(),
+			false,
+			hash,
+		)
+	}
 }
 
 #[async_trait::async_trait]
-impl<B: BlockT, C, P, CAW, CIDP> Verifier<B> for AuraVerifier<C, P, CAW, CIDP, NumberFor<B>>
+impl<B: BlockT, C, P, CAW, CIDP> Verifier<B>
+	for AuraVerifier<C, P, CAW, CIDP, NumberFor<B>, B::Header>
 where
 	C: ProvideRuntimeApi<B> + Send + Sync + sc_client_api::backend::AuxStore + BlockOf,
 	C::Api: BlockBuilderApi<B> + AuraApi<B, AuthorityId<P>> + ApiExt<B>,
@@ -203,40 +818,141 @@ where
 	) -> Result<(BlockImportParams<B, ()>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String> {
 		let hash = block.header.hash();
 		let parent_hash = *block.header.parent_hash();
-		let authorities = authorities(
-			self.client.as_ref(),
-			parent_hash,
-			*block.header.number(),
-			&self.compatibility_mode,
-		)
-		.map_err(|e| format!("Could not fetch authorities at {:?}: {}", parent_hash, e))?;
+		let number = *block.header.number();
+		let tracked_authorities = self
+			.consensus_log_authorities
+			.as_ref()
+			.and_then(|tracked| tracked.get(&parent_hash))
+			.cloned();
+		let authorities = match tracked_authorities {
+			Some(authorities) => authorities,
+			None => fetch_authorities(
+				self.client.as_ref(),
+				&AuraApiAuthorities,
+				parent_hash,
+				*block.header.number(),
+				&self.compatibility_mode,
+			)
+			.map_err(|e| {
+				let e = self.note_rejection(e);
+				format!("Could not fetch authorities at {:?}: {}", parent_hash, e)
+			})?,
+		};
 
 		let create_inherent_data_providers = self
 			.create_inherent_data_providers
 			.create_inherent_data_providers(parent_hash, ())
 			.await
-			.map_err(|e| Error::<B>::Client(sp_blockchain::Error::Application(e)))?;
+			.map_err(|e| {
+				self.note_rejection(Error::<B>::Client(sp_blockchain::Error::Application(e)))
+			})?;
 
 		let mut inherent_data = create_inherent_data_providers
 			.create_inherent_data()
-			.map_err(Error::<B>::Inherent)?;
+			.map_err(|e| self.note_rejection(Error::<B>::Inherent(e)))?;
+
+		// blocks from before a slot duration change count their slots in the old duration.
+		// chains with a genesis offset count the current slot themselves, as the slot of the
+		// inherent data providers may count from the Unix epoch.
+		let slot_duration = match scheduled_slot_duration(&self.slot_duration_schedule, &number) {
+			Some(slot_duration) => Some(slot_duration),
+			None if self.genesis_timestamp != 0 => Some(
+				self.client
+					.runtime_api()
+					.slot_duration(&BlockId::Hash(parent_hash))
+					.map_err(|e| self.note_rejection(Error::<B>::Client(e.into())))?,
+			),
+			None => None,
+		};
+		let slot_now = match slot_duration {
+			Some(slot_duration) => slot_at_timestamp(
+				create_inherent_data_providers.timestamp(),
+				slot_duration,
+				self.genesis_timestamp,
+			),
+			None => create_inherent_data_providers.slot(),
+		};
 
-		let slot_now = create_inherent_data_providers.slot();
+		let handover_header =
+			in_handover_window(self.handover_window.as_ref(), &number).then(|| block.header.clone());
 
 		// we add one to allow for some small drift.
 		// FIXME #1019 in the future, alter this queue to allow deferring of
 		// headers
-		let checked_header = check_header::<C, B, P>(
-			&self.client,
+		let (checked_header, authorities) = match self.check_header::<B>(
 			slot_now + 1,
 			block.header,
 			hash,
 			&authorities[..],
-			self.check_for_equivocation,
-		)
-		.map_err(|e| e.to_string())?;
+		) {
+			Err(Error::BadSignature(_) | Error::InvalidAuthorityIndex { .. })
+				if handover_header.is_some() =>
+			{
+				// Within the handover window the block may also be sealed by the set that is
+				// enacted by the block itself, i.e. the set at `parent + 1`.
+				let handover_mode = match self.compatibility_mode.behavior_at(&number) {
+					StageBehavior::UseInitializeBlock => CompatibilityMode::None,
+					StageBehavior::None | StageBehavior::LegacySealFormat =>
+						CompatibilityMode::UseInitializeBlock { until: number + 1u32.into() },
+				};
+				let next_authorities = fetch_authorities(
+					self.client.as_ref(),
+					&AuraApiAuthorities,
+					parent_hash,
+					number,
+					&handover_mode,
+				)
+				.map_err(|e| {
+					let e = self.note_rejection(e);
+					format!("Could not fetch handover authorities at {:?}: {}", parent_hash, e)
+				})?;
+
+				debug!(
+					target: "aura",
+					"Verifying {:?} against the handover authority set at #{:?}.",
+					hash,
+					number,
+				);
+
+				let checked_header = self
+					.check_header::<B>(
+						slot_now + 1,
+						handover_header.expect("matched on `is_some` above; qed"),
+						hash,
+						&next_authorities[..],
+					)
+					.map_err(|e| self.note_rejection(e))?;
+
+				(checked_header, next_authorities)
+			},
+			res => (res.map_err(|e| self.note_rejection(e))?, authorities),
+		};
 		match checked_header {
-			CheckedHeader::Checked(pre_header, (slot, seal)) => {
+			CheckedHeader::Checked(mut pre_header, (slot, seal)) => {
+				// the digests of a pre-seal hook are covered by the seal, but are no more part of
+				// the header the runtime executes than the seal itself.
+				let pre_seal_digests = take_pre_seal_digests::<B>(&mut pre_header, self.engine_id);
+				let author = expected_slot_author::<P>(
+					slot,
+					&authorities,
+					self.authority_weights.as_ref(),
+					self.offline_authorities.as_ref(),
+					is_secondary_slot::<B>(&pre_header),
+				)
+				.cloned();
+
+				if let (Some(tracked), Some(author)) = (self.monotonic_slots.as_ref(), &author) {
+					if let Some(previous_slot) = tracked.violation(&parent_hash, author, slot) {
+						return Err(self
+							.note_rejection(aura_err(Error::<B>::NonMonotonicSlot {
+								hash,
+								slot,
+								previous_slot,
+							}))
+							.to_string())
+					}
+				}
+
 				// if the body is passed through, we need to use the runtime
 				// to check that the internally-set timestamp in the inherents
 				// actually matches the slot set in the seal.
@@ -245,16 +961,20 @@ where
 
 					inherent_data.aura_replace_inherent_data(slot);
 
-					// skip the inherents verification if the runtime API is old.
-					if self
-						.client
-						.runtime_api()
-						.has_api_with::<dyn BlockBuilderApi<B>, _>(
-							&BlockId::Hash(parent_hash),
-							|v| v >= 2,
-						)
-						.map_err(|e| e.to_string())?
+					// skip the inherents verification if the runtime API is old, or if blocks are
+					// not executed at all.
+					if !self.verify_seals_only &&
+						self.client
+							.runtime_api()
+							.has_api_with::<dyn BlockBuilderApi<B>, _>(
+								&BlockId::Hash(parent_hash),
+								|v| v >= 2,
+							)
+							.map_err(|e| e.to_string())?
 					{
+						let timestamp_inherent_data =
+							self.check_timestamp_slot_consistency.then(|| inherent_data.clone());
+
 						self.check_inherents(
 							new_block.clone(),
 							BlockId::Hash(parent_hash),
@@ -263,7 +983,19 @@ where
 							block.origin.into(),
 						)
 						.await
-						.map_err(|e| e.to_string())?;
+						.map_err(|e| self.note_rejection(e))?;
+
+						if let Some(inherent_data) = timestamp_inherent_data {
+							self.check_timestamp_consistency(
+								new_block.clone(),
+								hash,
+								BlockId::Hash(parent_hash),
+								inherent_data,
+								slot,
+								block.origin.into(),
+							)
+							.map_err(|e| self.note_rejection(e))?;
+						}
 					}
 
 					let (_, inner_body) = new_block.deconstruct();
@@ -278,15 +1010,34 @@ where
 					"pre_header" => ?pre_header,
 				);
 
+				if let Some(author) = author {
+					if let Some(tracked) = self.monotonic_slots.as_mut() {
+						tracked.note_block(hash, &parent_hash, author.clone(), slot);
+					}
+					note_last_seen(&mut self.last_seen, author, slot, number, &authorities);
+				}
+
+				if let Some(tracked) = self.consensus_log_authorities.as_mut() {
+					let change = find_authorities_change::<B, AuthorityId<P>>(&pre_header);
+					tracked.note_block(hash, authorities, change);
+				}
+
 				block.header = pre_header;
+				block.post_digests.extend(pre_seal_digests);
 				block.post_digests.push(seal);
 				block.fork_choice = Some(ForkChoiceStrategy::LongestChain);
 				block.post_hash = Some(hash);
+				if self.verify_seals_only {
+					block.state_action = StateAction::Skip;
+				}
 
 				Ok((block, None))
 			},
 			CheckedHeader::Deferred(a, b) => {
 				debug!(target: "aura", "Checking {:?} failed; {:?}, {:?}.", hash, a, b);
+				if let Some(metrics) = self.metrics.as_ref() {
+					metrics.note_deferred((*b).saturating_sub(*slot_now + 1));
+				}
 				telemetry!(
 					self.telemetry;
 					CONSENSUS_DEBUG;
@@ -326,7 +1077,10 @@ impl Default for CheckForEquivocation {
 }
 
 /// Parameters of [`import_queue`].
-pub struct ImportQueueParams<'a, Block: BlockT, I, C, S, CAW, CIDP> {
+///
+/// Build them with [`ImportQueueParamsBuilder`](crate::ImportQueueParamsBuilder), which has
+/// defaults for the optional parameters, or spell out every field.
+pub struct ImportQueueParams<'a, Block: BlockT, I, C, S, CAW, CIDP, E, A> {
 	/// The block import to use.
 	pub block_import: I,
 	/// The justification import.
@@ -349,6 +1103,60 @@ pub struct ImportQueueParams<'a, Block: BlockT, I, C, S, CAW, CIDP> {
 	///
 	/// If in doubt, use `Default::default()`.
 	pub compatibility_mode: CompatibilityMode<NumberFor<Block>>,
+	/// Track the authority set from `ConsensusLog::AuthoritiesChange` digests instead of
+	/// calling into the runtime for every block.
+	///
+	/// See [`BuildVerifierParams::use_consensus_log_authorities`].
+	pub use_consensus_log_authorities: bool,
+	/// Block-number window in which seals of the next authority set are accepted as well.
+	///
+	/// See [`BuildVerifierParams::handover_window`].
+	pub handover_window: Option<(NumberFor<Block>, NumberFor<Block>)>,
+	/// Reject blocks whose timestamp inherent contradicts their slot.
+	///
+	/// See [`BuildVerifierParams::check_timestamp_slot_consistency`].
+	pub check_timestamp_slot_consistency: bool,
+	/// Historical slot durations, as `(from_block, slot_duration)` entries.
+	///
+	/// See [`BuildVerifierParams::slot_duration_schedule`].
+	pub slot_duration_schedule: Vec<(NumberFor<Block>, SlotDuration)>,
+	/// Channel that receives every newly detected equivocation.
+	///
+	/// See [`BuildVerifierParams::equivocation_sender`].
+	pub equivocation_sender: Option<mpsc::Sender<E>>,
+	/// Weights the slot authors are selected by instead of round-robin.
+	///
+	/// See [`BuildVerifierParams::authority_weights`].
+	pub authority_weights: Option<AuthorityWeights>,
+	/// Hook called with the two headers of every newly detected equivocation.
+	///
+	/// See [`BuildVerifierParams::on_equivocation`].
+	pub on_equivocation: Option<Box<dyn Fn(&Block::Header, &Block::Header) + Send + Sync>>,
+	/// Accept blocks authored in secondary slots.
+	///
+	/// See [`BuildVerifierParams::accept_secondary_slots`].
+	pub accept_secondary_slots: bool,
+	/// See [`BuildVerifierParams::equivocation_window_slots`].
+	pub equivocation_window_slots: u64,
+	/// See [`BuildVerifierParams::offline_authorities`].
+	pub offline_authorities: Option<OfflineAuthorities<A>>,
+	/// See [`BuildVerifierParams::max_future_slots`].
+	pub max_future_slots: u64,
+	/// See [`BuildVerifierParams::engine_id`].
+	pub engine_id: ConsensusEngineId,
+	/// See [`BuildVerifierParams::enforce_monotonic_slots`].
+	pub enforce_monotonic_slots: bool,
+	/// See [`BuildVerifierParams::verify_seals_only`].
+	pub verify_seals_only: bool,
+	/// See [`BuildVerifierParams::genesis_timestamp`].
+	pub genesis_timestamp: u64,
+	/// Number of threads verifying the seals of incoming blocks in parallel.
+	///
+	/// With more than one thread, the seals of each batch of incoming blocks, e.g. during
+	/// initial sync, are checked in parallel before the batch is handed to the import queue.
+	/// The verifier then skips the signature check of every preverified seal, all other checks
+	/// stay serial and blocks are imported in order. Use `1` to verify everything serially.
+	pub verification_concurrency: usize,
 }
 
 /// Start an import queue for the Aura consensus algorithm.
@@ -364,8 +1172,24 @@ pub fn import_queue<P, Block, I, C, S, CAW, CIDP>(
 		check_for_equivocation,
 		telemetry,
 		compatibility_mode,
-	}: ImportQueueParams<Block, I, C, S, CAW, CIDP>,
-) -> Result<DefaultImportQueue<Block, C>, sp_consensus::Error>
+		use_consensus_log_authorities,
+		handover_window,
+		check_timestamp_slot_consistency,
+		slot_duration_schedule,
+		equivocation_sender,
+		authority_weights,
+		on_equivocation,
+		accept_secondary_slots,
+		equivocation_window_slots,
+		offline_authorities,
+		max_future_slots,
+		engine_id,
+		enforce_monotonic_slots,
+		verify_seals_only,
+		genesis_timestamp,
+		verification_concurrency,
+	}: ImportQueueParams<Block, I, C, S, CAW, CIDP, EquivocationProof<Block, P>, AuthorityId<P>>,
+) -> Result<AuraImportQueue<Block, C>, sp_consensus::Error>
 where
 	Block: BlockT,
 	C::Api: BlockBuilderApi<Block> + AuraApi<Block, AuthorityId<P>> + ApiExt<Block>,
@@ -389,20 +1213,62 @@ where
 	CIDP: CreateInherentDataProviders<Block, ()> + Sync + Send + 'static,
 	CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
 {
-	let verifier = build_verifier::<P, _, _, _, _>(BuildVerifierParams {
-		client,
+	let verifier = build_verifier::<P, _, _, _, _, _>(BuildVerifierParams {
+		client: client.clone(),
 		create_inherent_data_providers,
 		can_author_with,
 		check_for_equivocation,
 		telemetry,
-		compatibility_mode,
+		compatibility_mode: compatibility_mode.clone(),
+		use_consensus_log_authorities,
+		registry: registry.cloned(),
+		handover_window,
+		check_timestamp_slot_consistency,
+		slot_duration_schedule,
+		equivocation_sender,
+		authority_weights: authority_weights.clone(),
+		on_equivocation,
+		accept_secondary_slots,
+		equivocation_window_slots,
+		offline_authorities,
+		max_future_slots,
+		engine_id,
+		enforce_monotonic_slots,
+		verify_seals_only,
+		genesis_timestamp,
 	});
 
-	Ok(BasicQueue::new(verifier, Box::new(block_import), justification_import, spawner, registry))
+	let block_import = Box::new(block_import);
+	if verification_concurrency <= 1 {
+		let queue =
+			BasicQueue::new(verifier, block_import, justification_import, spawner, registry);
+		return Ok(SealPreverifyingQueue::new(queue))
+	}
+
+	let preverified_seals = PreverifiedSeals::new();
+	let preverifier = SealPreverifier::new(
+		client,
+		compatibility_mode,
+		authority_weights,
+		verification_concurrency,
+		engine_id,
+		preverified_seals.clone(),
+	);
+	let verifier = verifier.with_preverified_seals(preverified_seals);
+	let queue = BasicQueue::new(verifier, block_import, justification_import, spawner, registry);
+
+	let (queue, worker) = SealPreverifyingQueue::with_preverifier(queue, preverifier);
+	spawner.spawn_essential_blocking(
+		"aura-seal-preverification",
+		Some("block-import"),
+		Box::pin(worker),
+	);
+
+	Ok(queue)
 }
 
 /// Parameters of [`build_verifier`].
-pub struct BuildVerifierParams<C, CIDP, CAW, N> {
+pub struct BuildVerifierParams<C, CIDP, CAW, N, E, H, A> {
 	/// The client to interact with the chain.
 	pub client: Arc<C>,
 	/// Something that can create the inherent data providers.
@@ -417,10 +1283,133 @@ pub struct BuildVerifierParams<C, CIDP, CAW, N> {
 	///
 	/// If in doubt, use `Default::default()`.
 	pub compatibility_mode: CompatibilityMode<N>,
+	/// Track the authority set from `ConsensusLog::AuthoritiesChange` digests instead of
+	/// calling into the runtime for every block.
+	///
+	/// The runtime is still queried whenever the set for a parent block is unknown, e.g. for
+	/// the first block imported after a restart. Only enable this on chains that deposit an
+	/// `AuthoritiesChange` log in every block that changes the authority set.
+	pub use_consensus_log_authorities: bool,
+	/// The prometheus registry the verifier metrics are registered at.
+	pub registry: Option<Registry>,
+	/// Inclusive block-number window of a planned authority-set handover.
+	///
+	/// Blocks numbered within the window are accepted when sealed by either the authority set
+	/// at their parent or the set at `parent + 1`, i.e. the set the block itself enacts.
+	///
+	/// This is a security relaxation: during the window both the outgoing and the incoming set
+	/// can author, so a slot can legitimately have two different authors and equivocation
+	/// detection only applies per set. Keep the window as short as possible and make sure it
+	/// covers only the blocks around the planned set change.
+	pub handover_window: Option<(N, N)>,
+	/// Reject blocks whose timestamp inherent contradicts their slot.
+	///
+	/// The block's timestamp is checked by the runtime against `slot * slot_duration`, within
+	/// the runtime's timestamp drift tolerance. This catches authors that manipulate their
+	/// clock to claim slots out of order, at the cost of a second `check_inherents` call for
+	/// every block with a body.
+	pub check_timestamp_slot_consistency: bool,
+	/// Historical slot durations, as `(from_block, slot_duration)` entries.
+	///
+	/// Each entry applies from its block number until the next entry. The time-based checks of
+	/// a block, i.e. whether its slot is in the future and whether its timestamp matches its
+	/// slot, use the duration of the entry covering the block's number. Blocks below the first
+	/// entry, or all blocks if the schedule is empty, use the current slot duration.
+	///
+	/// This is needed to verify chains whose slot duration changed, e.g. when bridging them.
+	pub slot_duration_schedule: Vec<(N, SlotDuration)>,
+	/// Channel that receives every newly detected equivocation, as an [`EquivocationProof`].
+	///
+	/// Requires [`Self::check_for_equivocation`]. Each offender is reported at most once per
+	/// slot. The channel is bounded and never waited on: proofs that don't fit are dropped,
+	/// which is logged and counted in the `aura_verifier_equivocation_reports_dropped_total`
+	/// metric.
+	pub equivocation_sender: Option<mpsc::Sender<E>>,
+	/// Weights the slot authors are selected by instead of round-robin, see
+	/// [`AuthorityWeights`].
+	///
+	/// Must match the weights the authoring nodes are configured with, as blocks sealed by
+	/// any other author than the one selected by the weights are rejected.
+	pub authority_weights: Option<AuthorityWeights>,
+	/// Hook called with the two headers of every newly detected equivocation, i.e. two blocks
+	/// sealed by the same author for the same slot.
+	///
+	/// Requires [`Self::check_for_equivocation`]. The hook is called once per pair of headers,
+	/// e.g. to submit an offence report to the runtime. It runs on the import queue, so it
+	/// should hand off any expensive work.
+	pub on_equivocation: Option<Box<dyn Fn(&H, &H) + Send + Sync>>,
+	/// Accept blocks authored in secondary slots.
+	///
+	/// Blocks marked with a [`SECONDARY_SLOT_ENGINE_ID`](crate::SECONDARY_SLOT_ENGINE_ID)
+	/// pre-runtime digest have to be sealed by the secondary author of their slot, see
+	/// [`BuildAuraWorkerParams::secondary_slot_delay`](crate::BuildAuraWorkerParams) for the
+	/// tradeoffs. Without this they are rejected, so all nodes of a network have to agree on it.
+	pub accept_secondary_slots: bool,
+	/// The number of slots before the current slot whose headers are kept to detect
+	/// equivocations, see [`Self::check_for_equivocation`].
+	///
+	/// The headers are kept in the aux store, so equivocations spanning a restart of the node are
+	/// detected. Headers of older slots are not checked for equivocation, and are pruned once
+	/// they are twice the window behind. Defaults to
+	/// [`MAX_SLOT_CAPACITY`](sc_consensus_slots::MAX_SLOT_CAPACITY), the window of
+	/// [`sc_consensus_slots::check_equivocation`].
+	pub equivocation_window_slots: u64,
+	/// Flags the authorities that are known to be offline, so their slots fall through to the
+	/// next authority of the set that is not flagged.
+	///
+	/// This has to be the predicate given to the worker as
+	/// [`BuildAuraWorkerParams::offline_authorities`](crate::BuildAuraWorkerParams), see there
+	/// for the safety requirements. `None` expects the plain schedule.
+	pub offline_authorities: Option<OfflineAuthorities<A>>,
+	/// The number of slots a block may be ahead of the current slot, allowing for a slot of
+	/// drift, and still be deferred.
+	///
+	/// Deferred blocks are not imported now, but are accepted once they are received again after
+	/// their slot has come. Blocks further ahead are rejected with
+	/// [`Error::BlockTooFarInFuture`].
+	pub max_future_slots: u64,
+	/// The consensus engine ID of the pre-runtime digest and the seal of blocks.
+	///
+	/// This has to be the ID given to the worker as
+	/// [`BuildAuraWorkerParams::engine_id`](crate::BuildAuraWorkerParams), blocks with another
+	/// ID are rejected. Changing it is a hard fork. The default is [`AURA_ENGINE_ID`].
+	pub engine_id: ConsensusEngineId,
+	/// Reject blocks whose slot is not above every earlier slot of their author on the same
+	/// chain, with [`Error::NonMonotonicSlot`].
+	///
+	/// This hardens against replaying an author's old slots through a reorg, which equivocation
+	/// detection does not catch as the slots differ. Blocks are only compared against their own
+	/// ancestors, so an author sealing blocks on competing forks is not affected. The slots are
+	/// tracked in memory for the most recently verified blocks only, so the first block verified
+	/// on top of an untracked parent, e.g. after a restart, is accepted unchecked.
+	pub enforce_monotonic_slots: bool,
+	/// Verify the seals and slots of blocks, but import them without executing them.
+	///
+	/// Meant for backfilling an archive node from a trusted snapshot of sealed blocks, relying on
+	/// the state of the snapshot. Seals and slots are checked as usual, but the inherents are
+	/// not, and blocks are imported with `StateAction::Skip`. **Nothing but the header is
+	/// verified:** the seal covers the extrinsics root, but neither is the body checked against
+	/// it nor is the state root checked against the executed body. Only use this with blocks
+	/// from a source trusted not to have tampered with them.
+	///
+	/// As the imported blocks have no state, this implies
+	/// [`Self::use_consensus_log_authorities`]: the authority sets are tracked from the digests,
+	/// starting from the state of the parent of the first block. The blocks have to be imported
+	/// in one run on top of a block with state, as the tracked sets are lost on a restart.
+	pub verify_seals_only: bool,
+	/// The Unix timestamp in milliseconds at which slot 0 starts, for chains whose slots are
+	/// offset from the Unix epoch.
+	///
+	/// The current slot, which blocks must not be too far ahead of, and the start of the slot of
+	/// a block are computed from it, the way the runtime of such a chain counts its slots. The
+	/// inherent data providers of the verifier and the worker have to count their slots the same
+	/// way, see [`crate::timestamp_to_slot_with_offset`]. `0` counts the slots from the Unix
+	/// epoch, as `pallet-aura` does.
+	pub genesis_timestamp: u64,
 }
 
 /// Build the [`AuraVerifier`]
-pub fn build_verifier<P, C, CIDP, CAW, N>(
+pub fn build_verifier<P, C, CIDP, CAW, N, H>(
 	BuildVerifierParams {
 		client,
 		create_inherent_data_providers,
@@ -428,14 +1417,707 @@ pub fn build_verifier<P, C, CIDP, CAW, N>(
 		check_for_equivocation,
 		telemetry,
 		compatibility_mode,
-	}: BuildVerifierParams<C, CIDP, CAW, N>,
-) -> AuraVerifier<C, P, CAW, CIDP, N> {
-	AuraVerifier::<_, P, _, _, _>::new(
+		use_consensus_log_authorities,
+		registry,
+		handover_window,
+		check_timestamp_slot_consistency,
+		slot_duration_schedule,
+		equivocation_sender,
+		authority_weights,
+		on_equivocation,
+		accept_secondary_slots,
+		equivocation_window_slots,
+		offline_authorities,
+		max_future_slots,
+		engine_id,
+		enforce_monotonic_slots,
+		verify_seals_only,
+		genesis_timestamp,
+	}: BuildVerifierParams<
+		C,
+		CIDP,
+		CAW,
+		N,
+		sp_consensus_slots::EquivocationProof<H, AuthorityId<P>>,
+		H,
+		AuthorityId<P>,
+	>,
+) -> AuraVerifier<C, P, CAW, CIDP, N, H>
+where
+	P: Pair,
+	H: Header,
+{
+	let metrics = match registry.as_ref().map(VerifierMetrics::register) {
+		Some(Ok(metrics)) => Some(metrics),
+		Some(Err(err)) => {
+			warn!(target: "aura", "Failed to register Prometheus metrics: {}", err);
+			None
+		},
+		None => None,
+	};
+
+	AuraVerifier::<_, P, _, _, _, _>::new(
 		client,
 		create_inherent_data_providers,
 		can_author_with,
 		check_for_equivocation,
 		telemetry,
 		compatibility_mode,
+		use_consensus_log_authorities,
+		metrics,
+		handover_window,
+		check_timestamp_slot_consistency,
+		slot_duration_schedule,
+		equivocation_sender,
+		authority_weights,
+		on_equivocation,
+		accept_secondary_slots,
+		equivocation_window_slots,
+		offline_authorities,
+		max_future_slots,
+		engine_id,
+		enforce_monotonic_slots,
+		verify_seals_only,
+		genesis_timestamp,
 	)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_consensus_aura::{digests::CompatibleDigestItem, ed25519::AuthorityId};
+	use sp_keyring::Ed25519Keyring;
+	use sp_runtime::Digest;
+	use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+	fn authorities(keys: &[Ed25519Keyring]) -> Vec<AuthorityId> {
+		keys.iter().map(|key| key.public().into()).collect()
+	}
+
+	fn header_with_logs(number: u64, logs: Vec<DigestItem>) -> TestHeader {
+		TestHeader::new(number, Default::default(), Default::default(), Default::default(), Digest { logs })
+	}
+
+	#[test]
+	fn set_change_digest_updates_tracked_authorities() {
+		let initial = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		let next = authorities(&[Ed25519Keyring::Charlie]);
+		let mut tracked = ConsensusLogAuthorities::<u64, AuthorityId>::new();
+
+		let plain = header_with_logs(1, vec![]);
+		tracked.note_block(1, initial.clone(), find_authorities_change::<Block, _>(&plain));
+		assert_eq!(tracked.get(&1), Some(&initial));
+
+		let change = header_with_logs(
+			2,
+			vec![DigestItem::Consensus(
+				AURA_ENGINE_ID,
+				ConsensusLog::AuthoritiesChange(next.clone()).encode(),
+			)],
+		);
+		tracked.note_block(2, initial.clone(), find_authorities_change::<Block, _>(&change));
+		assert_eq!(tracked.get(&2), Some(&next));
+		assert_eq!(tracked.get(&1), Some(&initial));
+	}
+
+	#[test]
+	fn foreign_consensus_logs_are_ignored() {
+		let header = header_with_logs(
+			1,
+			vec![DigestItem::Consensus(
+				*b"FRNK",
+				ConsensusLog::AuthoritiesChange(authorities(&[Ed25519Keyring::Dave])).encode(),
+			)],
+		);
+		assert!(find_authorities_change::<Block, AuthorityId>(&header).is_none());
+	}
+
+	#[test]
+	fn inconsistent_timestamp_does_not_match_slot_start() {
+		let slot_duration = SlotDuration::from_millis(6000);
+
+		assert_eq!(*slot_start_timestamp(0.into(), slot_duration, 0), 0);
+		assert_eq!(*slot_start_timestamp(10.into(), slot_duration, 0), 60_000);
+		assert_ne!(*slot_start_timestamp(10.into(), slot_duration, 0), 120_000);
+		assert_eq!(*slot_start_timestamp(u64::MAX.into(), slot_duration, 0), u64::MAX);
+		assert_eq!(*slot_start_timestamp(10.into(), slot_duration, 500), 60_500);
+	}
+
+	#[test]
+	fn handover_window_is_inclusive() {
+		let window = Some((10u64, 20u64));
+
+		assert!(!in_handover_window(window.as_ref(), &9));
+		assert!(in_handover_window(window.as_ref(), &10));
+		assert!(in_handover_window(window.as_ref(), &15));
+		assert!(in_handover_window(window.as_ref(), &20));
+		assert!(!in_handover_window(window.as_ref(), &21));
+		assert!(!in_handover_window(None, &15u64));
+	}
+
+	#[test]
+	fn tracked_authorities_are_bounded() {
+		let initial = authorities(&[Ed25519Keyring::Alice]);
+		let mut tracked = ConsensusLogAuthorities::<u64, AuthorityId>::new();
+
+		for hash in 0..(MAX_TRACKED_AUTHORITY_SETS as u64 + 10) {
+			tracked.note_block(hash, initial.clone(), None);
+		}
+
+		assert!(tracked.get(&0).is_none());
+		assert!(tracked.get(&(MAX_TRACKED_AUTHORITY_SETS as u64 + 9)).is_some());
+		assert_eq!(tracked.sets.len(), MAX_TRACKED_AUTHORITY_SETS);
+	}
+
+	#[test]
+	fn last_seen_keeps_latest_slot_of_current_authorities() {
+		let initial = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		let next = authorities(&[Ed25519Keyring::Bob, Ed25519Keyring::Charlie]);
+		let mut last_seen = HashMap::new();
+
+		note_last_seen(&mut last_seen, initial[0].clone(), 4.into(), 10u64, &initial);
+		note_last_seen(&mut last_seen, initial[1].clone(), 5.into(), 11, &initial);
+		// a block of an older slot on a fork doesn't replace the latest one
+		note_last_seen(&mut last_seen, initial[1].clone(), 3.into(), 9, &initial);
+		assert_eq!(last_seen.get(&initial[0]), Some(&(4.into(), 10)));
+		assert_eq!(last_seen.get(&initial[1]), Some(&(5.into(), 11)));
+
+		note_last_seen(&mut last_seen, next[1].clone(), 7.into(), 12, &next);
+		assert_eq!(last_seen.len(), 2);
+		assert!(!last_seen.contains_key(&initial[0]));
+		assert_eq!(last_seen.get(&next[0]), Some(&(5.into(), 11)));
+		assert_eq!(last_seen.get(&next[1]), Some(&(7.into(), 12)));
+	}
+
+	#[test]
+	fn slot_duration_schedule_switches_at_change_boundary() {
+		let old = SlotDuration::from_millis(12_000);
+		let new = SlotDuration::from_millis(6_000);
+		let schedule = vec![(100u64, new), (0, old)];
+
+		assert_eq!(scheduled_slot_duration::<u64>(&[], &50), None);
+		assert_eq!(scheduled_slot_duration(&schedule, &0), Some(old));
+		assert_eq!(scheduled_slot_duration(&schedule, &99), Some(old));
+		assert_eq!(scheduled_slot_duration(&schedule, &100), Some(new));
+		assert_eq!(scheduled_slot_duration(&schedule, &1_000), Some(new));
+		assert_eq!(scheduled_slot_duration(&schedule[..1], &99), None);
+
+		let now = sp_timestamp::Timestamp::new(1_200_000);
+		assert_eq!(slot_at_timestamp(now, old, 0), Slot::from(100));
+		assert_eq!(slot_at_timestamp(now, new, 0), Slot::from(200));
+		// a genesis offset shifts the slots of both durations
+		assert_eq!(slot_at_timestamp(now, old, 600_000), Slot::from(50));
+		assert_eq!(slot_at_timestamp(now, new, 600_000), Slot::from(100));
+	}
+
+	#[test]
+	fn check_seal_accepts_only_the_slot_author() {
+		use sp_consensus_aura::ed25519::{AuthorityPair, AuthoritySignature};
+
+		let alice = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+		let mut header = header_with_logs(
+			1,
+			vec![<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(
+				2.into(),
+			)],
+		);
+		let signature = alice.sign(header.hash().as_ref());
+		header
+			.digest_mut()
+			.push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature));
+
+		// slot 2 is Alice's in the first set and Bob's in the second
+		let sealed_by_author = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		let sealed_by_other = authorities(&[Ed25519Keyring::Bob, Ed25519Keyring::Charlie]);
+		let mode = CompatibilityMode::None;
+
+		assert_eq!(
+			check_seal::<Block, AuthorityPair>(
+				header.clone(),
+				&sealed_by_author,
+				None,
+				None,
+				&mode,
+				AURA_ENGINE_ID,
+			)
+			.ok(),
+			Some(2.into()),
+		);
+		assert!(matches!(
+			check_seal::<Block, AuthorityPair>(
+				header.clone(),
+				&sealed_by_other,
+				None,
+				None,
+				&mode,
+				AURA_ENGINE_ID,
+			),
+			Err(Error::BadSignature(_)),
+		));
+
+		// without the seal, the pre-runtime digest is mistaken for it
+		header.digest_mut().pop();
+		assert!(matches!(
+			check_seal::<Block, AuthorityPair>(
+				header,
+				&sealed_by_author,
+				None,
+				None,
+				&mode,
+				AURA_ENGINE_ID,
+			),
+			Err(Error::HeaderBadSeal(_)),
+		));
+	}
+
+	#[test]
+	fn check_seal_expects_the_secondary_author_of_marked_blocks() {
+		use sp_consensus_aura::ed25519::{AuthorityPair, AuthoritySignature};
+
+		let sealed_header = |seed: &str, slot: Slot, secondary: bool| {
+			let pair = AuthorityPair::from_string(seed, None).expect("Valid seed");
+			let pre_digest =
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(slot);
+			let mut logs = vec![pre_digest];
+			if secondary {
+				logs.push(DigestItem::PreRuntime(crate::SECONDARY_SLOT_ENGINE_ID, Vec::new()));
+			}
+			let mut header = header_with_logs(1, logs);
+			let signature = pair.sign(header.hash().as_ref());
+			header.digest_mut().push(
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature),
+			);
+			header
+		};
+		let authorities = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		let check = |header| {
+			check_seal::<Block, AuthorityPair>(
+				header,
+				&authorities,
+				None,
+				None,
+				&CompatibilityMode::None,
+				AURA_ENGINE_ID,
+			)
+		};
+
+		// slot 2 is Alice's, Bob is its secondary author
+		let slot = Slot::from(2);
+		assert!(check(sealed_header("//Bob", slot, true)).is_ok());
+		assert!(matches!(check(sealed_header("//Alice", slot, true)), Err(Error::BadSignature(_))));
+		assert!(matches!(check(sealed_header("//Bob", slot, false)), Err(Error::BadSignature(_))));
+
+		// a single authority has no secondary author
+		let alone = authorities[..1].to_vec();
+		assert!(matches!(
+			check_seal::<Block, AuthorityPair>(
+				sealed_header("//Alice", slot, true),
+				&alone,
+				None,
+				None,
+				&CompatibilityMode::None,
+				AURA_ENGINE_ID,
+			),
+			Err(Error::SlotAuthorNotFound),
+		));
+	}
+
+	#[test]
+	fn check_seal_validates_the_authority_index() {
+		use sp_consensus_aura::ed25519::{AuthorityPair, AuthoritySignature};
+
+		let sealed_header = |index: Option<u32>| {
+			let pair = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+			let pre_digest =
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(2.into());
+			let mut logs = vec![pre_digest];
+			if let Some(index) = index {
+				logs.push(DigestItem::PreRuntime(crate::AUTHORITY_INDEX_ENGINE_ID, index.encode()));
+			}
+			let mut header = header_with_logs(1, logs);
+			let signature = pair.sign(header.hash().as_ref());
+			header.digest_mut().push(
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature),
+			);
+			header
+		};
+		// slot 2 is Alice's, at index 0
+		let authorities = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		let check = |index| {
+			check_seal::<Block, AuthorityPair>(
+				sealed_header(index),
+				&authorities,
+				None,
+				None,
+				&CompatibilityMode::None,
+				AURA_ENGINE_ID,
+			)
+		};
+
+		assert_eq!(check(None).ok(), Some(2.into()));
+		assert_eq!(check(Some(0)).ok(), Some(2.into()));
+		assert!(matches!(check(Some(1)), Err(Error::InvalidAuthorityIndex { index: 1, .. })));
+		assert!(matches!(check(Some(7)), Err(Error::InvalidAuthorityIndex { index: 7, .. })));
+		assert_eq!(find_authority_index::<Block>(&sealed_header(Some(1))).ok(), Some(Some(1)));
+		assert_eq!(find_authority_index::<Block>(&sealed_header(None)).ok(), Some(None));
+	}
+
+	#[test]
+	fn equivocations_are_reported_once_and_dropped_when_channel_is_full() {
+		let offenders = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		let proof = |offender: &AuthorityId, slot: u64| sp_consensus_slots::EquivocationProof {
+			offender: offender.clone(),
+			slot: slot.into(),
+			first_header: header_with_logs(1, vec![]),
+			second_header: header_with_logs(2, vec![]),
+		};
+		let (sender, mut receiver) = mpsc::channel(0);
+		let mut reporter = EquivocationReporter::new(sender);
+
+		assert!(reporter.report(proof(&offenders[0], 3)));
+		// already reported, so it doesn't need room in the channel
+		assert!(reporter.report(proof(&offenders[0], 3)));
+		assert!(!reporter.report(proof(&offenders[1], 3)));
+
+		assert_eq!(receiver.try_next().unwrap(), Some(proof(&offenders[0], 3)));
+		assert!(reporter.report(proof(&offenders[1], 3)));
+		assert_eq!(receiver.try_next().unwrap(), Some(proof(&offenders[1], 3)));
+		assert!(receiver.try_next().is_err());
+	}
+
+	#[test]
+	fn slots_must_increase_per_author_on_the_same_chain() {
+		let mut tracked = MonotonicSlots::<u64, u8>::new();
+		tracked.note_block(1, &0, 0, 5.into());
+		tracked.note_block(2, &1, 1, 6.into());
+
+		// replaying Alice's slot, or an earlier one, on top of her block is rejected
+		assert_eq!(tracked.violation(&2, &0, 5.into()), Some(5.into()));
+		assert_eq!(tracked.violation(&2, &0, 4.into()), Some(5.into()));
+		assert_eq!(tracked.violation(&2, &0, 7.into()), None);
+		// Bob has no earlier block on the chain of block 1
+		assert_eq!(tracked.violation(&1, &1, 2.into()), None);
+		// chains ending at untracked blocks are not checked
+		assert_eq!(tracked.violation(&9, &0, 1.into()), None);
+	}
+
+	#[test]
+	fn slots_on_competing_forks_are_not_compared() {
+		let mut tracked = MonotonicSlots::<u64, u8>::new();
+		// Alice seals block 1 at slot 2, then block 2 at slot 8 on fork A
+		tracked.note_block(1, &0, 0, 2.into());
+		tracked.note_block(2, &1, 0, 8.into());
+
+		// a fork B from block 1 legitimately has Alice seal slot 4, below her slot 8 on fork A
+		assert_eq!(tracked.violation(&1, &0, 4.into()), None);
+		tracked.note_block(3, &1, 0, 4.into());
+
+		// both forks keep their own highest slot of Alice
+		assert_eq!(tracked.violation(&3, &0, 6.into()), None);
+		assert_eq!(tracked.violation(&2, &0, 6.into()), Some(8.into()));
+		assert_eq!(tracked.violation(&3, &0, 4.into()), Some(4.into()));
+	}
+
+	#[test]
+	fn blocks_beyond_the_future_window_are_rejected() {
+		use sp_consensus_aura::ed25519::{AuthorityPair, AuthoritySignature};
+
+		let alice = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+		let sealed_header = |slot: u64| {
+			let pre_digest =
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(
+					slot.into(),
+				);
+			let mut header = TestHeader::new(
+				1,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Digest { logs: vec![pre_digest] },
+			);
+			let signature = alice.sign(header.hash().as_ref());
+			let seal =
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature);
+			header.digest_mut().push(seal);
+			header
+		};
+		let verifier = AuraVerifier::<_, AuthorityPair, (), (), u64, TestHeader>::new(
+			Arc::new(substrate_test_runtime_client::new()),
+			(),
+			(),
+			CheckForEquivocation::No,
+			None,
+			CompatibilityMode::None,
+			false,
+			None,
+			None,
+			false,
+			Vec::new(),
+			None,
+			None,
+			None,
+			false,
+			1000,
+			None,
+			2,
+			AURA_ENGINE_ID,
+			false,
+		);
+		let authorities = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		let check = |slot: u64| {
+			let header = sealed_header(slot);
+			let hash = header.hash();
+			verifier.check_header::<Block>(3.into(), header, hash, &authorities)
+		};
+
+		assert!(matches!(check(2), Ok(CheckedHeader::Checked(..))));
+		assert!(matches!(check(4), Ok(CheckedHeader::Deferred(_, slot)) if *slot == 4));
+		assert!(matches!(check(5), Ok(CheckedHeader::Deferred(_, slot)) if *slot == 5));
+		assert!(matches!(
+			check(6),
+			Err(Error::BlockTooFarInFuture { block_slot, now_slot })
+				if *block_slot == 6 && *now_slot == 3
+		));
+	}
+
+	#[test]
+	fn equivocation_hook_is_called_once_per_conflicting_pair() {
+		use sp_consensus_aura::ed25519::{AuthorityPair, AuthoritySignature};
+		use std::sync::atomic::{AtomicUsize, Ordering};
+
+		let alice = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+		let pre_digest =
+			<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(2.into());
+		let sealed_header = |state_root: u8| {
+			let mut header = TestHeader::new(
+				1,
+				Default::default(),
+				[state_root; 32].into(),
+				Default::default(),
+				Digest { logs: vec![pre_digest.clone()] },
+			);
+			let signature = alice.sign(header.hash().as_ref());
+			let seal = <DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature);
+			header.digest_mut().push(seal);
+			header
+		};
+
+		let calls = Arc::new(AtomicUsize::new(0));
+		let hook_calls = calls.clone();
+		let verifier = AuraVerifier::<_, AuthorityPair, (), (), u64, TestHeader>::new(
+			Arc::new(substrate_test_runtime_client::new()),
+			(),
+			(),
+			CheckForEquivocation::Yes,
+			None,
+			CompatibilityMode::None,
+			false,
+			None,
+			None,
+			false,
+			Vec::new(),
+			None,
+			None,
+			Some(Box::new(move |first: &TestHeader, second: &TestHeader| {
+				assert_ne!(first.hash(), second.hash());
+				hook_calls.fetch_add(1, Ordering::SeqCst);
+			})),
+			false,
+			1000,
+			None,
+			u64::MAX,
+			AURA_ENGINE_ID,
+			false,
+		);
+
+		// slot 2 is Alice's
+		let authorities = authorities(&[Ed25519Keyring::Alice, Ed25519Keyring::Bob]);
+		for header in [sealed_header(1), sealed_header(2), sealed_header(2)] {
+			let hash = header.hash();
+			assert!(matches!(
+				verifier.check_header::<Block>(3.into(), header, hash, &authorities),
+				Ok(CheckedHeader::Checked(..)),
+			));
+		}
+
+		assert_eq!(calls.load(Ordering::SeqCst), 1);
+	}
+
+	/// Block 1 of the sr25519 test client with genesis `genesis_hash`, sealed in `slot` by
+	/// `keyring`, carrying `extra_logs` between its pre-digest and its seal.
+	fn sr25519_sealed_block(
+		genesis_hash: <Block as BlockT>::Hash,
+		slot: Slot,
+		keyring: sp_keyring::Sr25519Keyring,
+		extra_logs: Vec<DigestItem>,
+	) -> BlockImportParams<Block, ()> {
+		sr25519_sealed_block_at(1, genesis_hash, slot, keyring, extra_logs)
+	}
+
+	/// Like [`sr25519_sealed_block`], for block `number` on top of `parent_hash`.
+	fn sr25519_sealed_block_at(
+		number: u64,
+		parent_hash: <Block as BlockT>::Hash,
+		slot: Slot,
+		keyring: sp_keyring::Sr25519Keyring,
+		extra_logs: Vec<DigestItem>,
+	) -> BlockImportParams<Block, ()> {
+		use sp_consensus_aura::sr25519::{AuthorityPair, AuthoritySignature};
+
+		let pair = AuthorityPair::from_string(&keyring.to_seed(), None).expect("Valid seed");
+		let mut logs =
+			vec![<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(slot)];
+		logs.extend(extra_logs);
+		let mut header = TestHeader::new(
+			number,
+			Default::default(),
+			Default::default(),
+			parent_hash,
+			Digest { logs },
+		);
+		let signature = pair.sign(header.hash().as_ref());
+		header
+			.digest_mut()
+			.push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature));
+		let mut block = BlockImportParams::new(sp_consensus::BlockOrigin::File, header);
+		block.body = Some(Vec::new());
+		block
+	}
+
+	/// The sr25519 test client, the keys of the author of `slot` at block 1 and of another
+	/// well-known key, and a verifier of the client at slot 10.
+	fn sr25519_test_verifier(
+		slot: Slot,
+		verify_seals_only: bool,
+	) -> (
+		<Block as BlockT>::Hash,
+		sp_keyring::Sr25519Keyring,
+		sp_keyring::Sr25519Keyring,
+		impl Verifier<Block>,
+	) {
+		use sp_consensus_aura::sr25519::{
+			AuthorityId as Sr25519AuthorityId, AuthorityPair as Sr25519AuthorityPair,
+		};
+		use sp_keyring::Sr25519Keyring;
+
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let genesis_hash = client.info().genesis_hash;
+		let authorities = fetch_authorities::<Sr25519AuthorityId, Block, _, _>(
+			client.as_ref(),
+			&AuraApiAuthorities,
+			genesis_hash,
+			1,
+			&CompatibilityMode::None,
+		)
+		.expect("The test runtime implements `AuraApi`");
+
+		let author = &authorities[*slot as usize % authorities.len()];
+		let author_keyring = Sr25519Keyring::iter()
+			.find(|keyring| Sr25519AuthorityId::from(keyring.public()) == *author)
+			.expect("The test runtime authorities are well-known keys");
+		let other_keyring = Sr25519Keyring::iter()
+			.find(|keyring| Sr25519AuthorityId::from(keyring.public()) != *author)
+			.expect("There are more well-known keys than authors");
+
+		let verifier = build_verifier::<Sr25519AuthorityPair, _, _, _, _, _>(
+			BuildVerifierParams {
+				client,
+				create_inherent_data_providers: |_, _| async {
+					let slot = sp_consensus_aura::inherents::InherentDataProvider::new(10.into());
+					Ok((sp_timestamp::InherentDataProvider::new(10_000.into()), slot))
+				},
+				can_author_with: sp_consensus::AlwaysCanAuthor,
+				check_for_equivocation: CheckForEquivocation::No,
+				telemetry: None,
+				compatibility_mode: CompatibilityMode::None,
+				use_consensus_log_authorities: false,
+				registry: None,
+				handover_window: None,
+				check_timestamp_slot_consistency: false,
+				slot_duration_schedule: Vec::new(),
+				equivocation_sender: None,
+				authority_weights: None,
+				on_equivocation: None,
+				accept_secondary_slots: false,
+				equivocation_window_slots: 0,
+				offline_authorities: None,
+				max_future_slots: u64::MAX,
+				engine_id: AURA_ENGINE_ID,
+				enforce_monotonic_slots: false,
+				verify_seals_only,
+				genesis_timestamp: 0,
+			},
+		);
+
+		(genesis_hash, author_keyring, other_keyring, verifier)
+	}
+
+	#[test]
+	fn verify_only_mode_checks_the_seal_but_skips_execution() {
+		let slot = Slot::from(3);
+		let (genesis_hash, author, other, mut verifier) = sr25519_test_verifier(slot, true);
+		let seal = |keyring| sr25519_sealed_block(genesis_hash, slot, keyring, Vec::new());
+
+		let (block, _) = futures::executor::block_on(verifier.verify(seal(author)))
+			.expect("Sealed by the slot author");
+		assert!(matches!(block.state_action, StateAction::Skip));
+		assert_eq!(block.body, Some(Vec::new()));
+		assert_eq!(block.post_digests.len(), 1);
+		assert_eq!(
+			find_pre_digest_with_engine_id::<Block>(&block.header, AURA_ENGINE_ID).ok(),
+			Some(slot),
+		);
+
+		assert!(futures::executor::block_on(verifier.verify(seal(other))).is_err());
+	}
+
+	#[test]
+	fn verify_only_mode_tracks_the_authorities_of_blocks_without_state() {
+		let slot = Slot::from(3);
+		let (genesis_hash, author, _, mut verifier) = sr25519_test_verifier(slot, true);
+
+		let (first, _) = futures::executor::block_on(
+			verifier.verify(sr25519_sealed_block(genesis_hash, slot, author, Vec::new())),
+		)
+		.expect("Sealed by the slot author");
+		let first_hash = first.post_hash();
+
+		// the first block was neither executed nor even imported, so its authorities can only be
+		// known from tracking them. The author of slot 6 is the one of slot 3 again.
+		let second = sr25519_sealed_block_at(2, first_hash, 6.into(), author, Vec::new());
+		let (second, _) = futures::executor::block_on(verifier.verify(second))
+			.expect("Authorities of the parent are tracked");
+		assert!(matches!(second.state_action, StateAction::Skip));
+	}
+
+	#[test]
+	fn unknown_digest_items_next_to_the_aura_digests_are_ignored() {
+		let slot = Slot::from(3);
+		let (genesis_hash, author, other, mut verifier) = sr25519_test_verifier(slot, false);
+		let unknown = vec![
+			DigestItem::PreRuntime(*b"rand", vec![7; 32]),
+			DigestItem::Consensus(*b"rand", vec![1, 2, 3]),
+			// undecodable under the Aura engine ID, e.g. a log of a later runtime
+			DigestItem::Consensus(AURA_ENGINE_ID, vec![0xff]),
+			DigestItem::Other(vec![4, 5, 6]),
+			DigestItem::Seal(*b"rand", vec![8; 64]),
+		];
+
+		let block = sr25519_sealed_block(genesis_hash, slot, author, unknown.clone());
+		let (block, _) = futures::executor::block_on(verifier.verify(block))
+			.expect("Unknown digest items are ignored");
+		assert!(matches!(block.state_action, StateAction::Execute));
+		assert_eq!(&block.header.digest().logs()[1..], &unknown[..4]);
+		// seals of other engines ahead of the Aura seal are not executed, like the Aura seal
+		assert_eq!(block.post_digests.len(), 2);
+		assert_eq!(block.post_digests[0], unknown[4]);
+
+		// the items are covered by the seal like any other
+		let mut forged = sr25519_sealed_block(genesis_hash, slot, author, unknown);
+		forged.header.digest_mut().logs.remove(1);
+		assert!(futures::executor::block_on(verifier.verify(forged)).is_err());
+		let block =
+			sr25519_sealed_block(genesis_hash, slot, other, vec![DigestItem::Other(Vec::new())]);
+		assert!(futures::executor::block_on(verifier.verify(block)).is_err());
+	}
+}
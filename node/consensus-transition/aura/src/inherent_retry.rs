@@ -0,0 +1,147 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Retrying the creation of the inherent data providers within the slot.
+//!
+//! See [`StartAuraParams::inherent_retry`](crate::StartAuraParams).
+
+use crate::Error;
+use futures::{future::Either, prelude::*};
+use log::debug;
+use sp_inherents::CreateInherentDataProviders;
+use sp_runtime::traits::Block as BlockT;
+use std::{marker::PhantomData, time::Duration};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Inherent data providers whose creation is retried once on failure, as long as the retry
+/// fits into `budget`.
+pub(crate) struct RetryingInherentDataProviders<CIDP, B> {
+	inner: CIDP,
+	retry: bool,
+	budget: Duration,
+	_block: PhantomData<fn() -> B>,
+}
+
+impl<CIDP, B> RetryingInherentDataProviders<CIDP, B> {
+	/// Wrap `inner`, retrying a failed creation if `retry` is set.
+	///
+	/// The failed attempt and the retry together take at most `budget`, the proposing time of a
+	/// slot, so retrying never pushes the block into the next slot.
+	pub(crate) fn new(inner: CIDP, retry: bool, budget: Duration) -> Self {
+		Self { inner, retry, budget, _block: PhantomData }
+	}
+}
+
+#[async_trait::async_trait]
+impl<CIDP, B> CreateInherentDataProviders<B, ()> for RetryingInherentDataProviders<CIDP, B>
+where
+	B: BlockT,
+	CIDP: CreateInherentDataProviders<B, ()>,
+{
+	type InherentDataProviders = CIDP::InherentDataProviders;
+
+	async fn create_inherent_data_providers(
+		&self,
+		parent: B::Hash,
+		extra_args: (),
+	) -> Result<Self::InherentDataProviders, BoxError> {
+		let started = std::time::Instant::now();
+		let error = match self.inner.create_inherent_data_providers(parent, extra_args).await {
+			Ok(providers) => return Ok(providers),
+			Err(error) if !self.retry => return Err(error),
+			Err(error) => error,
+		};
+
+		let remaining = match self.budget.checked_sub(started.elapsed()) {
+			Some(remaining) if !remaining.is_zero() => remaining,
+			_ => return Err(exhausted::<B>(error)),
+		};
+		debug!(
+			target: "aura",
+			"Creating the inherent data providers failed, retrying within {:?}: {}",
+			remaining,
+			error,
+		);
+
+		let retry = self.inner.create_inherent_data_providers(parent, ());
+		match future::select(retry, futures_timer::Delay::new(remaining)).await {
+			Either::Left((Ok(providers), _)) => Ok(providers),
+			Either::Left((Err(error), _)) => Err(exhausted::<B>(error)),
+			Either::Right(((), _)) => Err(exhausted::<B>(error)),
+		}
+	}
+}
+
+/// The error of a creation that failed even after retrying.
+fn exhausted<B: BlockT>(error: BoxError) -> BoxError {
+	Box::new(Error::<B>::Inherent(sp_inherents::Error::Application(error)))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	};
+	use substrate_test_runtime_client::runtime::Block;
+
+	/// Inherent data providers whose creation fails the first `failures` times.
+	struct Flaky {
+		failures: usize,
+		calls: Arc<AtomicUsize>,
+	}
+
+	#[async_trait::async_trait]
+	impl CreateInherentDataProviders<Block, ()> for Flaky {
+		type InherentDataProviders = sp_timestamp::InherentDataProvider;
+
+		async fn create_inherent_data_providers(
+			&self,
+			_: <Block as BlockT>::Hash,
+			_: (),
+		) -> Result<Self::InherentDataProviders, BoxError> {
+			if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+				return Err("provider unavailable".into())
+			}
+			Ok(sp_timestamp::InherentDataProvider::new(0.into()))
+		}
+	}
+
+	fn create(failures: usize, retry: bool) -> (bool, usize) {
+		let calls = Arc::new(AtomicUsize::new(0));
+		let providers = RetryingInherentDataProviders::new(
+			Flaky { failures, calls: calls.clone() },
+			retry,
+			Duration::from_secs(1),
+		);
+		let created =
+			block_on(providers.create_inherent_data_providers(Default::default(), ())).is_ok();
+		(created, calls.load(Ordering::SeqCst))
+	}
+
+	#[test]
+	fn failed_creation_is_retried_once() {
+		assert_eq!(create(1, true), (true, 2));
+		assert_eq!(create(1, false), (false, 1));
+		assert_eq!(create(2, true), (false, 2));
+		assert_eq!(create(0, true), (true, 1));
+	}
+}
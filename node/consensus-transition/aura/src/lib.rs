@@ -26,56 +26,163 @@
 //! and it will be built upon the longest valid chain that has been seen.
 //!
 //! Blocks from future steps will be either deferred or rejected depending on how
-//! far in the future they are.
+//! far in the future they are, see [`ImportQueueParams::max_future_slots`].
 //!
 //! NOTE: Aura itself is designed to be generic over the crypto used.
 #![forbid(missing_docs, unsafe_code)]
-use std::{fmt::Debug, hash::Hash, marker::PhantomData, pin::Pin, sync::Arc};
+use std::{
+	collections::{BTreeMap, BTreeSet, HashMap, VecDeque},
+	fmt::Debug,
+	hash::Hash,
+	marker::PhantomData,
+	ops::RangeInclusive,
+	pin::Pin,
+	sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		Arc,
+	},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
 use futures::prelude::*;
-use log::{debug, trace};
+use log::{debug, error, info, trace, warn};
+use parking_lot::Mutex;
 
 use codec::{Codec, Decode, Encode};
 
+use prometheus_endpoint::Registry;
 use sc_client_api::{backend::AuxStore, BlockOf, UsageProvider};
 use sc_consensus::{BlockImport, BlockImportParams, ForkChoiceStrategy, StateAction};
 use sc_consensus_slots::{
 	BackoffAuthoringBlocksStrategy, InherentDataProviderExt, SimpleSlotWorkerToSlotWorker,
 	SlotInfo, StorageChanges,
 };
-use sc_telemetry::TelemetryHandle;
+use sc_telemetry::{telemetry, TelemetryHandle, CONSENSUS_DEBUG, CONSENSUS_INFO};
 use sp_api::{Core, ProvideRuntimeApi};
 use sp_application_crypto::{AppKey, AppPublic};
-use sp_blockchain::{HeaderBackend, Result as CResult};
+use sp_blockchain::{HeaderBackend, HeaderMetadata, Result as CResult};
 use sp_consensus::{
 	BlockOrigin, CanAuthorWith, Environment, Error as ConsensusError, Proposer, SelectChain,
 };
 use sp_consensus_slots::Slot;
-use sp_core::crypto::{ByteArray, Pair, Public};
-use sp_inherents::CreateInherentDataProviders;
-use sp_keystore::{SyncCryptoStore, SyncCryptoStorePtr};
+use sp_core::{
+	crypto::{ByteArray, CryptoTypeId, KeyTypeId, Pair, Public},
+	hexdisplay::HexDisplay,
+};
+use sp_inherents::{CreateInherentDataProviders, InherentData};
+use sp_keystore::{CryptoStore, SyncCryptoStore, SyncCryptoStorePtr};
 use sp_runtime::{
 	generic::BlockId,
-	traits::{Block as BlockT, Header, Member, NumberFor, Zero},
-	DigestItem,
+	traits::{Block as BlockT, Header, Member, NumberFor, Saturating, UniqueSaturatedInto, Zero},
+	ConsensusEngineId, DigestItem,
 };
 
+mod audit;
+mod authored_blocks;
+mod authorities_retry;
+mod checkpoint;
+#[cfg(feature = "dry-run")]
+mod dry_run;
+mod dynamic_slot_duration;
+mod equivocation;
+mod equivocation_window;
 mod import_queue;
+mod inherent_retry;
+mod metrics;
+mod multi_crypto;
+mod observer;
+mod offline_verifier;
+mod params_builder;
+mod propose_retry;
+mod seal_preverification;
+mod slot_lifecycle;
+mod spans;
+#[cfg(feature = "testing")]
+mod testing;
 
+pub use audit::{audit_authoring, AuthoringDiscrepancy};
+pub use authored_blocks::{AuthoredBlockInfo, AuthoredBlocks, AuthoredBlocksStream};
+pub use checkpoint::{
+	load_authorities_checkpoint, write_authorities_checkpoint, AuthoritiesCheckpoint,
+};
+#[cfg(feature = "dry-run")]
+pub use dry_run::{dry_run_proposal, DryRunProposal};
+pub use dynamic_slot_duration::SlotDurationChanges;
+pub use equivocation::{
+	make_equivocation_proof, make_equivocation_proof_with_engine_id, verify_equivocation_proof,
+	verify_equivocation_proof_with_engine_id, EquivocationProof,
+};
 pub use import_queue::{
 	build_verifier, import_queue, AuraVerifier, BuildVerifierParams, CheckForEquivocation,
-	ImportQueueParams,
+	ImportQueueParams, SealVerification,
+};
+pub use metrics::{AuraMetrics, VerifierMetrics};
+pub use multi_crypto::{
+	check_multi_seal, claim_slot_multi, seal_multi, MultiAuthorityId, MultiAuthorityPair,
+	MultiAuthoritySignature,
 };
-pub use sc_consensus_slots::SlotProportion;
+pub use observer::{build_observer, AuraObserver, BuildObserverParams};
+pub use offline_verifier::{build_verifier_with_authorities, OfflineVerifier, TrustedAuthorities};
+pub use params_builder::{
+	BuildAuraWorkerParamsBuilder, ImportQueueParamsBuilder, StartAuraParamsBuilder,
+};
+pub use sc_consensus_slots::{SlotLenienceType, SlotProportion};
+pub use seal_preverification::{AuraImportQueue, SealPreverifyingQueue};
 pub use sp_consensus::SyncOracle;
+#[cfg(feature = "testing")]
+pub use testing::build_aura_worker_on_fixed_head;
+#[cfg(all(feature = "testing", debug_assertions))]
+pub use testing::build_aura_worker_with_deterministic_seal;
+
 pub use sp_consensus_aura::{
 	digests::CompatibleDigestItem,
 	inherents::{InherentDataProvider, InherentType as AuraInherent, INHERENT_IDENTIFIER},
 	AuraApi, ConsensusLog, SlotDuration, AURA_ENGINE_ID,
 };
 
+use authored_blocks::ProofNotifyingWorker;
+use authorities_retry::{AuthoritiesFetchFailed, AuthoritiesRetryingWorker};
+use import_queue::find_authorities_change;
+use propose_retry::{CreateRetryProposer, RetryingProposer};
+use slot_lifecycle::{SlotLifecycle, SlotLifecycleImport};
+use spans::Span;
+
 type AuthorityId<P> = <P as Pair>::Public;
 
+/// Log target of the JSON events emitted for authored blocks.
+///
+/// See [`BuildAuraWorkerParams::json_events`].
+pub const AUTHORED_BLOCK_EVENT_TARGET: &str = "aura::authored_block";
+
+/// Engine id of the pre-runtime digest marking a block as authored in a secondary slot.
+///
+/// See [`BuildAuraWorkerParams::secondary_slot_delay`].
+pub const SECONDARY_SLOT_ENGINE_ID: ConsensusEngineId = *b"aur2";
+
+/// Engine id of the pre-runtime digest carrying the SCALE encoded `u32` index of the author of a
+/// block in the authority set.
+///
+/// See [`BuildAuraWorkerParams::include_authority_index`].
+pub const AUTHORITY_INDEX_ENGINE_ID: ConsensusEngineId = *b"aurx";
+
+/// Number of consecutive authored blocks whose proposing overran its budget before the worker
+/// warns that the node is overloaded.
+const PROPOSAL_OVERRUN_STREAK: u32 = 3;
+
+/// Minimum time between two warnings about proposing overrunning its budget.
+const PROPOSAL_OVERRUN_WARN_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Number of chain heads of peers each estimate of the clock drift is taken from.
+///
+/// See [`BuildAuraWorkerParams::clock_drift_threshold`].
+const CLOCK_DRIFT_SAMPLES: usize = 15;
+
+/// Shortest slot duration [`start_aura`] accepts.
+pub const MIN_SLOT_DURATION: Duration = Duration::from_secs(1);
+
+/// Longest slot duration [`start_aura`] accepts.
+pub const MAX_SLOT_DURATION: Duration = Duration::from_secs(600);
+
 /// Run `AURA` in a compatibility mode.
 ///
 /// This is required for when the chain was launched and later there
@@ -103,6 +210,42 @@ pub enum CompatibilityMode<N> {
 		/// changes, between the old nodes (running with `initialize_block`) and the new nodes.
 		until: N,
 	},
+	/// Seal blocks in the legacy format, signing the SCALE encoded `(slot, pre_hash)` pair
+	/// instead of just the pre-seal hash.
+	///
+	/// Some historical blocks of chains migrated from old forks are sealed this way. Blocks
+	/// below `until` are sealed in the legacy format by the worker and their seals are
+	/// verified against it on import; from `until` on the current format is used.
+	///
+	/// The authorities are fetched as with [`CompatibilityMode::None`].
+	LegacySealFormat {
+		/// The block number from which on blocks are sealed in the current format. Like the
+		/// `until` block of [`CompatibilityMode::UseInitializeBlock`], this should be a block in
+		/// the future at the time the mode is configured, on which all nodes have upgraded to a
+		/// release that runs with the compatibility mode. Nodes that did not upgrade reject the
+		/// blocks sealed in the other format, so there is a hard fork at the switch between
+		/// upgraded and old nodes.
+		until: N,
+	},
+	/// Apply a different behavior in each of several block number ranges, for chains that went
+	/// through more than one consensus breaking change.
+	///
+	/// The stage whose range contains a block number applies to it, blocks outside of all
+	/// ranges behave as with [`CompatibilityMode::None`]. Build this with
+	/// [`CompatibilityMode::staged`], which rejects overlapping ranges.
+	Staged(Vec<(RangeInclusive<N>, StageBehavior)>),
+}
+
+/// Behavior within a stage of [`CompatibilityMode::Staged`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StageBehavior {
+	/// Behave as with [`CompatibilityMode::None`].
+	None,
+	/// Call `initialize_block` before fetching the authorities, as with
+	/// [`CompatibilityMode::UseInitializeBlock`].
+	UseInitializeBlock,
+	/// Seal blocks in the legacy format, as with [`CompatibilityMode::LegacySealFormat`].
+	LegacySealFormat,
 }
 
 impl<N> Default for CompatibilityMode<N> {
@@ -111,6 +254,43 @@ impl<N> Default for CompatibilityMode<N> {
 	}
 }
 
+impl<N: Ord + Debug> CompatibilityMode<N> {
+	/// Create a [`CompatibilityMode::Staged`] from the given stages.
+	///
+	/// # Panics
+	///
+	/// Panics if any two of the ranges overlap, as the behavior within the overlap would be
+	/// ambiguous.
+	pub fn staged(mut stages: Vec<(RangeInclusive<N>, StageBehavior)>) -> Self {
+		stages.sort_by(|(a, _), (b, _)| a.start().cmp(b.start()));
+		for pair in stages.windows(2) {
+			let (previous, next) = (&pair[0].0, &pair[1].0);
+			if next.start() <= previous.end() {
+				panic!("Overlapping compatibility mode stages {:?} and {:?}", previous, next);
+			}
+		}
+
+		Self::Staged(stages)
+	}
+}
+
+impl<N: PartialOrd> CompatibilityMode<N> {
+	/// The behavior that applies to the block `number`.
+	pub fn behavior_at(&self, number: &N) -> StageBehavior {
+		match self {
+			Self::None => StageBehavior::None,
+			Self::UseInitializeBlock { until } if number < until =>
+				StageBehavior::UseInitializeBlock,
+			Self::LegacySealFormat { until } if number < until => StageBehavior::LegacySealFormat,
+			Self::UseInitializeBlock { .. } | Self::LegacySealFormat { .. } => StageBehavior::None,
+			Self::Staged(stages) => stages
+				.iter()
+				.find(|(range, _)| range.contains(number))
+				.map_or(StageBehavior::None, |(_, behavior)| *behavior),
+		}
+	}
+}
+
 /// Get the slot duration for Aura.
 pub fn slot_duration<A, B, C>(client: &C) -> CResult<SlotDuration>
 where
@@ -123,508 +303,5787 @@ where
 	client.runtime_api().slot_duration(&best_block_id).map_err(|err| err.into())
 }
 
-/// Get slot author for given block along with authorities.
-fn slot_author<P: Pair>(slot: Slot, authorities: &[AuthorityId<P>]) -> Option<&AuthorityId<P>> {
-	if authorities.is_empty() {
+/// The Unix timestamp in milliseconds at which `slot` starts, with slots of `duration`.
+///
+/// Saturates at `u64::MAX` for slots beyond the representable time.
+pub fn slot_to_timestamp(slot: Slot, duration: SlotDuration) -> u64 {
+	(*slot).saturating_mul(duration.as_millis())
+}
+
+/// The slot at the Unix timestamp `ts` in milliseconds, with slots of `duration`.
+///
+/// A zero `duration` yields the genesis slot, as no slot has ever passed.
+pub fn timestamp_to_slot(ts: u64, duration: SlotDuration) -> Slot {
+	ts.checked_div(duration.as_millis()).unwrap_or_default().into()
+}
+
+/// Like [`slot_to_timestamp`], on a chain whose slot 0 starts at the Unix timestamp
+/// `genesis_timestamp` in milliseconds.
+pub fn slot_to_timestamp_with_offset(
+	slot: Slot,
+	duration: SlotDuration,
+	genesis_timestamp: u64,
+) -> u64 {
+	genesis_timestamp.saturating_add(slot_to_timestamp(slot, duration))
+}
+
+/// Like [`timestamp_to_slot`], on a chain whose slot 0 starts at the Unix timestamp
+/// `genesis_timestamp` in milliseconds.
+///
+/// Timestamps before `genesis_timestamp` yield the genesis slot.
+pub fn timestamp_to_slot_with_offset(
+	ts: u64,
+	duration: SlotDuration,
+	genesis_timestamp: u64,
+) -> Slot {
+	timestamp_to_slot(ts.saturating_sub(genesis_timestamp), duration)
+}
+
+/// Get the index of the slot author in an authority set of `authorities_len` authorities.
+fn slot_author_index(slot: Slot, authorities_len: usize) -> Option<usize> {
+	if authorities_len == 0 {
 		return None
 	}
 
-	let idx = *slot % (authorities.len() as u64);
+	let idx = *slot % (authorities_len as u64);
 	assert!(
 		idx <= usize::MAX as u64,
 		"It is impossible to have a vector with length beyond the address space; qed",
 	);
 
-	let current_author = authorities.get(idx as usize).expect(
+	Some(idx as usize)
+}
+
+/// Get the index of the slot author in `authorities`, chosen by cumulative-weight bucketing
+/// if `weights` are given.
+///
+/// Falls back to [`slot_author_index`] without weights or if the weights of the set sum up to
+/// zero.
+fn slot_author_index_weighted<A: AsRef<[u8]>>(
+	slot: Slot,
+	authorities: &[A],
+	weights: Option<&AuthorityWeights>,
+) -> Option<usize> {
+	let weights = match weights {
+		Some(weights) =>
+			authorities.iter().map(|a| weights.weight(a.as_ref()) as u128).collect::<Vec<_>>(),
+		None => return slot_author_index(slot, authorities.len()),
+	};
+	let total = weights.iter().sum::<u128>();
+	if total == 0 {
+		return slot_author_index(slot, authorities.len())
+	}
+
+	let mut point = *slot as u128 % total;
+	weights.iter().position(|weight| {
+		if point < *weight {
+			true
+		} else {
+			point -= weight;
+			false
+		}
+	})
+}
+
+/// Get slot author for given block along with authorities.
+fn slot_author<P: Pair>(slot: Slot, authorities: &[AuthorityId<P>]) -> Option<&AuthorityId<P>> {
+	slot_author_weighted::<P>(slot, authorities, None)
+}
+
+/// Get the authority expected to author `slot` given the authority set `authorities`.
+///
+/// Returns `None` if `authorities` is empty.
+pub fn expected_author<P: Pair>(
+	slot: Slot,
+	authorities: &[AuthorityId<P>],
+) -> Option<&AuthorityId<P>> {
+	slot_author::<P>(slot, authorities)
+}
+
+/// Get the expected authors of the `count` slots starting at `from`, given the authority set
+/// `authorities`.
+///
+/// The schedule is empty if `authorities` is, and stops short at the last representable slot.
+pub fn authoring_schedule<P: Pair>(
+	from: Slot,
+	count: u64,
+	authorities: &[AuthorityId<P>],
+) -> Vec<(Slot, AuthorityId<P>)> {
+	if authorities.is_empty() {
+		return Vec::new()
+	}
+
+	(0..count)
+		.map_while(|offset| (*from).checked_add(offset))
+		.filter_map(|slot| {
+			let slot = Slot::from(slot);
+			expected_author::<P>(slot, authorities).map(|author| (slot, author.clone()))
+		})
+		.collect()
+}
+
+/// Get slot author for given block along with authorities, weighted by `weights` if given.
+fn slot_author_weighted<'a, P: Pair>(
+	slot: Slot,
+	authorities: &'a [AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+) -> Option<&'a AuthorityId<P>> {
+	let idx = slot_author_index_weighted(slot, authorities, weights)?;
+
+	let current_author = authorities.get(idx).expect(
 		"authorities not empty; index constrained to list length;this is a valid index; qed",
 	);
 
 	Some(current_author)
 }
 
-/// Parameters of [`start_aura`].
-pub struct StartAuraParams<C, SC, I, PF, SO, L, CIDP, BS, CAW, N> {
-	/// The duration of a slot.
-	pub slot_duration: SlotDuration,
-	/// The client to interact with the chain.
-	pub client: Arc<C>,
-	/// A select chain implementation to select the best block.
-	pub select_chain: SC,
-	/// The block import.
-	pub block_import: I,
-	/// The proposer factory to build proposer instances.
-	pub proposer_factory: PF,
-	/// The sync oracle that can give us the current sync status.
-	pub sync_oracle: SO,
-	/// Hook into the sync module to control the justification sync process.
-	pub justification_sync_link: L,
-	/// Something that can create the inherent data providers.
-	pub create_inherent_data_providers: CIDP,
-	/// Should we force the authoring of blocks?
-	pub force_authoring: bool,
-	/// The backoff strategy when we miss slots.
-	pub backoff_authoring_blocks: Option<BS>,
-	/// The keystore used by the node.
-	pub keystore: SyncCryptoStorePtr,
-	/// Can we author a block with this node?
-	pub can_author_with: CAW,
-	/// The proportion of the slot dedicated to proposing.
-	///
-	/// The block proposing will be limited to this proportion of the slot from the starting of the
-	/// slot. However, the proposing can still take longer when there is some lenience factor
-	/// applied, because there were no blocks produced for some slots.
-	pub block_proposal_slot_portion: SlotProportion,
-	/// The maximum proportion of the slot dedicated to proposing with any lenience factor applied
-	/// due to no blocks being produced.
-	pub max_block_proposal_slot_portion: Option<SlotProportion>,
-	/// Telemetry instance used to report telemetry metrics.
-	pub telemetry: Option<TelemetryHandle>,
-	/// Compatibility mode that should be used.
-	///
-	/// If in doubt, use `Default::default()`.
-	pub compatibility_mode: CompatibilityMode<N>,
+/// Get the author of `slot` skipping the authorities flagged by `offline`, i.e. the first
+/// authority from the slot author onwards, wrapping around the set, that is not offline.
+///
+/// Falls back to the slot author if every authority is flagged offline.
+fn live_slot_author<'a, P: Pair>(
+	slot: Slot,
+	authorities: &'a [AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+	offline: Option<&OfflineAuthorities<AuthorityId<P>>>,
+) -> Option<&'a AuthorityId<P>> {
+	let primary = slot_author_index_weighted(slot, authorities, weights)?;
+	let offline = match offline {
+		Some(offline) => offline,
+		None => return authorities.get(primary),
+	};
+
+	(0..authorities.len())
+		.map(|offset| &authorities[(primary + offset) % authorities.len()])
+		.find(|authority| !offline(authority))
+		.or_else(|| authorities.get(primary))
 }
 
-/// Start the aura worker. The returned future should be run in a futures executor.
-pub fn start_aura<P, B, C, SC, I, PF, SO, L, CIDP, BS, CAW, Error>(
-	StartAuraParams {
-		slot_duration,
-		client,
-		select_chain,
-		block_import,
-		proposer_factory,
-		sync_oracle,
-		justification_sync_link,
-		create_inherent_data_providers,
-		force_authoring,
-		backoff_authoring_blocks,
-		keystore,
-		can_author_with,
-		block_proposal_slot_portion,
-		max_block_proposal_slot_portion,
-		telemetry,
-		compatibility_mode,
-	}: StartAuraParams<C, SC, I, PF, SO, L, CIDP, BS, CAW, NumberFor<B>>,
-) -> Result<impl Future<Output = ()>, sp_consensus::Error>
+/// Get the secondary author of `slot`, i.e. the author of the following slot, unless that is the
+/// author of `slot` itself.
+///
+/// See [`BuildAuraWorkerParams::secondary_slot_delay`].
+fn secondary_slot_author<'a, P: Pair>(
+	slot: Slot,
+	authorities: &'a [AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+) -> Option<&'a AuthorityId<P>> {
+	let primary = slot_author_weighted::<P>(slot, authorities, weights)?;
+	let secondary = slot_author_weighted::<P>(slot + 1, authorities, weights)?;
+	(secondary != primary).then(|| secondary)
+}
+
+/// Get the author expected to seal a block in `slot`, the secondary author if the block is
+/// marked as authored in a secondary slot.
+///
+/// Authorities flagged by `offline` are skipped for primary slots only.
+fn expected_slot_author<'a, P: Pair>(
+	slot: Slot,
+	authorities: &'a [AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+	offline: Option<&OfflineAuthorities<AuthorityId<P>>>,
+	secondary: bool,
+) -> Option<&'a AuthorityId<P>> {
+	if secondary {
+		secondary_slot_author::<P>(slot, authorities, weights)
+	} else {
+		live_slot_author::<P>(slot, authorities, weights, offline)
+	}
+}
+
+/// Why a slot was claimed or not, see [`claim_outcome`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClaimOutcome<A> {
+	/// The keystore holds the key of the slot author.
+	Claimed(A),
+	/// The slot author is another authority, the keystore holds the key of some authority of the
+	/// set.
+	NotAuthor,
+	/// The keystore holds the key of no authority of the set, so no slot is ever claimed with it.
+	MissingKey {
+		/// The index of the slot author in the authority set.
+		author_index: usize,
+	},
+	/// The authority set is empty.
+	NoAuthorities,
+	/// The keystore does not hold the key of the slot author, and it was not looked into why.
+	NotClaimed,
+}
+
+impl<A> ClaimOutcome<A> {
+	/// The claimed authority, if the slot was claimed.
+	fn into_claim(self) -> Option<A> {
+		match self {
+			Self::Claimed(author) => Some(author),
+			_ => None,
+		}
+	}
+}
+
+/// Claim `slot` if the keystore holds the key of its expected author under `key_type`.
+///
+/// With `diagnose`, the reason why the slot was not claimed is looked up as well. This checks the
+/// keystore for the key of every authority, so it is only worth it if the reason is reported.
+fn claim_outcome<P: Pair>(
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	slot: Slot,
+	authorities: &[AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+	offline: Option<&OfflineAuthorities<AuthorityId<P>>>,
+	diagnose: bool,
+) -> ClaimOutcome<AuthorityId<P>> {
+	let author = match live_slot_author::<P>(slot, authorities, weights, offline) {
+		Some(author) => author,
+		None => return ClaimOutcome::NoAuthorities,
+	};
+	if has_authority_key::<P>(keystore, key_type, author) {
+		return ClaimOutcome::Claimed(author.clone())
+	}
+	if !diagnose {
+		return ClaimOutcome::NotClaimed
+	}
+
+	if authorities.iter().any(|authority| has_authority_key::<P>(keystore, key_type, authority)) {
+		ClaimOutcome::NotAuthor
+	} else {
+		let author_index = authorities
+			.iter()
+			.position(|authority| authority == author)
+			.expect("the author is taken from the authorities; qed");
+		ClaimOutcome::MissingKey { author_index }
+	}
+}
+
+/// Claim `slot` if the keystore holds the key of its expected author under `key_type`.
+fn claim_slot_with_keystore<P: Pair>(
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	slot: Slot,
+	authorities: &[AuthorityId<P>],
+	weights: Option<&AuthorityWeights>,
+	offline: Option<&OfflineAuthorities<AuthorityId<P>>>,
+) -> Option<AuthorityId<P>> {
+	claim_outcome::<P>(keystore, key_type, slot, authorities, weights, offline, false).into_claim()
+}
+
+/// Whether the keystore holds the private key of `authority` under `key_type`.
+fn has_authority_key<P: Pair>(
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	authority: &AuthorityId<P>,
+) -> bool {
+	SyncCryptoStore::has_keys(keystore, &[(authority.to_raw_vec(), key_type)])
+}
+
+/// Check that the keystore signed the seal `message` of a slot claimed by `author` with exactly
+/// the key of `author`.
+///
+/// Claiming only matches the raw public key under the Aura key type, which a keystore also
+/// matches if it files another key under it, e.g. after a botched key rotation. It then signs
+/// with that key, and the seal would be rejected by every verifier.
+fn ensure_signed_by_author<B: BlockT, P: Pair>(
+	author: &AuthorityId<P>,
+	message: &[u8],
+	signature: &P::Signature,
+) -> Result<(), Error<B>> {
+	if P::verify(signature, message, author) {
+		Ok(())
+	} else {
+		Err(Error::AmbiguousKey(author.to_raw_vec()))
+	}
+}
+
+/// The length of the signatures of the crypto scheme `crypto_id`, if it is a known one.
+fn signature_len(crypto_id: CryptoTypeId) -> Option<usize> {
+	match crypto_id {
+		sp_core::sr25519::CRYPTO_ID | sp_core::ed25519::CRYPTO_ID => Some(64),
+		sp_core::ecdsa::CRYPTO_ID => Some(65),
+		_ => None,
+	}
+}
+
+/// Check that the `signature` the keystore made with a key of the crypto scheme `crypto_id` has
+/// the length of the signatures of that scheme.
+///
+/// Keystore backends such as HSMs occasionally return malformed signatures, which otherwise
+/// only fail to decode without telling why.
+fn check_signature_len<B: BlockT>(
+	crypto_id: CryptoTypeId,
+	signature: &[u8],
+) -> Result<(), Error<B>> {
+	match signature_len(crypto_id) {
+		Some(expected) if expected != signature.len() =>
+			Err(Error::MalformedSignature { expected, actual: signature.len() }),
+		_ => Ok(()),
+	}
+}
+
+/// The indices of the authorities in `authorities` whose private keys the keystore holds under
+/// `key_type`, i.e. the authorities whose slots a worker with this keystore claims.
+///
+/// A keystore can hold the keys of several authorities, and the same key can be held by
+/// several nodes. This is meant to verify the key placement without waiting for a slot.
+pub fn claimable_authorities<P: Pair>(
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	authorities: &[AuthorityId<P>],
+) -> Vec<usize> {
+	authorities
+		.iter()
+		.enumerate()
+		.filter(|(_, authority)| has_authority_key::<P>(keystore, key_type, authority))
+		.map(|(index, _)| index)
+		.collect()
+}
+
+/// Whether `header` was authored with one of the keys held by the keystore, given the
+/// `authorities` that sealed it, i.e. the authority set at its parent.
+///
+/// The author is the author of the slot of the header, or its secondary author if the header is
+/// marked as such. Headers without an Aura pre-digest, and headers of slots whose author's key
+/// is not held, are not authored by us. This is meant to tally the blocks authored by this node
+/// from the chain history, the seal is not checked.
+///
+/// This assumes the defaults of the worker, see [`authored_by_us_with`] for chains authored
+/// with authority weights, offline authorities, another key type or engine ID.
+pub fn authored_by_us<P: Pair, B: BlockT>(
+	header: &B::Header,
+	authorities: &[AuthorityId<P>],
+	keystore: &dyn SyncCryptoStore,
+) -> bool {
+	authored_by_us_with::<P, B>(header, authorities, keystore, AuthorshipParams::default())
+}
+
+/// How the author of a slot is determined, see [`authored_by_us_with`].
+///
+/// These have to match the parameters the chain is authored with. The default is that of the
+/// worker.
+pub struct AuthorshipParams<'a, A> {
+	/// See [`BuildAuraWorkerParams::authority_weights`].
+	pub weights: Option<&'a AuthorityWeights>,
+	/// See [`BuildAuraWorkerParams::offline_authorities`]. Offline authorities are skipped for
+	/// primary slots only.
+	pub offline: Option<&'a OfflineAuthorities<A>>,
+	/// See [`BuildAuraWorkerParams::key_type`].
+	pub key_type: KeyTypeId,
+	/// See [`BuildAuraWorkerParams::engine_id`].
+	pub engine_id: ConsensusEngineId,
+}
+
+impl<'a, A> Default for AuthorshipParams<'a, A> {
+	fn default() -> Self {
+		Self {
+			weights: None,
+			offline: None,
+			key_type: sp_application_crypto::key_types::AURA,
+			engine_id: AURA_ENGINE_ID,
+		}
+	}
+}
+
+/// [`authored_by_us`], with the author of a slot determined by `params`.
+pub fn authored_by_us_with<P: Pair, B: BlockT>(
+	header: &B::Header,
+	authorities: &[AuthorityId<P>],
+	keystore: &dyn SyncCryptoStore,
+	params: AuthorshipParams<AuthorityId<P>>,
+) -> bool {
+	let AuthorshipParams { weights, offline, key_type, engine_id } = params;
+	let slot = match find_pre_digest_with_engine_id::<B>(header, engine_id) {
+		Ok(slot) => slot,
+		Err(_) => return false,
+	};
+	let secondary = is_secondary_slot::<B>(header);
+	expected_slot_author::<P>(slot, authorities, weights, offline, secondary)
+		.map_or(false, |author| has_authority_key::<P>(keystore, key_type, author))
+}
+
+/// Check that `public` is part of the authority set at `at` and that the keystore holds its
+/// private key.
+///
+/// This is meant as a pre-flight check when provisioning a new validator. The authority set is
+/// read through `AuraApi::authorities` and the key is looked up under the Aura key type, see
+/// [`keystore_matches_authority_with`] for runtimes authored with another authority provider,
+/// compatibility mode or key type.
+pub fn keystore_matches_authority<P, B, C>(
+	client: &C,
+	keystore: &dyn SyncCryptoStore,
+	public: &AuthorityId<P>,
+	at: B::Hash,
+) -> Result<bool, ConsensusError>
 where
-	P: Pair + Send + Sync,
-	P::Public: AppPublic + Hash + Member + Encode + Decode,
-	P::Signature: TryFrom<Vec<u8>> + Hash + Member + Encode + Decode,
+	P: Pair,
+	P::Public: Codec + Debug,
 	B: BlockT,
-	C: ProvideRuntimeApi<B> + BlockOf + AuxStore + HeaderBackend<B> + Send + Sync,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
 	C::Api: AuraApi<B, AuthorityId<P>>,
-	SC: SelectChain<B>,
-	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
-	PF: Environment<B, Error = Error> + Send + Sync + 'static,
-	PF::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
-	SO: SyncOracle + Send + Sync + Clone,
-	L: sc_consensus::JustificationSyncLink<B>,
-	CIDP: CreateInherentDataProviders<B, ()> + Send,
-	CIDP::InherentDataProviders: InherentDataProviderExt + Send,
-	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
-	CAW: CanAuthorWith<B> + Send,
-	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
 {
-	let worker = build_aura_worker::<P, _, _, _, _, _, _, _, _>(BuildAuraWorkerParams {
+	keystore_matches_authority_with::<P, B, C, _>(
 		client,
-		block_import,
-		proposer_factory,
 		keystore,
-		sync_oracle: sync_oracle.clone(),
-		justification_sync_link,
-		force_authoring,
-		backoff_authoring_blocks,
-		telemetry,
-		block_proposal_slot_portion,
-		max_block_proposal_slot_portion,
-		compatibility_mode,
-	});
+		public,
+		at,
+		&AuraApiAuthorities,
+		&CompatibilityMode::None,
+		sp_application_crypto::key_types::AURA,
+	)
+}
 
-	Ok(sc_consensus_slots::start_slot_worker(
-		slot_duration,
-		select_chain,
-		worker,
-		sync_oracle,
-		create_inherent_data_providers,
-		can_author_with,
-	))
+/// [`keystore_matches_authority`], with the authority set read through `provider` in
+/// `compatibility_mode` and the key looked up under `key_type`.
+///
+/// These have to match the parameters the worker is started with, see
+/// [`BuildAuraWorkerParams`].
+pub fn keystore_matches_authority_with<P, B, C, AP>(
+	client: &C,
+	keystore: &dyn SyncCryptoStore,
+	public: &AuthorityId<P>,
+	at: B::Hash,
+	provider: &AP,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+	key_type: KeyTypeId,
+) -> Result<bool, ConsensusError>
+where
+	P: Pair,
+	P::Public: Codec + Debug,
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: sp_api::Core<B>,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api>,
+{
+	let number = client
+		.number(at)
+		.map_err(|e| ConsensusError::ChainLookup(e.to_string()))?
+		.ok_or_else(|| ConsensusError::ChainLookup(format!("Unknown block {:?}", at)))?;
+	let authorities = authorities(client, provider, at, number + 1u32.into(), compatibility_mode)?;
+
+	Ok(authorities.contains(public) && has_authority_key::<P>(keystore, key_type, public))
 }
 
-/// Parameters of [`build_aura_worker`].
-pub struct BuildAuraWorkerParams<C, I, PF, SO, L, BS, N> {
-	/// The client to interact with the chain.
-	pub client: Arc<C>,
-	/// The block import.
-	pub block_import: I,
-	/// The proposer factory to build proposer instances.
-	pub proposer_factory: PF,
-	/// The sync oracle that can give us the current sync status.
-	pub sync_oracle: SO,
-	/// Hook into the sync module to control the justification sync process.
-	pub justification_sync_link: L,
-	/// Should we force the authoring of blocks?
-	pub force_authoring: bool,
-	/// The backoff strategy when we miss slots.
-	pub backoff_authoring_blocks: Option<BS>,
-	/// The keystore used by the node.
-	pub keystore: SyncCryptoStorePtr,
-	/// The proportion of the slot dedicated to proposing.
-	///
-	/// The block proposing will be limited to this proportion of the slot from the starting of the
-	/// slot. However, the proposing can still take longer when there is some lenience factor
-	/// applied, because there were no blocks produced for some slots.
-	pub block_proposal_slot_portion: SlotProportion,
-	/// The maximum proportion of the slot dedicated to proposing with any lenience factor applied
-	/// due to no blocks being produced.
-	pub max_block_proposal_slot_portion: Option<SlotProportion>,
-	/// Telemetry instance used to report telemetry metrics.
-	pub telemetry: Option<TelemetryHandle>,
-	/// Compatibility mode that should be used.
-	///
-	/// If in doubt, use `Default::default()`.
-	pub compatibility_mode: CompatibilityMode<N>,
+/// Mismatches between the Aura keys of a keystore and an authority set, as found by
+/// [`check_keystore_against_authorities`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeystoreAuthorityDrift<A> {
+	/// Keys held by the keystore that are not part of the authority set.
+	pub unknown_local_keys: Vec<A>,
+	/// Authorities whose keys are not held by the keystore.
+	pub missing_local_keys: Vec<A>,
+}
+
+impl<A> KeystoreAuthorityDrift<A> {
+	/// Whether none of the local keys is part of the authority set.
+	pub fn no_local_authority(&self, authorities: usize) -> bool {
+		self.missing_local_keys.len() == authorities
+	}
+}
+
+/// The hash to seal a header with once `digests` are appended to it as post digests.
+fn pre_seal_hash<B: BlockT>(header: &B::Header, hash: &B::Hash, digests: &[DigestItem]) -> B::Hash {
+	if digests.is_empty() {
+		return *hash
+	}
+
+	let mut header = header.clone();
+	for item in digests {
+		header.digest_mut().push(item.clone());
+	}
+	header.hash()
 }
 
-/// Build the aura worker.
+/// Whether a [`PreSealHook`] may add `item` ahead of the seal issued under `engine_id`.
 ///
-/// The caller is responsible for running this worker, otherwise it will do nothing.
-pub fn build_aura_worker<P, B, C, PF, I, SO, L, BS, Error>(
-	BuildAuraWorkerParams {
-		client,
-		block_import,
-		proposer_factory,
-		sync_oracle,
-		justification_sync_link,
-		backoff_authoring_blocks,
-		keystore,
-		block_proposal_slot_portion,
-		max_block_proposal_slot_portion,
-		telemetry,
-		force_authoring,
-		compatibility_mode,
-	}: BuildAuraWorkerParams<C, I, PF, SO, L, BS, NumberFor<B>>,
-) -> impl sc_consensus_slots::SlotWorker<B, <PF::Proposer as Proposer<B>>::Proof>
+/// Only seals of other engines are: importing nodes move them to the post digests along with the
+/// seal, so the runtime never executes them. The runtime checks the digests of the header it
+/// executes against the ones it computed, any other item would fail the import.
+fn is_pre_seal_digest(item: &DigestItem, engine_id: ConsensusEngineId) -> bool {
+	matches!(item, DigestItem::Seal(id, _) if *id != engine_id)
+}
+
+/// Remove the digests a [`PreSealHook`] added from the end of `header`, whose seal issued under
+/// `engine_id` is already removed.
+fn take_pre_seal_digests<B: BlockT>(
+	header: &mut B::Header,
+	engine_id: ConsensusEngineId,
+) -> Vec<DigestItem> {
+	let logs = &mut header.digest_mut().logs;
+	let start = logs
+		.iter()
+		.rposition(|item| !is_pre_seal_digest(item, engine_id))
+		.map_or(0, |last| last + 1);
+	logs.split_off(start)
+}
+
+/// Compare the Aura keys held by `keystore` against `authorities`.
+///
+/// Keys of another crypto scheme stored under the Aura key type are ignored.
+pub fn check_keystore_against_authorities<P>(
+	keystore: &dyn SyncCryptoStore,
+	authorities: &[AuthorityId<P>],
+) -> Result<KeystoreAuthorityDrift<AuthorityId<P>>, sp_keystore::Error>
+where
+	P: Pair,
+	P::Public: AppPublic,
+{
+	keystore_authority_drift::<P>(keystore, <AuthorityId<P> as AppKey>::ID, authorities)
+}
 
+/// [`check_keystore_against_authorities`] for keys filed under `key_type`.
+fn keystore_authority_drift<P>(
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	authorities: &[AuthorityId<P>],
+) -> Result<KeystoreAuthorityDrift<AuthorityId<P>>, sp_keystore::Error>
 where
-	B: BlockT,
-	C: ProvideRuntimeApi<B> + BlockOf + AuxStore + HeaderBackend<B> + Send + Sync,
-	C::Api: AuraApi<B, AuthorityId<P>>,
-	PF: Environment<B, Error = Error> + Send + Sync + 'static,
-	PF::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
-	P: Pair + Send + Sync,
-	P::Public: AppPublic + Hash + Member + Encode + Decode,
-	P::Signature: TryFrom<Vec<u8>> + Hash + Member + Encode + Decode,
-	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
-	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
-	SO: SyncOracle + Send + Sync + Clone,
-	L: sc_consensus::JustificationSyncLink<B>,
-	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	P: Pair,
+	P::Public: AppPublic,
 {
-	SimpleSlotWorkerToSlotWorker(AuraWorker {
-		client,
-		block_import,
-		env: proposer_factory,
-		keystore,
-		sync_oracle,
-		justification_sync_link,
-		force_authoring,
-		backoff_authoring_blocks,
-		telemetry,
-		block_proposal_slot_portion,
-		max_block_proposal_slot_portion,
-		compatibility_mode,
-		_key_type: PhantomData::<P>,
+	let local = SyncCryptoStore::keys(keystore, key_type)?
+		.into_iter()
+		.filter(|key| key.0 == <AuthorityId<P> as AppKey>::CRYPTO_ID)
+		.filter_map(|key| AuthorityId::<P>::from_slice(&key.1).ok())
+		.collect::<Vec<_>>();
+
+	Ok(KeystoreAuthorityDrift {
+		unknown_local_keys: local
+			.iter()
+			.filter(|key| !authorities.contains(key))
+			.cloned()
+			.collect(),
+		missing_local_keys: authorities
+			.iter()
+			.filter(|authority| !local.contains(authority))
+			.cloned()
+			.collect(),
 	})
 }
 
-struct AuraWorker<C, E, I, P, SO, L, BS, N> {
-	client: Arc<C>,
-	block_import: I,
-	env: E,
-	keystore: SyncCryptoStorePtr,
-	sync_oracle: SO,
-	justification_sync_link: L,
-	force_authoring: bool,
-	backoff_authoring_blocks: Option<BS>,
-	block_proposal_slot_portion: SlotProportion,
-	max_block_proposal_slot_portion: Option<SlotProportion>,
-	telemetry: Option<TelemetryHandle>,
-	compatibility_mode: CompatibilityMode<N>,
-	_key_type: PhantomData<P>,
+/// A local authority key entering or leaving the authority set, see [`local_set_changes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum LocalSetChange<N, A> {
+	/// The key is part of the authority set from the block numbered `N` on.
+	Enters(N, A),
+	/// The key is no longer part of the authority set from the block numbered `N` on.
+	Leaves(N, A),
 }
 
-#[async_trait::async_trait]
-impl<B, C, E, I, P, Error, SO, L, BS> sc_consensus_slots::SimpleSlotWorker<B>
-	for AuraWorker<C, E, I, P, SO, L, BS, NumberFor<B>>
+/// Find where the keys accepted by `is_local` enter or leave the authority set between
+/// consecutive entries of `sets`, the authority sets of increasing block numbers.
+fn local_set_changes<N, A, F>(sets: &[(N, Vec<A>)], is_local: F) -> Vec<LocalSetChange<N, A>>
 where
-	B: BlockT,
-	C: ProvideRuntimeApi<B> + BlockOf + HeaderBackend<B> + Sync,
-	C::Api: AuraApi<B, AuthorityId<P>>,
-	E: Environment<B, Error = Error> + Send + Sync,
-	E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
-	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
-	P: Pair + Send + Sync,
-	P::Public: AppPublic + Public + Member + Encode + Decode + Hash,
-	P::Signature: TryFrom<Vec<u8>> + Member + Encode + Decode + Hash + Debug,
-	SO: SyncOracle + Send + Clone + Sync,
-	L: sc_consensus::JustificationSyncLink<B>,
-	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
-	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
+	N: Copy,
+	A: Clone + PartialEq,
+	F: Fn(&A) -> bool,
 {
-	type BlockImport = I;
-	type SyncOracle = SO;
-	type JustificationSyncLink = L;
-	type CreateProposer =
-		Pin<Box<dyn Future<Output = Result<E::Proposer, sp_consensus::Error>> + Send + 'static>>;
-	type Proposer = E::Proposer;
-	type Claim = P::Public;
-	type EpochData = Vec<AuthorityId<P>>;
+	let mut changes = Vec::new();
+	for pair in sets.windows(2) {
+		let ((_, before), (number, after)) = (&pair[0], &pair[1]);
+		changes.extend(
+			after
+				.iter()
+				.filter(|authority| is_local(authority) && !before.contains(authority))
+				.map(|authority| LocalSetChange::Enters(*number, authority.clone())),
+		);
+		changes.extend(
+			before
+				.iter()
+				.filter(|authority| is_local(authority) && !after.contains(authority))
+				.map(|authority| LocalSetChange::Leaves(*number, authority.clone())),
+		);
+	}
+	changes
+}
 
-	fn logging_target(&self) -> &'static str {
-		"aura"
+/// Log where the keys `keystore` holds under `key_type` enter or leave the authority set within
+/// the `lookahead` blocks following the best block, see [`authorities_at`].
+fn log_authority_set_lookahead<P, B, C, AP>(
+	client: &C,
+	provider: &AP,
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	lookahead: u32,
+) where
+	P: Pair,
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: sp_api::Core<B>,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api>,
+{
+	let info = client.info();
+	let mut sets = Vec::new();
+	for ahead in 1..=lookahead {
+		let number = info.best_number + ahead.into();
+		match authorities_at(client, provider, info.best_hash, number) {
+			Ok(authorities) => sets.push((number, authorities)),
+			Err(e) => {
+				debug!(
+					target: "aura",
+					"Looking ahead at the authority set of #{} failed: {}",
+					number,
+					e,
+				);
+				break
+			},
+		}
 	}
 
-	fn block_import(&mut self) -> &mut Self::BlockImport {
-		&mut self.block_import
+	let changes =
+		local_set_changes(&sets, |authority| has_authority_key::<P>(keystore, key_type, authority));
+	if changes.is_empty() {
+		debug!(
+			target: "aura",
+			"No local key enters or leaves the authority set within the next {} blocks",
+			lookahead,
+		);
+	}
+	for change in changes {
+		match change {
+			LocalSetChange::Enters(number, key) => info!(
+				target: "aura",
+				"🔑 Local key {:?} enters the authority set at #{}",
+				key,
+				number,
+			),
+			LocalSetChange::Leaves(number, key) => warn!(
+				target: "aura",
+				"🔑 Local key {:?} leaves the authority set at #{}",
+				key,
+				number,
+			),
+		}
 	}
+}
 
-	fn epoch_data(
-		&self,
-		header: &B::Header,
-		_slot: Slot,
-	) -> Result<Self::EpochData, sp_consensus::Error> {
-		authorities(
-			self.client.as_ref(),
-			header.hash(),
-			*header.number() + 1u32.into(),
-			&self.compatibility_mode,
-		)
+/// Check that `slot_duration` lies within [`MIN_SLOT_DURATION`] and [`MAX_SLOT_DURATION`].
+fn check_slot_duration<B: BlockT>(slot_duration: SlotDuration) -> Result<(), StartAuraError<B>> {
+	let duration = Duration::from_millis(slot_duration.as_millis());
+	if duration < MIN_SLOT_DURATION || duration > MAX_SLOT_DURATION {
+		return Err(StartAuraError::SlotDurationOutOfRange(slot_duration))
 	}
+	Ok(())
+}
 
-	fn authorities_len(&self, epoch_data: &Self::EpochData) -> Option<usize> {
-		Some(epoch_data.len())
+/// Check that authoring can begin at all, and warn about the keys `keystore` holds under
+/// `key_type` that do not match the authority set at the best block.
+///
+/// Failing to fetch the authority set is only logged, as the worker retries on every slot.
+fn check_start_preconditions<P, B, C>(
+	client: &C,
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	slot_duration: SlotDuration,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+) -> Result<(), StartAuraError<B>>
+where
+	P: Pair,
+	P::Public: AppPublic,
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+{
+	check_slot_duration::<B>(slot_duration)?;
+
+	let info = client.info();
+	let runtime_slot_duration = client
+		.runtime_api()
+		.slot_duration(&BlockId::Hash(info.best_hash))
+		.map_err(|error| StartAuraError::SlotDurationUnavailable { hash: info.best_hash, error })?;
+	if runtime_slot_duration.as_millis() != slot_duration.as_millis() {
+		warn!(
+			target: "aura",
+			"⚠️  Authoring with a slot duration of {}ms, but the runtime at #{} uses {}ms",
+			slot_duration.as_millis(),
+			info.best_number,
+			runtime_slot_duration.as_millis(),
+		);
 	}
 
-	async fn claim_slot(
-		&self,
-		_header: &B::Header,
-		slot: Slot,
-		epoch_data: &Self::EpochData,
-	) -> Option<Self::Claim> {
-		let expected_author = slot_author::<P>(slot, epoch_data);
-		expected_author.and_then(|p| {
-			if SyncCryptoStore::has_keys(
-				&*self.keystore,
-				&[(p.to_raw_vec(), sp_application_crypto::key_types::AURA)],
-			) {
-				Some(p.clone())
-			} else {
-				None
-			}
+	let authorities = match authorities(
+		client,
+		&AuraApiAuthorities,
+		info.best_hash,
+		info.best_number + 1u32.into(),
+		compatibility_mode,
+	) {
+		Ok(authorities) => authorities,
+		Err(e) => {
+			debug!(target: "aura", "Skipping the start checks, no authority set: {}", e);
+			return Ok(())
+		},
+	};
+	if authorities.is_empty() {
+		return Err(StartAuraError::EmptyAuthoritySet {
+			number: info.best_number,
+			hash: info.best_hash,
 		})
 	}
+	let drift = keystore_authority_drift::<P>(keystore, key_type, &authorities)
+		.map_err(StartAuraError::KeystoreUnavailable)?;
 
-	fn pre_digest_data(&self, slot: Slot, _claim: &Self::Claim) -> Vec<sp_runtime::DigestItem> {
-		vec![<DigestItem as CompatibleDigestItem<P::Signature>>::aura_pre_digest(slot)]
+	if !drift.unknown_local_keys.is_empty() {
+		warn!(
+			target: "aura",
+			"⚠️  The keystore holds Aura keys that are not in the authority set at #{}: {:?}",
+			info.best_number,
+			drift.unknown_local_keys,
+		);
+	}
+	// Every other authority is missing locally on a healthy node, so only warn if the node
+	// holds Aura keys but will never author with any of them.
+	if !drift.unknown_local_keys.is_empty() && drift.no_local_authority(authorities.len()) {
+		warn!(
+			target: "aura",
+			"⚠️  None of the local Aura keys is in the authority set at #{}, which is: {:?}",
+			info.best_number,
+			drift.missing_local_keys,
+		);
+	} else {
+		debug!(
+			target: "aura",
+			"Authorities without a local key at #{}: {:?}",
+			info.best_number,
+			drift.missing_local_keys,
+		);
 	}
 
-	async fn block_import_params(
-		&self,
-		header: B::Header,
-		header_hash: &B::Hash,
-		body: Vec<B::Extrinsic>,
-		storage_changes: StorageChanges<<Self::BlockImport as BlockImport<B>>::Transaction, B>,
-		public: Self::Claim,
-		_epoch: Self::EpochData,
-	) -> Result<
-		sc_consensus::BlockImportParams<B, <Self::BlockImport as BlockImport<B>>::Transaction>,
-		sp_consensus::Error,
-	> {
-		// sign the pre-sealed hash of the block and then
-		// add it to a digest item.
-		let public_type_pair = public.to_public_crypto_pair();
-		let public = public.to_raw_vec();
-		let signature = SyncCryptoStore::sign_with(
-			&*self.keystore,
-			<AuthorityId<P> as AppKey>::ID,
-			&public_type_pair,
-			header_hash.as_ref(),
-		)
-		.map_err(|e| sp_consensus::Error::CannotSign(public.clone(), e.to_string()))?
-		.ok_or_else(|| {
-			sp_consensus::Error::CannotSign(
-				public.clone(),
-				"Could not find key in keystore.".into(),
-			)
-		})?;
-		let signature = signature
-			.clone()
-			.try_into()
-			.map_err(|_| sp_consensus::Error::InvalidSignature(signature, public))?;
-
-		let signature_digest_item =
-			<DigestItem as CompatibleDigestItem<P::Signature>>::aura_seal(signature);
+	Ok(())
+}
 
-		let mut import_block = BlockImportParams::new(BlockOrigin::Own, header);
-		import_block.post_digests.push(signature_digest_item);
-		import_block.body = Some(body);
-		import_block.state_action =
-			StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(storage_changes));
-		import_block.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+/// Handle to preload the authority set of an upcoming block.
+///
+/// When an authority-set change is scheduled at a known block, the set can be fetched ahead of
+/// time through [`AuthoritiesPreloader::preload_authorities_at`]. The worker fetches requested
+/// sets at its next slot by initializing the requested block on top of the current chain head
+/// and pins them. Once the chain reaches the block, the pinned set is compared with the set
+/// actually enacted on-chain and any discrepancy is logged.
+///
+/// Fetching ahead of time is best effort: it relies on the set change being triggered by the
+/// block number alone, as is the case for periodic sessions.
+pub struct AuthoritiesPreloader<A, N> {
+	inner: Arc<Mutex<PreloadedAuthorities<A, N>>>,
+}
 
-		Ok(import_block)
-	}
+struct PreloadedAuthorities<A, N> {
+	requested: BTreeSet<N>,
+	pinned: BTreeMap<N, Vec<A>>,
+}
 
-	fn force_authoring(&self) -> bool {
-		self.force_authoring
+impl<A, N> Clone for AuthoritiesPreloader<A, N> {
+	fn clone(&self) -> Self {
+		Self { inner: self.inner.clone() }
 	}
+}
 
-	fn should_backoff(&self, slot: Slot, chain_head: &B::Header) -> bool {
-		if let Some(ref strategy) = self.backoff_authoring_blocks {
-			if let Ok(chain_head_slot) = find_pre_digest::<B, P::Signature>(chain_head) {
-				return strategy.should_backoff(
-					*chain_head.number(),
-					chain_head_slot,
-					self.client.info().finalized_number,
-					slot,
-					self.logging_target(),
-				)
-			}
+impl<A, N: Ord> Default for AuthoritiesPreloader<A, N> {
+	fn default() -> Self {
+		Self {
+			inner: Arc::new(Mutex::new(PreloadedAuthorities {
+				requested: BTreeSet::new(),
+				pinned: BTreeMap::new(),
+			})),
 		}
-		false
 	}
+}
 
-	fn sync_oracle(&mut self) -> &mut Self::SyncOracle {
-		&mut self.sync_oracle
+impl<A: Clone, N: Ord + Copy> AuthoritiesPreloader<A, N> {
+	/// Create a new handle. Pass a clone to the worker parameters.
+	pub fn new() -> Self {
+		Self::default()
 	}
 
-	fn justification_sync_link(&mut self) -> &mut Self::JustificationSyncLink {
-		&mut self.justification_sync_link
+	/// Request the authority set active at block `number` to be fetched and pinned.
+	pub fn preload_authorities_at(&self, number: N) {
+		self.inner.lock().requested.insert(number);
 	}
 
-	fn proposer(&mut self, block: &B::Header) -> Self::CreateProposer {
-		self.env
-			.init(block)
-			.map_err(|e| sp_consensus::Error::ClientImport(format!("{:?}", e)))
-			.boxed()
+	/// The pinned authority set of block `number`, if it was preloaded.
+	pub fn pinned_authorities(&self, number: N) -> Option<Vec<A>> {
+		self.inner.lock().pinned.get(&number).cloned()
 	}
 
-	fn telemetry(&self) -> Option<TelemetryHandle> {
-		self.telemetry.clone()
+	/// Take the requests for blocks after `number`, dropping the ones that already passed.
+	fn take_requests_after(&self, number: N) -> Vec<N> {
+		let mut inner = self.inner.lock();
+		std::mem::take(&mut inner.requested).into_iter().filter(|n| *n > number).collect()
 	}
 
-	fn proposing_remaining_duration(&self, slot_info: &SlotInfo<B>) -> std::time::Duration {
-		let parent_slot = find_pre_digest::<B, P::Signature>(&slot_info.chain_head).ok();
-
-		sc_consensus_slots::proposing_remaining_duration(
-			parent_slot,
-			slot_info,
-			&self.block_proposal_slot_portion,
-			self.max_block_proposal_slot_portion.as_ref(),
-			sc_consensus_slots::SlotLenienceType::Exponential,
-			self.logging_target(),
-		)
+	fn pin(&self, number: N, authorities: Vec<A>) {
+		self.inner.lock().pinned.insert(number, authorities);
 	}
-}
 
-fn aura_err<B: BlockT>(error: Error<B>) -> Error<B> {
-	debug!(target: "aura", "{}", error);
-	error
+	/// Take the pinned set of block `number`, dropping the ones of earlier blocks.
+	fn take_pinned(&self, number: N) -> Option<Vec<A>> {
+		let mut inner = self.inner.lock();
+		let mut pinned = inner.pinned.split_off(&number);
+		let found = pinned.remove(&number);
+		inner.pinned = pinned;
+		found
+	}
 }
 
-/// Aura Errors
-#[derive(Debug, thiserror::Error)]
-pub enum Error<B: BlockT> {
-	/// Multiple Aura pre-runtime headers
-	#[error("Multiple Aura pre-runtime headers")]
-	MultipleHeaders,
-	/// No Aura pre-runtime digest found
-	#[error("No Aura pre-runtime digest found")]
-	NoDigestFound,
-	/// Header is unsealed
-	#[error("Header {0:?} is unsealed")]
-	HeaderUnsealed(B::Hash),
-	/// Header has a bad seal
-	#[error("Header {0:?} has a bad seal")]
-	HeaderBadSeal(B::Hash),
-	/// Slot Author not found
-	#[error("Slot Author not found")]
-	SlotAuthorNotFound,
-	/// Bad signature
-	#[error("Bad signature on {0:?}")]
-	BadSignature(B::Hash),
-	/// Client Error
-	#[error(transparent)]
-	Client(sp_blockchain::Error),
-	/// Unknown inherent error for identifier
-	#[error("Unknown inherent error for identifier: {}", String::from_utf8_lossy(.0))]
-	UnknownInherentError(sp_inherents::InherentIdentifier),
-	/// Inherents Error
-	#[error("Inherent error: {0}")]
-	Inherent(sp_inherents::Error),
+/// Cache of authority sets, keyed by the hash of the block they were fetched at.
+///
+/// The authority set at a block only differs from the set at its parent if the block
+/// announces a change through a `ConsensusLog::AuthoritiesChange` digest. So once the set at
+/// a block is known, it is reused for all its descendants until one of them announces a
+/// change, which invalidates the cached set for that branch.
+pub struct AuthoritiesCache<H, A> {
+	capacity: usize,
+	sets: HashMap<H, Vec<A>>,
+	insertion_order: VecDeque<H>,
 }
 
-impl<B: BlockT> From<Error<B>> for String {
-	fn from(error: Error<B>) -> String {
-		error.to_string()
+impl<H: Hash + Eq + Clone, A: Clone + Decode> AuthoritiesCache<H, A> {
+	/// Create a cache holding at most `capacity` authority sets.
+	pub fn new(capacity: usize) -> Self {
+		Self { capacity, sets: HashMap::new(), insertion_order: VecDeque::new() }
 	}
-}
 
-/// Get pre-digests from the header
-pub fn find_pre_digest<B: BlockT, Signature: Codec>(header: &B::Header) -> Result<Slot, Error<B>> {
-	if header.number().is_zero() {
-		return Ok(0.into())
-	}
+	/// The authorities at `header`, if cached for it or inherited from its parent.
+	pub fn authorities_at<B: BlockT<Hash = H>>(&mut self, header: &B::Header) -> Option<Vec<A>> {
+		let hash = header.hash();
+		if let Some(authorities) = self.sets.get(&hash) {
+			return Some(authorities.clone())
+		}
 
-	let mut pre_digest: Option<Slot> = None;
-	for log in header.digest().logs() {
-		trace!(target: "aura", "Checking log {:?}", log);
-		match (CompatibleDigestItem::<Signature>::as_aura_pre_digest(log), pre_digest.is_some()) {
-			(Some(_), true) => return Err(aura_err(Error::MultipleHeaders)),
-			(None, _) => trace!(target: "aura", "Ignoring digest not meant for us"),
-			(s, false) => pre_digest = s,
+		if find_authorities_change::<B, A>(header).is_some() {
+			return None
 		}
+
+		let authorities = self.sets.get(header.parent_hash())?.clone();
+		self.insert(hash, authorities.clone());
+		Some(authorities)
 	}
-	pre_digest.ok_or_else(|| aura_err(Error::NoDigestFound))
-}
 
-fn authorities<A, B, C>(
-	client: &C,
-	parent_hash: B::Hash,
-	context_block_number: NumberFor<B>,
-	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
-) -> Result<Vec<A>, ConsensusError>
-where
-	A: Codec + Debug,
-	B: BlockT,
-	C: ProvideRuntimeApi<B>,
-	C::Api: AuraApi<B, A>,
-{
-	let runtime_api = client.runtime_api();
+	/// Cache the `authorities` at the block `hash`.
+	pub fn insert(&mut self, hash: H, authorities: Vec<A>) {
+		if self.sets.insert(hash.clone(), authorities).is_none() {
+			self.insertion_order.push_back(hash);
+		}
 
-	match compatibility_mode {
-		CompatibilityMode::None => {},
-		// Use `initialize_block` until we hit the block that should disable the mode.
-		CompatibilityMode::UseInitializeBlock { until } =>
-			if *until > context_block_number {
-				runtime_api
-					.initialize_block(
-						&BlockId::Hash(parent_hash),
-						&B::Header::new(
-							context_block_number,
-							Default::default(),
-							Default::default(),
-							parent_hash,
-							Default::default(),
-						),
-					)
-					.map_err(|_| sp_consensus::Error::InvalidAuthoritiesSet)?;
-			},
+		while self.insertion_order.len() > self.capacity {
+			if let Some(oldest) = self.insertion_order.pop_front() {
+				self.sets.remove(&oldest);
+			}
+		}
 	}
+}
 
-	runtime_api
-		.authorities(&BlockId::Hash(parent_hash))
-		.ok()
-		.ok_or(sp_consensus::Error::InvalidAuthoritiesSet)
+/// Block production weights of authorities, keyed by their public key.
+///
+/// With weights the author of a slot is chosen by cumulative-weight bucketing over the
+/// authority set instead of plain round-robin, so each authority authors a share of the slots
+/// proportional to its weight. The author still only depends on the slot and the set.
+/// Authorities without a weight count with a weight of one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuthorityWeights {
+	weights: HashMap<Vec<u8>, u64>,
 }
 
+impl AuthorityWeights {
+	/// Create weights with every authority counting with a weight of one.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Set the weight of `authority`.
+	pub fn insert<A: ByteArray>(&mut self, authority: &A, weight: u64) {
+		self.weights.insert(authority.to_raw_vec(), weight);
+	}
+
+	/// The weight of the authority with the raw public key `authority`.
+	fn weight(&self, authority: &[u8]) -> u64 {
+		self.weights.get(authority).copied().unwrap_or(1)
+	}
+}
+
+/// Handle to pause and resume authoring of a running worker.
+///
+/// While paused the worker declines to claim slots, so no blocks are authored, but block import
+/// and networking keep running, e.g. while rotating the session keys. Clones of the handle
+/// control the same worker.
+#[derive(Clone, Default)]
+pub struct AuraControl {
+	paused: Arc<AtomicBool>,
+}
+
+impl AuraControl {
+	/// Create a handle of a worker that is authoring.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// Stop authoring from the next slot on.
+	pub fn pause(&self) {
+		self.paused.store(true, Ordering::SeqCst);
+	}
+
+	/// Resume authoring from the next slot on.
+	pub fn resume(&self) {
+		self.paused.store(false, Ordering::SeqCst);
+	}
+
+	/// Whether authoring is paused.
+	pub fn is_paused(&self) -> bool {
+		self.paused.load(Ordering::SeqCst)
+	}
+}
+
+/// Handle to read the slot the worker last claimed or observed, e.g. for health endpoints.
+///
+/// Reading it is cheap and lock-free, so monitoring can poll it to detect a stuck worker. Clones
+/// of the handle track the same worker.
+#[derive(Clone, Default)]
+pub struct SlotTracker {
+	slot: Arc<AtomicU64>,
+}
+
+impl SlotTracker {
+	/// Create a handle of a worker that has not seen any slot yet.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The highest slot the worker tried to claim or authored a block in.
+	///
+	/// This is slot `0` until the worker saw its first slot.
+	pub fn current_slot(&self) -> Slot {
+		self.slot.load(Ordering::Relaxed).into()
+	}
+
+	fn note(&self, slot: Slot) {
+		self.slot.fetch_max(*slot, Ordering::Relaxed);
+	}
+}
+
+/// Source of the authority set the worker claims slots and seals blocks with.
+///
+/// The default, [`AuraApiAuthorities`], reads the set through `AuraApi::authorities`. Chains that
+/// moved that call behind another runtime API, e.g. for a staged migration, implement this for
+/// their runtime API instead and pass it as [`BuildAuraWorkerParams::authority_provider`].
+pub trait AuthorityProvider<B: BlockT, A, Api> {
+	/// The authorities at the block `at`, read through `runtime_api`.
+	///
+	/// The state of `runtime_api` may already be advanced by `initialize_block`, see
+	/// [`CompatibilityMode::UseInitializeBlock`], so the set has to be read through it rather
+	/// than through a new instance of the runtime API.
+	fn authorities(&self, runtime_api: &Api, at: &BlockId<B>) -> Result<Vec<A>, sp_api::ApiError>;
+}
+
+/// [`AuthorityProvider`] reading the authorities through `AuraApi::authorities`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuraApiAuthorities;
+
+impl<B: BlockT, A: Codec, Api: AuraApi<B, A>> AuthorityProvider<B, A, Api> for AuraApiAuthorities {
+	fn authorities(&self, runtime_api: &Api, at: &BlockId<B>) -> Result<Vec<A>, sp_api::ApiError> {
+		AuraApi::authorities(runtime_api, at)
+	}
+}
+
+/// Backoff strategy that stops authoring while too many blocks are unfinalized.
+///
+/// Authoring is backed off as long as the chain head is more than `max_unfinalized` blocks
+/// ahead of the finalized block, e.g. during a GRANDPA stall, to avoid piling up unfinalized
+/// blocks. Unlike [`sc_consensus_slots::BackoffAuthoringOnFinalizedHeadLagging`] it never
+/// authors while the lag exceeds the threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FinalityLagBackoff {
+	max_unfinalized: u32,
+}
+
+impl FinalityLagBackoff {
+	/// Back off while more than `max_unfinalized` blocks are unfinalized.
+	pub fn new(max_unfinalized: u32) -> Self {
+		Self { max_unfinalized }
+	}
+}
+
+impl<N> BackoffAuthoringBlocksStrategy<N> for FinalityLagBackoff
+where
+	N: Saturating + PartialOrd + From<u32>,
+{
+	fn should_backoff(
+		&self,
+		chain_head_number: N,
+		_chain_head_slot: Slot,
+		finalized_number: N,
+		slot_now: Slot,
+		logging_target: &str,
+	) -> bool {
+		let should_backoff =
+			chain_head_number.saturating_sub(finalized_number) > self.max_unfinalized.into();
+		if should_backoff {
+			debug!(
+				target: logging_target,
+				"Backing off authoring at slot {}, more than {} blocks are unfinalized",
+				slot_now,
+				self.max_unfinalized,
+			);
+		}
+		should_backoff
+	}
+}
+
+/// Backoff strategy that backs off whenever either of two strategies does.
+///
+/// E.g. combines [`FinalityLagBackoff`] with
+/// [`sc_consensus_slots::BackoffAuthoringOnFinalizedHeadLagging`]. The second strategy is not
+/// asked once the first one backs off.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CombinedBackoff<S1, S2> {
+	first: S1,
+	second: S2,
+}
+
+impl<S1, S2> CombinedBackoff<S1, S2> {
+	/// Back off whenever `first` or `second` does.
+	pub fn new(first: S1, second: S2) -> Self {
+		Self { first, second }
+	}
+}
+
+impl<N, S1, S2> BackoffAuthoringBlocksStrategy<N> for CombinedBackoff<S1, S2>
+where
+	N: Clone,
+	S1: BackoffAuthoringBlocksStrategy<N>,
+	S2: BackoffAuthoringBlocksStrategy<N>,
+{
+	fn should_backoff(
+		&self,
+		chain_head_number: N,
+		chain_head_slot: Slot,
+		finalized_number: N,
+		slot_now: Slot,
+		logging_target: &str,
+	) -> bool {
+		self.first.should_backoff(
+			chain_head_number.clone(),
+			chain_head_slot,
+			finalized_number.clone(),
+			slot_now,
+			logging_target,
+		) || self.second.should_backoff(
+			chain_head_number,
+			chain_head_slot,
+			finalized_number,
+			slot_now,
+			logging_target,
+		)
+	}
+}
+
+/// Advisory limit on the number of transactions of the next proposed block.
+///
+/// The worker publishes the limit before every proposal, see
+/// [`BuildAuraWorkerParams::max_extrinsics`] and
+/// [`BuildAuraWorkerParams::max_tx_per_lenient_block`]. Proposers that honour it read it through
+/// a clone of the handle, e.g. from a wrapper around the proposer factory.
+#[derive(Clone, Default)]
+pub struct TransactionCountHint {
+	max_transactions: Arc<Mutex<Option<usize>>>,
+}
+
+impl TransactionCountHint {
+	/// Create a new handle. Pass a clone to the worker parameters.
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	/// The maximum number of transactions the next block should include, if limited.
+	pub fn max_transactions(&self) -> Option<usize> {
+		*self.max_transactions.lock()
+	}
+
+	fn set(&self, max_transactions: Option<usize>) {
+		*self.max_transactions.lock() = max_transactions;
+	}
+}
+
+/// A [`SyncOracle`] that reports the node as synced and online.
+///
+/// Meant for single-node development chains, which have no network to sync from. A validator
+/// with a real network should pass its network service, so it does not author while syncing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysSyncedOracle;
+
+impl SyncOracle for AlwaysSyncedOracle {
+	fn is_major_syncing(&mut self) -> bool {
+		false
+	}
+
+	fn is_offline(&mut self) -> bool {
+		false
+	}
+}
+
+/// Adjusts the proposing time the worker computed for a slot.
+///
+/// See [`BuildAuraWorkerParams::deadline_adjuster`].
+pub type DeadlineAdjuster<B> = Arc<dyn Fn(Duration, &SlotInfo<B>) -> Duration + Send + Sync>;
+
+/// Flags the authorities that are known to be offline.
+///
+/// See [`BuildAuraWorkerParams::offline_authorities`].
+pub type OfflineAuthorities<A> = Arc<dyn Fn(&A) -> bool + Send + Sync>;
+
+/// Clock returning the time since the Unix epoch.
+///
+/// See [`BuildAuraWorkerParams::time_source`].
+pub type TimeSource = Arc<dyn Fn() -> Duration + Send + Sync>;
+
+/// The current time since the Unix epoch, read from `time_source` if given and from the system
+/// time otherwise.
+fn time_now(time_source: Option<&TimeSource>) -> Duration {
+	match time_source {
+		Some(time_source) => time_source(),
+		None => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+	}
+}
+
+/// Computes extra digests of an authored block right before it is sealed.
+///
+/// See [`BuildAuraWorkerParams::pre_seal_hook`].
+pub type PreSealHook<B> = Arc<dyn Fn(&<B as BlockT>::Header) -> Vec<DigestItem> + Send + Sync>;
+
+/// Decides from the inherent data of a slot whether to author in it.
+///
+/// See [`BuildAuraWorkerParams::should_author`].
+pub type ShouldAuthor = Arc<dyn Fn(&InherentData) -> bool + Send + Sync>;
+
+/// Whether `should_author` allows authoring with `inherent_data`, which it does if not given.
+fn may_author(should_author: Option<&ShouldAuthor>, inherent_data: &InherentData) -> bool {
+	should_author.map_or(true, |should_author| should_author(inherent_data))
+}
+
+/// Which telemetry events the worker reports.
+///
+/// See [`BuildAuraWorkerParams::telemetry_level`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TelemetryLevel {
+	/// Report the outcome of claiming every slot as well as the authored blocks.
+	Verbose,
+	/// Only report the authored blocks, i.e. their slot lifecycle and failed imports.
+	AuthoredBlocks,
+}
+
+impl TelemetryLevel {
+	/// Whether the per-slot claim events are reported.
+	fn reports_slot_claims(&self) -> bool {
+		matches!(self, Self::Verbose)
+	}
+}
+
+impl Default for TelemetryLevel {
+	fn default() -> Self {
+		Self::Verbose
+	}
+}
+
+/// Counts the transactions ready to be included in the next block, e.g. the `ready` count of
+/// the status of the transaction pool.
+///
+/// See [`SkipEmptyConfig::ready_transactions`].
+pub type ReadyTransactions = Arc<dyn Fn() -> usize + Send + Sync>;
+
+/// When to skip authoring empty blocks.
+///
+/// Whether a block would be empty is decided before proposing it, from the transactions ready to
+/// be included, signed or not. A skipped slot is neither proposed in nor counted as missed.
+///
+/// See [`BuildAuraWorkerParams::skip_empty_blocks`].
+#[derive(Clone)]
+pub struct SkipEmptyConfig {
+	/// The number of slots after the last authored block from which on an empty block is
+	/// authored anyway, as a heartbeat to keep finality progressing.
+	pub min_interval: u64,
+	/// Counts the transactions ready to be included, a block is empty if there are none.
+	pub ready_transactions: ReadyTransactions,
+}
+
+/// Whether to skip authoring in `slot` for lack of transactions, given the slot of the last
+/// block authored by the worker, see [`SkipEmptyConfig`].
+///
+/// The first block after starting is always authored.
+fn skips_empty_block(
+	config: &SkipEmptyConfig,
+	slot: Slot,
+	last_authored_slot: Option<Slot>,
+) -> bool {
+	last_authored_slot.map_or(false, |last| *slot.saturating_sub(*last) < config.min_interval) &&
+		(config.ready_transactions)() == 0
+}
+
+/// [`CanAuthorWith`] that always allows authoring, in place of the wrapped `CanAuthorWith`.
+///
+/// **Dangerous:** the wrapped check keeps a node from authoring with a native runtime that is
+/// incompatible with the on-chain runtime, e.g. during a runtime upgrade. Authoring regardless
+/// can produce blocks the rest of the network rejects. Only use this to recover a stalled chain,
+/// and only for as long as it takes.
+///
+/// The wrapped check is not run at all, it is only kept to name the check being bypassed. Unlike
+/// `sp_consensus::AlwaysCanAuthor`, the bypass is not silent: every slot is logged as a warning
+/// to keep the override auditable.
+#[derive(Debug, Clone)]
+pub struct AlwaysCanAuthor<CAW>(pub CAW);
+
+impl<B: BlockT, CAW> CanAuthorWith<B> for AlwaysCanAuthor<CAW> {
+	fn can_author_with(&self, at: &BlockId<B>) -> Result<(), String> {
+		warn!(
+			target: "aura",
+			"⚠️  Bypassing the authoring compatibility check at {}, authoring regardless.",
+			at,
+		);
+		Ok(())
+	}
+}
+
+/// The depth of the fork `head` is on, if it branches off the finalized chain more than
+/// `max_reorg_depth` blocks below the finalized block.
+///
+/// The depth is the number of blocks from the common ancestor of `head` and the finalized block
+/// up to the finalized block. A head descending from the finalized block is not on a fork,
+/// however far ahead of it. `common_ancestor` looks up the number of the lowest common ancestor
+/// of two blocks by hash; if it is unknown, `head` is not considered on a deep fork.
+fn find_deep_fork<B: BlockT, F>(
+	head: &B::Header,
+	finalized_number: NumberFor<B>,
+	finalized_hash: B::Hash,
+	max_reorg_depth: u32,
+	common_ancestor: F,
+) -> Option<NumberFor<B>>
+where
+	F: FnOnce(B::Hash, B::Hash) -> Option<NumberFor<B>>,
+{
+	let ancestor = common_ancestor(head.hash(), finalized_hash)?;
+	let depth = finalized_number.saturating_sub(ancestor);
+	(depth > max_reorg_depth.into()).then(|| depth)
+}
+
+/// Parameters of [`start_aura`].
+///
+/// Build them with [`StartAuraParamsBuilder`], which has defaults for the optional parameters,
+/// or spell out every field.
+pub struct StartAuraParams<
+	C,
+	SC,
+	I,
+	PF,
+	SO,
+	L,
+	CIDP,
+	BS,
+	CAW,
+	N,
+	A,
+	B: BlockT,
+	AP = AuraApiAuthorities,
+> {
+	/// The duration of a slot.
+	pub slot_duration: SlotDuration,
+	/// The client to interact with the chain.
+	pub client: Arc<C>,
+	/// A select chain implementation to select the best block.
+	pub select_chain: SC,
+	/// The block import.
+	pub block_import: I,
+	/// The proposer factory to build proposer instances.
+	pub proposer_factory: PF,
+	/// The sync oracle that can give us the current sync status.
+	pub sync_oracle: SO,
+	/// Hook into the sync module to control the justification sync process.
+	pub justification_sync_link: L,
+	/// Something that can create the inherent data providers.
+	pub create_inherent_data_providers: CIDP,
+	/// Should we force the authoring of blocks?
+	pub force_authoring: bool,
+	/// The backoff strategy when we miss slots.
+	pub backoff_authoring_blocks: Option<BS>,
+	/// The keystore used by the node.
+	pub keystore: SyncCryptoStorePtr,
+	/// Can we author a block with this node?
+	///
+	/// See [`AlwaysCanAuthor`] to author despite a failing check in an emergency.
+	pub can_author_with: CAW,
+	/// The proportion of the slot dedicated to proposing.
+	///
+	/// The block proposing will be limited to this proportion of the slot from the starting of the
+	/// slot. However, the proposing can still take longer when there is some lenience factor
+	/// applied, because there were no blocks produced for some slots.
+	pub block_proposal_slot_portion: SlotProportion,
+	/// The maximum proportion of the slot dedicated to proposing with any lenience factor applied
+	/// due to no blocks being produced.
+	pub max_block_proposal_slot_portion: Option<SlotProportion>,
+	/// Telemetry instance used to report telemetry metrics.
+	pub telemetry: Option<TelemetryHandle>,
+	/// Compatibility mode that should be used.
+	///
+	/// If in doubt, use `Default::default()`.
+	pub compatibility_mode: CompatibilityMode<N>,
+	/// The prometheus registry the worker metrics are registered at.
+	pub prometheus_registry: Option<Registry>,
+	/// Emit a JSON event for every authored block.
+	///
+	/// See [`BuildAuraWorkerParams::json_events`].
+	pub json_events: bool,
+	/// Handle to preload the authority sets of upcoming blocks.
+	pub authorities_preloader: Option<AuthoritiesPreloader<A, N>>,
+	/// Skip authoring while the chain head lags more than this many slots behind.
+	/// See [`BuildAuraWorkerParams::max_head_slot_lag`].
+	pub max_head_slot_lag: Option<u64>,
+	/// Slots the worker must not author, even when it is their expected author.
+	/// See [`BuildAuraWorkerParams::skip_slots`].
+	pub skip_slots: Option<Box<dyn Fn(Slot) -> bool + Send + Sync>>,
+	/// Check the seal of every authored block before importing it.
+	/// See [`BuildAuraWorkerParams::self_verify_before_import`].
+	pub self_verify_before_import: bool,
+	/// Minimum wall-clock time between two blocks authored by this node.
+	/// See [`BuildAuraWorkerParams::min_inter_block_time`].
+	pub min_inter_block_time: Option<Duration>,
+	/// Retry fetching the authority set once before giving up on a slot.
+	/// See [`BuildAuraWorkerParams::authorities_retry`].
+	pub authorities_retry: bool,
+	/// Advisory limit on the transactions of blocks proposed under slot lenience.
+	/// See [`BuildAuraWorkerParams::max_tx_per_lenient_block`].
+	pub max_tx_per_lenient_block: Option<usize>,
+	/// Handle the transaction limit of the next proposal is published through.
+	/// See [`BuildAuraWorkerParams::transaction_count_hint`].
+	pub transaction_count_hint: Option<TransactionCountHint>,
+	/// Cache the authority sets fetched from the runtime, keeping at most this many sets.
+	/// See [`BuildAuraWorkerParams::authorities_cache_capacity`].
+	pub authorities_cache_capacity: Option<usize>,
+	/// Weights to select the slot authors by instead of round-robin.
+	/// See [`BuildAuraWorkerParams::authority_weights`].
+	pub authority_weights: Option<AuthorityWeights>,
+	/// How the proposing time grows after slots without blocks.
+	/// See [`BuildAuraWorkerParams::slot_lenience_type`].
+	pub slot_lenience_type: SlotLenienceType,
+	/// Follow changes of the runtime's slot duration, restarting the slot timer with the new
+	/// duration.
+	///
+	/// The slot duration is re-read from the runtime at every block that changes the authority
+	/// set, see [`BuildAuraWorkerParams::slot_duration_changes`].
+	///
+	/// This is a consensus-critical setting: all nodes must agree on the block from which on
+	/// the new duration applies, i.e. the runtime must only change the slot duration together
+	/// with the authority set, and every node must run with this enabled before the change.
+	/// Nodes that keep the old duration claim slots at the wrong times and have their blocks
+	/// rejected. `create_inherent_data_providers` must derive the slot from the current slot
+	/// duration as well, the import queue must be given the old duration through
+	/// [`ImportQueueParams::slot_duration_schedule`], and the runtime must keep slot numbers
+	/// increasing across the change.
+	pub dynamic_slot_duration: bool,
+	/// Fork choice applied to the blocks authored by the node.
+	/// See [`BuildAuraWorkerParams::fork_choice`].
+	pub fork_choice: ForkChoiceStrategy,
+	/// Portion of the slot after which a missing block may be authored by the secondary author.
+	/// See [`BuildAuraWorkerParams::secondary_slot_delay`].
+	pub secondary_slot_delay: Option<SlotProportion>,
+	/// Sign blocks through the async keystore interface.
+	/// See [`BuildAuraWorkerParams::async_keystore`].
+	pub async_keystore: bool,
+	/// Adjusts the proposing time computed for each slot.
+	/// See [`BuildAuraWorkerParams::deadline_adjuster`].
+	pub deadline_adjuster: Option<DeadlineAdjuster<B>>,
+	/// See [`BuildAuraWorkerParams::pre_seal_hook`].
+	pub pre_seal_hook: Option<PreSealHook<B>>,
+	/// See [`BuildAuraWorkerParams::offline_authorities`].
+	pub offline_authorities: Option<OfflineAuthorities<A>>,
+	/// See [`BuildAuraWorkerParams::engine_id`].
+	pub engine_id: ConsensusEngineId,
+	/// See [`BuildAuraWorkerParams::authored_blocks`].
+	pub authored_blocks: Option<AuthoredBlocks<B::Hash>>,
+	/// See [`BuildAuraWorkerParams::time_source`].
+	pub time_source: Option<TimeSource>,
+	/// Retry creating the inherent data providers of a slot once if it fails.
+	///
+	/// The retry only happens while the slot's portion for proposing, see
+	/// `block_proposal_slot_portion`, has not elapsed, and is abandoned once it does, so it never
+	/// delays the block into the next slot. A creation that fails even after retrying is reported
+	/// as [`Error::Inherent`].
+	pub inherent_retry: bool,
+	/// See [`BuildAuraWorkerParams::authored_block_origin`].
+	pub authored_block_origin: BlockOrigin,
+	/// See [`BuildAuraWorkerParams::max_extrinsics`].
+	pub max_extrinsics: Option<usize>,
+	/// See [`BuildAuraWorkerParams::slot_tiebreak_fork_choice`].
+	pub slot_tiebreak_fork_choice: bool,
+	/// See [`BuildAuraWorkerParams::authority_provider`].
+	pub authority_provider: AP,
+	/// Retry a failed import of an authored block once.
+	///
+	/// See [`BuildAuraWorkerParams::retry_self_import`].
+	pub retry_self_import: bool,
+	/// Log where the local keys enter or leave the authority set within this many blocks
+	/// following the best block at startup.
+	///
+	/// Each of the blocks is initialized on top of the best block to read its authority set, see
+	/// [`authorities_at`], so keep this in the order of a session length.
+	pub authorities_lookahead: Option<u32>,
+	/// See [`BuildAuraWorkerParams::should_author`].
+	pub should_author: Option<ShouldAuthor>,
+	/// See [`BuildAuraWorkerParams::include_authority_index`].
+	pub include_authority_index: bool,
+	/// See [`BuildAuraWorkerParams::max_reorg_depth`].
+	pub max_reorg_depth: Option<u32>,
+	/// See [`BuildAuraWorkerParams::telemetry_level`].
+	pub telemetry_level: TelemetryLevel,
+	/// See [`BuildAuraWorkerParams::skip_empty_blocks`].
+	pub skip_empty_blocks: Option<SkipEmptyConfig>,
+	/// See [`BuildAuraWorkerParams::key_type`].
+	pub key_type: KeyTypeId,
+	/// See [`BuildAuraWorkerParams::max_propose_retries`].
+	pub max_propose_retries: u32,
+	/// See [`BuildAuraWorkerParams::clock_drift_threshold`].
+	pub clock_drift_threshold: Option<u64>,
+}
+
+/// Start the aura worker. The returned future should be run in a futures executor.
+///
+/// Authoring can be paused and resumed through the returned [`AuraControl`], and the slot the
+/// worker is at is read through the returned [`SlotTracker`]. Problems that keep the node from
+/// ever authoring, like an empty authority set, are reported as a
+/// [`StartAuraError`] before the worker is built.
+pub fn start_aura<P, B, C, SC, I, PF, SO, L, CIDP, BS, CAW, Error, AP>(
+	StartAuraParams {
+		slot_duration,
+		client,
+		select_chain,
+		block_import,
+		proposer_factory,
+		sync_oracle,
+		justification_sync_link,
+		create_inherent_data_providers,
+		force_authoring,
+		backoff_authoring_blocks,
+		keystore,
+		can_author_with,
+		block_proposal_slot_portion,
+		max_block_proposal_slot_portion,
+		telemetry,
+		compatibility_mode,
+		prometheus_registry,
+		json_events,
+		authorities_preloader,
+		max_head_slot_lag,
+		skip_slots,
+		self_verify_before_import,
+		min_inter_block_time,
+		authorities_retry,
+		max_tx_per_lenient_block,
+		transaction_count_hint,
+		authorities_cache_capacity,
+		authority_weights,
+		slot_lenience_type,
+		dynamic_slot_duration,
+		fork_choice,
+		secondary_slot_delay,
+		async_keystore,
+		deadline_adjuster,
+		pre_seal_hook,
+		offline_authorities,
+		engine_id,
+		authored_blocks,
+		time_source,
+		inherent_retry,
+		authored_block_origin,
+		max_extrinsics,
+		slot_tiebreak_fork_choice,
+		authority_provider,
+		retry_self_import,
+		authorities_lookahead,
+		should_author,
+		include_authority_index,
+		max_reorg_depth,
+		telemetry_level,
+		skip_empty_blocks,
+		key_type,
+		max_propose_retries,
+		clock_drift_threshold,
+	}: StartAuraParams<C, SC, I, PF, SO, L, CIDP, BS, CAW, NumberFor<B>, AuthorityId<P>, B, AP>,
+) -> Result<(impl Future<Output = ()>, AuraControl, SlotTracker), StartAuraError<B>>
+where
+	P: Pair + Send + Sync,
+	P::Public: AppPublic + Hash + Member + Encode + Decode,
+	P::Signature: TryFrom<Vec<u8>> + Hash + Member + Encode + Decode,
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ BlockOf
+		+ AuxStore
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B>
+		+ Send
+		+ Sync,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+	SC: SelectChain<B>,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	PF: Environment<B, Error = Error> + Send + Sync + 'static,
+	PF::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	<PF::Proposer as Proposer<B>>::Proof: Encode,
+	SO: SyncOracle + Send + Sync + Clone,
+	L: sc_consensus::JustificationSyncLink<B>,
+	CIDP: CreateInherentDataProviders<B, ()> + Send,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	CAW: CanAuthorWith<B> + Send,
+	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api> + Send + Sync + 'static,
+{
+	check_start_preconditions::<P, B, C>(
+		&*client,
+		&*keystore,
+		key_type,
+		slot_duration,
+		&compatibility_mode,
+	)?;
+	if let Some(lookahead) = authorities_lookahead {
+		log_authority_set_lookahead::<P, B, C, AP>(
+			&*client,
+			&authority_provider,
+			&*keystore,
+			key_type,
+			lookahead,
+		);
+	}
+
+	let create_inherent_data_providers = inherent_retry::RetryingInherentDataProviders::new(
+		create_inherent_data_providers,
+		inherent_retry,
+		Duration::from_millis(slot_duration.as_millis()).mul_f32(block_proposal_slot_portion.get()),
+	);
+
+	let control = AuraControl::new();
+	let (slot_duration_changes, slot_duration_receiver) =
+		SlotDurationChanges::new(slot_duration);
+	let (worker, slot_tracker) =
+		build_aura_worker::<P, _, _, _, _, _, _, _, _, _>(BuildAuraWorkerParams {
+			client,
+			block_import,
+			proposer_factory,
+			keystore,
+			sync_oracle: sync_oracle.clone(),
+			justification_sync_link,
+			force_authoring,
+			backoff_authoring_blocks,
+			telemetry,
+			block_proposal_slot_portion,
+			max_block_proposal_slot_portion,
+			compatibility_mode,
+			prometheus_registry,
+			json_events,
+			authorities_preloader,
+			max_head_slot_lag,
+			skip_slots,
+			self_verify_before_import,
+			min_inter_block_time,
+			authorities_retry,
+			max_tx_per_lenient_block,
+			transaction_count_hint,
+			authorities_cache_capacity,
+			authority_weights,
+			control: Some(control.clone()),
+			slot_lenience_type,
+			slot_duration_changes: dynamic_slot_duration.then(|| slot_duration_changes),
+			fork_choice,
+			secondary_slot_delay,
+			async_keystore,
+			deadline_adjuster,
+			pre_seal_hook,
+			offline_authorities,
+			engine_id,
+			authored_blocks,
+			time_source,
+			authored_block_origin,
+			max_extrinsics,
+			slot_tiebreak_fork_choice,
+			authority_provider,
+			retry_self_import,
+			should_author,
+			include_authority_index,
+			max_reorg_depth,
+			telemetry_level,
+			skip_empty_blocks,
+			key_type,
+			max_propose_retries,
+			clock_drift_threshold,
+		});
+
+	let future = if dynamic_slot_duration {
+		future::Either::Left(dynamic_slot_duration::run_with_dynamic_slot_duration(
+			slot_duration,
+			slot_duration_receiver,
+			select_chain,
+			worker,
+			sync_oracle,
+			create_inherent_data_providers,
+			can_author_with,
+		))
+	} else {
+		future::Either::Right(sc_consensus_slots::start_slot_worker(
+			slot_duration,
+			select_chain,
+			worker,
+			sync_oracle,
+			create_inherent_data_providers,
+			can_author_with,
+		))
+	};
+
+	Ok((future, control, slot_tracker))
+}
+
+/// Parameters of [`build_aura_worker`].
+///
+/// Build them with [`BuildAuraWorkerParamsBuilder`], which has defaults for the optional
+/// parameters, or spell out every field.
+pub struct BuildAuraWorkerParams<C, I, PF, SO, L, BS, N, A, B: BlockT, AP = AuraApiAuthorities> {
+	/// The client to interact with the chain.
+	pub client: Arc<C>,
+	/// The block import.
+	pub block_import: I,
+	/// The proposer factory to build proposer instances.
+	pub proposer_factory: PF,
+	/// The sync oracle that can give us the current sync status.
+	pub sync_oracle: SO,
+	/// Hook into the sync module to control the justification sync process.
+	pub justification_sync_link: L,
+	/// Should we force the authoring of blocks?
+	pub force_authoring: bool,
+	/// The backoff strategy when we miss slots.
+	pub backoff_authoring_blocks: Option<BS>,
+	/// The keystore used by the node.
+	pub keystore: SyncCryptoStorePtr,
+	/// The proportion of the slot dedicated to proposing.
+	///
+	/// The block proposing will be limited to this proportion of the slot from the starting of the
+	/// slot. However, the proposing can still take longer when there is some lenience factor
+	/// applied, because there were no blocks produced for some slots.
+	pub block_proposal_slot_portion: SlotProportion,
+	/// The maximum proportion of the slot dedicated to proposing with any lenience factor applied
+	/// due to no blocks being produced.
+	pub max_block_proposal_slot_portion: Option<SlotProportion>,
+	/// Telemetry instance used to report telemetry metrics.
+	pub telemetry: Option<TelemetryHandle>,
+	/// Compatibility mode that should be used.
+	///
+	/// If in doubt, use `Default::default()`.
+	pub compatibility_mode: CompatibilityMode<N>,
+	/// The prometheus registry the worker metrics are registered at.
+	pub prometheus_registry: Option<Registry>,
+	/// Emit a single-line JSON event for every authored block.
+	///
+	/// The events are logged at info level under [`AUTHORED_BLOCK_EVENT_TARGET`], in addition to
+	/// the human readable logs, and have the following stable format:
+	///
+	/// ```json
+	/// {"slot":42,"hash":"0x..","number":7,"author":"0x..","budget_ms":4000,"lenience_slots":0}
+	/// ```
+	///
+	/// `hash` is the hash of the sealed block, `author` the hex encoded public key that sealed
+	/// it, `budget_ms` the time granted for proposing and `lenience_slots` the number of slots
+	/// skipped since the parent block, which determines the lenience applied to the budget.
+	/// New fields may be appended, but existing fields keep their name and meaning.
+	pub json_events: bool,
+	/// Handle to preload the authority sets of upcoming blocks.
+	///
+	/// See [`AuthoritiesPreloader`].
+	pub authorities_preloader: Option<AuthoritiesPreloader<A, N>>,
+	/// Skip authoring while the chain head lags more than this many slots behind.
+	///
+	/// After an extended outage the head returned by the select chain can be far in the past and
+	/// a block built on it would carry a huge slot gap that peers may reject. With this set, the
+	/// worker logs a warning and declines to author until the head is within the given number
+	/// of slots of the current slot again, e.g. because the node caught up through sync. The
+	/// genesis block is exempt, as its slot is always zero.
+	pub max_head_slot_lag: Option<u64>,
+	/// Slots the worker must not author, even when it is their expected author.
+	///
+	/// `claim_slot` declines every slot the predicate matches and logs the skip. This is meant for
+	/// test networks, to deterministically inject missed slots, e.g. to reproduce partial outages.
+	pub skip_slots: Option<Box<dyn Fn(Slot) -> bool + Send + Sync>>,
+	/// Check the seal of every authored block before importing it.
+	///
+	/// The sealed header goes through the same seal checks the import queue runs, and a block that
+	/// fails them is logged as an error and not imported. This guards against authoring bugs, e.g.
+	/// in how the block is sealed, at the cost of verifying every authored signature once more.
+	pub self_verify_before_import: bool,
+	/// Minimum wall-clock time between two blocks authored by this node.
+	///
+	/// The worker declines to claim its slots until this much time has passed since it sealed its
+	/// last block, and logs each slot it skips this way. This deliberately misses slots, e.g. to
+	/// keep the block rate below what the database can write on chains with very short slots.
+	/// The skipped slots count towards the proposing lenience of the following slots. They also
+	/// widen the slot gap to the chain head that `backoff_authoring_blocks` strategies look at,
+	/// so a node that is backing off may author again sooner after a skip.
+	pub min_inter_block_time: Option<Duration>,
+	/// Retry fetching the authority set once before giving up on a slot.
+	///
+	/// Any runtime failure while fetching the authorities is reported as `InvalidAuthoritiesSet`,
+	/// which makes the worker skip the slot. With this set, the worker retries the runtime call
+	/// once after a short delay, which recovers slots lost to a momentary runtime unavailability.
+	/// The delay is awaited without blocking the executor, and the slot is only retried if the
+	/// delay leaves time for authoring. A persistent failure still fails the slot after the
+	/// retry. Retries are counted in the `aura_authorities_retries_total` metric.
+	pub authorities_retry: bool,
+	/// Advisory limit on the transactions of blocks proposed under slot lenience.
+	///
+	/// After slots were missed, lenience grants the proposer a bigger budget, which can make it
+	/// pull in a burst of transactions. When the current slot is lenient, i.e. it does not
+	/// directly follow the slot of the chain head, this limit is published through
+	/// [`Self::transaction_count_hint`] for the next proposal; otherwise no limit is published.
+	/// The limit is only a hint: it takes effect only if the proposer reads the hint.
+	pub max_tx_per_lenient_block: Option<usize>,
+	/// Handle the transaction limit of the next proposal is published through.
+	///
+	/// See [`TransactionCountHint`] and [`Self::max_tx_per_lenient_block`].
+	pub transaction_count_hint: Option<TransactionCountHint>,
+	/// Cache the authority sets fetched from the runtime, keeping at most this many sets.
+	///
+	/// By default the worker calls into the runtime to fetch and decode the authority set at
+	/// every slot, which is measurable for large sets. With a cache, the set of a chain head is
+	/// inherited from its parent unless the head announces a set change, see
+	/// [`AuthoritiesCache`]. The cache is bypassed while a compatibility mode is active.
+	/// See also [`Self::with_authorities_cache`].
+	///
+	/// With a cache, every fetched set is also persisted as an [`AuthoritiesCheckpoint`]. After
+	/// a restart the cache is seeded from the checkpoint, once the runtime confirmed it at the
+	/// first slot.
+	pub authorities_cache_capacity: Option<usize>,
+	/// Weights to select the slot authors by instead of round-robin.
+	///
+	/// The import queue must be configured with the same weights, see
+	/// [`ImportQueueParams::authority_weights`], otherwise the authored blocks are rejected.
+	pub authority_weights: Option<AuthorityWeights>,
+	/// Handle to pause and resume authoring, see [`AuraControl`].
+	pub control: Option<AuraControl>,
+	/// How the proposing time grows after slots without blocks.
+	///
+	/// Use [`SlotLenienceType::Exponential`] unless the exponential ramp-up grants more time than
+	/// the hardware needs and overshoots the slot, in which case [`SlotLenienceType::Linear`]
+	/// gives smoother proposal budgets.
+	pub slot_lenience_type: SlotLenienceType,
+	/// Handle to report changes of the runtime's slot duration through.
+	///
+	/// At every block that changes the authority set, the worker reads the slot duration from
+	/// the runtime and sends it to the handle's receiver if it differs from the current one.
+	/// Whoever runs the worker is responsible for restarting the slot timer with the new
+	/// duration; [`start_aura`] does so with [`StartAuraParams::dynamic_slot_duration`].
+	pub slot_duration_changes: Option<SlotDurationChanges>,
+	/// Fork choice applied to the blocks authored by the node.
+	///
+	/// [`ForkChoiceStrategy::LongestChain`] keeps the usual behaviour. Deployments whose finality
+	/// gadget decides on the best block can use `ForkChoiceStrategy::Custom(false)`, so that
+	/// importing an own block never re-orgs by itself.
+	pub fork_choice: ForkChoiceStrategy,
+	/// Portion of the slot after which the secondary author may author a slot left empty.
+	///
+	/// The secondary author of a slot is the author of the following slot. If it is local and no
+	/// block of the slot has been imported once this portion of the slot has passed, it claims the
+	/// slot and marks its block with a [`SECONDARY_SLOT_ENGINE_ID`] pre-runtime digest. `None`
+	/// disables secondary slots.
+	///
+	/// This trades safety for liveness: a slot of an offline authority is no longer lost, but
+	/// - secondary blocks are rejected by every verifier without
+	///   [`ImportQueueParams::accept_secondary_slots`], so enabling them is a consensus change
+	///   that all nodes have to adopt at once;
+	/// - a block of the primary author arriving after the delay competes with the secondary block
+	///   for the same height, so the delay has to leave room for block propagation and short forks
+	///   become more frequent when it does not;
+	/// - the delay is taken from the proposing time of the secondary author;
+	/// - the runtime attributes every block to the primary author of its slot, e.g. for rewards.
+	pub secondary_slot_delay: Option<SlotProportion>,
+	/// Sign blocks through the async keystore interface.
+	///
+	/// With `true` the worker awaits [`CryptoStore::sign_with`] instead of blocking on
+	/// [`SyncCryptoStore::sign_with`], which keeps the executor responsive for remote or
+	/// HSM-backed keystores. Local keystores sign quickly, so `false` is fine for them.
+	pub async_keystore: bool,
+	/// Adjusts the proposing time computed for each slot.
+	///
+	/// Called with the proposing time derived from the slot duration and lenience, and the slot.
+	/// Whatever it returns is capped at the time left until the end of the slot, e.g. to propose
+	/// faster but smaller blocks under high mempool pressure. Returning a zero duration skips the
+	/// slot. `None` keeps the computed proposing time.
+	pub deadline_adjuster: Option<DeadlineAdjuster<B>>,
+	/// Computes extra digests of each authored block right before it is sealed.
+	///
+	/// Called with the proposed header once its body is final. The returned digests are appended to
+	/// the post digests ahead of the seal and are covered by its signature. Only seals issued under
+	/// another engine ID than [`Self::engine_id`] are allowed, other items are dropped with a
+	/// warning: importing nodes move the seals to the post digests along with the Aura seal, while
+	/// the runtime would reject any other item it did not issue itself. `None` adds no digests.
+	pub pre_seal_hook: Option<PreSealHook<B>>,
+	/// Flags the authorities that are known to be offline, so their slots fall through to the next
+	/// authority of the set that is not flagged.
+	///
+	/// **This changes who may author a slot, so it is a consensus change.** Every node of the
+	/// network has to pass the same predicate to the worker and as
+	/// [`ImportQueueParams::offline_authorities`](crate::ImportQueueParams) to the verifier, and it
+	/// has to give the same answer on every node at the time a slot is authored and whenever its
+	/// block is imported. A predicate based on local observations, e.g. peer connectivity, makes
+	/// nodes disagree on the author of a slot and rejects blocks or forks the chain. Derive it from
+	/// on-chain data instead. Only primary slots fall through, secondary slots keep their author.
+	/// `None` keeps the plain schedule.
+	pub offline_authorities: Option<OfflineAuthorities<A>>,
+	/// The consensus engine ID of the pre-runtime digest and the seal of authored blocks.
+	///
+	/// Forks of a chain may rebrand it, the default is [`AURA_ENGINE_ID`]. **Changing it is a hard
+	/// fork:** the verifier only accepts blocks with the ID given as
+	/// [`ImportQueueParams::engine_id`], so all nodes of a network have to agree on it.
+	pub engine_id: ConsensusEngineId,
+	/// Handle to send every block authored by this node through, once it is sealed.
+	///
+	/// Create it with [`AuthoredBlocks::new`], which also returns the stream of the blocks, e.g.
+	/// for dashboards. The stream is bounded and drops the oldest blocks when it is not polled in
+	/// time, so a slow consumer never stalls authoring. A block is sent at the end of its slot's
+	/// authoring, along with the proof of its proposal if the proposer produces one, see
+	/// [`AuthoredBlockInfo::proof`].
+	pub authored_blocks: Option<AuthoredBlocks<B::Hash>>,
+	/// Clock the worker reads the current time from, as the time since the Unix epoch.
+	///
+	/// It is used for the minimum time between authored blocks and the timestamps of
+	/// [`Self::authored_blocks`]. The default is the system time.
+	///
+	/// This is meant for deterministic tests only. **Never override it in real deployments**, the
+	/// worker would otherwise author by a different time than the rest of the network.
+	pub time_source: Option<TimeSource>,
+	/// The origin the blocks authored by this node are imported with.
+	///
+	/// Defaults to [`BlockOrigin::Own`]. A test harness replaying authored blocks through a shared
+	/// import pipeline can use another origin to tell them apart from live authoring.
+	pub authored_block_origin: BlockOrigin,
+	/// Advisory limit on the transactions of every proposed block.
+	///
+	/// Caps the body of blocks that are valid for the slot but too large for slower validators
+	/// to import in time. The limit is published through [`Self::transaction_count_hint`] before
+	/// every proposal. In lenient slots the lower of it and [`Self::max_tx_per_lenient_block`] is
+	/// published. The [`Proposer`] interface has no notion of a limit, so this is only a hint as
+	/// well: it takes effect only if the proposer, or a wrapper around its factory, reads it.
+	pub max_extrinsics: Option<usize>,
+	/// Break ties between the blocks authored by the node and an equally long best chain by slot.
+	///
+	/// Instead of [`Self::fork_choice`], an authored block becomes the new best block if it is
+	/// higher than the best block, or as high and authored in a lower slot. This keeps equally
+	/// long forks of different authors from flapping. It only affects the blocks authored by the
+	/// node, so all nodes of a network have to enable it together, or they keep disagreeing on
+	/// the best chain.
+	pub slot_tiebreak_fork_choice: bool,
+	/// Where the worker reads the authority sets from, see [`AuthorityProvider`].
+	///
+	/// Defaults to [`AuraApiAuthorities`], i.e. `AuraApi::authorities`. The runtime still has to
+	/// implement `AuraApi` for the slot duration. This only affects the worker: the verifier always
+	/// reads the authorities through `AuraApi`.
+	pub authority_provider: AP,
+	/// Retry a failed import of an authored block once, as long as its slot has not ended.
+	///
+	/// The retry executes the block anew, as the failed import consumed its storage changes. An
+	/// import that fails for good is reported as the `aura.self_import_failed` telemetry event
+	/// either way.
+	pub retry_self_import: bool,
+	/// Decides from the inherent data of each slot whether to author in it.
+	///
+	/// Called with the inherent data created for the slot before anything is proposed. Returning
+	/// `false` skips the slot, e.g. while an emergency-halt inherent data provider signals to stop,
+	/// without tearing down the worker. `None` authors in every slot.
+	pub should_author: Option<ShouldAuthor>,
+	/// Include the index of the author in the authority set in each authored block, as an
+	/// [`AUTHORITY_INDEX_ENGINE_ID`] pre-runtime digest.
+	///
+	/// Verifiers check the index against the authority set before the seal, rejecting
+	/// out-of-range indices without verifying a signature, and blocks whose index does not point
+	/// to their author. The runtime and the verifiers of older releases ignore the digest, so this
+	/// can be enabled without coordinating with the rest of the network.
+	pub include_authority_index: bool,
+	/// Skip slots whose chain head is on a fork deeper than this many blocks.
+	///
+	/// A head not descending from the finalized block is not authored on if its common ancestor
+	/// with the finalized block is more than this many blocks below it, to avoid adding to the
+	/// churn of deep reorgs. A head descending from the finalized block is always authored on,
+	/// however far finality lags behind. The skip is logged. `None` authors on any head.
+	pub max_reorg_depth: Option<u32>,
+	/// Which telemetry events the worker reports, see [`TelemetryLevel`].
+	///
+	/// Only takes effect if [`Self::telemetry`] is given. Defaults to [`TelemetryLevel::Verbose`].
+	pub telemetry_level: TelemetryLevel,
+	/// Skip authoring blocks without transactions, except for a heartbeat block every now and
+	/// then, see [`SkipEmptyConfig`].
+	///
+	/// Saves disk space on chains with little traffic. `None` authors empty blocks like any other.
+	pub skip_empty_blocks: Option<SkipEmptyConfig>,
+	/// The key type the authority keys are filed under in the keystore.
+	///
+	/// Forks filing their Aura keys under a key type of their own set it to that one. Defaults to
+	/// [`AURA`](sp_application_crypto::key_types::AURA).
+	pub key_type: KeyTypeId,
+	/// How many times to retry a failed proposal within the slot.
+	///
+	/// Each retry proposes with a new proposer, created once a proposal failed and handed what is
+	/// left of the proposing time of the slot after creating it, so retrying never pushes the
+	/// block past the slot. Failures to create the inherent data are not retried here, see
+	/// [`StartAuraParams::inherent_retry`]. Defaults to `0`, i.e. a failed proposal loses the slot.
+	pub max_propose_retries: u32,
+	/// Estimate the drift of the local clock from the chain heads of peers, and report drift of
+	/// more than this many slots.
+	///
+	/// The slots of recent chain heads authored by peers are compared to the local slot they were
+	/// seen in. Once their median is off by more than the threshold, a warning is logged and the
+	/// `aura.clock_drift_slots` telemetry event reported, as a drifting clock makes the node author
+	/// in the wrong slots and reject blocks of peers as too far in the future. `None` does not
+	/// estimate the drift.
+	pub clock_drift_threshold: Option<u64>,
+}
+
+impl<C, I, PF, SO, L, BS, N, A, B: BlockT, AP>
+	BuildAuraWorkerParams<C, I, PF, SO, L, BS, N, A, B, AP>
+{
+	/// Cache up to `capacity` authority sets in the worker.
+	///
+	/// See [`Self::authorities_cache_capacity`].
+	pub fn with_authorities_cache(self, capacity: usize) -> Self {
+		Self { authorities_cache_capacity: Some(capacity), ..self }
+	}
+}
+
+/// Build the aura worker, along with the [`SlotTracker`] of its slot.
+///
+/// The caller is responsible for running this worker, otherwise it will do nothing.
+pub fn build_aura_worker<P, B, C, PF, I, SO, L, BS, Error, AP>(
+	params: BuildAuraWorkerParams<C, I, PF, SO, L, BS, NumberFor<B>, AuthorityId<P>, B, AP>,
+) -> (impl sc_consensus_slots::SlotWorker<B, <PF::Proposer as Proposer<B>>::Proof>, SlotTracker)
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ BlockOf
+		+ AuxStore
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B>
+		+ Send
+		+ Sync,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+	PF: Environment<B, Error = Error> + Send + Sync + 'static,
+	PF::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	<PF::Proposer as Proposer<B>>::Proof: Encode,
+	P: Pair + Send + Sync,
+	P::Public: AppPublic + Hash + Member + Encode + Decode,
+	P::Signature: TryFrom<Vec<u8>> + Hash + Member + Encode + Decode,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
+	SO: SyncOracle + Send + Sync + Clone,
+	L: sc_consensus::JustificationSyncLink<B>,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api> + Send + Sync + 'static,
+{
+	let (worker, slot_tracker) = new_aura_worker::<P, B, C, PF, I, SO, L, BS, Error, AP>(params);
+	(worker.into_slot_worker(), slot_tracker)
+}
+
+/// Build the [`AuraWorker`] of [`build_aura_worker`], before it is wrapped into a slot worker.
+pub(crate) fn new_aura_worker<P, B, C, PF, I, SO, L, BS, Error, AP>(
+	BuildAuraWorkerParams {
+		client,
+		block_import,
+		proposer_factory,
+		sync_oracle,
+		justification_sync_link,
+		backoff_authoring_blocks,
+		keystore,
+		block_proposal_slot_portion,
+		max_block_proposal_slot_portion,
+		telemetry,
+		force_authoring,
+		compatibility_mode,
+		prometheus_registry,
+		json_events,
+		authorities_preloader,
+		max_head_slot_lag,
+		skip_slots,
+		self_verify_before_import,
+		min_inter_block_time,
+		authorities_retry,
+		max_tx_per_lenient_block,
+		transaction_count_hint,
+		authorities_cache_capacity,
+		authority_weights,
+		control,
+		slot_lenience_type,
+		slot_duration_changes,
+		fork_choice,
+		secondary_slot_delay,
+		async_keystore,
+		deadline_adjuster,
+		pre_seal_hook,
+		offline_authorities,
+		engine_id,
+		authored_blocks,
+		time_source,
+		authored_block_origin,
+		max_extrinsics,
+		slot_tiebreak_fork_choice,
+		authority_provider,
+		retry_self_import,
+		should_author,
+		include_authority_index,
+		max_reorg_depth,
+		telemetry_level,
+		skip_empty_blocks,
+		key_type,
+		max_propose_retries,
+		clock_drift_threshold,
+	}: BuildAuraWorkerParams<C, I, PF, SO, L, BS, NumberFor<B>, AuthorityId<P>, B, AP>,
+) -> (AuraWorker<C, PF, I, P, SO, L, BS, NumberFor<B>, B, AP>, SlotTracker)
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ BlockOf
+		+ AuxStore
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B>
+		+ Send
+		+ Sync,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+	PF: Environment<B, Error = Error> + Send + Sync + 'static,
+	PF::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	<PF::Proposer as Proposer<B>>::Proof: Encode,
+	P: Pair + Send + Sync,
+	P::Public: AppPublic + Hash + Member + Encode + Decode,
+	P::Signature: TryFrom<Vec<u8>> + Hash + Member + Encode + Decode,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
+	SO: SyncOracle + Send + Sync + Clone,
+	L: sc_consensus::JustificationSyncLink<B>,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api> + Send + Sync + 'static,
+{
+	let authorities_checkpoint = authorities_cache_capacity
+		.and_then(|_| match load_authorities_checkpoint(&*client) {
+			Ok(checkpoint) => checkpoint,
+			Err(e) => {
+				warn!(target: "aura", "Failed to load the authorities checkpoint: {}", e);
+				None
+			},
+		});
+
+	let metrics = match prometheus_registry.as_ref().map(AuraMetrics::register) {
+		Some(Ok(metrics)) => Some(metrics),
+		Some(Err(err)) => {
+			warn!(target: "aura", "Failed to register Prometheus metrics: {}", err);
+			None
+		},
+		None => None,
+	};
+
+	let slot_tracker = SlotTracker::new();
+	let worker = AuraWorker {
+		client,
+		block_import: SlotLifecycleImport::new(
+			block_import,
+			telemetry.clone(),
+			retry_self_import,
+		),
+		env: Arc::new(Mutex::new(proposer_factory)),
+		keystore,
+		sync_oracle,
+		justification_sync_link,
+		force_authoring,
+		backoff_authoring_blocks,
+		telemetry,
+		block_proposal_slot_portion,
+		max_block_proposal_slot_portion,
+		compatibility_mode,
+		metrics,
+		json_events,
+		authorities_preloader,
+		max_head_slot_lag,
+		skip_slots,
+		self_verify_before_import,
+		min_inter_block_time,
+		last_authored_at: Mutex::new(None),
+		last_authored_slot: Mutex::new(None),
+		authorities_fetch_failed: authorities_retry.then(AuthoritiesFetchFailed::default),
+		max_tx_per_lenient_block,
+		transaction_count_hint,
+		authorities_cache: authorities_cache_capacity
+			.map(|capacity| Mutex::new(AuthoritiesCache::new(capacity))),
+		authorities_checkpoint: Mutex::new(authorities_checkpoint),
+		authority_weights,
+		control,
+		slot_lenience_type,
+		slot_duration_changes,
+		fork_choice,
+		secondary_slot_delay,
+		async_keystore,
+		deadline_adjuster,
+		pre_seal_hook,
+		offline_authorities,
+		engine_id,
+		authored_blocks,
+		time_source,
+		authored_block_origin,
+		max_extrinsics,
+		slot_tiebreak_fork_choice,
+		authority_provider,
+		should_author,
+		include_authority_index,
+		max_reorg_depth,
+		telemetry_level,
+		skip_empty_blocks,
+		key_type,
+		max_propose_retries,
+		slot_progress: Mutex::new(SlotProgress::default()),
+		missed_slots: Mutex::new(MissedSlots::default()),
+		authority_membership: Mutex::new(AuthorityMembership::default()),
+		clock_drift: clock_drift_threshold.map(|threshold| Mutex::new(ClockDrift::new(threshold))),
+		proposal_overruns: Mutex::new(ProposalOverruns::default()),
+		slot_tracker: slot_tracker.clone(),
+		#[cfg(all(feature = "testing", debug_assertions))]
+		deterministic_signature: None,
+		_key_type: PhantomData::<P>,
+	};
+
+	(worker, slot_tracker)
+}
+
+/// Bookkeeping of the slot the worker is currently working on.
+#[derive(Default)]
+struct SlotProgress {
+	/// The time granted for proposing.
+	proposing_budget: Duration,
+	/// The number of slots skipped since the parent block.
+	lenience_slots: u64,
+	/// When the slot was claimed.
+	claimed_at: Option<Instant>,
+	/// Index of the claiming authority in the authority set.
+	authority_index: Option<usize>,
+	/// Size of the authority set.
+	authorities_len: usize,
+	/// When the creation of the proposer started.
+	proposing_started_at: Option<Instant>,
+	/// The raw public key of the claiming authority.
+	claimed_by: Option<Vec<u8>>,
+	/// The claimed slot.
+	claimed_slot: Option<Slot>,
+	/// When the slot may be claimed by its secondary author.
+	secondary_slot_at: Option<Instant>,
+	/// Whether [`BuildAuraWorkerParams::should_author`] halted authoring in the slot.
+	halted: bool,
+	/// Whether the slot was claimed by its secondary author.
+	secondary: bool,
+	/// When the slot ends.
+	slot_ends_at: Option<Instant>,
+}
+
+/// Count of the slots each local authority key claimed without sealing a block in them, e.g.
+/// because proposing failed or authoring backed off.
+#[derive(Default)]
+struct MissedSlots {
+	counts: HashMap<Vec<u8>, u64>,
+}
+
+impl MissedSlots {
+	/// Account for the finished slot `progress`, returning the slot, the authority index and the
+	/// new count of missed slots of the key if the slot was claimed but not sealed.
+	///
+	/// The worker takes the progress of sealed slots, so only missed slots are still claimed.
+	fn note(&mut self, progress: SlotProgress) -> Option<(Slot, Option<usize>, u64)> {
+		let (slot, key) = progress.claimed_slot.zip(progress.claimed_by)?;
+		let count = self.counts.entry(key).or_default();
+		*count += 1;
+		Some((slot, progress.authority_index, *count))
+	}
+
+	/// The number of slots `key` missed.
+	fn count(&self, key: &[u8]) -> u64 {
+		self.counts.get(key).copied().unwrap_or_default()
+	}
+}
+
+/// The authority set the worker fetched last, to tell where local keys enter or leave it.
+struct AuthorityMembership<A> {
+	previous: Option<Vec<A>>,
+}
+
+impl<A> Default for AuthorityMembership<A> {
+	fn default() -> Self {
+		Self { previous: None }
+	}
+}
+
+impl<A: Clone + PartialEq> AuthorityMembership<A> {
+	/// Account for the `authorities` active from block `number` on, returning where the keys
+	/// accepted by `is_local` entered or left the set since the previous one.
+	///
+	/// The first set is only recorded, as there is nothing to compare it to.
+	fn note<N: Copy>(
+		&mut self,
+		number: N,
+		authorities: &[A],
+		is_local: impl Fn(&A) -> bool,
+	) -> Vec<LocalSetChange<N, A>> {
+		if self.previous.as_deref() == Some(authorities) {
+			return Vec::new()
+		}
+		match self.previous.replace(authorities.to_vec()) {
+			Some(previous) =>
+				local_set_changes(&[(number, previous), (number, authorities.to_vec())], is_local),
+			None => Vec::new(),
+		}
+	}
+}
+
+/// Offsets of the slots of recent chain heads authored by peers from the local slot, to
+/// estimate the drift of the local clock.
+struct ClockDrift<H> {
+	threshold: u64,
+	last_head: Option<H>,
+	offsets: Vec<i64>,
+}
+
+impl<H: PartialEq> ClockDrift<H> {
+	/// Report estimated drift of more than `threshold` slots.
+	fn new(threshold: u64) -> Self {
+		Self { threshold, last_head: None, offsets: Vec::with_capacity(CLOCK_DRIFT_SAMPLES) }
+	}
+
+	/// Account for the chain head `head` of slot `head_slot`, seen at the start of the local
+	/// `slot`, returning the estimated drift in slots if it exceeds the threshold.
+	///
+	/// A head imported in time is of the slot before, which is no drift. Each head is sampled
+	/// once, and the drift is estimated as the median offset of every [`CLOCK_DRIFT_SAMPLES`]
+	/// heads, so the odd head of an earlier slot, e.g. after a missed slot, does not count.
+	fn note(&mut self, head: H, head_slot: Slot, slot: Slot) -> Option<i64> {
+		if self.last_head.as_ref() == Some(&head) {
+			return None
+		}
+		self.last_head = Some(head);
+		self.offsets.push(*head_slot as i64 - (*slot as i64 - 1));
+		if self.offsets.len() < CLOCK_DRIFT_SAMPLES {
+			return None
+		}
+
+		let mut offsets = std::mem::take(&mut self.offsets);
+		offsets.sort_unstable();
+		let drift = offsets[offsets.len() / 2];
+		(drift.unsigned_abs() > self.threshold).then(|| drift)
+	}
+}
+
+/// Streak of consecutive authored blocks whose proposing took longer than its budget.
+#[derive(Default)]
+struct ProposalOverruns {
+	streak: u32,
+	warned_at: Option<Instant>,
+}
+
+impl ProposalOverruns {
+	/// Account for an authored block whose proposing took `duration` of the given `budget`,
+	/// returning the length of the streak if it is time to warn about it.
+	///
+	/// Warnings are rate limited to one per [`PROPOSAL_OVERRUN_WARN_INTERVAL`].
+	fn note(&mut self, duration: Duration, budget: Duration, now: Instant) -> Option<u32> {
+		if duration <= budget {
+			self.streak = 0;
+			return None
+		}
+
+		self.streak += 1;
+		let rate_limited = self.warned_at.map_or(false, |warned_at| {
+			now.duration_since(warned_at) < PROPOSAL_OVERRUN_WARN_INTERVAL
+		});
+		if self.streak < PROPOSAL_OVERRUN_STREAK || rate_limited {
+			return None
+		}
+
+		self.warned_at = Some(now);
+		Some(self.streak)
+	}
+}
+
+struct AuraWorker<C, E, I, P: Pair, SO, L, BS, N, B: BlockT, AP> {
+	client: Arc<C>,
+	block_import: SlotLifecycleImport<I>,
+	env: Arc<Mutex<E>>,
+	keystore: SyncCryptoStorePtr,
+	sync_oracle: SO,
+	justification_sync_link: L,
+	force_authoring: bool,
+	backoff_authoring_blocks: Option<BS>,
+	block_proposal_slot_portion: SlotProportion,
+	max_block_proposal_slot_portion: Option<SlotProportion>,
+	telemetry: Option<TelemetryHandle>,
+	compatibility_mode: CompatibilityMode<N>,
+	metrics: Option<AuraMetrics>,
+	json_events: bool,
+	authorities_preloader: Option<AuthoritiesPreloader<AuthorityId<P>, N>>,
+	max_head_slot_lag: Option<u64>,
+	skip_slots: Option<Box<dyn Fn(Slot) -> bool + Send + Sync>>,
+	self_verify_before_import: bool,
+	min_inter_block_time: Option<Duration>,
+	last_authored_at: Mutex<Option<Duration>>,
+	/// The slot of the last block the worker authored, see [`SkipEmptyConfig`].
+	last_authored_slot: Mutex<Option<Slot>>,
+	/// Raised when fetching the authorities failed, if the slot is to be retried then.
+	authorities_fetch_failed: Option<AuthoritiesFetchFailed>,
+	max_tx_per_lenient_block: Option<usize>,
+	transaction_count_hint: Option<TransactionCountHint>,
+	authorities_cache: Option<Mutex<AuthoritiesCache<B::Hash, AuthorityId<P>>>>,
+	/// The checkpoint of an earlier run, until it is confirmed and seeds the cache.
+	authorities_checkpoint: Mutex<Option<AuthoritiesCheckpoint<B::Hash, N, AuthorityId<P>>>>,
+	authority_weights: Option<AuthorityWeights>,
+	control: Option<AuraControl>,
+	slot_lenience_type: SlotLenienceType,
+	slot_duration_changes: Option<SlotDurationChanges>,
+	fork_choice: ForkChoiceStrategy,
+	secondary_slot_delay: Option<SlotProportion>,
+	async_keystore: bool,
+	deadline_adjuster: Option<DeadlineAdjuster<B>>,
+	pre_seal_hook: Option<PreSealHook<B>>,
+	offline_authorities: Option<OfflineAuthorities<AuthorityId<P>>>,
+	engine_id: ConsensusEngineId,
+	authored_blocks: Option<AuthoredBlocks<B::Hash>>,
+	time_source: Option<TimeSource>,
+	authored_block_origin: BlockOrigin,
+	max_extrinsics: Option<usize>,
+	slot_tiebreak_fork_choice: bool,
+	authority_provider: AP,
+	should_author: Option<ShouldAuthor>,
+	include_authority_index: bool,
+	max_reorg_depth: Option<u32>,
+	telemetry_level: TelemetryLevel,
+	skip_empty_blocks: Option<SkipEmptyConfig>,
+	key_type: KeyTypeId,
+	max_propose_retries: u32,
+	slot_progress: Mutex<SlotProgress>,
+	missed_slots: Mutex<MissedSlots>,
+	authority_membership: Mutex<AuthorityMembership<AuthorityId<P>>>,
+	/// See [`BuildAuraWorkerParams::clock_drift_threshold`].
+	clock_drift: Option<Mutex<ClockDrift<B::Hash>>>,
+	proposal_overruns: Mutex<ProposalOverruns>,
+	slot_tracker: SlotTracker,
+	/// The fixed signature to seal with instead of signing, see
+	/// `build_aura_worker_with_deterministic_seal`.
+	#[cfg(all(feature = "testing", debug_assertions))]
+	deterministic_signature: Option<Vec<u8>>,
+	_key_type: PhantomData<P>,
+}
+
+impl<C, E, I, P, SO, L, BS, N, B, AP> AuraWorker<C, E, I, P, SO, L, BS, N, B, AP>
+where
+	B: BlockT,
+	P: Pair,
+	P::Public: Codec + Debug + PartialEq,
+{
+	/// Wrap the worker into the slot worker returned by [`build_aura_worker`].
+	pub(crate) fn into_slot_worker(
+		self,
+	) -> ProofNotifyingWorker<
+		AuthoritiesRetryingWorker<SimpleSlotWorkerToSlotWorker<Self>>,
+		B::Hash,
+	> {
+		let authored_blocks = self.authored_blocks.clone();
+		let worker = AuthoritiesRetryingWorker {
+			fetch_failed: self.authorities_fetch_failed.clone(),
+			metrics: self.metrics.clone(),
+			worker: SimpleSlotWorkerToSlotWorker(self),
+		};
+		ProofNotifyingWorker { worker, authored_blocks }
+	}
+
+	/// The fixed signature to seal with instead of signing with the keystore, if set.
+	#[cfg(all(feature = "testing", debug_assertions))]
+	fn deterministic_signature(&self) -> Option<Vec<u8>> {
+		self.deterministic_signature.clone()
+	}
+
+	/// Outside of debug builds with the `testing` feature, seals are always signed with the
+	/// keystore.
+	#[cfg(not(all(feature = "testing", debug_assertions)))]
+	fn deterministic_signature(&self) -> Option<Vec<u8>> {
+		None
+	}
+
+	/// The number of slots claimed by the local `authority` in which no block was sealed since
+	/// the worker started.
+	fn missed_slots(&self, authority: &AuthorityId<P>) -> u64 {
+		self.missed_slots.lock().count(&authority.to_raw_vec())
+	}
+
+	/// Account for the `finished` slot, warning about it if it was claimed but not sealed.
+	fn note_missed_slot(&self, finished: SlotProgress) {
+		if let Some((slot, authority_index, missed)) = self.missed_slots.lock().note(finished) {
+			warn!(
+				target: "aura",
+				"Missed claimed slot {} (authority index {:?}), {} missed slots in total",
+				slot,
+				authority_index,
+				missed,
+			);
+			if self.telemetry_level.reports_slot_claims() {
+				telemetry!(
+					self.telemetry;
+					CONSENSUS_INFO;
+					"aura.missed_slots";
+					"slot" => *slot,
+					"authority_index" => authority_index,
+					"missed" => missed,
+				);
+			}
+		}
+	}
+
+	/// Claim `slot` as its secondary author, once the secondary slot delay has passed without a
+	/// block of the slot being imported.
+	///
+	/// See [`BuildAuraWorkerParams::secondary_slot_delay`].
+	async fn claim_secondary_slot(
+		&self,
+		slot: Slot,
+		authorities: &[AuthorityId<P>],
+	) -> Option<AuthorityId<P>>
+	where
+		C: HeaderBackend<B>,
+		P::Signature: Codec,
+	{
+		let secondary_slot_at = self.slot_progress.lock().secondary_slot_at?;
+		let author =
+			secondary_slot_author::<P>(slot, authorities, self.authority_weights.as_ref())?;
+		if !has_authority_key::<P>(&*self.keystore, self.key_type, author) {
+			return None
+		}
+
+		futures_timer::Delay::new(secondary_slot_at.saturating_duration_since(Instant::now()))
+			.await;
+
+		let best_hash = self.client.info().best_hash;
+		let best_slot = self
+			.client
+			.header(BlockId::Hash(best_hash))
+			.ok()
+			.flatten()
+			.and_then(|header| find_pre_digest_with_engine_id::<B>(&header, self.engine_id).ok());
+		if best_slot.map_or(false, |best_slot| best_slot >= slot) {
+			debug!(
+				target: "aura",
+				"Slot {} was authored in time, not claiming it as secondary",
+				slot,
+			);
+			return None
+		}
+
+		info!(
+			target: "aura",
+			"No block in slot {} yet, claiming it as secondary author {}",
+			slot,
+			HexDisplay::from(author),
+		);
+		self.slot_progress.lock().secondary = true;
+		Some(author.clone())
+	}
+
+	/// Fetch the requested upcoming authority sets and check the pinned set of the block with
+	/// number `context_block_number` against `authorities`.
+	fn preload_authorities(
+		&self,
+		preloader: &AuthoritiesPreloader<AuthorityId<P>, NumberFor<B>>,
+		header: &B::Header,
+		context_block_number: NumberFor<B>,
+		authorities: &[AuthorityId<P>],
+	) where
+		C: ProvideRuntimeApi<B>,
+		C::Api: AuraApi<B, AuthorityId<P>>,
+		AP: AuthorityProvider<B, AuthorityId<P>, C::Api>,
+	{
+		for number in preloader.take_requests_after(context_block_number) {
+			match crate::authorities(
+				self.client.as_ref(),
+				&self.authority_provider,
+				header.hash(),
+				number,
+				&CompatibilityMode::UseInitializeBlock { until: number + 1u32.into() },
+			) {
+				Ok(preloaded) => {
+					info!(
+						target: "aura",
+						"Preloaded {} authorities for block #{:?}.",
+						preloaded.len(),
+						number,
+					);
+					preloader.pin(number, preloaded);
+				},
+				Err(err) => warn!(
+					target: "aura",
+					"Failed to preload the authorities for block #{:?}: {}",
+					number,
+					err,
+				),
+			}
+		}
+
+		if let Some(pinned) = preloader.take_pinned(context_block_number) {
+			if pinned[..] != authorities[..] {
+				warn!(
+					target: "aura",
+					"Authorities at block #{:?} differ from the preloaded set: expected {:?}, found {:?}",
+					context_block_number,
+					pinned,
+					authorities,
+				);
+			} else {
+				debug!(
+					target: "aura",
+					"Authorities at block #{:?} match the preloaded set.",
+					context_block_number,
+				);
+			}
+		}
+	}
+
+	/// Report the local keys entering or leaving the authority set, given the `authorities`
+	/// active from block `context_block_number` on.
+	fn note_authority_membership(
+		&self,
+		context_block_number: NumberFor<B>,
+		authorities: &[AuthorityId<P>],
+	) {
+		let changes = self.authority_membership.lock().note(
+			context_block_number,
+			authorities,
+			|authority| has_authority_key::<P>(&*self.keystore, self.key_type, authority),
+		);
+		for change in changes {
+			match change {
+				LocalSetChange::Enters(number, key) => {
+					info!(
+						target: "aura",
+						"🔑 Local key {:?} became an authority at #{}",
+						key,
+						number,
+					);
+					telemetry!(
+						self.telemetry;
+						CONSENSUS_INFO;
+						"aura.became_authority";
+						"authority" => ?key,
+						"block" => ?number,
+					);
+				},
+				LocalSetChange::Leaves(number, key) => {
+					info!(
+						target: "aura",
+						"🔑 Local key {:?} left the authority set at #{}",
+						key,
+						number,
+					);
+					telemetry!(
+						self.telemetry;
+						CONSENSUS_INFO;
+						"aura.left_authority";
+						"authority" => ?key,
+						"block" => ?number,
+					);
+				},
+			}
+		}
+	}
+
+	/// Sample the slot of `chain_head`, seen at the start of `slot`, to estimate the drift of
+	/// the local clock, and report the drift if it is too large.
+	fn note_clock_drift(
+		&self,
+		clock_drift: &Mutex<ClockDrift<B::Hash>>,
+		chain_head: &B::Header,
+		slot: Slot,
+	) {
+		// the genesis block has no slot.
+		let head_slot = match find_pre_digest_with_engine_id::<B>(chain_head, self.engine_id) {
+			Ok(head_slot) => head_slot,
+			Err(_) => return,
+		};
+		// blocks authored by the worker follow the local clock, so they tell nothing about it.
+		if *self.last_authored_slot.lock() == Some(head_slot) {
+			return
+		}
+
+		if let Some(drift) = clock_drift.lock().note(chain_head.hash(), head_slot, slot) {
+			warn!(
+				target: "aura",
+				"⏰ The chain heads of peers are {} slots off the local slot {}, the local clock \
+				 may be drifting",
+				drift,
+				slot,
+			);
+			telemetry!(
+				self.telemetry;
+				CONSENSUS_INFO;
+				"aura.clock_drift_slots";
+				"slot" => *slot,
+				"drift" => drift,
+			);
+		}
+	}
+
+	/// The depth of the fork `chain_head` is on, if it is too deep to author on.
+	///
+	/// See [`BuildAuraWorkerParams::max_reorg_depth`].
+	fn deep_fork(&self, chain_head: &B::Header) -> Option<NumberFor<B>>
+	where
+		C: HeaderBackend<B> + HeaderMetadata<B>,
+	{
+		let max_reorg_depth = self.max_reorg_depth?;
+		let info = self.client.info();
+		find_deep_fork::<B, _>(
+			chain_head,
+			info.finalized_number,
+			info.finalized_hash,
+			max_reorg_depth,
+			|one, two| {
+				// walks the cached header metadata rather than reading the headers.
+				sp_blockchain::lowest_common_ancestor(&*self.client, one, two)
+					.ok()
+					.map(|ancestor| ancestor.number)
+			},
+		)
+	}
+}
+
+#[async_trait::async_trait]
+impl<B, C, E, I, P, Error, SO, L, BS, AP> sc_consensus_slots::SimpleSlotWorker<B>
+	for AuraWorker<C, E, I, P, SO, L, BS, NumberFor<B>, B, AP>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + BlockOf + AuxStore + HeaderBackend<B> + HeaderMetadata<B> + Sync,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+	E: Environment<B, Error = Error> + Send + Sync + 'static,
+	E::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	P: Pair + Send + Sync,
+	P::Public: AppPublic + Public + Member + Encode + Decode + Hash,
+	P::Signature: TryFrom<Vec<u8>> + Member + Encode + Decode + Hash + Debug,
+	SO: SyncOracle + Send + Clone + Sync,
+	L: sc_consensus::JustificationSyncLink<B>,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api> + Send + Sync + 'static,
+{
+	type BlockImport = SlotLifecycleImport<I>;
+	type SyncOracle = SO;
+	type JustificationSyncLink = L;
+	type CreateProposer = Pin<
+		Box<
+			dyn Future<Output = Result<RetryingProposer<B, E::Proposer>, sp_consensus::Error>>
+				+ Send
+				+ 'static,
+		>,
+	>;
+	type Proposer = RetryingProposer<B, E::Proposer>;
+	type Claim = P::Public;
+	type EpochData = Vec<AuthorityId<P>>;
+
+	fn logging_target(&self) -> &'static str {
+		"aura"
+	}
+
+	fn block_import(&mut self) -> &mut Self::BlockImport {
+		&mut self.block_import
+	}
+
+	fn epoch_data(
+		&self,
+		header: &B::Header,
+		slot: Slot,
+	) -> Result<Self::EpochData, sp_consensus::Error> {
+		if let Some(clock_drift) = self.clock_drift.as_ref() {
+			self.note_clock_drift(clock_drift, header, slot);
+		}
+
+		if let Some(changes) = self.slot_duration_changes.as_ref() {
+			if find_authorities_change::<B, AuthorityId<P>>(header).is_some() {
+				match self.client.runtime_api().slot_duration(&BlockId::Hash(header.hash())) {
+					Ok(slot_duration) =>
+						if changes.note(slot_duration) {
+							info!(
+								target: "aura",
+								"Slot duration changed to {}ms at #{:?}.",
+								slot_duration.as_millis(),
+								header.number(),
+							);
+						},
+					Err(e) => warn!(
+						target: "aura",
+						"Failed to read the slot duration at {:?}: {}",
+						header.hash(),
+						e,
+					),
+				}
+			}
+		}
+
+		let context_block_number = *header.number() + 1u32.into();
+		let compatibility_mode_active = matches!(
+			self.compatibility_mode.behavior_at(&context_block_number),
+			StageBehavior::UseInitializeBlock
+		);
+		let cache = self.authorities_cache.as_ref().filter(|_| !compatibility_mode_active);
+		let checkpoint = cache.and_then(|_| self.authorities_checkpoint.lock().take());
+		if let (Some(cache), Some(checkpoint)) = (cache, checkpoint) {
+			match authorities(
+				self.client.as_ref(),
+				&self.authority_provider,
+				checkpoint.hash,
+				checkpoint.number + 1u32.into(),
+				&self.compatibility_mode,
+			) {
+				Ok(authorities) if authorities == checkpoint.authorities =>
+					cache.lock().insert(checkpoint.hash, authorities),
+				_ => debug!(
+					target: "aura",
+					"Discarding the authorities checkpoint at {:?}, the runtime disagrees.",
+					checkpoint.hash,
+				),
+			}
+		}
+		let cached = cache.and_then(|cache| cache.lock().authorities_at::<B>(header));
+		let authorities = match cached {
+			Some(authorities) => ensure_authorities::<B, _>(authorities, header.hash())?,
+			None => {
+				let authorities = authorities(
+					self.client.as_ref(),
+					&self.authority_provider,
+					header.hash(),
+					context_block_number,
+					&self.compatibility_mode,
+				)
+				.map_err(|e| {
+					if let (ConsensusError::InvalidAuthoritiesSet, Some(fetch_failed)) =
+						(&e, self.authorities_fetch_failed.as_ref())
+					{
+						fetch_failed.raise();
+					}
+					e
+				})?;
+				let authorities = ensure_authorities::<B, _>(authorities, header.hash())?;
+
+				if let Some(cache) = cache {
+					cache.lock().insert(header.hash(), authorities.clone());
+
+					let checkpoint = AuthoritiesCheckpoint {
+						hash: header.hash(),
+						number: *header.number(),
+						authorities: authorities.clone(),
+					};
+					if let Err(e) = write_authorities_checkpoint(self.client.as_ref(), &checkpoint)
+					{
+						warn!(target: "aura", "Failed to write the authorities checkpoint: {}", e);
+					}
+				}
+				authorities
+			},
+		};
+
+		// cached or not, the sets of the blocks ahead are preloaded.
+		if let Some(preloader) = self.authorities_preloader.as_ref() {
+			self.preload_authorities(preloader, header, context_block_number, &authorities);
+		}
+		self.note_authority_membership(context_block_number, &authorities);
+
+		Ok(authorities)
+	}
+
+	fn authorities_len(&self, epoch_data: &Self::EpochData) -> Option<usize> {
+		Some(epoch_data.len())
+	}
+
+	async fn claim_slot(
+		&self,
+		header: &B::Header,
+		slot: Slot,
+		epoch_data: &Self::EpochData,
+	) -> Option<Self::Claim> {
+		self.slot_tracker.note(slot);
+
+		let span = Span::claim_slot(slot);
+		span.instrument(async move {
+			if self.control.as_ref().map_or(false, AuraControl::is_paused) {
+				debug!(target: "aura", "Authoring is paused, skipping slot {}", slot);
+				return None
+			}
+
+			if self.slot_progress.lock().halted {
+				debug!(target: "aura", "Skipping slot {}, authoring is halted", slot);
+				return None
+			}
+
+			if let Some(depth) = self.deep_fork(header) {
+				debug!(
+					target: "aura",
+					"Skipping slot {}, chain head {} forks off {} blocks below the finalized block",
+					slot,
+					header.hash(),
+					depth,
+				);
+				return None
+			}
+
+			if let Some(max_lag) = self.max_head_slot_lag {
+				if !header.number().is_zero() {
+					let head_slot = find_pre_digest_with_engine_id::<B>(header, self.engine_id);
+					if let Ok(head_slot) = head_slot {
+						let lag = *slot.saturating_sub(*head_slot);
+						if lag > max_lag {
+							warn!(
+								target: "aura",
+								"Chain head #{:?} is {} slots behind slot {}, more than the allowed {}. \
+								 Not authoring until the node has caught up.",
+								header.number(),
+								lag,
+								slot,
+								max_lag,
+							);
+							return None
+						}
+					}
+				}
+			}
+
+			if let Some(metrics) = self.metrics.as_ref() {
+				metrics.authorities.set(epoch_data.len() as u64);
+			}
+
+			let report_outcome =
+				self.telemetry.is_some() && self.telemetry_level.reports_slot_claims();
+			let outcome = claim_outcome::<P>(
+				&*self.keystore,
+				self.key_type,
+				slot,
+				epoch_data,
+				self.authority_weights.as_ref(),
+				self.offline_authorities.as_ref(),
+				report_outcome || log::log_enabled!(target: "aura", log::Level::Trace),
+			);
+			trace!(target: "aura", "Claiming slot {}: {:?}", slot, outcome);
+			if report_outcome && !matches!(outcome, ClaimOutcome::Claimed(_)) {
+				telemetry!(
+					self.telemetry;
+					CONSENSUS_DEBUG;
+					"aura.slot_not_claimed";
+					"slot" => *slot,
+					"reason" => ?outcome,
+				);
+			}
+
+			let claim = match outcome.into_claim() {
+				Some(claim) => Some(claim),
+				None => self.claim_secondary_slot(slot, epoch_data).await,
+			};
+
+			if let Some(author) = &claim {
+				if self.skip_slots.as_ref().map_or(false, |skip| skip(slot)) {
+					info!(
+						target: "aura",
+						"Deliberately skipping slot {} of local authority {}",
+						slot,
+						HexDisplay::from(author),
+					);
+					return None
+				}
+
+				if let Some(config) = self.skip_empty_blocks.as_ref() {
+					if skips_empty_block(config, slot, *self.last_authored_slot.lock()) {
+						info!(
+							target: "aura",
+							"Skipping slot {}, no transactions are ready and the last block was \
+							 authored less than {} slots ago",
+							slot,
+							config.min_interval,
+						);
+						return None
+					}
+				}
+
+				if let (Some(min_interval), Some(last_authored_at)) =
+					(self.min_inter_block_time, *self.last_authored_at.lock())
+				{
+					let elapsed =
+						time_now(self.time_source.as_ref()).saturating_sub(last_authored_at);
+					if elapsed < min_interval {
+						info!(
+							target: "aura",
+							"Skipping slot {}, only {:?} passed since the last authored block, less than \
+							 the minimum of {:?}",
+							slot,
+							elapsed,
+							min_interval,
+						);
+						return None
+					}
+				}
+			}
+
+			if let Some(author) = &claim {
+				// the claiming authority, which need not be the one scheduled for the slot, e.g.
+				// when the slot fell through to it from an offline authority.
+				let authority_index = epoch_data.iter().position(|authority| authority == author);
+				{
+					let mut slot_progress = self.slot_progress.lock();
+					slot_progress.claimed_at = Some(Instant::now());
+					slot_progress.authority_index = authority_index;
+					slot_progress.authorities_len = epoch_data.len();
+					slot_progress.claimed_by = Some(author.to_raw_vec());
+					slot_progress.claimed_slot = Some(slot);
+				}
+				Span::record_authority_index(authority_index);
+				if let Some(metrics) = self.metrics.as_ref() {
+					metrics.claimed_slots.inc();
+				}
+
+				if self.telemetry_level.reports_slot_claims() {
+					telemetry!(
+						self.telemetry;
+						CONSENSUS_DEBUG;
+						"aura.claimed_slot";
+						"slot" => *slot,
+						"author" => ?author,
+						"authority_index" => authority_index,
+						"authorities_len" => epoch_data.len(),
+						"missed_slots" => self.missed_slots(author),
+					);
+				}
+			}
+
+			claim
+		})
+		.await
+	}
+
+	fn pre_digest_data(&self, slot: Slot, _claim: &Self::Claim) -> Vec<sp_runtime::DigestItem> {
+		let mut pre_digest = vec![DigestItem::PreRuntime(self.engine_id, slot.encode())];
+		let slot_progress = self.slot_progress.lock();
+		if slot_progress.secondary {
+			pre_digest.push(DigestItem::PreRuntime(SECONDARY_SLOT_ENGINE_ID, Vec::new()));
+		}
+		if let (true, Some(index)) = (self.include_authority_index, slot_progress.authority_index)
+		{
+			let index = (index as u32).encode();
+			pre_digest.push(DigestItem::PreRuntime(AUTHORITY_INDEX_ENGINE_ID, index));
+		}
+		pre_digest
+	}
+
+	async fn block_import_params(
+		&self,
+		header: B::Header,
+		header_hash: &B::Hash,
+		body: Vec<B::Extrinsic>,
+		storage_changes: StorageChanges<<Self::BlockImport as BlockImport<B>>::Transaction, B>,
+		public: Self::Claim,
+		epoch: Self::EpochData,
+	) -> Result<
+		sc_consensus::BlockImportParams<B, <Self::BlockImport as BlockImport<B>>::Transaction>,
+		sp_consensus::Error,
+	> {
+		let span = {
+			let slot_progress = self.slot_progress.lock();
+			Span::propose(slot_progress.claimed_slot, slot_progress.authority_index)
+		};
+		span.instrument(async move {
+			let proposed_at = Instant::now();
+
+			let slot = find_pre_digest_with_engine_id::<B>(&header, self.engine_id)
+				.map_err(|e| sp_consensus::Error::ClientImport(e.to_string()))?;
+			self.slot_tracker.note(slot);
+
+			// the hook digests end up in the header the verifier hashes once it removed the seal,
+			// so they have to be covered by the signature.
+			let pre_seal_digests = self
+				.pre_seal_hook
+				.as_ref()
+				.map(|hook| hook(&header))
+				.unwrap_or_default()
+				.into_iter()
+				.filter(|item| {
+					let allowed = is_pre_seal_digest(item, self.engine_id);
+					if !allowed {
+						warn!(
+							target: "aura",
+							"Dropping pre-seal digest {:?}, only foreign seals are allowed.",
+							item,
+						);
+					}
+					allowed
+				})
+				.collect::<Vec<_>>();
+			let header_hash = pre_seal_hash::<B>(&header, header_hash, &pre_seal_digests);
+
+			// sign the pre-sealed hash of the block, or the legacy message containing it, and then
+			// add it to a digest item.
+			let message =
+				seal_message::<B>(*header.number(), slot, &header_hash, &self.compatibility_mode);
+			let public_type_pair = public.to_public_crypto_pair();
+			let author = public;
+			let public = author.to_raw_vec();
+			let deterministic_signature = self.deterministic_signature();
+			let signature = match deterministic_signature.clone() {
+				Some(signature) => signature,
+				None => {
+					let signed = if self.async_keystore {
+						CryptoStore::sign_with(
+							&*self.keystore,
+							self.key_type,
+							&public_type_pair,
+							&message,
+						)
+						.await
+					} else {
+						SyncCryptoStore::sign_with(
+							&*self.keystore,
+							self.key_type,
+							&public_type_pair,
+							&message,
+						)
+					};
+					signed
+						.map_err(|e| {
+							sp_consensus::Error::CannotSign(public.clone(), e.to_string())
+						})?
+						.ok_or_else(|| {
+							sp_consensus::Error::CannotSign(
+								public.clone(),
+								"Could not find key in keystore.".into(),
+							)
+						})?
+				},
+			};
+			check_signature_len::<B>(public_type_pair.0, &signature)
+				.map_err(|e| sp_consensus::Error::CannotSign(public.clone(), e.to_string()))?;
+			let signature: P::Signature = signature
+				.clone()
+				.try_into()
+				.map_err(|_| sp_consensus::Error::InvalidSignature(signature, public.clone()))?;
+			// a deterministic signature is not made by the author, on purpose.
+			if deterministic_signature.is_none() {
+				ensure_signed_by_author::<B, P>(&author, &message, &signature)
+					.map_err(|e| sp_consensus::Error::CannotSign(public.clone(), e.to_string()))?;
+			}
+
+			let signature_digest_item = DigestItem::Seal(self.engine_id, signature.encode());
+			let sign_duration = proposed_at.elapsed();
+
+			let fork_choice = if self.slot_tiebreak_fork_choice {
+				let best_hash = self.client.info().best_hash;
+				match self.client.header(BlockId::Hash(best_hash)).ok().flatten() {
+					Some(best_header) => ForkChoiceStrategy::Custom(
+						wins_slot_tiebreak::<B>(&header, &best_header, self.engine_id),
+					),
+					None => self.fork_choice,
+				}
+			} else {
+				self.fork_choice
+			};
+
+			let mut import_block =
+				own_block_import_params(header, self.authored_block_origin, fork_choice);
+			import_block.post_digests.extend(pre_seal_digests);
+			import_block.post_digests.push(signature_digest_item);
+			import_block.body = Some(body);
+			import_block.state_action =
+				StateAction::ApplyChanges(sc_consensus::StorageChanges::Changes(storage_changes));
+
+			if self.self_verify_before_import {
+				let mut sealed_header = import_block.header.clone();
+				for item in &import_block.post_digests {
+					sealed_header.digest_mut().push(item.clone());
+				}
+				if let Err(e) = import_queue::check_seal::<B, P>(
+					sealed_header,
+					&epoch,
+					self.authority_weights.as_ref(),
+					self.offline_authorities.as_ref(),
+					&self.compatibility_mode,
+					self.engine_id,
+				) {
+					error!(
+						target: "aura",
+						"Authored block {:?} fails seal verification, not importing it: {}",
+						import_block.post_hash(),
+						e,
+					);
+					self.note_missed_slot(std::mem::take(&mut *self.slot_progress.lock()));
+					return Err(sp_consensus::Error::ClientImport(e.to_string()))
+				}
+			}
+
+			// the slot is only sealed once the block passed its own verification.
+			let slot_progress = std::mem::take(&mut *self.slot_progress.lock());
+			if let Some(metrics) = self.metrics.as_ref() {
+				metrics.authored_blocks.inc();
+				if let Some(claimed_at) = slot_progress.claimed_at {
+					metrics.claim_to_seal.observe(claimed_at.elapsed().as_secs_f64());
+				}
+				if let Some(started) = slot_progress.proposing_started_at {
+					metrics
+						.proposal_duration
+						.observe(proposed_at.duration_since(started).as_secs_f64());
+				}
+			}
+
+			*self.last_authored_at.lock() = Some(time_now(self.time_source.as_ref()));
+			*self.last_authored_slot.lock() = Some(slot);
+
+			let propose_duration = slot_progress
+				.proposing_started_at
+				.map(|started| proposed_at.duration_since(started));
+			let overruns = propose_duration.and_then(|duration| {
+				self.proposal_overruns.lock().note(
+					duration,
+					slot_progress.proposing_budget,
+					Instant::now(),
+				)
+			});
+			if let Some(streak) = overruns {
+				warn!(
+					target: "aura",
+					"⚠️  Proposing overran its budget of {:?} in the last {} authored blocks, \
+					 the node may be overloaded and its blocks late.",
+					slot_progress.proposing_budget,
+					streak,
+				);
+			}
+
+			self.block_import.note_sealed(
+				SlotLifecycle {
+					slot,
+					authority_index: slot_progress.authority_index,
+					authorities_len: slot_progress.authorities_len,
+					proposing_budget: slot_progress.proposing_budget,
+					propose_duration: propose_duration.unwrap_or_default(),
+					sign_duration,
+				},
+				slot_progress.slot_ends_at,
+			);
+
+			if self.json_events {
+				let number: u64 = (*import_block.header.number()).unique_saturated_into();
+				info!(
+					target: AUTHORED_BLOCK_EVENT_TARGET,
+					"{{\"slot\":{},\"hash\":\"{:?}\",\"number\":{},\"author\":\"0x{}\",\"budget_ms\":{},\"lenience_slots\":{}}}",
+					*slot,
+					import_block.post_hash(),
+					number,
+					HexDisplay::from(&public),
+					slot_progress.proposing_budget.as_millis(),
+					slot_progress.lenience_slots,
+				);
+			}
+
+			// sent once the proof of the proposal is known, see `ProofNotifyingWorker`.
+			if let Some(authored_blocks) = &self.authored_blocks {
+				authored_blocks.stage(AuthoredBlockInfo {
+					hash: import_block.post_hash(),
+					slot,
+					authority_index: slot_progress.authority_index,
+					timestamp: time_now(self.time_source.as_ref()).as_millis() as u64,
+					proof: None,
+				});
+			}
+
+			Ok::<_, sp_consensus::Error>(import_block)
+		})
+		.await
+	}
+
+	fn force_authoring(&self) -> bool {
+		self.force_authoring
+	}
+
+	fn should_backoff(&self, slot: Slot, chain_head: &B::Header) -> bool {
+		if let Some(ref strategy) = self.backoff_authoring_blocks {
+			let chain_head_slot = find_pre_digest_with_engine_id::<B>(chain_head, self.engine_id);
+			if let Ok(chain_head_slot) = chain_head_slot {
+				return strategy.should_backoff(
+					*chain_head.number(),
+					chain_head_slot,
+					self.client.info().finalized_number,
+					slot,
+					self.logging_target(),
+				)
+			}
+		}
+		false
+	}
+
+	fn sync_oracle(&mut self) -> &mut Self::SyncOracle {
+		&mut self.sync_oracle
+	}
+
+	fn justification_sync_link(&mut self) -> &mut Self::JustificationSyncLink {
+		&mut self.justification_sync_link
+	}
+
+	fn proposer(&mut self, block: &B::Header) -> Self::CreateProposer {
+		let slot_progress = self.slot_progress.get_mut();
+		slot_progress.proposing_started_at = Some(Instant::now());
+		let span = Span::propose(slot_progress.claimed_slot, slot_progress.authority_index);
+
+		// a proposer is consumed by proposing, so every retry creates its own.
+		let create_retry: CreateRetryProposer<B, E::Proposer> = {
+			let (env, parent) = (self.env.clone(), block.clone());
+			Box::new(move || env.lock().init(&parent).boxed())
+		};
+		let retries = self.max_propose_retries;
+		span.instrument(
+			self.env
+				.lock()
+				.init(block)
+				.map_ok(move |proposer| RetryingProposer::new(proposer, create_retry, retries))
+				.map_err(|e| sp_consensus::Error::ClientImport(format!("{:?}", e))),
+		)
+		.boxed()
+	}
+
+	fn telemetry(&self) -> Option<TelemetryHandle> {
+		self.telemetry.clone()
+	}
+
+	fn proposing_remaining_duration(&self, slot_info: &SlotInfo<B>) -> std::time::Duration {
+		let parent_slot =
+			find_pre_digest_with_engine_id::<B>(&slot_info.chain_head, self.engine_id).ok();
+
+		let proposing_budget = compute_proposing_duration(
+			parent_slot,
+			slot_info,
+			&self.block_proposal_slot_portion,
+			self.max_block_proposal_slot_portion.as_ref(),
+			&self.slot_lenience_type,
+		);
+		let proposing_budget = match self.deadline_adjuster.as_ref() {
+			Some(adjust) => {
+				let adjusted = cap_at_slot_end(
+					adjust(proposing_budget, slot_info),
+					slot_info.ends_at,
+					Instant::now(),
+				);
+				// the slot worker skips a slot without time to propose in it.
+				if adjusted.is_zero() {
+					debug!(
+						target: "aura",
+						"No time left to propose in slot {} after adjusting the deadline",
+						slot_info.slot,
+					);
+				}
+				adjusted
+			},
+			None => proposing_budget,
+		};
+		// decided here, as the inherent data of the slot is not at hand when claiming it.
+		let halted = !may_author(self.should_author.as_ref(), &slot_info.inherent_data);
+
+		if let Some(metrics) = self.metrics.as_ref() {
+			metrics.current_slot.set(*slot_info.slot);
+		}
+
+		let lenience_slots =
+			parent_slot.map_or(0, |parent_slot| *slot_info.slot.saturating_sub(*parent_slot + 1));
+
+		if let Some(hint) = self.transaction_count_hint.as_ref() {
+			hint.set(transaction_limit(
+				self.max_extrinsics,
+				self.max_tx_per_lenient_block,
+				lenience_slots,
+			));
+		}
+
+		let secondary_slot_at = self.secondary_slot_delay.as_ref().and_then(|delay| {
+			let slot_started_at = slot_info.ends_at.checked_sub(slot_info.duration)?;
+			Some(slot_started_at + slot_info.duration.mul_f32(delay.get()))
+		});
+
+		let finished = std::mem::replace(
+			&mut *self.slot_progress.lock(),
+			SlotProgress {
+				proposing_budget,
+				lenience_slots,
+				secondary_slot_at,
+				halted,
+				slot_ends_at: Some(slot_info.ends_at),
+				..Default::default()
+			},
+		);
+		self.note_missed_slot(finished);
+
+		proposing_budget
+	}
+}
+
+/// The time to propose a block in the slot of `slot_info` on top of a parent authored in
+/// `parent_slot`.
+///
+/// This is `base` of the slot, extended by the lenience of `lenience_type` for the slots missed
+/// since the parent and capped at `max` of the slot if given.
+pub(crate) fn compute_proposing_duration<B: BlockT>(
+	parent_slot: Option<Slot>,
+	slot_info: &SlotInfo<B>,
+	base: &SlotProportion,
+	max: Option<&SlotProportion>,
+	lenience_type: &SlotLenienceType,
+) -> Duration {
+	sc_consensus_slots::proposing_remaining_duration(
+		parent_slot,
+		slot_info,
+		base,
+		max,
+		// `SlotLenienceType` is neither `Copy` nor `Clone`.
+		match lenience_type {
+			SlotLenienceType::Linear => SlotLenienceType::Linear,
+			SlotLenienceType::Exponential => SlotLenienceType::Exponential,
+		},
+		"aura",
+	)
+}
+
+/// The transaction limit of a proposal after `lenience_slots` missed slots: the lower of
+/// `max_extrinsics` and, if the slot is lenient, `max_tx_per_lenient_block`.
+fn transaction_limit(
+	max_extrinsics: Option<usize>,
+	max_tx_per_lenient_block: Option<usize>,
+	lenience_slots: u64,
+) -> Option<usize> {
+	match (max_extrinsics, max_tx_per_lenient_block.filter(|_| lenience_slots > 0)) {
+		(Some(limit), Some(lenient_limit)) => Some(limit.min(lenient_limit)),
+		(limit, lenient_limit) => limit.or(lenient_limit),
+	}
+}
+
+/// Cap the proposing time `budget` at the time left at `now` until the slot ends at `ends_at`.
+fn cap_at_slot_end(budget: Duration, ends_at: Instant, now: Instant) -> Duration {
+	budget.min(ends_at.saturating_duration_since(now))
+}
+
+/// Import params of a block authored by the node, imported with the given origin and fork choice.
+fn own_block_import_params<B: BlockT, Transaction>(
+	header: B::Header,
+	origin: BlockOrigin,
+	fork_choice: ForkChoiceStrategy,
+) -> BlockImportParams<B, Transaction> {
+	let mut import_block = BlockImportParams::new(origin, header);
+	import_block.fork_choice = Some(fork_choice);
+	import_block
+}
+
+/// Whether `header` should replace `best_header` as the best block: if it is higher, or as high
+/// and authored in a lower slot.
+///
+/// Headers without a pre-digest under `engine_id` never win a tie.
+fn wins_slot_tiebreak<B: BlockT>(
+	header: &B::Header,
+	best_header: &B::Header,
+	engine_id: ConsensusEngineId,
+) -> bool {
+	match header.number().cmp(best_header.number()) {
+		std::cmp::Ordering::Greater => true,
+		std::cmp::Ordering::Less => false,
+		std::cmp::Ordering::Equal => {
+			let slot = find_pre_digest_with_engine_id::<B>(header, engine_id);
+			let best_slot = find_pre_digest_with_engine_id::<B>(best_header, engine_id);
+			matches!((slot, best_slot), (Ok(slot), Ok(best_slot)) if slot < best_slot)
+		},
+	}
+}
+
+fn aura_err<B: BlockT>(error: Error<B>) -> Error<B> {
+	debug!(target: "aura", "{}", error);
+	error
+}
+
+/// Reject the empty authority set fetched at `hash`, under which no slot can ever be claimed.
+///
+/// The slot worker logs the error as a warning on every slot, which sets a misconfigured
+/// authority set apart from slots that are simply not ours.
+fn ensure_authorities<B: BlockT, A>(
+	authorities: Vec<A>,
+	hash: B::Hash,
+) -> Result<Vec<A>, sp_consensus::Error> {
+	if authorities.is_empty() {
+		return Err(sp_consensus::Error::Other(Box::new(aura_err(Error::<B>::NoAuthorities(hash)))))
+	}
+
+	Ok(authorities)
+}
+
+/// Aura Errors
+#[derive(Debug, thiserror::Error)]
+pub enum Error<B: BlockT> {
+	/// Multiple Aura pre-runtime headers
+	#[error("Multiple Aura pre-runtime headers")]
+	MultipleHeaders,
+	/// No Aura pre-runtime digest found
+	#[error("No Aura pre-runtime digest found")]
+	NoDigestFound,
+	/// Header is unsealed
+	#[error("Header {0:?} is unsealed")]
+	HeaderUnsealed(B::Hash),
+	/// Header has a bad seal
+	#[error("Header {0:?} has a bad seal")]
+	HeaderBadSeal(B::Hash),
+	/// Slot Author not found
+	#[error("Slot Author not found")]
+	SlotAuthorNotFound,
+	/// Bad signature
+	#[error("Bad signature on {0:?}")]
+	BadSignature(B::Hash),
+	/// Client Error
+	#[error(transparent)]
+	Client(sp_blockchain::Error),
+	/// Unknown inherent error for identifier
+	#[error("Unknown inherent error for identifier: {}", String::from_utf8_lossy(.0))]
+	UnknownInherentError(sp_inherents::InherentIdentifier),
+	/// Inherents Error
+	#[error("Inherent error: {0}")]
+	Inherent(sp_inherents::Error),
+	/// Timestamp inconsistent with the slot
+	#[error("Timestamp of {0:?} is inconsistent with its slot {1}")]
+	TimestampSlotMismatch(B::Hash, Slot),
+	/// Fetching the authorities from the runtime failed
+	#[error("Fetching the authorities from the runtime failed: {0}")]
+	RuntimeAuthorities(String),
+	/// Header authored in a secondary slot without secondary slots being accepted
+	#[error("Header {0:?} is authored in a secondary slot, which is not accepted")]
+	UnexpectedSecondarySlot(B::Hash),
+	/// No authorities in the authority set
+	#[error("The authority set at {0:?} is empty, no slot can be claimed")]
+	NoAuthorities(B::Hash),
+	/// Block slot too far ahead of the current slot
+	#[error("Block slot {block_slot} is too far ahead of the current slot {now_slot}")]
+	BlockTooFarInFuture {
+		/// The slot of the block.
+		block_slot: Slot,
+		/// The current slot, allowing for a slot of drift.
+		now_slot: Slot,
+	},
+	/// Block slot not above an earlier slot of its author on the same chain
+	#[error("Slot {slot} of {hash:?} is not above its author's earlier slot {previous_slot}")]
+	NonMonotonicSlot {
+		/// The hash of the block.
+		hash: B::Hash,
+		/// The slot of the block.
+		slot: Slot,
+		/// The highest earlier slot of the block's author on its chain.
+		previous_slot: Slot,
+	},
+	/// Block authority index not pointing to its slot author
+	#[error("Header {hash:?} carries authority index {index}, which is not its slot author's")]
+	InvalidAuthorityIndex {
+		/// The hash of the block.
+		hash: B::Hash,
+		/// The authority index carried by the block.
+		index: u32,
+	},
+	/// Keystore signing with another key filed under the public key of the slot author
+	#[error("The keystore holds another key than {} under its public key", HexDisplay::from(.0))]
+	AmbiguousKey(Vec<u8>),
+	/// No trusted authority set known for the block
+	#[error("No trusted authority set is known for {0:?}")]
+	UnknownAuthorities(B::Hash),
+	/// Keystore returning a signature of the wrong length
+	#[error("The keystore returned a signature of {actual} bytes, expected {expected} bytes")]
+	MalformedSignature {
+		/// The length of the signatures of the crypto scheme of the key.
+		expected: usize,
+		/// The length of the returned signature.
+		actual: usize,
+	},
+}
+
+impl<B: BlockT> From<Error<B>> for String {
+	fn from(error: Error<B>) -> String {
+		error.to_string()
+	}
+}
+
+impl<B: BlockT> Error<B> {
+	/// A short name of the kind of the error, e.g. for metric labels.
+	pub fn reason(&self) -> &'static str {
+		match self {
+			Self::MultipleHeaders => "multiple_headers",
+			Self::NoDigestFound => "no_digest",
+			Self::HeaderUnsealed(_) => "unsealed",
+			Self::HeaderBadSeal(_) => "bad_seal",
+			Self::SlotAuthorNotFound => "slot_author_not_found",
+			Self::BadSignature(_) => "bad_signature",
+			Self::Client(_) => "client",
+			Self::UnknownInherentError(_) => "unknown_inherent",
+			Self::Inherent(_) => "inherent",
+			Self::TimestampSlotMismatch(..) => "timestamp_slot_mismatch",
+			Self::RuntimeAuthorities(_) => "runtime_authorities",
+			Self::UnexpectedSecondarySlot(_) => "unexpected_secondary_slot",
+			Self::NoAuthorities(_) => "no_authorities",
+			Self::BlockTooFarInFuture { .. } => "too_far_in_future",
+			Self::NonMonotonicSlot { .. } => "non_monotonic_slot",
+			Self::InvalidAuthorityIndex { .. } => "invalid_authority_index",
+			Self::AmbiguousKey(_) => "ambiguous_key",
+			Self::UnknownAuthorities(_) => "unknown_authorities",
+			Self::MalformedSignature { .. } => "malformed_signature",
+		}
+	}
+}
+
+/// Reasons why [`start_aura`] fails, each of which would keep the node from ever authoring.
+#[derive(Debug, thiserror::Error)]
+pub enum StartAuraError<B: BlockT> {
+	/// The authority set at the best block is empty
+	#[error("The authority set at #{number} ({hash:?}) is empty, no slot can ever be claimed")]
+	EmptyAuthoritySet {
+		/// The number of the best block.
+		number: NumberFor<B>,
+		/// The hash of the best block.
+		hash: B::Hash,
+	},
+	/// The keystore is inaccessible
+	#[error("Could not read the Aura keys from the keystore: {0}")]
+	KeystoreUnavailable(sp_keystore::Error),
+	/// Reading the slot duration from the runtime failed
+	#[error("Could not read the slot duration from the runtime at {hash:?}: {error}")]
+	SlotDurationUnavailable {
+		/// The hash of the block the slot duration was read at.
+		hash: B::Hash,
+		/// The runtime API error.
+		error: sp_api::ApiError,
+	},
+	/// The slot duration is outside of [`MIN_SLOT_DURATION`] and [`MAX_SLOT_DURATION`]
+	#[error(
+		"The slot duration of {}ms is outside of the accepted range of {}ms to {}ms",
+		.0.as_millis(),
+		MIN_SLOT_DURATION.as_millis(),
+		MAX_SLOT_DURATION.as_millis()
+	)]
+	SlotDurationOutOfRange(SlotDuration),
+}
+
+impl<B: BlockT> From<StartAuraError<B>> for sp_consensus::Error {
+	fn from(error: StartAuraError<B>) -> Self {
+		sp_consensus::Error::Other(Box::new(error))
+	}
+}
+
+/// Get pre-digests from the header
+pub fn find_pre_digest<B: BlockT, Signature: Codec>(header: &B::Header) -> Result<Slot, Error<B>> {
+	find_pre_digest_with_engine_id::<B>(header, AURA_ENGINE_ID)
+}
+
+/// Get the slot from the pre-runtime digest of the header issued under `engine_id`.
+///
+/// See [`BuildAuraWorkerParams::engine_id`].
+pub fn find_pre_digest_with_engine_id<B: BlockT>(
+	header: &B::Header,
+	engine_id: ConsensusEngineId,
+) -> Result<Slot, Error<B>> {
+	if header.number().is_zero() {
+		return Ok(0.into())
+	}
+
+	find_pre_digest_item_with_engine_id::<B>(header, engine_id).map(|(slot, _)| slot)
+}
+
+/// Get the Aura pre-runtime digest item from the header, along with the slot it carries.
+///
+/// Unlike [`find_pre_digest`], this does not special-case the genesis block, which has no
+/// pre-runtime digest and thus yields [`Error::NoDigestFound`].
+pub fn find_pre_digest_item<B: BlockT, Signature: Codec>(
+	header: &B::Header,
+) -> Result<(Slot, DigestItem), Error<B>> {
+	find_pre_digest_item_with_engine_id::<B>(header, AURA_ENGINE_ID)
+}
+
+/// Get the pre-runtime digest item of the header issued under `engine_id`, along with the slot
+/// it carries.
+///
+/// See [`BuildAuraWorkerParams::engine_id`].
+pub fn find_pre_digest_item_with_engine_id<B: BlockT>(
+	header: &B::Header,
+	engine_id: ConsensusEngineId,
+) -> Result<(Slot, DigestItem), Error<B>> {
+	let mut pre_digest: Option<(Slot, DigestItem)> = None;
+	for log in header.digest().logs() {
+		trace!(target: "aura", "Checking log {:?}", log);
+		match (log.pre_runtime_try_to::<Slot>(&engine_id), pre_digest.is_some()) {
+			(Some(_), true) => return Err(aura_err(Error::MultipleHeaders)),
+			(None, _) => trace!(target: "aura", "Ignoring digest not meant for us"),
+			(Some(slot), false) => pre_digest = Some((slot, log.clone())),
+		}
+	}
+	pre_digest.ok_or_else(|| aura_err(Error::NoDigestFound))
+}
+
+/// The bytes the seal of a block with the pre-seal hash `header_hash` signs.
+///
+/// These are the raw bytes of the hash of the header without its seal, i.e. with all digests
+/// but the seal, as passed to the keystore by the worker. External signers produce a compatible
+/// seal by signing exactly these bytes with the key of the slot author. Blocks sealed in the
+/// format of [`CompatibilityMode::LegacySealFormat`] sign the message of [`seal_message`]
+/// instead.
+pub fn seal_signing_payload<B: BlockT>(header_hash: &B::Hash) -> Vec<u8> {
+	header_hash.as_ref().to_vec()
+}
+
+/// The message the seal of the block `number` in `slot` with the pre-seal hash `pre_hash`
+/// signs.
+///
+/// This is the [`seal_signing_payload`] of the pre-seal hash, unless the block is sealed in the
+/// format of [`CompatibilityMode::LegacySealFormat`].
+pub fn seal_message<B: BlockT>(
+	number: NumberFor<B>,
+	slot: Slot,
+	pre_hash: &B::Hash,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+) -> Vec<u8> {
+	match compatibility_mode.behavior_at(&number) {
+		StageBehavior::LegacySealFormat => (slot, pre_hash).encode(),
+		StageBehavior::None | StageBehavior::UseInitializeBlock =>
+			seal_signing_payload::<B>(pre_hash),
+	}
+}
+
+/// Get the index of the author of `header` in the authority set, if the header carries it in an
+/// [`AUTHORITY_INDEX_ENGINE_ID`] pre-runtime digest.
+///
+/// Headers without the digest, e.g. of authors not including it, yield `None`.
+pub fn find_authority_index<B: BlockT>(header: &B::Header) -> Result<Option<u32>, Error<B>> {
+	let mut index = None;
+	for log in header.digest().logs() {
+		match (log.pre_runtime_try_to::<u32>(&AUTHORITY_INDEX_ENGINE_ID), index.is_some()) {
+			(Some(_), true) => return Err(aura_err(Error::MultipleHeaders)),
+			(Some(found), false) => index = Some(found),
+			(None, _) => {},
+		}
+	}
+	Ok(index)
+}
+
+/// Whether `header` is marked as authored in a secondary slot.
+///
+/// See [`BuildAuraWorkerParams::secondary_slot_delay`].
+pub fn is_secondary_slot<B: BlockT>(header: &B::Header) -> bool {
+	header.digest().logs().iter().any(|log| {
+		matches!(log, DigestItem::PreRuntime(id, _) if *id == SECONDARY_SLOT_ENGINE_ID)
+	})
+}
+
+/// Strip the Aura seal from `header`.
+///
+/// Returns the header as it was before sealing, i.e. the header whose hash the seal signs,
+/// together with the signature of the removed seal. Only a seal in the last digest item is
+/// removed, as that is where it is placed on sealing; if there is none, the header is
+/// returned unchanged.
+pub fn strip_seal<B: BlockT, Signature: Codec>(
+	header: &B::Header,
+) -> (B::Header, Option<Signature>) {
+	strip_seal_with_engine_id::<B, Signature>(header, AURA_ENGINE_ID)
+}
+
+/// Strip the seal issued under `engine_id` from `header`.
+///
+/// See [`strip_seal`] and [`BuildAuraWorkerParams::engine_id`].
+pub fn strip_seal_with_engine_id<B: BlockT, Signature: Codec>(
+	header: &B::Header,
+	engine_id: ConsensusEngineId,
+) -> (B::Header, Option<Signature>) {
+	let mut pre_header = header.clone();
+	let signature =
+		pre_header.digest().logs().last().and_then(|log| log.seal_try_to::<Signature>(&engine_id));
+
+	if signature.is_some() {
+		pre_header.digest_mut().pop();
+	}
+
+	(pre_header, signature)
+}
+
+/// Check that the sealed `header` is sealed by the author of its slot among `authorities`.
+///
+/// Runs the seal checks of the import queue without a client, e.g. for offline validation of
+/// blocks: the slot is read from the pre-runtime digest, the expected author is selected
+/// round-robin and the seal must be its signature over the pre-sealed header hash. Blocks of
+/// chains using [`AuthorityWeights`] or [`CompatibilityMode::LegacySealFormat`] are not
+/// covered.
+pub fn check_seal<P, B>(header: &B::Header, authorities: &[AuthorityId<P>]) -> Result<(), Error<B>>
+where
+	P: Pair,
+	P::Signature: Codec,
+	B: BlockT,
+{
+	check_seal_with_engine_id::<P, B>(header, authorities, AURA_ENGINE_ID)
+}
+
+/// Check that the sealed `header` is sealed under `engine_id` by the author of its slot among
+/// `authorities`.
+///
+/// See [`check_seal`] and [`BuildAuraWorkerParams::engine_id`].
+pub fn check_seal_with_engine_id<P, B>(
+	header: &B::Header,
+	authorities: &[AuthorityId<P>],
+	engine_id: ConsensusEngineId,
+) -> Result<(), Error<B>>
+where
+	P: Pair,
+	P::Signature: Codec,
+	B: BlockT,
+{
+	import_queue::check_seal::<B, P>(
+		header.clone(),
+		authorities,
+		None,
+		None,
+		&CompatibilityMode::None,
+		engine_id,
+	)
+	.map(drop)
+}
+
+/// Estimate the time until `target_slot` is reached, based on the slot of the best block.
+///
+/// Returns a zero duration if the best block is already at or beyond `target_slot`.
+pub fn time_until_slot<B, C, Signature>(
+	client: &C,
+	target_slot: Slot,
+	slot_duration: SlotDuration,
+) -> Result<Duration, Error<B>>
+where
+	B: BlockT,
+	C: HeaderBackend<B>,
+	Signature: Codec,
+{
+	time_until_slot_with_engine_id::<B, C>(client, target_slot, slot_duration, AURA_ENGINE_ID)
+}
+
+/// Estimate the time until `target_slot` is reached, based on the slot of the best block as
+/// found in its pre-runtime digest issued under `engine_id`.
+///
+/// See [`time_until_slot`] and [`BuildAuraWorkerParams::engine_id`].
+pub fn time_until_slot_with_engine_id<B, C>(
+	client: &C,
+	target_slot: Slot,
+	slot_duration: SlotDuration,
+	engine_id: ConsensusEngineId,
+) -> Result<Duration, Error<B>>
+where
+	B: BlockT,
+	C: HeaderBackend<B>,
+{
+	let best_hash = client.info().best_hash;
+	let best_header = client
+		.header(BlockId::Hash(best_hash))
+		.map_err(Error::Client)?
+		.ok_or_else(|| {
+			Error::Client(sp_blockchain::Error::UnknownBlock(format!("{:?}", best_hash)))
+		})?;
+	let best_slot = find_pre_digest_with_engine_id::<B>(&best_header, engine_id)?;
+	let slots = target_slot.saturating_sub(*best_slot);
+
+	Ok(Duration::from_millis(slot_duration.as_millis().saturating_mul(*slots)))
+}
+
+fn authorities<A, B, C, AP>(
+	client: &C,
+	provider: &AP,
+	parent_hash: B::Hash,
+	context_block_number: NumberFor<B>,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+) -> Result<Vec<A>, ConsensusError>
+where
+	A: Codec + Debug,
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: sp_api::Core<B>,
+	AP: AuthorityProvider<B, A, C::Api>,
+{
+	fetch_authorities(client, provider, parent_hash, context_block_number, compatibility_mode)
+		.map_err(|e: Error<B>| {
+			warn!(target: "aura", "Fetching the authorities at {:?} failed: {}", parent_hash, e);
+			sp_consensus::Error::InvalidAuthoritiesSet
+		})
+}
+
+/// Fetch the authorities like [`authorities`], keeping the runtime API error on failure.
+fn fetch_authorities<A, B, C, AP>(
+	client: &C,
+	provider: &AP,
+	parent_hash: B::Hash,
+	context_block_number: NumberFor<B>,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+) -> Result<Vec<A>, Error<B>>
+where
+	A: Codec + Debug,
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: sp_api::Core<B>,
+	AP: AuthorityProvider<B, A, C::Api>,
+{
+	// Use `initialize_block` until we hit the block that should disable the mode.
+	let initialize = match compatibility_mode.behavior_at(&context_block_number) {
+		StageBehavior::None | StageBehavior::LegacySealFormat => false,
+		StageBehavior::UseInitializeBlock => true,
+	};
+
+	fetch_authorities_in_context(client, provider, parent_hash, context_block_number, initialize)
+}
+
+/// Get the authority set of a block numbered `number` built on top of `parent_hash`.
+///
+/// `number` may lie further ahead than the child of `parent_hash`, to look ahead at authority set
+/// changes: a block of that number is initialized on top of the state of `parent_hash`, so a set
+/// change the runtime enacts when initializing it is included. Changes the runtime would enact
+/// in the blocks in between are not.
+pub fn authorities_at<A, B, C, AP>(
+	client: &C,
+	provider: &AP,
+	parent_hash: B::Hash,
+	number: NumberFor<B>,
+) -> Result<Vec<A>, Error<B>>
+where
+	A: Codec + Debug,
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: sp_api::Core<B>,
+	AP: AuthorityProvider<B, A, C::Api>,
+{
+	fetch_authorities_in_context(client, provider, parent_hash, number, true)
+}
+
+/// Fetch the authorities at `parent_hash`, after initializing a block numbered
+/// `context_block_number` on top of it if `initialize` is set.
+fn fetch_authorities_in_context<A, B, C, AP>(
+	client: &C,
+	provider: &AP,
+	parent_hash: B::Hash,
+	context_block_number: NumberFor<B>,
+	initialize: bool,
+) -> Result<Vec<A>, Error<B>>
+where
+	A: Codec + Debug,
+	B: BlockT,
+	C: ProvideRuntimeApi<B>,
+	C::Api: sp_api::Core<B>,
+	AP: AuthorityProvider<B, A, C::Api>,
+{
+	let runtime_api = client.runtime_api();
+
+	if initialize {
+		runtime_api
+			.initialize_block(
+				&BlockId::Hash(parent_hash),
+				&B::Header::new(
+					context_block_number,
+					Default::default(),
+					Default::default(),
+					parent_hash,
+					Default::default(),
+				),
+			)
+			.map_err(|e| Error::RuntimeAuthorities(e.to_string()))?;
+	}
+
+	provider
+		.authorities(&*runtime_api, &BlockId::Hash(parent_hash))
+		.map_err(|e| Error::RuntimeAuthorities(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use sc_consensus_slots::SimpleSlotWorker;
+	use sc_keystore::LocalKeystore;
+	use sp_application_crypto::key_types::AURA;
+	use sp_consensus_aura::sr25519::{AuthorityId, AuthorityPair};
+	use sp_keyring::sr25519::Keyring;
+	use std::{collections::HashSet, sync::atomic::AtomicUsize};
+	use substrate_test_runtime_client::{
+		runtime::{Block as TestBlock, Header as TestBlockHeader},
+		Backend as TestBackend, TestClient,
+	};
+
+	/// Proposer building on its parent with the test client, unless it was told to fail.
+	struct TestProposer {
+		client: Arc<TestClient>,
+		parent_hash: <TestBlock as BlockT>::Hash,
+		fail: bool,
+	}
+
+	impl Proposer<TestBlock> for TestProposer {
+		type Error = sp_consensus::Error;
+		type Transaction = sc_client_api::TransactionFor<TestBackend, TestBlock>;
+		type Proposal = future::Ready<
+			Result<sp_consensus::Proposal<TestBlock, Self::Transaction, ()>, sp_consensus::Error>,
+		>;
+		type ProofRecording = sp_consensus::DisableProofRecording;
+		type Proof = ();
+
+		fn propose(
+			self,
+			_: InherentData,
+			digests: sp_runtime::Digest,
+			_: Duration,
+			_: Option<usize>,
+		) -> Self::Proposal {
+			use sc_block_builder::BlockBuilderProvider;
+
+			if self.fail {
+				let error = sp_consensus::Error::ClientImport("Proposing failed".into());
+				return future::ready(Err(error))
+			}
+			let built = self
+				.client
+				.new_block_at(&BlockId::Hash(self.parent_hash), digests, false)
+				.and_then(|builder| builder.build())
+				.map_err(|e| sp_consensus::Error::ClientImport(e.to_string()));
+			future::ready(built.map(|built| sp_consensus::Proposal {
+				block: built.block,
+				proof: (),
+				storage_changes: built.storage_changes,
+			}))
+		}
+	}
+
+	/// Environment whose first `failures` proposers fail, counting the proposers it created.
+	#[derive(Clone)]
+	struct TestFactory {
+		client: Arc<TestClient>,
+		failures: usize,
+		created: Arc<AtomicUsize>,
+	}
+
+	impl TestFactory {
+		fn new(client: Arc<TestClient>, failures: usize) -> Self {
+			Self { client, failures, created: Default::default() }
+		}
+
+		fn created(&self) -> usize {
+			self.created.load(Ordering::SeqCst)
+		}
+	}
+
+	impl Environment<TestBlock> for TestFactory {
+		type Proposer = TestProposer;
+		type CreateProposer = future::Ready<Result<TestProposer, sp_consensus::Error>>;
+		type Error = sp_consensus::Error;
+
+		fn init(&mut self, parent: &TestBlockHeader) -> Self::CreateProposer {
+			let fail = self.created.fetch_add(1, Ordering::SeqCst) < self.failures;
+			let client = self.client.clone();
+			future::ready(Ok(TestProposer { client, parent_hash: parent.hash(), fail }))
+		}
+	}
+
+	type TestWorker = AuraWorker<
+		TestClient,
+		TestFactory,
+		Arc<TestClient>,
+		AuthorityPair,
+		AlwaysSyncedOracle,
+		(),
+		(),
+		NumberFor<TestBlock>,
+		TestBlock,
+		AuraApiAuthorities,
+	>;
+
+	/// An [`AuraWorker`] authoring with the keys in `keystore` on top of `client`, whose genesis
+	/// authorities are Alice, Bob and Charlie.
+	fn test_worker(
+		client: Arc<TestClient>,
+		keystore: SyncCryptoStorePtr,
+		proposer_factory: TestFactory,
+		max_propose_retries: u32,
+	) -> TestWorker {
+		let params = BuildAuraWorkerParamsBuilder::new(
+			client.clone(),
+			client,
+			proposer_factory,
+			AlwaysSyncedOracle,
+			(),
+			keystore,
+		)
+		.max_propose_retries(max_propose_retries)
+		.build();
+		new_aura_worker::<AuthorityPair, TestBlock, _, _, _, _, _, (), _, _>(params).0
+	}
+
+	/// The slot `slot` on top of `chain_head`, with plenty of time left.
+	fn test_slot_info(chain_head: TestBlockHeader, slot: u64) -> SlotInfo<TestBlock> {
+		SlotInfo {
+			slot: slot.into(),
+			timestamp: Default::default(),
+			ends_at: Instant::now() + Duration::from_secs(3600),
+			inherent_data: Default::default(),
+			duration: Duration::from_millis(1000),
+			chain_head,
+			block_size_limit: None,
+		}
+	}
+
+	/// Seed an in-memory keystore with `local_keys` authority keys, mix them into a set together
+	/// with well-known remote authorities and have a worker claim `slots` slots with that set.
+	///
+	/// Asserts that exactly the slots assigned to one of the local keys are claimed, each with
+	/// the expected key.
+	fn multi_key_authoring_stress(local_keys: usize, slots: u64) {
+		let keystore = Arc::new(LocalKeystore::in_memory());
+
+		let mut authorities: Vec<AuthorityId> =
+			vec![Keyring::Alice.public().into(), Keyring::Bob.public().into()];
+		for _ in 0..local_keys {
+			let public = SyncCryptoStore::sr25519_generate_new(&*keystore, AURA, None)
+				.expect("Generates key");
+			authorities.push(public.into());
+			authorities.push(Keyring::Charlie.public().into());
+		}
+
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let genesis =
+			client.header(BlockId::Number(0)).expect("Reads genesis").expect("Genesis exists");
+		let factory = TestFactory::new(client.clone(), 0);
+		let worker = test_worker(client, keystore.clone(), factory, 0);
+
+		let mut claimed = HashSet::new();
+		for slot in 0..slots {
+			let slot = Slot::from(slot);
+			let expected = slot_author::<AuthorityPair>(slot, &authorities).cloned();
+			let is_local = expected.as_ref().map_or(false, |author| {
+				SyncCryptoStore::has_keys(&*keystore, &[(author.to_raw_vec(), AURA)])
+			});
+
+			match block_on(worker.claim_slot(&genesis, slot, &authorities)) {
+				Some(claim) => {
+					assert!(is_local, "claimed slot {} of a remote authority", slot);
+					assert_eq!(Some(claim), expected);
+					assert!(claimed.insert(slot), "slot {} claimed twice", slot);
+				},
+				None => assert!(!is_local, "missed slot {} of a local key", slot),
+			}
+		}
+
+		let expected_claims = (0..slots)
+			.filter(|slot| (*slot as usize % authorities.len()) % 2 == 0)
+			.filter(|slot| *slot as usize % authorities.len() >= 2)
+			.count();
+		assert_eq!(claimed.len(), expected_claims);
+	}
+
+	#[test]
+	fn claims_exactly_the_slots_of_local_keys() {
+		multi_key_authoring_stress(1, 200);
+	}
+
+	#[test]
+	fn claims_slots_of_many_local_keys() {
+		multi_key_authoring_stress(16, 500);
+	}
+
+	#[test]
+	fn claimable_authorities_are_those_with_local_keys() {
+		let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+		let keystore = LocalKeystore::open(keystore_path.path(), None).expect("Creates keystore");
+		let local = (0..2)
+			.map(|_| SyncCryptoStore::sr25519_generate_new(&keystore, AURA, None))
+			.collect::<Result<Vec<_>, _>>()
+			.expect("Generates keys");
+
+		let authorities: Vec<AuthorityId> = vec![
+			Keyring::Alice.public().into(),
+			local[0].into(),
+			Keyring::Bob.public().into(),
+			local[1].into(),
+		];
+		let claimable = |authorities: &[AuthorityId], key_type| {
+			claimable_authorities::<AuthorityPair>(&keystore, key_type, authorities)
+		};
+		assert_eq!(claimable(&authorities, AURA), vec![1, 3]);
+		assert!(claimable(&authorities[..1], AURA).is_empty());
+		assert!(claimable(&authorities, sp_core::crypto::key_types::BABE).is_empty());
+	}
+
+	#[test]
+	fn headers_of_the_slots_of_local_keys_are_authored_by_us() {
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let keystore = LocalKeystore::in_memory();
+		let local: AuthorityId = SyncCryptoStore::sr25519_generate_new(&keystore, AURA, None)
+			.expect("Generates key")
+			.into();
+		let authorities = vec![Keyring::Alice.public().into(), local];
+		let header = |logs: Vec<DigestItem>| {
+			TestHeader::new(
+				1,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Digest { logs },
+			)
+		};
+		let in_slot = |slot: u64| {
+			header(vec![DigestItem::PreRuntime(AURA_ENGINE_ID, Slot::from(slot).encode())])
+		};
+		let ours = |header: &TestHeader| {
+			authored_by_us::<AuthorityPair, Block>(header, &authorities, &keystore)
+		};
+
+		assert!(ours(&in_slot(1)));
+		assert!(ours(&in_slot(3)));
+		assert!(!ours(&in_slot(2)));
+		assert!(!ours(&header(Vec::new())));
+		assert!(!authored_by_us::<AuthorityPair, Block>(&in_slot(1), &[], &keystore));
+		let another_engine_id = AuthorshipParams { engine_id: *b"fork", ..Default::default() };
+		assert!(!authored_by_us_with::<AuthorityPair, Block>(
+			&in_slot(1),
+			&authorities,
+			&keystore,
+			another_engine_id,
+		));
+
+		// the secondary author of slot 2 is the author of slot 3
+		let mut secondary = in_slot(2);
+		secondary.digest_mut().push(DigestItem::PreRuntime(SECONDARY_SLOT_ENGINE_ID, Vec::new()));
+		assert!(ours(&secondary));
+
+		// the slots of an offline authority go to the next live one
+		let alice: AuthorityId = Keyring::Alice.public().into();
+		let offline: OfflineAuthorities<AuthorityId> =
+			Arc::new(move |authority| *authority == alice);
+		let ours_with = |header: &TestHeader, weights, offline| {
+			let params = AuthorshipParams { weights, offline, ..Default::default() };
+			authored_by_us_with::<AuthorityPair, Block>(header, &authorities, &keystore, params)
+		};
+		assert!(ours_with(&in_slot(2), None, Some(&offline)));
+		// but the secondary author is not skipped
+		let mut secondary = in_slot(1);
+		secondary.digest_mut().push(DigestItem::PreRuntime(SECONDARY_SLOT_ENGINE_ID, Vec::new()));
+		assert!(!ours_with(&secondary, None, Some(&offline)));
+
+		// weighted authors own the slots of their bucket
+		let mut weights = AuthorityWeights::new();
+		weights.insert(&authorities[0], 3);
+		for slot in 0..8u64 {
+			let expected =
+				slot_author_weighted::<AuthorityPair>(slot.into(), &authorities, Some(&weights)) ==
+					Some(&authorities[1]);
+			assert_eq!(ours_with(&in_slot(slot), Some(&weights), None), expected);
+		}
+		assert!((0..8u64).any(|slot| {
+			ours_with(&in_slot(slot), Some(&weights), None) != ours(&in_slot(slot))
+		}));
+	}
+
+	#[test]
+	fn always_synced_oracle_never_holds_back_authoring() {
+		let mut oracle = AlwaysSyncedOracle;
+		assert!(!oracle.is_major_syncing());
+		assert!(!oracle.is_offline());
+	}
+
+	#[test]
+	fn keystore_drift_lists_keys_missing_on_either_side() {
+		let keystore_path = tempfile::tempdir().expect("Creates keystore path");
+		let keystore = LocalKeystore::open(keystore_path.path(), None).expect("Creates keystore");
+		let local = (0..2)
+			.map(|_| SyncCryptoStore::sr25519_generate_new(&keystore, AURA, None))
+			.collect::<Result<Vec<_>, _>>()
+			.expect("Generates keys");
+		SyncCryptoStore::ed25519_generate_new(&keystore, AURA, None).expect("Generates key");
+
+		let authorities: Vec<AuthorityId> = vec![Keyring::Alice.public().into(), local[0].into()];
+		let drift = check_keystore_against_authorities::<AuthorityPair>(&keystore, &authorities)
+			.expect("Readable keystore");
+		assert_eq!(drift.unknown_local_keys, vec![AuthorityId::from(local[1])]);
+		assert_eq!(drift.missing_local_keys, vec![authorities[0].clone()]);
+		assert!(!drift.no_local_authority(authorities.len()));
+
+		let drift =
+			check_keystore_against_authorities::<AuthorityPair>(&keystore, &authorities[..1])
+				.expect("Readable keystore");
+		assert_eq!(drift.unknown_local_keys.len(), 2);
+		assert!(drift.no_local_authority(1));
+	}
+
+	#[test]
+	fn strip_seal_removes_only_the_seal() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let pair = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+		let pre_header = TestHeader::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest {
+				logs: vec![<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(
+					3.into(),
+				)],
+			},
+		);
+		let signature = pair.sign(pre_header.hash().as_ref());
+		let mut header = pre_header.clone();
+		header.digest_mut().push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(
+			signature.clone(),
+		));
+
+		let (stripped, seal) = strip_seal::<Block, AuthoritySignature>(&header);
+		assert_eq!(stripped.hash(), pre_header.hash());
+		assert_eq!(seal, Some(signature));
+
+		assert_eq!(
+			strip_seal_with_engine_id::<Block, AuthoritySignature>(&header, *b"fork"),
+			(header.clone(), None),
+		);
+		assert_eq!(strip_seal::<Block, AuthoritySignature>(&pre_header), (pre_header, None));
+	}
+
+	#[test]
+	fn check_seal_verifies_the_slot_author() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let alice = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+		let bob = AuthorityPair::from_string("//Bob", None).expect("Valid seed");
+		let authorities = vec![alice.public(), bob.public()];
+		let sealed_header = |pair: &AuthorityPair, slot: u64| {
+			let slot = Slot::from(slot);
+			let pre_digest =
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(slot);
+			let mut header = TestHeader::new(
+				1,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Digest { logs: vec![pre_digest] },
+			);
+			let signature = pair.sign(header.hash().as_ref());
+			header.digest_mut().push(
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature),
+			);
+			header
+		};
+
+		for (pair, slot) in [(&alice, 4), (&bob, 5)] {
+			let header = sealed_header(pair, slot);
+			assert!(check_seal::<AuthorityPair, Block>(&header, &authorities).is_ok());
+		}
+		assert!(matches!(
+			check_seal::<AuthorityPair, Block>(&sealed_header(&bob, 4), &authorities),
+			Err(Error::BadSignature(_)),
+		));
+		assert!(matches!(
+			check_seal::<AuthorityPair, Block>(&sealed_header(&alice, 4), &[]),
+			Err(Error::SlotAuthorNotFound),
+		));
+
+		let (unsealed, _) = strip_seal::<Block, AuthoritySignature>(&sealed_header(&alice, 4));
+		assert!(matches!(
+			check_seal::<AuthorityPair, Block>(&unsealed, &authorities),
+			Err(Error::HeaderUnsealed(_)),
+		));
+	}
+
+	#[test]
+	fn pre_seal_digests_are_covered_by_the_seal() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let alice = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+		let slot = Slot::from(4);
+		let pre_digest =
+			<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(slot);
+		let header = TestHeader::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest { logs: vec![pre_digest] },
+		);
+		let hook_digests = vec![DigestItem::Seal(*b"hook", vec![1, 2, 3])];
+
+		let seal = |hash: <Block as BlockT>::Hash| {
+			let mut sealed = header.clone();
+			for item in &hook_digests {
+				sealed.digest_mut().push(item.clone());
+			}
+			sealed.digest_mut().push(
+				<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(
+					alice.sign(hash.as_ref()),
+				),
+			);
+			sealed
+		};
+
+		assert_eq!(pre_seal_hash::<Block>(&header, &header.hash(), &[]), header.hash());
+		let hash = pre_seal_hash::<Block>(&header, &header.hash(), &hook_digests);
+		assert!(check_seal::<AuthorityPair, Block>(&seal(hash), &[alice.public()]).is_ok());
+		assert!(matches!(
+			check_seal::<AuthorityPair, Block>(&seal(header.hash()), &[alice.public()]),
+			Err(Error::BadSignature(_)),
+		));
+
+		let mut unsealed = seal(hash);
+		unsealed.digest_mut().pop();
+		assert_eq!(take_pre_seal_digests::<Block>(&mut unsealed, AURA_ENGINE_ID), hook_digests);
+		assert_eq!(unsealed, header);
+		assert!(take_pre_seal_digests::<Block>(&mut unsealed, AURA_ENGINE_ID).is_empty());
+
+		assert!(!is_pre_seal_digest(&DigestItem::Other(vec![1, 2, 3]), AURA_ENGINE_ID));
+		assert!(!is_pre_seal_digest(&DigestItem::Consensus(*b"hook", Vec::new()), AURA_ENGINE_ID));
+		assert!(!is_pre_seal_digest(&DigestItem::Seal(AURA_ENGINE_ID, Vec::new()), AURA_ENGINE_ID));
+	}
+
+	#[test]
+	fn authority_index_digest_is_found_next_to_the_pre_digest() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let pre_digest =
+			<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(5.into());
+		let index = DigestItem::PreRuntime(AUTHORITY_INDEX_ENGINE_ID, 3u32.encode());
+		let header = |logs| {
+			TestHeader::new(1, Default::default(), Default::default(), Default::default(), Digest {
+				logs,
+			})
+		};
+
+		let with_index = header(vec![pre_digest.clone(), index.clone()]);
+		assert_eq!(find_pre_digest::<Block, AuthoritySignature>(&with_index).ok(), Some(5.into()));
+		assert_eq!(find_authority_index::<Block>(&with_index).ok(), Some(Some(3)));
+
+		let without_index = header(vec![pre_digest.clone()]);
+		assert_eq!(find_authority_index::<Block>(&without_index).ok(), Some(None));
+
+		let twice = header(vec![pre_digest, index.clone(), index]);
+		assert!(matches!(find_authority_index::<Block>(&twice), Err(Error::MultipleHeaders)));
+	}
+
+	#[test]
+	fn find_pre_digest_item_returns_the_raw_item() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let pre_digest =
+			<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(3.into());
+		let header = |number, logs| {
+			TestHeader::new(
+				number,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Digest { logs },
+			)
+		};
+
+		let other = DigestItem::Other(vec![1, 2, 3]);
+		let single = header(1, vec![other.clone(), pre_digest.clone()]);
+		assert_eq!(
+			find_pre_digest_item::<Block, AuthoritySignature>(&single).ok(),
+			Some((3.into(), pre_digest.clone())),
+		);
+		assert_eq!(find_pre_digest::<Block, AuthoritySignature>(&single).ok(), Some(3.into()));
+
+		let multiple = header(1, vec![pre_digest.clone(), pre_digest]);
+		assert!(matches!(
+			find_pre_digest_item::<Block, AuthoritySignature>(&multiple),
+			Err(Error::MultipleHeaders),
+		));
+
+		let genesis = header(0, vec![other]);
+		assert!(matches!(
+			find_pre_digest_item::<Block, AuthoritySignature>(&genesis),
+			Err(Error::NoDigestFound),
+		));
+		assert_eq!(find_pre_digest::<Block, AuthoritySignature>(&genesis).ok(), Some(0.into()));
+	}
+
+	#[test]
+	fn pre_digests_are_found_under_the_configured_engine_id() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let engine_id = *b"edg0";
+		let header = TestHeader::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest { logs: vec![DigestItem::PreRuntime(engine_id, Slot::from(7).encode())] },
+		);
+
+		assert_eq!(
+			find_pre_digest_with_engine_id::<Block>(&header, engine_id).ok(),
+			Some(7.into()),
+		);
+		assert!(matches!(
+			find_pre_digest::<Block, AuthoritySignature>(&header),
+			Err(Error::NoDigestFound),
+		));
+		assert!(matches!(
+			find_pre_digest_with_engine_id::<Block>(&header, *b"edg1"),
+			Err(Error::NoDigestFound),
+		));
+	}
+
+	#[test]
+	fn missed_slots_count_claimed_but_unsealed_slots_per_key() {
+		let claimed = |slot: u64, key: &[u8], authority_index| SlotProgress {
+			claimed_by: Some(key.to_vec()),
+			claimed_slot: Some(slot.into()),
+			authority_index: Some(authority_index),
+			..Default::default()
+		};
+		let mut missed = MissedSlots::default();
+
+		assert_eq!(missed.note(SlotProgress::default()), None);
+		assert_eq!(missed.note(claimed(4, b"alice", 0)), Some((4.into(), Some(0), 1)));
+		assert_eq!(missed.note(claimed(7, b"bob", 1)), Some((7.into(), Some(1), 1)));
+		assert_eq!(missed.note(claimed(8, b"alice", 0)), Some((8.into(), Some(0), 2)));
+
+		assert_eq!(missed.count(b"alice"), 2);
+		assert_eq!(missed.count(b"bob"), 1);
+		assert_eq!(missed.count(b"charlie"), 0);
+	}
+
+	#[test]
+	fn slot_author_index_rotates_through_the_set() {
+		assert_eq!(slot_author_index(0.into(), 0), None);
+		assert_eq!(slot_author_index(7.into(), 1), Some(0));
+		assert_eq!(slot_author_index(7.into(), 3), Some(1));
+		assert_eq!(slot_author_index(u64::MAX.into(), 4), Some(3));
+	}
+
+	#[test]
+	fn authoring_schedule_enumerates_upcoming_authors() {
+		let authorities: Vec<AuthorityId> = vec![
+			Keyring::Alice.public().into(),
+			Keyring::Bob.public().into(),
+			Keyring::Charlie.public().into(),
+		];
+
+		assert_eq!(expected_author::<AuthorityPair>(4.into(), &authorities), Some(&authorities[1]));
+		assert_eq!(expected_author::<AuthorityPair>(4.into(), &[]), None);
+
+		let schedule = authoring_schedule::<AuthorityPair>(4.into(), 4, &authorities);
+		assert_eq!(
+			schedule,
+			vec![
+				(4.into(), authorities[1].clone()),
+				(5.into(), authorities[2].clone()),
+				(6.into(), authorities[0].clone()),
+				(7.into(), authorities[1].clone()),
+			],
+		);
+		for (slot, author) in &schedule {
+			assert_eq!(expected_author::<AuthorityPair>(*slot, &authorities), Some(author));
+		}
+
+		assert!(authoring_schedule::<AuthorityPair>(4.into(), 4, &[]).is_empty());
+		assert!(authoring_schedule::<AuthorityPair>(4.into(), 0, &authorities).is_empty());
+		let last = Slot::from(u64::MAX - 1);
+		assert_eq!(authoring_schedule::<AuthorityPair>(last, 4, &authorities).len(), 2);
+	}
+
+	#[test]
+	fn weighted_slot_author_is_proportional_to_weights() {
+		let authorities: Vec<AuthorityId> = vec![
+			Keyring::Alice.public().into(),
+			Keyring::Bob.public().into(),
+			Keyring::Charlie.public().into(),
+		];
+
+		for slot in 0..12u64 {
+			assert_eq!(
+				slot_author_index_weighted(slot.into(), &authorities, None),
+				slot_author_index(slot.into(), authorities.len()),
+			);
+		}
+
+		let mut weights = AuthorityWeights::new();
+		weights.insert(&authorities[0], 3);
+		weights.insert(&authorities[2], 0);
+		let authors = (0..10u64)
+			.map(|slot| slot_author_index_weighted(slot.into(), &authorities, Some(&weights)))
+			.collect::<Vec<_>>();
+		assert_eq!(
+			authors,
+			[0, 0, 0, 1, 0, 0, 0, 1, 0, 0].into_iter().map(Some).collect::<Vec<_>>(),
+		);
+
+		// all weights zero falls back to round-robin
+		let mut zero = AuthorityWeights::new();
+		for authority in &authorities {
+			zero.insert(authority, 0);
+		}
+		assert_eq!(slot_author_index_weighted(4.into(), &authorities, Some(&zero)), Some(1));
+		let empty: &[AuthorityId] = &[];
+		assert_eq!(slot_author_index_weighted(4.into(), empty, Some(&weights)), None);
+	}
+
+	#[test]
+	fn slots_of_offline_authorities_fall_through_to_the_next_live_one() {
+		let authorities: Vec<AuthorityId> = vec![
+			Keyring::Alice.public().into(),
+			Keyring::Bob.public().into(),
+			Keyring::Charlie.public().into(),
+		];
+		let author = |slot: u64, offline: &[Keyring]| {
+			let offline = offline.iter().map(|key| key.public().into()).collect::<Vec<_>>();
+			let offline: OfflineAuthorities<AuthorityId> =
+				Arc::new(move |authority| offline.contains(authority));
+			live_slot_author::<AuthorityPair>(slot.into(), &authorities, None, Some(&offline))
+				.cloned()
+		};
+
+		assert_eq!(author(0, &[]), Some(authorities[0].clone()));
+		assert_eq!(author(0, &[Keyring::Alice]), Some(authorities[1].clone()));
+		assert_eq!(author(0, &[Keyring::Alice, Keyring::Bob]), Some(authorities[2].clone()));
+		// wraps around the set
+		assert_eq!(author(2, &[Keyring::Charlie]), Some(authorities[0].clone()));
+		// with every authority offline the slot keeps its author
+		assert_eq!(
+			author(1, &[Keyring::Alice, Keyring::Bob, Keyring::Charlie]),
+			Some(authorities[1].clone()),
+		);
+		assert_eq!(
+			live_slot_author::<AuthorityPair>(1.into(), &authorities, None, None),
+			Some(&authorities[1]),
+		);
+	}
+
+	#[test]
+	fn proposing_duration_grows_with_missed_slots_up_to_the_max() {
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let slot_info = |slot: u64| SlotInfo::<Block> {
+			slot: slot.into(),
+			timestamp: Default::default(),
+			ends_at: Instant::now() + Duration::from_secs(3600),
+			inherent_data: Default::default(),
+			duration: Duration::from_millis(1000),
+			chain_head: TestHeader::new(
+				1,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			),
+			block_size_limit: None,
+		};
+		let base = SlotProportion::new(0.5);
+		let duration = |parent_slot: Option<u64>, max: Option<&SlotProportion>| {
+			compute_proposing_duration(
+				parent_slot.map(Slot::from),
+				&slot_info(10),
+				&base,
+				max,
+				&SlotLenienceType::Exponential,
+			)
+		};
+
+		assert_eq!(duration(None, None), Duration::from_millis(500));
+		assert_eq!(duration(Some(9), None), Duration::from_millis(500));
+
+		// the lenience doubles every second missed slot
+		assert_eq!(duration(Some(7), None), Duration::from_millis(1500));
+		assert_eq!(duration(Some(5), None), Duration::from_millis(2500));
+		assert_eq!(duration(Some(3), None), Duration::from_millis(4500));
+
+		let max = SlotProportion::new(0.8);
+		assert_eq!(duration(Some(9), Some(&max)), Duration::from_millis(500));
+		assert_eq!(duration(Some(3), Some(&max)), Duration::from_millis(800));
+	}
+
+	#[test]
+	fn finality_lag_backoff_starts_above_the_threshold() {
+		let backoff = FinalityLagBackoff::new(5);
+		let should_backoff = |head: u64, finalized: u64| {
+			backoff.should_backoff(head, 0.into(), finalized, 0.into(), "aura")
+		};
+
+		assert!(!should_backoff(10, 10));
+		assert!(!should_backoff(14, 10));
+		// lag equal to the threshold is still fine
+		assert!(!should_backoff(15, 10));
+		assert!(should_backoff(16, 10));
+		// finalized ahead of the head, e.g. while the head is being updated
+		assert!(!should_backoff(9, 10));
+
+		assert!(FinalityLagBackoff::new(0).should_backoff(11u64, 0.into(), 10, 0.into(), "aura"));
+	}
+
+	#[test]
+	fn combined_backoff_backs_off_if_either_strategy_does() {
+		/// Backs off in a single slot.
+		struct BackoffAtSlot(u64);
+
+		impl BackoffAuthoringBlocksStrategy<u64> for BackoffAtSlot {
+			fn should_backoff(&self, _: u64, _: Slot, _: u64, slot_now: Slot, _: &str) -> bool {
+				*slot_now == self.0
+			}
+		}
+
+		fn should_backoff(
+			backoff: &impl BackoffAuthoringBlocksStrategy<u64>,
+			head: u64,
+			slot: u64,
+		) -> bool {
+			backoff.should_backoff(head, 0.into(), 10, slot.into(), "aura")
+		}
+
+		let backoff = CombinedBackoff::new(FinalityLagBackoff::new(5), BackoffAtSlot(7));
+		assert!(!should_backoff(&backoff, 12, 3));
+		// only the finality lag
+		assert!(should_backoff(&backoff, 16, 3));
+		// only the slot
+		assert!(should_backoff(&backoff, 12, 7));
+		assert!(should_backoff(&backoff, 16, 7));
+
+		// the order of the strategies does not matter
+		let backoff = CombinedBackoff::new(BackoffAtSlot(7), FinalityLagBackoff::new(5));
+		assert!(!should_backoff(&backoff, 12, 3));
+		assert!(should_backoff(&backoff, 16, 3));
+		assert!(should_backoff(&backoff, 12, 7));
+
+		// `()` never backs off
+		let backoff = CombinedBackoff::new((), ());
+		assert!(!should_backoff(&backoff, 100, 7));
+	}
+
+	#[test]
+	fn empty_blocks_are_skipped_until_a_heartbeat_is_due() {
+		let ready = Arc::new(AtomicUsize::new(0));
+		let config = SkipEmptyConfig {
+			min_interval: 5,
+			ready_transactions: {
+				let ready = ready.clone();
+				Arc::new(move || ready.load(Ordering::SeqCst))
+			},
+		};
+		let skips = |slot: u64, last_authored_slot: Option<u64>| {
+			skips_empty_block(&config, slot.into(), last_authored_slot.map(Into::into))
+		};
+
+		assert!(skips(11, Some(10)));
+		assert!(skips(14, Some(10)));
+		// a heartbeat is authored once the interval passed
+		assert!(!skips(15, Some(10)));
+		assert!(!skips(100, Some(10)));
+		// so is the first block after starting
+		assert!(!skips(11, None));
+		// blocks with transactions are always authored, whether they are signed or not
+		ready.store(1, Ordering::SeqCst);
+		assert!(!skips(11, Some(10)));
+	}
+
+	#[test]
+	fn clock_drift_is_reported_from_the_median_offset_of_peer_heads() {
+		let mut clock_drift = ClockDrift::<u64>::new(2);
+		let mut note = |head: u64, head_slot: u64, slot: u64| {
+			clock_drift.note(head, head_slot.into(), slot.into())
+		};
+
+		// heads of peers 3 slots ahead of the local clock
+		for head in 1..CLOCK_DRIFT_SAMPLES as u64 {
+			assert_eq!(note(head, 100 + head + 3, 101 + head), None);
+			// a head is only sampled once
+			assert_eq!(note(head, 100 + head + 3, 102 + head), None);
+		}
+		assert_eq!(note(100, 203, 201), Some(3));
+
+		// heads seen in time, with the odd missed slot in between
+		for head in 101..100 + CLOCK_DRIFT_SAMPLES as u64 {
+			let missed = u64::from(head % 4 == 0);
+			assert_eq!(note(head, head - missed, head + 1), None);
+		}
+		assert_eq!(note(200, 200, 201), None);
+
+		// heads behind the local clock, by up to the threshold and beyond it
+		for head in 201..200 + CLOCK_DRIFT_SAMPLES as u64 {
+			assert_eq!(note(head, head - 2, head + 1), None);
+		}
+		assert_eq!(note(300, 298, 301), None);
+		for head in 301..300 + CLOCK_DRIFT_SAMPLES as u64 {
+			assert_eq!(note(head, head - 5, head + 1), None);
+		}
+		assert_eq!(note(400, 395, 401), Some(-5));
+	}
+
+	#[test]
+	fn transaction_limit_is_the_lower_of_both_caps() {
+		assert_eq!(transaction_limit(None, None, 3), None);
+		assert_eq!(transaction_limit(Some(100), None, 0), Some(100));
+		assert_eq!(transaction_limit(Some(100), Some(10), 0), Some(100));
+		assert_eq!(transaction_limit(Some(100), Some(10), 2), Some(10));
+		assert_eq!(transaction_limit(Some(5), Some(10), 2), Some(5));
+		assert_eq!(transaction_limit(None, Some(10), 2), Some(10));
+	}
+
+	#[test]
+	fn should_author_decides_from_the_inherent_data() {
+		const HALT: sp_inherents::InherentIdentifier = *b"halt0000";
+
+		let should_author: ShouldAuthor = Arc::new(|inherent_data: &InherentData| {
+			!inherent_data.get_data::<bool>(&HALT).ok().flatten().unwrap_or_default()
+		});
+		let mut inherent_data = InherentData::new();
+		assert!(may_author(None, &inherent_data));
+		assert!(may_author(Some(&should_author), &inherent_data));
+
+		inherent_data.put_data(HALT, &true).expect("Puts inherent data");
+		assert!(!may_author(Some(&should_author), &inherent_data));
+		assert!(may_author(None, &inherent_data));
+
+		inherent_data.replace_data(HALT, &false);
+		assert!(may_author(Some(&should_author), &inherent_data));
+	}
+
+	#[test]
+	fn bypassed_authoring_checks_always_allow_authoring() {
+		use sp_consensus::NeverCanAuthor;
+		use substrate_test_runtime_client::runtime::Block;
+
+		struct CountingCanAuthor(AtomicUsize);
+		impl CanAuthorWith<Block> for CountingCanAuthor {
+			fn can_author_with(&self, _: &BlockId<Block>) -> Result<(), String> {
+				self.0.fetch_add(1, Ordering::SeqCst);
+				Err("incompatible".into())
+			}
+		}
+
+		let at = BlockId::<Block>::Number(1);
+		assert!(NeverCanAuthor.can_author_with(&at).is_err());
+		assert_eq!(AlwaysCanAuthor(NeverCanAuthor).can_author_with(&at), Ok(()));
+
+		// the wrapped check is not run at all
+		let bypassed = AlwaysCanAuthor(CountingCanAuthor(AtomicUsize::new(0)));
+		assert_eq!(bypassed.can_author_with(&at), Ok(()));
+		assert_eq!(bypassed.0 .0.load(Ordering::SeqCst), 0);
+	}
+
+	#[test]
+	fn only_verbose_telemetry_reports_slot_claims() {
+		assert_eq!(TelemetryLevel::default(), TelemetryLevel::Verbose);
+		assert!(TelemetryLevel::Verbose.reports_slot_claims());
+		assert!(!TelemetryLevel::AuthoredBlocks.reports_slot_claims());
+	}
+
+	#[test]
+	fn heads_of_deep_forks_are_not_authored_on() {
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let child = |parent: &TestHeader, fork: u8| {
+			TestHeader::new(
+				parent.number + 1,
+				[fork; 32].into(),
+				Default::default(),
+				parent.hash(),
+				Default::default(),
+			)
+		};
+		let build = |from: &TestHeader, fork: u8, len: usize| {
+			let mut chain = vec![child(from, fork)];
+			while chain.len() < len {
+				chain.push(child(chain.last().unwrap(), fork));
+			}
+			chain
+		};
+
+		let header = |number| {
+			TestHeader::new(
+				number,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Default::default(),
+			)
+		};
+		let genesis = header(0);
+		// the finalized chain, and forks of it branching off two and one blocks below the
+		// finalized block
+		let canon = build(&genesis, 1, 6);
+		let fork = build(&genesis, 2, 6);
+		let shallow_fork = build(&canon[0], 3, 4);
+		let headers: HashMap<_, _> = canon
+			.iter()
+			.chain(&fork)
+			.chain(&shallow_fork)
+			.chain([&genesis])
+			.map(|h| (h.hash(), h.clone()))
+			.collect();
+		let common_ancestor = |one: <Block as BlockT>::Hash, two: <Block as BlockT>::Hash| {
+			let (mut one, mut two) = (headers.get(&one)?, headers.get(&two)?);
+			while one.hash() != two.hash() {
+				if one.number >= two.number {
+					one = headers.get(&one.parent_hash)?;
+				} else {
+					two = headers.get(&two.parent_hash)?;
+				}
+			}
+			Some(one.number)
+		};
+		let finalized = &canon[1];
+		let deep_fork = |head: &TestHeader, max_reorg_depth: u32| {
+			find_deep_fork::<Block, _>(
+				head,
+				finalized.number,
+				finalized.hash(),
+				max_reorg_depth,
+				common_ancestor,
+			)
+		};
+
+		// heads descending from the finalized block are authored on, however far ahead
+		assert_eq!(deep_fork(&canon[5], 0), None);
+		assert_eq!(deep_fork(finalized, 0), None);
+		// the depth counts from the fork point, not from the head
+		assert_eq!(deep_fork(&fork[5], 1), Some(2));
+		assert_eq!(deep_fork(&fork[5], 2), None);
+		assert_eq!(deep_fork(&fork[0], 1), Some(2));
+		assert_eq!(deep_fork(&shallow_fork[3], 0), Some(1));
+		assert_eq!(deep_fork(&shallow_fork[3], 1), None);
+		// an ancestor of the finalized block reverts the blocks above it
+		assert_eq!(deep_fork(&canon[0], 0), Some(1));
+		// unknown ancestry is not held against the head
+		assert_eq!(deep_fork(&child(&header(4), 3), 0), None);
+	}
+
+	#[test]
+	fn halted_slots_and_heads_of_deep_forks_are_skipped_when_claiming() {
+		use sc_block_builder::BlockBuilderProvider;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::{ClientBlockImportExt, ClientExt};
+
+		const HALT: sp_inherents::InherentIdentifier = *b"halt0000";
+
+		let keystore = LocalKeystore::in_memory();
+		SyncCryptoStore::sr25519_generate_new(&keystore, AURA, Some(&Keyring::Alice.to_seed()))
+			.expect("Inserts key");
+
+		// two blocks on top of genesis, the first of them finalized
+		let mut client = substrate_test_runtime_client::new();
+		let block_in_slot = |client: &TestClient, slot: u64| {
+			let pre_digest = DigestItem::PreRuntime(AURA_ENGINE_ID, Slot::from(slot).encode());
+			let digest = Digest { logs: vec![pre_digest] };
+			client
+				.new_block(digest)
+				.expect("Creates block builder")
+				.build()
+				.expect("Builds block")
+				.block
+		};
+		let finalized = block_in_slot(&client, 1);
+		let fork = block_in_slot(&client, 2);
+		for block in [&finalized, &fork] {
+			block_on(client.import(BlockOrigin::Own, block.clone())).expect("Imports block");
+		}
+		client.finalize_block(BlockId::Hash(finalized.hash()), None).expect("Finalizes block");
+
+		let client = Arc::new(client);
+		let factory = TestFactory::new(client.clone(), 0);
+		let mut worker = test_worker(client, Arc::new(keystore), factory, 0);
+		worker.should_author = Some(Arc::new(|inherent_data: &InherentData| {
+			!inherent_data.get_data::<bool>(&HALT).ok().flatten().unwrap_or_default()
+		}));
+		worker.max_reorg_depth = Some(0);
+
+		let authorities = vec![Keyring::Alice.public().into()];
+		let claim = |worker: &TestWorker, head: &TestBlockHeader, halt: bool| {
+			let mut slot_info = test_slot_info(head.clone(), 4);
+			slot_info.inherent_data.put_data(HALT, &halt).expect("Puts inherent data");
+			// skipping the slot leaves the proposing time alone
+			assert!(!worker.proposing_remaining_duration(&slot_info).is_zero());
+			block_on(worker.claim_slot(head, slot_info.slot, &authorities)).is_some()
+		};
+
+		assert!(claim(&worker, finalized.header(), false));
+		assert!(!claim(&worker, finalized.header(), true));
+		// the fork branches off one block below the finalized block
+		assert!(!claim(&worker, fork.header(), false));
+		worker.max_reorg_depth = Some(1);
+		assert!(claim(&worker, fork.header(), false));
+	}
+
+	#[test]
+	fn adjusted_proposing_time_is_capped_at_the_slot_end() {
+		let now = Instant::now();
+		let ends_at = now + Duration::from_millis(500);
+
+		assert_eq!(
+			cap_at_slot_end(Duration::from_millis(200), ends_at, now),
+			Duration::from_millis(200),
+		);
+		assert_eq!(
+			cap_at_slot_end(Duration::from_secs(2), ends_at, now),
+			Duration::from_millis(500),
+		);
+		assert_eq!(
+			cap_at_slot_end(Duration::from_secs(2), ends_at, ends_at + Duration::from_millis(1)),
+			Duration::ZERO,
+		);
+	}
+
+	#[test]
+	fn own_blocks_are_imported_with_the_configured_origin_and_fork_choice() {
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let header = TestHeader::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+
+		let import_block = own_block_import_params::<Block, ()>(
+			header.clone(),
+			BlockOrigin::Own,
+			ForkChoiceStrategy::LongestChain,
+		);
+		assert_eq!(import_block.origin, BlockOrigin::Own);
+		assert_eq!(import_block.fork_choice, Some(ForkChoiceStrategy::LongestChain));
+
+		let import_block = own_block_import_params::<Block, ()>(
+			header,
+			BlockOrigin::File,
+			ForkChoiceStrategy::Custom(false),
+		);
+		assert_eq!(import_block.origin, BlockOrigin::File);
+		assert_eq!(import_block.fork_choice, Some(ForkChoiceStrategy::Custom(false)));
+	}
+
+	#[test]
+	fn equally_high_blocks_are_ordered_by_slot() {
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let header = |number: u64, slot: Option<u64>| {
+			let logs = slot
+				.map(|slot| DigestItem::PreRuntime(AURA_ENGINE_ID, Slot::from(slot).encode()))
+				.into_iter()
+				.collect();
+			TestHeader::new(
+				number,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Digest { logs },
+			)
+		};
+		let wins = |new: TestHeader, best: TestHeader| {
+			wins_slot_tiebreak::<Block>(&new, &best, AURA_ENGINE_ID)
+		};
+
+		assert!(wins(header(6, Some(9)), header(5, Some(4))));
+		assert!(!wins(header(4, Some(2)), header(5, Some(4))));
+		assert!(wins(header(5, Some(3)), header(5, Some(4))));
+		assert!(!wins(header(5, Some(4)), header(5, Some(4))));
+		assert!(!wins(header(5, Some(6)), header(5, Some(4))));
+		assert!(!wins(header(5, None), header(5, Some(4))));
+	}
+
+	#[test]
+	fn authorities_are_read_through_the_provider() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		/// Stands in for a runtime API other than `AuraApi`.
+		struct Renamed(Vec<AuthorityId>);
+
+		impl<Api> AuthorityProvider<Block, AuthorityId, Api> for Renamed {
+			fn authorities(
+				&self,
+				_: &Api,
+				_: &BlockId<Block>,
+			) -> Result<Vec<AuthorityId>, sp_api::ApiError> {
+				Ok(self.0.clone())
+			}
+		}
+
+		let client = substrate_test_runtime_client::new();
+		let genesis_hash = client.info().genesis_hash;
+		let renamed: Vec<AuthorityId> = vec![Keyring::Ferdie.public().into()];
+
+		let from_runtime = fetch_authorities::<AuthorityId, Block, _, _>(
+			&client,
+			&AuraApiAuthorities,
+			genesis_hash,
+			1,
+			&CompatibilityMode::None,
+		)
+		.expect("The test runtime implements `AuraApi`");
+		assert_ne!(from_runtime, renamed);
+
+		let from_provider = fetch_authorities::<AuthorityId, Block, _, _>(
+			&client,
+			&Renamed(renamed.clone()),
+			genesis_hash,
+			1,
+			&CompatibilityMode::None,
+		)
+		.ok();
+		assert_eq!(from_provider, Some(renamed));
+	}
+
+	#[test]
+	fn keystore_keys_match_authorities_of_the_set_only() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		let client = substrate_test_runtime_client::new();
+		let genesis_hash = client.info().genesis_hash;
+		let alice: AuthorityId = Keyring::Alice.public().into();
+		let bob: AuthorityId = Keyring::Bob.public().into();
+		let ferdie: AuthorityId = Keyring::Ferdie.public().into();
+
+		let keystore = sp_keystore::testing::KeyStore::new();
+		for (seed, public) in [("//Alice", &alice), ("//Ferdie", &ferdie)] {
+			SyncCryptoStore::insert_unknown(&keystore, AURA, seed, public.as_ref())
+				.expect("Inserts key");
+		}
+		let matches = |public| {
+			keystore_matches_authority::<AuthorityPair, Block, _>(
+				&client,
+				&keystore,
+				public,
+				genesis_hash,
+			)
+			.expect("The test runtime implements `AuraApi`")
+		};
+		assert!(matches(&alice));
+		// in the set, but the key is not held
+		assert!(!matches(&bob));
+		// the key is held, but not in the set
+		assert!(!matches(&ferdie));
+
+		struct Ferdie;
+		impl<Api> AuthorityProvider<Block, AuthorityId, Api> for Ferdie {
+			fn authorities(
+				&self,
+				_: &Api,
+				_: &BlockId<Block>,
+			) -> Result<Vec<AuthorityId>, sp_api::ApiError> {
+				Ok(vec![Keyring::Ferdie.public().into()])
+			}
+		}
+		let matches_with = |public, key_type| {
+			keystore_matches_authority_with::<AuthorityPair, Block, _, _>(
+				&client,
+				&keystore,
+				public,
+				genesis_hash,
+				&Ferdie,
+				&CompatibilityMode::None,
+				key_type,
+			)
+			.expect("Reads the authorities from the provider")
+		};
+		assert!(matches_with(&ferdie, AURA));
+		assert!(!matches_with(&alice, AURA));
+		assert!(!matches_with(&ferdie, sp_application_crypto::key_types::BABE));
+	}
+
+	#[test]
+	fn local_keys_entering_and_leaving_the_set_are_found_at_the_boundary() {
+		let alice: AuthorityId = Keyring::Alice.public().into();
+		let bob: AuthorityId = Keyring::Bob.public().into();
+		let charlie: AuthorityId = Keyring::Charlie.public().into();
+		let local = [bob.clone(), charlie.clone()];
+		let is_local = |authority: &AuthorityId| local.contains(authority);
+
+		let old_set = vec![alice.clone(), bob.clone()];
+		let new_set = vec![alice.clone(), charlie.clone()];
+		let sets = vec![
+			(10u64, old_set.clone()),
+			(11, old_set.clone()),
+			(12, new_set.clone()),
+			(13, new_set.clone()),
+		];
+		assert_eq!(
+			local_set_changes(&sets, is_local),
+			vec![LocalSetChange::Enters(12, charlie), LocalSetChange::Leaves(12, bob)],
+		);
+
+		// Only changes within the looked at blocks are reported.
+		assert!(local_set_changes(&sets[..2], is_local).is_empty());
+		assert!(local_set_changes(&sets[2..], is_local).is_empty());
+		assert!(local_set_changes(&sets, |authority| *authority == alice).is_empty());
+	}
+
+	#[test]
+	fn local_key_entering_and_leaving_the_fetched_sets_is_noted() {
+		let alice: AuthorityId = Keyring::Alice.public().into();
+		let bob: AuthorityId = Keyring::Bob.public().into();
+		let is_local = |authority: &AuthorityId| *authority == bob;
+		let without_bob = vec![alice.clone()];
+		let with_bob = vec![alice.clone(), bob.clone()];
+
+		let mut membership = AuthorityMembership::default();
+		// the first set is only recorded, even though it contains the local key
+		assert!(membership.note(1u64, &with_bob, is_local).is_empty());
+		assert_eq!(
+			membership.note(2, &without_bob, is_local),
+			vec![LocalSetChange::Leaves(2, bob.clone())],
+		);
+		assert!(membership.note(3, &without_bob, is_local).is_empty());
+		assert_eq!(
+			membership.note(4, &with_bob, is_local),
+			vec![LocalSetChange::Enters(4, bob.clone())],
+		);
+		assert!(membership.note(5, &with_bob, is_local).is_empty());
+		assert_eq!(
+			membership.note(6, &without_bob, is_local),
+			vec![LocalSetChange::Leaves(6, bob)],
+		);
+	}
+
+	#[test]
+	fn authorities_can_be_read_ahead_of_the_next_block() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		let client = substrate_test_runtime_client::new();
+		let genesis_hash = client.info().genesis_hash;
+
+		let next = fetch_authorities::<AuthorityId, Block, _, _>(
+			&client,
+			&AuraApiAuthorities,
+			genesis_hash,
+			1,
+			&CompatibilityMode::None,
+		)
+		.expect("The test runtime implements `AuraApi`");
+		for number in [1, 2, 100] {
+			let ahead = authorities_at::<AuthorityId, Block, _, _>(
+				&client,
+				&AuraApiAuthorities,
+				genesis_hash,
+				number,
+			)
+			.expect("The test runtime implements `AuraApi`");
+			assert_eq!(ahead, next);
+		}
+	}
+
+	#[test]
+	fn seals_signed_with_a_near_miss_key_are_rejected() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use substrate_test_runtime_client::runtime::Block;
+
+		let alice: AuthorityId = Keyring::Alice.public().into();
+		let message = b"pre-seal hash";
+		let sign = |keystore: &dyn SyncCryptoStore| -> AuthoritySignature {
+			SyncCryptoStore::sign_with(keystore, AURA, &alice.to_public_crypto_pair(), message)
+				.expect("Signs")
+				.expect("Holds a key filed under Alice")
+				.try_into()
+				.expect("Is an sr25519 signature")
+		};
+
+		let keystore = sp_keystore::testing::KeyStore::new();
+		SyncCryptoStore::insert_unknown(&keystore, AURA, "//Alice", alice.as_ref())
+			.expect("Inserts key");
+		let signature = sign(&keystore);
+		assert!(ensure_signed_by_author::<Block, AuthorityPair>(&alice, message, &signature)
+			.is_ok());
+
+		// Bob's key filed under Alice's public key, which claiming cannot tell apart
+		let near_miss = sp_keystore::testing::KeyStore::new();
+		SyncCryptoStore::insert_unknown(&near_miss, AURA, "//Bob", alice.as_ref())
+			.expect("Inserts key");
+		assert!(has_authority_key::<AuthorityPair>(&near_miss, AURA, &alice));
+		let signature = sign(&near_miss);
+		assert!(matches!(
+			ensure_signed_by_author::<Block, AuthorityPair>(&alice, message, &signature),
+			Err(Error::AmbiguousKey(public)) if public == alice.to_raw_vec(),
+		));
+	}
+
+	#[test]
+	fn signatures_of_the_wrong_length_are_reported() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		let keystore = LocalKeystore::in_memory();
+		let public =
+			SyncCryptoStore::sr25519_generate_new(&keystore, AURA, None).expect("Generates key");
+		let public_type_pair = AuthorityId::from(public).to_public_crypto_pair();
+		let signature = SyncCryptoStore::sign_with(&keystore, AURA, &public_type_pair, b"message")
+			.expect("Signs")
+			.expect("Holds the key");
+		let check = |signature: &[u8]| check_signature_len::<Block>(public_type_pair.0, signature);
+
+		assert!(check(&signature).is_ok());
+		// e.g. a backend cutting the signature short, or appending a trailer to it
+		assert!(matches!(
+			check(&signature[..63]),
+			Err(Error::MalformedSignature { expected: 64, actual: 63 }),
+		));
+		let mut padded = signature.clone();
+		padded.push(0);
+		assert!(matches!(
+			check(&padded),
+			Err(Error::MalformedSignature { expected: 64, actual: 65 }),
+		));
+		// the length of signatures of unknown schemes is not checked
+		assert!(check_signature_len::<Block>(CryptoTypeId(*b"unkn"), &[0; 3]).is_ok());
+	}
+
+	#[test]
+	fn claim_outcomes_tell_why_a_slot_was_not_claimed() {
+		let keystore = LocalKeystore::in_memory();
+		let local: AuthorityId = SyncCryptoStore::sr25519_generate_new(&keystore, AURA, None)
+			.expect("Generates key")
+			.into();
+		let remote: Vec<AuthorityId> =
+			vec![Keyring::Alice.public().into(), Keyring::Bob.public().into()];
+		let outcome = |slot: u64, authorities: &[AuthorityId], diagnose: bool| {
+			claim_outcome::<AuthorityPair>(
+				&keystore,
+				AURA,
+				slot.into(),
+				authorities,
+				None,
+				None,
+				diagnose,
+			)
+		};
+
+		let with_local = vec![remote[0].clone(), local.clone()];
+		assert_eq!(outcome(1, &with_local, true), ClaimOutcome::Claimed(local.clone()));
+		assert_eq!(outcome(2, &with_local, true), ClaimOutcome::NotAuthor);
+		assert_eq!(outcome(3, &remote, true), ClaimOutcome::MissingKey { author_index: 1 });
+		assert_eq!(outcome(3, &[], true), ClaimOutcome::NoAuthorities);
+
+		// without diagnosing, only the key of the slot author is looked up
+		assert_eq!(outcome(1, &with_local, false), ClaimOutcome::Claimed(local));
+		assert_eq!(outcome(2, &with_local, false), ClaimOutcome::NotClaimed);
+		assert_eq!(outcome(3, &remote, false), ClaimOutcome::NotClaimed);
+		assert_eq!(outcome(3, &[], false), ClaimOutcome::NoAuthorities);
+	}
+
+	#[test]
+	fn slots_are_claimed_and_sealed_with_keys_of_a_custom_key_type() {
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use substrate_test_runtime_client::runtime::Block;
+
+		const CUSTOM: KeyTypeId = KeyTypeId(*b"cstm");
+
+		let keystore = LocalKeystore::in_memory();
+		let local: AuthorityId = SyncCryptoStore::sr25519_generate_new(&keystore, CUSTOM, None)
+			.expect("Generates key")
+			.into();
+		let authorities = vec![Keyring::Alice.public().into(), local.clone()];
+
+		assert_eq!(
+			claim_slot_with_keystore::<AuthorityPair>(
+				&keystore,
+				CUSTOM,
+				1.into(),
+				&authorities,
+				None,
+				None,
+			),
+			Some(local.clone()),
+		);
+		// the key is not found under the default key type
+		assert_eq!(
+			claim_slot_with_keystore::<AuthorityPair>(
+				&keystore,
+				AURA,
+				1.into(),
+				&authorities,
+				None,
+				None,
+			),
+			None,
+		);
+
+		let message = b"pre-seal hash";
+		let signature: AuthoritySignature =
+			SyncCryptoStore::sign_with(&keystore, CUSTOM, &local.to_public_crypto_pair(), message)
+				.expect("Signs")
+				.expect("Holds the key")
+				.try_into()
+				.expect("Is an sr25519 signature");
+		assert!(ensure_signed_by_author::<Block, AuthorityPair>(&local, message, &signature)
+			.is_ok());
+	}
+
+	#[test]
+	fn failed_proposals_are_retried_in_the_slot_with_proposers_created_on_demand() {
+		let keystore = LocalKeystore::in_memory();
+		SyncCryptoStore::sr25519_generate_new(&keystore, AURA, Some(&Keyring::Alice.to_seed()))
+			.expect("Inserts key");
+		let keystore: SyncCryptoStorePtr = Arc::new(keystore);
+
+		// authors the slot of Alice on top of genesis, returning whether a block was authored and
+		// how many proposers were created
+		let author = |failures: usize, retries: u32| {
+			let client = Arc::new(substrate_test_runtime_client::new());
+			let genesis = client
+				.header(BlockId::Number(0))
+				.expect("Reads genesis")
+				.expect("Genesis exists");
+			let factory = TestFactory::new(client.clone(), failures);
+			let mut worker =
+				test_worker(client.clone(), keystore.clone(), factory.clone(), retries);
+			let result = block_on(worker.on_slot(test_slot_info(genesis, 3)));
+			if let Some(result) = &result {
+				let hash = result.block.header().hash();
+				assert!(client.header(BlockId::Hash(hash)).expect("Reads header").is_some());
+			}
+			(result.is_some(), factory.created())
+		};
+
+		// a successful proposal creates no proposer for a retry
+		assert_eq!(author(0, 2), (true, 1));
+		// the proposer of a retry is only created once a proposal failed
+		assert_eq!(author(1, 2), (true, 2));
+		// retries are used up, or not configured at all
+		assert_eq!(author(3, 2), (false, 3));
+		assert_eq!(author(1, 0), (false, 1));
+	}
+
+	#[test]
+	fn slots_falling_through_offline_authorities_carry_the_index_of_their_author() {
+		let keystore = LocalKeystore::in_memory();
+		SyncCryptoStore::sr25519_generate_new(&keystore, AURA, Some(&Keyring::Bob.to_seed()))
+			.expect("Inserts key");
+
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let genesis =
+			client.header(BlockId::Number(0)).expect("Reads genesis").expect("Genesis exists");
+		let factory = TestFactory::new(client.clone(), 0);
+		let mut worker = test_worker(client, Arc::new(keystore), factory, 0);
+		let alice: AuthorityId = Keyring::Alice.public().into();
+		let offline: OfflineAuthorities<AuthorityId> =
+			Arc::new(move |authority: &AuthorityId| *authority == alice);
+		worker.offline_authorities = Some(offline.clone());
+		worker.include_authority_index = true;
+
+		// slot 3 is scheduled for Alice, who is offline, so it falls through to Bob
+		let block = block_on(worker.on_slot(test_slot_info(genesis, 3)))
+			.expect("Authors a block")
+			.block;
+		assert_eq!(find_authority_index::<TestBlock>(block.header()).ok(), Some(Some(1)));
+
+		let authorities: Vec<AuthorityId> = vec![
+			Keyring::Alice.public().into(),
+			Keyring::Bob.public().into(),
+			Keyring::Charlie.public().into(),
+		];
+		assert_eq!(
+			import_queue::check_seal::<TestBlock, AuthorityPair>(
+				block.header().clone(),
+				&authorities,
+				None,
+				Some(&offline),
+				&CompatibilityMode::None,
+				AURA_ENGINE_ID,
+			)
+			.ok(),
+			Some(3.into()),
+		);
+	}
+
+	#[test]
+	fn blocks_with_pre_seal_digests_are_imported_by_other_nodes() {
+		use sc_consensus::{ImportResult, Verifier};
+
+		let keystore = LocalKeystore::in_memory();
+		SyncCryptoStore::sr25519_generate_new(&keystore, AURA, Some(&Keyring::Alice.to_seed()))
+			.expect("Inserts key");
+
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let genesis =
+			client.header(BlockId::Number(0)).expect("Reads genesis").expect("Genesis exists");
+		let factory = TestFactory::new(client.clone(), 0);
+		let mut worker = test_worker(client.clone(), Arc::new(keystore), factory, 0);
+		let hook_seal = DigestItem::Seal(*b"hook", vec![1, 2, 3]);
+		let hook_digests = vec![hook_seal.clone(), DigestItem::Other(vec![4, 5, 6])];
+		worker.pre_seal_hook = Some(Arc::new(move |_: &TestBlockHeader| hook_digests.clone()));
+
+		// slot 3 is Alice's
+		let block = block_on(worker.on_slot(test_slot_info(genesis, 3)))
+			.expect("Authors a block")
+			.block;
+		let logs = block.header().digest().logs();
+		assert_eq!(&logs[logs.len() - 2], &hook_seal);
+		assert!(!logs.contains(&DigestItem::Other(vec![4, 5, 6])));
+		assert_eq!(client.info().best_hash, block.hash());
+
+		// another node verifies the block and executes it with the runtime
+		let other_client = Arc::new(substrate_test_runtime_client::new());
+		let mut verifier = build_verifier::<AuthorityPair, _, _, _, _, _>(BuildVerifierParams {
+			client: other_client.clone(),
+			create_inherent_data_providers: |_, _| async {
+				let slot = sp_consensus_aura::inherents::InherentDataProvider::new(10.into());
+				Ok((sp_timestamp::InherentDataProvider::new(10_000.into()), slot))
+			},
+			can_author_with: sp_consensus::AlwaysCanAuthor,
+			check_for_equivocation: CheckForEquivocation::No,
+			telemetry: None,
+			compatibility_mode: CompatibilityMode::None,
+			use_consensus_log_authorities: false,
+			registry: None,
+			handover_window: None,
+			check_timestamp_slot_consistency: false,
+			slot_duration_schedule: Vec::new(),
+			equivocation_sender: None,
+			authority_weights: None,
+			on_equivocation: None,
+			accept_secondary_slots: false,
+			equivocation_window_slots: 0,
+			offline_authorities: None,
+			max_future_slots: u64::MAX,
+			engine_id: AURA_ENGINE_ID,
+			enforce_monotonic_slots: false,
+			verify_seals_only: false,
+			genesis_timestamp: 0,
+		});
+		let (header, body) = block.clone().deconstruct();
+		let mut incoming = BlockImportParams::new(BlockOrigin::NetworkBroadcast, header);
+		incoming.body = Some(body);
+		let (verified, _) = block_on(verifier.verify(incoming)).expect("Verifies the block");
+		assert!(!verified.header.digest().logs().contains(&hook_seal));
+
+		let mut importer = &*other_client;
+		let imported = block_on(
+			importer.import_block(verified.clear_storage_changes_and_mutate(), Default::default()),
+		);
+		assert!(matches!(imported, Ok(ImportResult::Imported(_))));
+		assert_eq!(
+			other_client.header(BlockId::Number(1)).expect("Reads the block"),
+			Some(block.header().clone()),
+		);
+	}
+
+	#[test]
+	fn slots_whose_blocks_fail_self_verification_are_missed() {
+		use std::sync::atomic::AtomicBool;
+
+		let keystore = LocalKeystore::in_memory();
+		SyncCryptoStore::sr25519_generate_new(&keystore, AURA, Some(&Keyring::Bob.to_seed()))
+			.expect("Inserts key");
+
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let genesis =
+			client.header(BlockId::Number(0)).expect("Reads genesis").expect("Genesis exists");
+		let factory = TestFactory::new(client.clone(), 0);
+		let mut worker = test_worker(client.clone(), Arc::new(keystore), factory, 0);
+		worker.self_verify_before_import = true;
+
+		// Alice is offline while the slot is claimed, so slot 3 falls through to Bob, but back by
+		// the time the block is verified, which then expects her seal.
+		let alice: AuthorityId = Keyring::Alice.public().into();
+		let alice_online = Arc::new(AtomicBool::new(false));
+		let online = alice_online.clone();
+		worker.offline_authorities = Some(Arc::new(move |authority: &AuthorityId| {
+			*authority == alice && !online.load(Ordering::SeqCst)
+		}));
+		worker.pre_seal_hook = Some(Arc::new(move |_: &TestBlockHeader| {
+			alice_online.store(true, Ordering::SeqCst);
+			Vec::new()
+		}));
+
+		assert!(block_on(worker.on_slot(test_slot_info(genesis, 3))).is_none());
+		assert_eq!(client.info().best_number, 0);
+		assert_eq!(worker.missed_slots(&Keyring::Bob.public().into()), 1);
+	}
+
+	#[test]
+	fn authorities_are_preloaded_when_the_set_at_the_head_is_cached() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let genesis =
+			client.header(BlockId::Number(0)).expect("Reads genesis").expect("Genesis exists");
+		let factory = TestFactory::new(client.clone(), 0);
+		let mut worker = test_worker(client, Arc::new(LocalKeystore::in_memory()), factory, 0);
+		let preloader = AuthoritiesPreloader::new();
+		worker.authorities_preloader = Some(preloader.clone());
+		worker.authorities_cache = Some(Mutex::new(AuthoritiesCache::new(16)));
+
+		let authorities = worker.epoch_data(&genesis, 1.into()).expect("Fetches authorities");
+		preloader.preload_authorities_at(5);
+		// the set at genesis is cached by now
+		assert_eq!(worker.epoch_data(&genesis, 2.into()).ok(), Some(authorities.clone()));
+		assert_eq!(preloader.pinned_authorities(5), Some(authorities));
+	}
+
+	#[test]
+	fn slot_tracker_clones_share_the_highest_slot() {
+		let tracker = SlotTracker::new();
+		let clone = tracker.clone();
+		assert_eq!(clone.current_slot(), Slot::from(0));
+
+		tracker.note(7.into());
+		assert_eq!(clone.current_slot(), Slot::from(7));
+		// an earlier slot, e.g. of a block authored late, does not move the tracker back
+		tracker.note(5.into());
+		assert_eq!(clone.current_slot(), Slot::from(7));
+	}
+
+	#[test]
+	fn aura_control_clones_share_the_pause_flag() {
+		let control = AuraControl::new();
+		let clone = control.clone();
+		assert!(!clone.is_paused());
+
+		control.pause();
+		assert!(clone.is_paused());
+
+		clone.resume();
+		assert!(!control.is_paused());
+	}
+
+	#[test]
+	fn authorities_cache_inherits_sets_until_a_change() {
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+		let header = |number: u64, parent_hash, logs: Vec<DigestItem>| {
+			TestHeader::new(
+				number,
+				Default::default(),
+				Default::default(),
+				parent_hash,
+				Digest { logs },
+			)
+		};
+		let initial: Vec<AuthorityId> = vec![Keyring::Alice.public().into()];
+		let next: Vec<AuthorityId> = vec![Keyring::Bob.public().into()];
+
+		let genesis = header(0, Default::default(), vec![]);
+		let plain = header(1, genesis.hash(), vec![]);
+		let change = header(
+			1,
+			genesis.hash(),
+			vec![DigestItem::Consensus(
+				AURA_ENGINE_ID,
+				ConsensusLog::AuthoritiesChange(next.clone()).encode(),
+			)],
+		);
+		let grandchild = header(2, plain.hash(), vec![]);
+
+		let mut cache = AuthoritiesCache::new(2);
+		assert_eq!(cache.authorities_at::<Block>(&plain), None);
+
+		cache.insert(genesis.hash(), initial.clone());
+		assert_eq!(cache.authorities_at::<Block>(&genesis), Some(initial.clone()));
+		assert_eq!(cache.authorities_at::<Block>(&change), None);
+		assert_eq!(cache.authorities_at::<Block>(&plain), Some(initial.clone()));
+
+		// inherited through `plain`, which evicts `genesis` from the cache of two sets
+		assert_eq!(cache.authorities_at::<Block>(&grandchild), Some(initial));
+		assert!(!cache.sets.contains_key(&genesis.hash()));
+	}
+
+	#[test]
+	fn start_aura_errors_keep_their_context_as_consensus_errors() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		let hash = Default::default();
+		let error: sp_consensus::Error =
+			StartAuraError::<Block>::EmptyAuthoritySet { number: 42, hash }.into();
+		assert!(matches!(error, sp_consensus::Error::Other(_)));
+		assert!(error.to_string().contains("#42"));
+
+		let error: sp_consensus::Error =
+			StartAuraError::<Block>::KeystoreUnavailable(sp_keystore::Error::Unavailable).into();
+		assert!(error.to_string().contains("keystore"));
+	}
+
+	#[test]
+	fn slot_durations_outside_the_sane_range_are_rejected() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		let millis = |duration: Duration| SlotDuration::from_millis(duration.as_millis() as u64);
+		let one_ms = Duration::from_millis(1);
+
+		assert!(check_slot_duration::<Block>(millis(MIN_SLOT_DURATION)).is_ok());
+		assert!(check_slot_duration::<Block>(millis(MAX_SLOT_DURATION)).is_ok());
+		assert!(check_slot_duration::<Block>(SlotDuration::from_millis(6000)).is_ok());
+
+		for duration in [Duration::ZERO, MIN_SLOT_DURATION - one_ms, MAX_SLOT_DURATION + one_ms] {
+			let error = check_slot_duration::<Block>(millis(duration)).unwrap_err();
+			assert!(matches!(error, StartAuraError::SlotDurationOutOfRange(_)));
+			assert!(error.to_string().contains(&format!("of {}ms", duration.as_millis())));
+		}
+	}
+
+	#[test]
+	fn slots_convert_to_timestamps_and_back() {
+		let duration = SlotDuration::from_millis(6000);
+
+		assert_eq!(slot_to_timestamp(0.into(), duration), 0);
+		assert_eq!(timestamp_to_slot(0, duration), Slot::from(0));
+		assert_eq!(timestamp_to_slot(5999, duration), Slot::from(0));
+		assert_eq!(slot_to_timestamp(42.into(), duration), 252_000);
+		assert_eq!(timestamp_to_slot(252_000, duration), Slot::from(42));
+
+		let last_slot = u64::MAX / 6000;
+		assert_eq!(slot_to_timestamp(last_slot.into(), duration), last_slot * 6000);
+		assert_eq!(timestamp_to_slot(u64::MAX, duration), Slot::from(last_slot));
+		assert_eq!(slot_to_timestamp((last_slot + 1).into(), duration), u64::MAX);
+		assert_eq!(slot_to_timestamp(u64::MAX.into(), duration), u64::MAX);
+
+		let zero = SlotDuration::from_millis(0);
+		assert_eq!(slot_to_timestamp(u64::MAX.into(), zero), 0);
+		assert_eq!(timestamp_to_slot(u64::MAX, zero), Slot::from(0));
+	}
+
+	#[test]
+	fn time_until_slot_counts_from_the_slot_of_the_best_block() {
+		use sc_block_builder::BlockBuilderProvider;
+		use sp_consensus_aura::sr25519::AuthoritySignature;
+		use sp_runtime::Digest;
+		use substrate_test_runtime_client::{
+			runtime::Block, ClientBlockImportExt, DefaultTestClientBuilderExt, TestClientBuilder,
+			TestClientBuilderExt,
+		};
+
+		let mut client = TestClientBuilder::new().build();
+		let digest =
+			Digest { logs: vec![DigestItem::PreRuntime(AURA_ENGINE_ID, Slot::from(10).encode())] };
+		let block = client
+			.new_block(digest)
+			.expect("Creates block builder")
+			.build()
+			.expect("Builds block")
+			.block;
+		futures::executor::block_on(client.import(BlockOrigin::Own, block))
+			.expect("Imports block");
+
+		let until = |slot: u64| {
+			time_until_slot::<Block, _, AuthoritySignature>(
+				&client,
+				slot.into(),
+				SlotDuration::from_millis(6000),
+			)
+			.expect("Finds the slot of the best block")
+		};
+		// past slots and the slot of the best block are reached already
+		assert_eq!(until(4), Duration::ZERO);
+		assert_eq!(until(10), Duration::ZERO);
+		assert_eq!(until(13), Duration::from_secs(18));
+	}
+
+	#[test]
+	fn slots_count_from_the_genesis_timestamp() {
+		let duration = SlotDuration::from_millis(6000);
+		let genesis = 1_600_000_003_000;
+
+		assert_eq!(timestamp_to_slot_with_offset(genesis, duration, genesis), Slot::from(0));
+		assert_eq!(timestamp_to_slot_with_offset(genesis + 5999, duration, genesis), Slot::from(0));
+		assert_eq!(timestamp_to_slot_with_offset(genesis + 6000, duration, genesis), Slot::from(1));
+		assert_eq!(slot_to_timestamp_with_offset(42.into(), duration, genesis), genesis + 252_000);
+		assert_eq!(timestamp_to_slot_with_offset(genesis - 1, duration, genesis), Slot::from(0));
+		// the naive slot is off by the slots passed before genesis
+		assert_ne!(timestamp_to_slot(genesis + 6000, duration), Slot::from(1));
+
+		assert_eq!(
+			timestamp_to_slot_with_offset(1234, duration, 0),
+			timestamp_to_slot(1234, duration),
+		);
+		assert_eq!(slot_to_timestamp_with_offset(u64::MAX.into(), duration, genesis), u64::MAX);
+	}
+
+	#[test]
+	fn time_is_read_from_the_time_source_if_given() {
+		let fixed: TimeSource = Arc::new(|| Duration::from_secs(42));
+		assert_eq!(time_now(Some(&fixed)), Duration::from_secs(42));
+
+		let system = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+		assert!(time_now(None) >= system);
+	}
+
+	#[test]
+	fn consecutive_proposal_overruns_warn_at_a_limited_rate() {
+		let budget = Duration::from_millis(1000);
+		let slow = Duration::from_millis(1500);
+		let start = Instant::now();
+		let mut overruns = ProposalOverruns::default();
+
+		assert_eq!(overruns.note(slow, budget, start), None);
+		assert_eq!(overruns.note(slow, budget, start), None);
+		assert_eq!(overruns.note(slow, budget, start), Some(3));
+		// rate limited
+		assert_eq!(overruns.note(slow, budget, start + Duration::from_secs(1)), None);
+		assert_eq!(
+			overruns.note(slow, budget, start + PROPOSAL_OVERRUN_WARN_INTERVAL),
+			Some(5),
+		);
+
+		// a timely proposal ends the streak
+		let later = start + 2 * PROPOSAL_OVERRUN_WARN_INTERVAL;
+		assert_eq!(overruns.note(budget, budget, later), None);
+		assert_eq!(overruns.note(slow, budget, later), None);
+		assert_eq!(overruns.note(slow, budget, later), None);
+		assert_eq!(overruns.note(slow, budget, later), Some(3));
+	}
+
+	#[test]
+	fn empty_authority_sets_are_rejected() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		let hash = Default::default();
+		let error = ensure_authorities::<Block, AuthorityId>(Vec::new(), hash)
+			.expect_err("An empty set is rejected");
+		assert!(error.to_string().contains("is empty"));
+
+		let authorities: Vec<AuthorityId> = vec![Keyring::Alice.public().into()];
+		assert_eq!(
+			ensure_authorities::<Block, _>(authorities.clone(), hash).ok(),
+			Some(authorities),
+		);
+	}
+
+	#[test]
+	fn seal_signing_payload_is_what_the_worker_signs() {
+		use substrate_test_runtime_client::runtime::Block;
+
+		let hash = sp_core::H256::repeat_byte(7);
+		let payload = seal_signing_payload::<Block>(&hash);
+		assert_eq!(payload, hash.as_bytes());
+		for mode in [CompatibilityMode::None, CompatibilityMode::UseInitializeBlock { until: 10 }] {
+			assert_eq!(seal_message::<Block>(1, 5.into(), &hash, &mode), payload);
+		}
+		let legacy = CompatibilityMode::LegacySealFormat { until: 10 };
+		assert_ne!(seal_message::<Block>(1, 5.into(), &hash, &legacy), payload);
+
+		// an external signature over the payload verifies like one of the keystore
+		let pair = Keyring::Alice.pair();
+		let signature = pair.sign(&payload);
+		let message = seal_message::<Block>(1, 5.into(), &hash, &CompatibilityMode::None);
+		assert!(sp_core::sr25519::Pair::verify(&signature, &message, &pair.public()));
+	}
+
+	#[test]
+	fn staged_compatibility_mode_selects_the_stage_by_block_number() {
+		let mode = CompatibilityMode::staged(vec![
+			(20..=29, StageBehavior::LegacySealFormat),
+			(0..=9, StageBehavior::UseInitializeBlock),
+		]);
+
+		assert_eq!(mode.behavior_at(&0), StageBehavior::UseInitializeBlock);
+		assert_eq!(mode.behavior_at(&9), StageBehavior::UseInitializeBlock);
+		assert_eq!(mode.behavior_at(&10), StageBehavior::None);
+		assert_eq!(mode.behavior_at(&20), StageBehavior::LegacySealFormat);
+		assert_eq!(mode.behavior_at(&29), StageBehavior::LegacySealFormat);
+		assert_eq!(mode.behavior_at(&30), StageBehavior::None);
+
+		let legacy = CompatibilityMode::LegacySealFormat { until: 5 };
+		assert_eq!(legacy.behavior_at(&4), StageBehavior::LegacySealFormat);
+		assert_eq!(legacy.behavior_at(&5), StageBehavior::None);
+	}
+
+	#[test]
+	#[should_panic(expected = "Overlapping compatibility mode stages")]
+	fn overlapping_compatibility_mode_stages_are_rejected() {
+		CompatibilityMode::staged(vec![
+			(0..=10, StageBehavior::UseInitializeBlock),
+			(10..=20, StageBehavior::LegacySealFormat),
+		]);
+	}
+}
@@ -0,0 +1,200 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Prometheus metrics reported by the Aura worker and verifier.
+
+use prometheus_endpoint::{
+	register, Counter, CounterVec, Gauge, Histogram, HistogramOpts, Opts, PrometheusError,
+	Registry, U64,
+};
+
+/// Metrics of the Aura worker.
+#[derive(Clone)]
+pub struct AuraMetrics {
+	/// Time from a slot being claimed until the authored block is sealed.
+	pub claim_to_seal: Histogram,
+	/// Authority set fetches retried after a failure.
+	pub authorities_retries: Counter<U64>,
+	/// The slot the worker is currently working on.
+	pub current_slot: Gauge<U64>,
+	/// Size of the authority set at the current slot.
+	pub authorities: Gauge<U64>,
+	/// Slots claimed by a local authority.
+	pub claimed_slots: Counter<U64>,
+	/// Blocks sealed in claimed slots. The ratio to [`Self::claimed_slots`] is the share of
+	/// claimed slots the worker actually authored a block in.
+	pub authored_blocks: Counter<U64>,
+	/// Time from starting to create the proposer until the block is proposed.
+	pub proposal_duration: Histogram,
+}
+
+impl AuraMetrics {
+	/// Register the metrics at the given registry.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			claim_to_seal: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"aura_claim_to_seal_seconds",
+						"Time from claiming a slot until the authored block is sealed",
+					)
+					.buckets(vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+				)?,
+				registry,
+			)?,
+			authorities_retries: register(
+				Counter::new(
+					"aura_authorities_retries_total",
+					"Number of authority set fetches retried after a failure",
+				)?,
+				registry,
+			)?,
+			current_slot: register(
+				Gauge::new("aura_current_slot", "The slot the worker is currently working on")?,
+				registry,
+			)?,
+			authorities: register(
+				Gauge::new("aura_authorities", "Size of the authority set at the current slot")?,
+				registry,
+			)?,
+			claimed_slots: register(
+				Counter::new(
+					"aura_claimed_slots_total",
+					"Number of slots claimed by a local authority",
+				)?,
+				registry,
+			)?,
+			authored_blocks: register(
+				Counter::new(
+					"aura_authored_blocks_total",
+					"Number of blocks sealed in claimed slots",
+				)?,
+				registry,
+			)?,
+			proposal_duration: register(
+				Histogram::with_opts(
+					HistogramOpts::new(
+						"aura_proposal_duration_seconds",
+						"Time from starting to create the proposer until the block is proposed",
+					)
+					.buckets(vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0]),
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Metrics of the Aura verifier.
+#[derive(Clone)]
+pub struct VerifierMetrics {
+	/// Blocks deferred for being from a future slot, bucketed by how many slots ahead they were.
+	pub deferred_blocks: CounterVec<U64>,
+	/// Equivocation reports dropped because the report channel was full or closed.
+	pub dropped_equivocation_reports: Counter<U64>,
+	/// Blocks rejected by the verifier, by the [`Error::reason`](crate::Error::reason) they
+	/// were rejected for. Deferred blocks are counted in [`Self::deferred_blocks`] only.
+	pub rejected_blocks: CounterVec<U64>,
+}
+
+impl VerifierMetrics {
+	/// Register the metrics at the given registry.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			deferred_blocks: register(
+				CounterVec::new(
+					Opts::new(
+						"aura_verifier_deferred_blocks_total",
+						"Number of blocks deferred for being from a future slot",
+					),
+					&["slots_ahead"],
+				)?,
+				registry,
+			)?,
+			dropped_equivocation_reports: register(
+				Counter::new(
+					"aura_verifier_equivocation_reports_dropped_total",
+					"Number of equivocation reports dropped because the channel was full or closed",
+				)?,
+				registry,
+			)?,
+			rejected_blocks: register(
+				CounterVec::new(
+					Opts::new(
+						"aura_verifier_rejected_blocks_total",
+						"Number of blocks rejected by the verifier, by reason",
+					),
+					&["reason"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	/// Note a block that was deferred for being `slots_ahead` slots in the future.
+	pub fn note_deferred(&self, slots_ahead: u64) {
+		self.deferred_blocks.with_label_values(&[deferred_bucket(slots_ahead)]).inc();
+	}
+
+	/// Note a block that was rejected for `reason`.
+	pub fn note_rejected(&self, reason: &str) {
+		self.rejected_blocks.with_label_values(&[reason]).inc();
+	}
+}
+
+/// The `slots_ahead` label of a block deferred for being `slots_ahead` slots in the future.
+fn deferred_bucket(slots_ahead: u64) -> &'static str {
+	match slots_ahead {
+		0..=1 => "1",
+		2..=4 => "2-4",
+		_ => "5+",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn deferred_blocks_are_bucketed_by_magnitude() {
+		assert_eq!(deferred_bucket(1), "1");
+		assert_eq!(deferred_bucket(2), "2-4");
+		assert_eq!(deferred_bucket(4), "2-4");
+		assert_eq!(deferred_bucket(5), "5+");
+		assert_eq!(deferred_bucket(u64::MAX), "5+");
+	}
+
+	#[test]
+	fn rejected_blocks_are_counted_by_reason() {
+		use crate::Error;
+		use substrate_test_runtime_client::runtime::Block;
+
+		let metrics = VerifierMetrics::register(&Registry::new()).expect("Registers metrics");
+		let rejected = |reason| metrics.rejected_blocks.with_label_values(&[reason]).get();
+
+		metrics.note_rejected(Error::<Block>::BadSignature(Default::default()).reason());
+		metrics.note_rejected(Error::<Block>::BadSignature(Default::default()).reason());
+		let too_far =
+			Error::<Block>::BlockTooFarInFuture { block_slot: 9.into(), now_slot: 1.into() };
+		metrics.note_rejected(too_far.reason());
+
+		assert_eq!(rejected("bad_signature"), 2);
+		assert_eq!(rejected("too_far_in_future"), 1);
+		assert_eq!(rejected("bad_seal"), 0);
+	}
+}
@@ -0,0 +1,435 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Authority sets mixing ed25519 and sr25519 keys, e.g. while a chain migrates between them.
+//!
+//! [`MultiAuthorityPair`] implements [`Pair`] over [`MultiAuthorityId`]s, as exposed by a runtime
+//! implementing `AuraApi<Block, MultiAuthorityId>`. The verifier, and so the
+//! [`import_queue`](crate::import_queue), verifies mixed sets when instantiated with it.
+//!
+//! The worker cannot be instantiated with it: it needs application keys of a single scheme, and
+//! the raw signatures of the keystore do not tell which scheme they were created with. Nodes
+//! authoring in a mixed set claim their slots with [`claim_slot_multi`] and seal their blocks
+//! with [`seal_multi`], which follow the same schedule as the worker.
+//!
+//! The pre-runtime digest is the same as for single scheme sets. The seal holds a SCALE encoded
+//! [`MultiAuthoritySignature`], whose variant records the scheme the block was signed with, and
+//! a seal only verifies if that is the scheme of the expected author's key.
+
+use crate::{
+	claim_slot_with_keystore, expected_slot_author, find_pre_digest_with_engine_id, import_queue,
+	is_secondary_slot, seal_message, AuthorityWeights, CompatibilityMode, Error,
+};
+use codec::{Decode, Encode};
+use sp_consensus_aura::{ed25519, sr25519};
+use sp_consensus_slots::Slot;
+use sp_core::crypto::{
+	ByteArray, CryptoType, CryptoTypePublicPair, Derive, DeriveJunction, KeyTypeId, Pair, Public,
+	SecretStringError,
+};
+use sp_keystore::SyncCryptoStore;
+use sp_runtime::{
+	traits::{Block as BlockT, Header, NumberFor},
+	ConsensusEngineId, DigestItem,
+};
+
+/// The key of an authority, of either supported scheme.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Encode, Decode)]
+pub enum MultiAuthorityId {
+	/// An ed25519 key.
+	Ed25519(ed25519::AuthorityId),
+	/// An sr25519 key.
+	Sr25519(sr25519::AuthorityId),
+}
+
+/// A seal signature, recording the scheme it was created with.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub enum MultiAuthoritySignature {
+	/// An ed25519 signature.
+	Ed25519(ed25519::AuthoritySignature),
+	/// An sr25519 signature.
+	Sr25519(sr25519::AuthoritySignature),
+}
+
+/// The key pair of an authority, of either supported scheme.
+///
+/// Pairs created from a seed or a phrase are sr25519 pairs.
+#[derive(Clone)]
+pub enum MultiAuthorityPair {
+	/// An ed25519 pair.
+	Ed25519(ed25519::AuthorityPair),
+	/// An sr25519 pair.
+	Sr25519(sr25519::AuthorityPair),
+}
+
+impl AsRef<[u8]> for MultiAuthorityId {
+	fn as_ref(&self) -> &[u8] {
+		match self {
+			Self::Ed25519(public) => public.as_ref(),
+			Self::Sr25519(public) => public.as_ref(),
+		}
+	}
+}
+
+impl AsMut<[u8]> for MultiAuthorityId {
+	fn as_mut(&mut self) -> &mut [u8] {
+		match self {
+			Self::Ed25519(public) => public.as_mut(),
+			Self::Sr25519(public) => public.as_mut(),
+		}
+	}
+}
+
+/// The scheme of a key cannot be told from its raw bytes, so this always fails.
+impl<'a> TryFrom<&'a [u8]> for MultiAuthorityId {
+	type Error = ();
+
+	fn try_from(_: &'a [u8]) -> Result<Self, ()> {
+		Err(())
+	}
+}
+
+impl ByteArray for MultiAuthorityId {
+	const LEN: usize = <sr25519::AuthorityId as ByteArray>::LEN;
+}
+
+impl Derive for MultiAuthorityId {}
+
+impl CryptoType for MultiAuthorityId {
+	type Pair = MultiAuthorityPair;
+}
+
+impl Public for MultiAuthorityId {
+	fn to_public_crypto_pair(&self) -> CryptoTypePublicPair {
+		match self {
+			Self::Ed25519(public) => public.to_public_crypto_pair(),
+			Self::Sr25519(public) => public.to_public_crypto_pair(),
+		}
+	}
+}
+
+impl AsRef<[u8]> for MultiAuthoritySignature {
+	fn as_ref(&self) -> &[u8] {
+		match self {
+			Self::Ed25519(signature) => signature.as_ref(),
+			Self::Sr25519(signature) => signature.as_ref(),
+		}
+	}
+}
+
+impl CryptoType for MultiAuthorityPair {
+	type Pair = Self;
+}
+
+impl Pair for MultiAuthorityPair {
+	type Public = MultiAuthorityId;
+	type Seed = [u8; 32];
+	type Signature = MultiAuthoritySignature;
+	type DeriveError = ();
+
+	fn generate_with_phrase(password: Option<&str>) -> (Self, String, Self::Seed) {
+		let (pair, phrase, seed) = sr25519::AuthorityPair::generate_with_phrase(password);
+		(Self::Sr25519(pair), phrase, seed)
+	}
+
+	fn from_phrase(
+		phrase: &str,
+		password: Option<&str>,
+	) -> Result<(Self, Self::Seed), SecretStringError> {
+		sr25519::AuthorityPair::from_phrase(phrase, password)
+			.map(|(pair, seed)| (Self::Sr25519(pair), seed))
+	}
+
+	fn derive<Iter: Iterator<Item = DeriveJunction>>(
+		&self,
+		path: Iter,
+		seed: Option<Self::Seed>,
+	) -> Result<(Self, Option<Self::Seed>), ()> {
+		match self {
+			Self::Ed25519(pair) => pair
+				.derive(path, seed)
+				.map(|(pair, seed)| (Self::Ed25519(pair), seed))
+				.map_err(drop),
+			Self::Sr25519(pair) => pair
+				.derive(path, seed)
+				.map(|(pair, seed)| (Self::Sr25519(pair), seed))
+				.map_err(drop),
+		}
+	}
+
+	fn from_seed(seed: &Self::Seed) -> Self {
+		Self::Sr25519(sr25519::AuthorityPair::from_seed(seed))
+	}
+
+	fn from_seed_slice(seed: &[u8]) -> Result<Self, SecretStringError> {
+		sr25519::AuthorityPair::from_seed_slice(seed).map(Self::Sr25519)
+	}
+
+	fn sign(&self, message: &[u8]) -> MultiAuthoritySignature {
+		match self {
+			Self::Ed25519(pair) => MultiAuthoritySignature::Ed25519(pair.sign(message)),
+			Self::Sr25519(pair) => MultiAuthoritySignature::Sr25519(pair.sign(message)),
+		}
+	}
+
+	fn verify<M: AsRef<[u8]>>(
+		signature: &MultiAuthoritySignature,
+		message: M,
+		public: &MultiAuthorityId,
+	) -> bool {
+		public.verify(signature, message.as_ref())
+	}
+
+	fn verify_weak<P: AsRef<[u8]>, M: AsRef<[u8]>>(
+		signature: &[u8],
+		message: M,
+		public: P,
+	) -> bool {
+		ed25519::AuthorityPair::verify_weak(signature, &message, &public) ||
+			sr25519::AuthorityPair::verify_weak(signature, &message, &public)
+	}
+
+	fn public(&self) -> MultiAuthorityId {
+		match self {
+			Self::Ed25519(pair) => MultiAuthorityId::Ed25519(pair.public()),
+			Self::Sr25519(pair) => MultiAuthorityId::Sr25519(pair.public()),
+		}
+	}
+
+	fn to_raw_vec(&self) -> Vec<u8> {
+		match self {
+			Self::Ed25519(pair) => pair.to_raw_vec(),
+			Self::Sr25519(pair) => pair.to_raw_vec(),
+		}
+	}
+}
+
+impl MultiAuthorityId {
+	/// Whether `signature` is a signature of `message` by this authority.
+	///
+	/// Signatures of another scheme than the one of the key never verify.
+	pub fn verify(&self, signature: &MultiAuthoritySignature, message: &[u8]) -> bool {
+		match (self, signature) {
+			(Self::Ed25519(public), MultiAuthoritySignature::Ed25519(signature)) =>
+				ed25519::AuthorityPair::verify(signature, message, public),
+			(Self::Sr25519(public), MultiAuthoritySignature::Sr25519(signature)) =>
+				sr25519::AuthorityPair::verify(signature, message, public),
+			_ => false,
+		}
+	}
+
+	/// Sign `message` with the private key of this authority held by the keystore under
+	/// `key_type`.
+	fn sign_with(
+		&self,
+		keystore: &dyn SyncCryptoStore,
+		key_type: KeyTypeId,
+		message: &[u8],
+	) -> Result<MultiAuthoritySignature, sp_consensus::Error> {
+		let public = self.to_raw_vec();
+		let signature =
+			SyncCryptoStore::sign_with(keystore, key_type, &self.to_public_crypto_pair(), message)
+				.map_err(|e| sp_consensus::Error::CannotSign(public.clone(), e.to_string()))?
+				.ok_or_else(|| {
+					sp_consensus::Error::CannotSign(
+						public.clone(),
+						"Could not find key in keystore.".into(),
+					)
+				})?;
+
+		let invalid = |signature: Vec<u8>| sp_consensus::Error::InvalidSignature(signature, public);
+		match self {
+			Self::Ed25519(_) => signature
+				.clone()
+				.try_into()
+				.map(MultiAuthoritySignature::Ed25519)
+				.map_err(|_| invalid(signature)),
+			Self::Sr25519(_) => signature
+				.clone()
+				.try_into()
+				.map(MultiAuthoritySignature::Sr25519)
+				.map_err(|_| invalid(signature)),
+		}
+	}
+}
+
+/// Claim `slot` if the keystore holds the key of its author under `key_type`, whichever scheme
+/// that key is of.
+///
+/// This is the claim of the worker, see [`BuildAuraWorkerParams`](crate::BuildAuraWorkerParams).
+pub fn claim_slot_multi(
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	slot: Slot,
+	authorities: &[MultiAuthorityId],
+	weights: Option<&AuthorityWeights>,
+) -> Option<MultiAuthorityId> {
+	claim_slot_with_keystore::<MultiAuthorityPair>(
+		keystore,
+		key_type,
+		slot,
+		authorities,
+		weights,
+		None,
+	)
+}
+
+/// Create the seal of `header`, claimed by `author`, with the key the keystore holds for it
+/// under `key_type`.
+///
+/// `header` is the header before sealing, carrying the pre-runtime digest issued under
+/// `engine_id`. The seal is to be pushed to it as the last digest item.
+pub fn seal_multi<B: BlockT>(
+	keystore: &dyn SyncCryptoStore,
+	key_type: KeyTypeId,
+	author: &MultiAuthorityId,
+	header: &B::Header,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+	engine_id: ConsensusEngineId,
+) -> Result<DigestItem, sp_consensus::Error> {
+	let slot = find_pre_digest_with_engine_id::<B>(header, engine_id)
+		.map_err(|e| sp_consensus::Error::ClientImport(e.to_string()))?;
+	let message = seal_message::<B>(*header.number(), slot, &header.hash(), compatibility_mode);
+	let signature = author.sign_with(keystore, key_type, &message)?;
+
+	Ok(DigestItem::Seal(engine_id, signature.encode()))
+}
+
+/// Check the seal of the sealed `header` against `authorities`, returning the slot of the header
+/// and its author.
+///
+/// These are the seal checks of the verifier instantiated with [`MultiAuthorityPair`].
+pub fn check_multi_seal<B: BlockT>(
+	header: B::Header,
+	authorities: &[MultiAuthorityId],
+	weights: Option<&AuthorityWeights>,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+	engine_id: ConsensusEngineId,
+) -> Result<(Slot, MultiAuthorityId), Error<B>> {
+	let secondary = is_secondary_slot::<B>(&header);
+	let slot = import_queue::check_seal::<B, MultiAuthorityPair>(
+		header,
+		authorities,
+		weights,
+		None,
+		compatibility_mode,
+		engine_id,
+	)?;
+	let author =
+		expected_slot_author::<MultiAuthorityPair>(slot, authorities, weights, None, secondary)
+			.expect("the seal is checked against the author of the slot; qed");
+
+	Ok((slot, author.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sc_keystore::LocalKeystore;
+	use sp_application_crypto::key_types::AURA;
+	use sp_consensus_aura::AURA_ENGINE_ID;
+	use sp_runtime::Digest;
+	use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+	#[test]
+	fn seals_are_created_and_checked_with_the_author_scheme() {
+		let keystore = LocalKeystore::in_memory();
+		let alice = SyncCryptoStore::ed25519_generate_new(&keystore, AURA, Some("//Alice"))
+			.expect("Generates a key");
+		let bob = SyncCryptoStore::sr25519_generate_new(&keystore, AURA, Some("//Bob"))
+			.expect("Generates a key");
+		let authorities = vec![
+			MultiAuthorityId::Ed25519(alice.into()),
+			MultiAuthorityId::Sr25519(bob.into()),
+		];
+		let mode = CompatibilityMode::None;
+		let header = |slot: u64| {
+			TestHeader::new(
+				1,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Digest {
+					logs: vec![DigestItem::PreRuntime(AURA_ENGINE_ID, Slot::from(slot).encode())],
+				},
+			)
+		};
+
+		for slot in 0..2u64 {
+			let author = claim_slot_multi(&keystore, AURA, slot.into(), &authorities, None)
+				.expect("The keystore holds both keys");
+			assert_eq!(author, authorities[slot as usize]);
+
+			let mut sealed = header(slot);
+			let seal =
+				seal_multi::<Block>(&keystore, AURA, &author, &sealed, &mode, AURA_ENGINE_ID)
+					.expect("Seals the header");
+			sealed.digest_mut().push(seal);
+			assert_eq!(
+				check_multi_seal::<Block>(sealed, &authorities, None, &mode, AURA_ENGINE_ID).ok(),
+				Some((slot.into(), author)),
+			);
+		}
+
+		// slot 1 is Bob's, so a seal by Alice's ed25519 key does not verify
+		let mut sealed = header(1);
+		let seal =
+			seal_multi::<Block>(&keystore, AURA, &authorities[0], &sealed, &mode, AURA_ENGINE_ID)
+				.expect("Seals the header");
+		sealed.digest_mut().push(seal);
+		assert!(matches!(
+			check_multi_seal::<Block>(sealed, &authorities, None, &mode, AURA_ENGINE_ID),
+			Err(Error::BadSignature(_)),
+		));
+	}
+
+	#[test]
+	fn the_verifier_checks_mixed_sets_with_the_multi_pair() {
+		let alice = MultiAuthorityPair::Ed25519(
+			ed25519::AuthorityPair::from_string("//Alice", None).expect("Valid seed"),
+		);
+		let bob = MultiAuthorityPair::from_string("//Bob", None).expect("Valid seed");
+		assert!(matches!(bob.public(), MultiAuthorityId::Sr25519(_)));
+		let authorities = vec![alice.public(), bob.public()];
+		let sealed = |pair: &MultiAuthorityPair, slot: u64| {
+			let mut header = TestHeader::new(
+				1,
+				Default::default(),
+				Default::default(),
+				Default::default(),
+				Digest {
+					logs: vec![DigestItem::PreRuntime(AURA_ENGINE_ID, Slot::from(slot).encode())],
+				},
+			);
+			let signature = pair.sign(header.hash().as_ref());
+			header.digest_mut().push(DigestItem::Seal(AURA_ENGINE_ID, signature.encode()));
+			header
+		};
+		let check = |header: &TestHeader| {
+			crate::check_seal_with_engine_id::<MultiAuthorityPair, Block>(
+				header,
+				&authorities,
+				AURA_ENGINE_ID,
+			)
+		};
+
+		assert!(check(&sealed(&alice, 4)).is_ok());
+		assert!(check(&sealed(&bob, 5)).is_ok());
+		assert!(matches!(check(&sealed(&alice, 5)), Err(Error::BadSignature(_))));
+	}
+}
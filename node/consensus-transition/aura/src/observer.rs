@@ -0,0 +1,156 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Verification-only Aura for observer nodes, which import blocks but never author.
+//!
+//! An [`AuraObserver`] checks the seal and the inherents of every block exactly like the
+//! verifier of an authoring node, without a keystore, proposer or slot worker. It is a
+//! [`Verifier`], so it plugs into a [`BasicQueue`](sc_consensus::import_queue::BasicQueue) in
+//! place of the [`AuraVerifier`] built by [`build_verifier`].
+
+use crate::{
+	build_verifier, AuraVerifier, AuthorityId, AuthorityWeights, BuildVerifierParams,
+	CheckForEquivocation, CompatibilityMode, OfflineAuthorities,
+};
+use codec::{Decode, Encode};
+use prometheus_endpoint::Registry;
+use sc_client_api::{backend::AuxStore, BlockOf};
+use sc_consensus::{import_queue::Verifier, BlockImportParams};
+use sc_consensus_slots::InherentDataProviderExt;
+use sc_telemetry::TelemetryHandle;
+use sp_api::{ApiExt, ProvideRuntimeApi};
+use sp_block_builder::BlockBuilder as BlockBuilderApi;
+use sp_blockchain::well_known_cache_keys::Id as CacheKeyId;
+use sp_consensus::AlwaysCanAuthor;
+use sp_consensus_aura::AuraApi;
+use sp_core::crypto::Pair;
+use sp_inherents::CreateInherentDataProviders;
+use sp_runtime::{
+	traits::{Block as BlockT, Header, NumberFor},
+	ConsensusEngineId,
+};
+use std::{fmt::Debug, hash::Hash, sync::Arc};
+
+/// Parameters of [`build_observer`].
+pub struct BuildObserverParams<C, CIDP, N, A> {
+	/// The client to interact with the chain.
+	pub client: Arc<C>,
+	/// Something that can create the inherent data providers.
+	pub create_inherent_data_providers: CIDP,
+	/// Telemetry instance used to report telemetry metrics.
+	pub telemetry: Option<TelemetryHandle>,
+	/// Compatibility mode that should be used.
+	///
+	/// If in doubt, use `Default::default()`.
+	pub compatibility_mode: CompatibilityMode<N>,
+	/// The prometheus registry the verifier metrics are registered at.
+	pub registry: Option<Registry>,
+	/// See [`BuildVerifierParams::authority_weights`].
+	pub authority_weights: Option<AuthorityWeights>,
+	/// See [`BuildVerifierParams::offline_authorities`].
+	pub offline_authorities: Option<OfflineAuthorities<A>>,
+	/// See [`BuildVerifierParams::accept_secondary_slots`].
+	pub accept_secondary_slots: bool,
+	/// See [`BuildVerifierParams::max_future_slots`].
+	pub max_future_slots: u64,
+	/// See [`BuildVerifierParams::engine_id`].
+	pub engine_id: ConsensusEngineId,
+	/// See [`BuildVerifierParams::enforce_monotonic_slots`].
+	pub enforce_monotonic_slots: bool,
+	/// See [`BuildVerifierParams::genesis_timestamp`].
+	pub genesis_timestamp: u64,
+}
+
+/// Verifier of an observer node, see the [module docs](self).
+///
+/// Built by [`build_observer`].
+pub struct AuraObserver<C, P: Pair, CIDP, N, H: Header> {
+	verifier: AuraVerifier<C, P, AlwaysCanAuthor, CIDP, N, H>,
+}
+
+/// Build the [`AuraObserver`] of an observer node.
+///
+/// The inherents of every block are checked, regardless of the native runtime version, as there
+/// is no authoring to fall back to. Equivocations are not checked for, as an observer has no use
+/// for the reports.
+pub fn build_observer<P, C, CIDP, N, H>(
+	BuildObserverParams {
+		client,
+		create_inherent_data_providers,
+		telemetry,
+		compatibility_mode,
+		registry,
+		authority_weights,
+		offline_authorities,
+		accept_secondary_slots,
+		max_future_slots,
+		engine_id,
+		enforce_monotonic_slots,
+		genesis_timestamp,
+	}: BuildObserverParams<C, CIDP, N, AuthorityId<P>>,
+) -> AuraObserver<C, P, CIDP, N, H>
+where
+	P: Pair,
+	H: Header,
+{
+	let verifier = build_verifier::<P, _, _, _, _, _>(BuildVerifierParams {
+		client,
+		create_inherent_data_providers,
+		can_author_with: AlwaysCanAuthor,
+		check_for_equivocation: CheckForEquivocation::No,
+		telemetry,
+		compatibility_mode,
+		use_consensus_log_authorities: false,
+		registry,
+		handover_window: None,
+		check_timestamp_slot_consistency: false,
+		slot_duration_schedule: Vec::new(),
+		equivocation_sender: None,
+		authority_weights,
+		on_equivocation: None,
+		accept_secondary_slots,
+		equivocation_window_slots: 0,
+		offline_authorities,
+		max_future_slots,
+		engine_id,
+		enforce_monotonic_slots,
+		verify_seals_only: false,
+		genesis_timestamp,
+	});
+
+	AuraObserver { verifier }
+}
+
+#[async_trait::async_trait]
+impl<B: BlockT, C, P, CIDP> Verifier<B> for AuraObserver<C, P, CIDP, NumberFor<B>, B::Header>
+where
+	C: ProvideRuntimeApi<B> + Send + Sync + AuxStore + BlockOf,
+	C::Api: BlockBuilderApi<B> + AuraApi<B, AuthorityId<P>> + ApiExt<B>,
+	P: Pair + Send + Sync + 'static,
+	P::Public: Send + Sync + Hash + Eq + Clone + Decode + Encode + Debug + 'static,
+	P::Signature: Encode + Decode,
+	CIDP: CreateInherentDataProviders<B, ()> + Send + Sync,
+	CIDP::InherentDataProviders: InherentDataProviderExt + Send + Sync,
+{
+	async fn verify(
+		&mut self,
+		block: BlockImportParams<B, ()>,
+	) -> Result<(BlockImportParams<B, ()>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String> {
+		self.verifier.verify(block).await
+	}
+}
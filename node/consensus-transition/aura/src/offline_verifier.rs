@@ -0,0 +1,203 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Seal verification against a trusted authority set, without a runtime API client.
+//!
+//! An [`OfflineVerifier`] checks the seals of blocks against authority sets it is given up
+//! front, e.g. a snapshot taken from a synced node, instead of reading them through `AuraApi`.
+//! This allows verifying exported blocks without a synced node. Only the seals are checked: the
+//! inherents and the slot against the current time are not, and neither are authority weights
+//! nor offline authorities taken into account.
+
+use crate::{import_queue::check_seal, AuthorityId, CompatibilityMode, Error};
+use codec::Codec;
+use log::trace;
+use sc_consensus::{
+	block_import::{BlockImportParams, ForkChoiceStrategy},
+	import_queue::Verifier,
+};
+use sp_blockchain::well_known_cache_keys::Id as CacheKeyId;
+use sp_consensus_slots::Slot;
+use sp_core::crypto::Pair;
+use sp_runtime::{
+	traits::{Block as BlockT, Header},
+	ConsensusEngineId,
+};
+use std::sync::Arc;
+
+/// The trusted authority sets an [`OfflineVerifier`] checks seals against.
+pub enum TrustedAuthorities<H, A> {
+	/// The same set sealed every block.
+	Static(Vec<A>),
+	/// Resolves the set that sealed a block from its header, i.e. the set at its parent, or
+	/// `None` if it is not known.
+	PerBlock(Arc<dyn Fn(&H) -> Option<Vec<A>> + Send + Sync>),
+}
+
+/// Verifier checking seals against [`TrustedAuthorities`], see the [module docs](self).
+///
+/// Built by [`build_verifier_with_authorities`].
+pub struct OfflineVerifier<P: Pair, H: Header> {
+	authorities: TrustedAuthorities<H, AuthorityId<P>>,
+	compatibility_mode: CompatibilityMode<H::Number>,
+	engine_id: ConsensusEngineId,
+}
+
+/// Build an [`OfflineVerifier`] checking the seals issued under `engine_id` against
+/// `authorities`.
+///
+/// `compatibility_mode` has to match the one the blocks were authored with, as it determines
+/// the message their seals sign.
+pub fn build_verifier_with_authorities<P: Pair, H: Header>(
+	authorities: TrustedAuthorities<H, AuthorityId<P>>,
+	compatibility_mode: CompatibilityMode<H::Number>,
+	engine_id: ConsensusEngineId,
+) -> OfflineVerifier<P, H> {
+	OfflineVerifier { authorities, compatibility_mode, engine_id }
+}
+
+impl<P, H> OfflineVerifier<P, H>
+where
+	P: Pair,
+	P::Signature: Codec,
+	H: Header,
+{
+	/// Check the seal of the sealed `header`, returning its slot.
+	///
+	/// Fails with [`Error::UnknownAuthorities`] if the authority set of the header cannot be
+	/// resolved, and with [`Error::NoAuthorities`] if it is empty, as no author can be derived
+	/// from the slot of the header then.
+	pub fn check_header<B: BlockT<Header = H>>(&self, header: &H) -> Result<Slot, Error<B>> {
+		let resolved;
+		let authorities = match &self.authorities {
+			TrustedAuthorities::Static(authorities) => authorities,
+			TrustedAuthorities::PerBlock(resolve) => {
+				resolved = resolve(header).ok_or_else(|| Error::UnknownAuthorities(header.hash()))?;
+				&resolved
+			},
+		};
+		if authorities.is_empty() {
+			return Err(Error::NoAuthorities(*header.parent_hash()))
+		}
+
+		check_seal::<B, P>(
+			header.clone(),
+			authorities,
+			None,
+			None,
+			&self.compatibility_mode,
+			self.engine_id,
+		)
+	}
+}
+
+#[async_trait::async_trait]
+impl<B, P> Verifier<B> for OfflineVerifier<P, B::Header>
+where
+	B: BlockT,
+	P: Pair + Send + Sync + 'static,
+	P::Signature: Codec,
+{
+	async fn verify(
+		&mut self,
+		mut block: BlockImportParams<B, ()>,
+	) -> Result<(BlockImportParams<B, ()>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String> {
+		let hash = block.header.hash();
+		let slot = self.check_header::<B>(&block.header)?;
+		let seal = block.header.digest_mut().pop().expect("the seal was checked above; qed");
+		trace!(target: "aura", "Checked the seal of {:?} in slot {}; importing.", hash, slot);
+
+		block.post_digests.push(seal);
+		block.fork_choice = Some(ForkChoiceStrategy::LongestChain);
+		block.post_hash = Some(hash);
+		Ok((block, None))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_consensus_aura::{
+		digests::CompatibleDigestItem,
+		sr25519::{AuthorityPair, AuthoritySignature},
+	};
+	use sp_keyring::Sr25519Keyring;
+	use sp_runtime::{Digest, DigestItem};
+	use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+	fn sealed_header(number: u64, slot: u64, keyring: Sr25519Keyring) -> TestHeader {
+		let pair = AuthorityPair::from_string(&keyring.to_seed(), None).expect("Valid seed");
+		let pre_digest =
+			<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(slot.into());
+		let mut header = TestHeader::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest { logs: vec![pre_digest] },
+		);
+		let signature = pair.sign(header.hash().as_ref());
+		header
+			.digest_mut()
+			.push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature));
+		header
+	}
+
+	#[test]
+	fn seals_are_checked_against_the_trusted_authorities() {
+		let authorities =
+			vec![Sr25519Keyring::Alice.public().into(), Sr25519Keyring::Bob.public().into()];
+		let verifier = build_verifier_with_authorities::<AuthorityPair, TestHeader>(
+			TrustedAuthorities::Static(authorities.clone()),
+			CompatibilityMode::None,
+			crate::AURA_ENGINE_ID,
+		);
+		let check = |header: &TestHeader| verifier.check_header::<Block>(header);
+
+		assert_eq!(check(&sealed_header(1, 4, Sr25519Keyring::Alice)).ok(), Some(4.into()));
+		assert_eq!(check(&sealed_header(1, 5, Sr25519Keyring::Bob)).ok(), Some(5.into()));
+		assert!(matches!(
+			check(&sealed_header(1, 5, Sr25519Keyring::Alice)),
+			Err(Error::BadSignature(_)),
+		));
+
+		// the set of the first block is unknown, the second has no authorities at all
+		let verifier = build_verifier_with_authorities::<AuthorityPair, TestHeader>(
+			TrustedAuthorities::PerBlock(Arc::new(move |header: &TestHeader| {
+				match header.number {
+					1 => None,
+					2 => Some(Vec::new()),
+					_ => Some(authorities.clone()),
+				}
+			})),
+			CompatibilityMode::None,
+			crate::AURA_ENGINE_ID,
+		);
+		let check = |header: &TestHeader| verifier.check_header::<Block>(header);
+
+		assert!(matches!(
+			check(&sealed_header(1, 4, Sr25519Keyring::Alice)),
+			Err(Error::UnknownAuthorities(_)),
+		));
+		assert!(matches!(
+			check(&sealed_header(2, 4, Sr25519Keyring::Alice)),
+			Err(Error::NoAuthorities(_)),
+		));
+		assert_eq!(check(&sealed_header(3, 4, Sr25519Keyring::Alice)).ok(), Some(4.into()));
+	}
+}
@@ -0,0 +1,1011 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Builders of [`StartAuraParams`], [`BuildAuraWorkerParams`] and [`ImportQueueParams`].
+
+use crate::{
+	AuraApiAuthorities, AuraControl, AuthoredBlocks, AuthoritiesPreloader, AuthorityWeights,
+	BuildAuraWorkerParams, CheckForEquivocation, CompatibilityMode, DeadlineAdjuster,
+	ImportQueueParams, OfflineAuthorities, PreSealHook, ShouldAuthor, SkipEmptyConfig,
+	SlotDuration, SlotDurationChanges, SlotLenienceType, SlotProportion, StartAuraParams,
+	TelemetryLevel, TimeSource, TransactionCountHint, AURA_ENGINE_ID,
+};
+use futures::channel::mpsc;
+use prometheus_endpoint::Registry;
+use sc_consensus::{import_queue::BoxJustificationImport, ForkChoiceStrategy};
+use sc_telemetry::TelemetryHandle;
+use sp_application_crypto::key_types::AURA;
+use sp_consensus::BlockOrigin;
+use sp_consensus_slots::Slot;
+use sp_core::crypto::KeyTypeId;
+use sp_keystore::SyncCryptoStorePtr;
+use sp_runtime::{
+	traits::{Block as BlockT, NumberFor},
+	ConsensusEngineId,
+};
+use std::{sync::Arc, time::Duration};
+
+/// Builder of [`StartAuraParams`], with defaults for all the optional parameters.
+///
+/// The parameters without a sensible default are taken by [`StartAuraParamsBuilder::new`]. The
+/// defaults are those of a plain Aura authority: no forced authoring, no backoff, proposing for
+/// two thirds of the slot, no compatibility mode and none of the optional worker features.
+pub struct StartAuraParamsBuilder<C, SC, I, PF, SO, L, CIDP, BS, CAW, N, A, B: BlockT> {
+	params: StartAuraParams<C, SC, I, PF, SO, L, CIDP, BS, CAW, N, A, B>,
+}
+
+impl<C, SC, I, PF, SO, L, CIDP, BS, CAW, N, A, B: BlockT>
+	StartAuraParamsBuilder<C, SC, I, PF, SO, L, CIDP, BS, CAW, N, A, B>
+{
+	/// Create a builder from the required parameters.
+	pub fn new(
+		slot_duration: SlotDuration,
+		client: Arc<C>,
+		select_chain: SC,
+		block_import: I,
+		proposer_factory: PF,
+		sync_oracle: SO,
+		justification_sync_link: L,
+		create_inherent_data_providers: CIDP,
+		keystore: SyncCryptoStorePtr,
+		can_author_with: CAW,
+	) -> Self {
+		Self {
+			params: StartAuraParams {
+				slot_duration,
+				client,
+				select_chain,
+				block_import,
+				proposer_factory,
+				sync_oracle,
+				justification_sync_link,
+				create_inherent_data_providers,
+				force_authoring: false,
+				backoff_authoring_blocks: None,
+				keystore,
+				can_author_with,
+				block_proposal_slot_portion: SlotProportion::new(2f32 / 3f32),
+				max_block_proposal_slot_portion: None,
+				telemetry: None,
+				compatibility_mode: CompatibilityMode::None,
+				prometheus_registry: None,
+				json_events: false,
+				authorities_preloader: None,
+				max_head_slot_lag: None,
+				skip_slots: None,
+				self_verify_before_import: false,
+				min_inter_block_time: None,
+				authorities_retry: false,
+				max_tx_per_lenient_block: None,
+				transaction_count_hint: None,
+				authorities_cache_capacity: None,
+				authority_weights: None,
+				slot_lenience_type: SlotLenienceType::Exponential,
+				dynamic_slot_duration: false,
+				fork_choice: ForkChoiceStrategy::LongestChain,
+				secondary_slot_delay: None,
+				async_keystore: false,
+				deadline_adjuster: None,
+				pre_seal_hook: None,
+				offline_authorities: None,
+				engine_id: AURA_ENGINE_ID,
+				authored_blocks: None,
+				time_source: None,
+				inherent_retry: false,
+				authored_block_origin: BlockOrigin::Own,
+				max_extrinsics: None,
+				slot_tiebreak_fork_choice: false,
+				authority_provider: AuraApiAuthorities,
+				retry_self_import: false,
+				authorities_lookahead: None,
+				should_author: None,
+				include_authority_index: false,
+				max_reorg_depth: None,
+				telemetry_level: TelemetryLevel::Verbose,
+				skip_empty_blocks: None,
+				key_type: AURA,
+				max_propose_retries: 0,
+				clock_drift_threshold: None,
+			},
+		}
+	}
+
+	/// Set [`StartAuraParams::force_authoring`].
+	pub fn force_authoring(mut self, force_authoring: bool) -> Self {
+		self.params.force_authoring = force_authoring;
+		self
+	}
+
+	/// Set [`StartAuraParams::backoff_authoring_blocks`].
+	pub fn backoff_authoring_blocks(mut self, backoff_authoring_blocks: BS) -> Self {
+		self.params.backoff_authoring_blocks = Some(backoff_authoring_blocks);
+		self
+	}
+
+	/// Set [`StartAuraParams::block_proposal_slot_portion`].
+	pub fn block_proposal_slot_portion(
+		mut self,
+		block_proposal_slot_portion: SlotProportion,
+	) -> Self {
+		self.params.block_proposal_slot_portion = block_proposal_slot_portion;
+		self
+	}
+
+	/// Set [`StartAuraParams::max_block_proposal_slot_portion`].
+	pub fn max_block_proposal_slot_portion(
+		mut self,
+		max_block_proposal_slot_portion: SlotProportion,
+	) -> Self {
+		self.params.max_block_proposal_slot_portion = Some(max_block_proposal_slot_portion);
+		self
+	}
+
+	/// Set [`StartAuraParams::telemetry`].
+	pub fn telemetry(mut self, telemetry: TelemetryHandle) -> Self {
+		self.params.telemetry = Some(telemetry);
+		self
+	}
+
+	/// Set [`StartAuraParams::compatibility_mode`].
+	pub fn compatibility_mode(mut self, compatibility_mode: CompatibilityMode<N>) -> Self {
+		self.params.compatibility_mode = compatibility_mode;
+		self
+	}
+
+	/// Set [`StartAuraParams::prometheus_registry`].
+	pub fn prometheus_registry(mut self, prometheus_registry: Registry) -> Self {
+		self.params.prometheus_registry = Some(prometheus_registry);
+		self
+	}
+
+	/// Set [`StartAuraParams::json_events`].
+	pub fn json_events(mut self, json_events: bool) -> Self {
+		self.params.json_events = json_events;
+		self
+	}
+
+	/// Set [`StartAuraParams::authorities_preloader`].
+	pub fn authorities_preloader(
+		mut self,
+		authorities_preloader: AuthoritiesPreloader<A, N>,
+	) -> Self {
+		self.params.authorities_preloader = Some(authorities_preloader);
+		self
+	}
+
+	/// Set [`StartAuraParams::max_head_slot_lag`].
+	pub fn max_head_slot_lag(mut self, max_head_slot_lag: u64) -> Self {
+		self.params.max_head_slot_lag = Some(max_head_slot_lag);
+		self
+	}
+
+	/// Set [`StartAuraParams::skip_slots`].
+	pub fn skip_slots(mut self, skip_slots: Box<dyn Fn(Slot) -> bool + Send + Sync>) -> Self {
+		self.params.skip_slots = Some(skip_slots);
+		self
+	}
+
+	/// Set [`StartAuraParams::self_verify_before_import`].
+	pub fn self_verify_before_import(mut self, self_verify_before_import: bool) -> Self {
+		self.params.self_verify_before_import = self_verify_before_import;
+		self
+	}
+
+	/// Set [`StartAuraParams::min_inter_block_time`].
+	pub fn min_inter_block_time(mut self, min_inter_block_time: Duration) -> Self {
+		self.params.min_inter_block_time = Some(min_inter_block_time);
+		self
+	}
+
+	/// Set [`StartAuraParams::authorities_retry`].
+	pub fn authorities_retry(mut self, authorities_retry: bool) -> Self {
+		self.params.authorities_retry = authorities_retry;
+		self
+	}
+
+	/// Set [`StartAuraParams::max_tx_per_lenient_block`].
+	pub fn max_tx_per_lenient_block(mut self, max_tx_per_lenient_block: usize) -> Self {
+		self.params.max_tx_per_lenient_block = Some(max_tx_per_lenient_block);
+		self
+	}
+
+	/// Set [`StartAuraParams::transaction_count_hint`].
+	pub fn transaction_count_hint(mut self, transaction_count_hint: TransactionCountHint) -> Self {
+		self.params.transaction_count_hint = Some(transaction_count_hint);
+		self
+	}
+
+	/// Set [`StartAuraParams::authorities_cache_capacity`].
+	pub fn authorities_cache_capacity(mut self, authorities_cache_capacity: usize) -> Self {
+		self.params.authorities_cache_capacity = Some(authorities_cache_capacity);
+		self
+	}
+
+	/// Set [`StartAuraParams::authority_weights`].
+	pub fn authority_weights(mut self, authority_weights: AuthorityWeights) -> Self {
+		self.params.authority_weights = Some(authority_weights);
+		self
+	}
+
+	/// Set [`StartAuraParams::slot_lenience_type`].
+	pub fn slot_lenience_type(mut self, slot_lenience_type: SlotLenienceType) -> Self {
+		self.params.slot_lenience_type = slot_lenience_type;
+		self
+	}
+
+	/// Set [`StartAuraParams::dynamic_slot_duration`].
+	pub fn dynamic_slot_duration(mut self, dynamic_slot_duration: bool) -> Self {
+		self.params.dynamic_slot_duration = dynamic_slot_duration;
+		self
+	}
+
+	/// Set [`StartAuraParams::fork_choice`].
+	pub fn fork_choice(mut self, fork_choice: ForkChoiceStrategy) -> Self {
+		self.params.fork_choice = fork_choice;
+		self
+	}
+
+	/// Set [`StartAuraParams::secondary_slot_delay`].
+	pub fn secondary_slot_delay(mut self, secondary_slot_delay: SlotProportion) -> Self {
+		self.params.secondary_slot_delay = Some(secondary_slot_delay);
+		self
+	}
+
+	/// Set [`StartAuraParams::async_keystore`].
+	pub fn async_keystore(mut self, async_keystore: bool) -> Self {
+		self.params.async_keystore = async_keystore;
+		self
+	}
+
+	/// Set [`StartAuraParams::deadline_adjuster`].
+	pub fn deadline_adjuster(mut self, deadline_adjuster: DeadlineAdjuster<B>) -> Self {
+		self.params.deadline_adjuster = Some(deadline_adjuster);
+		self
+	}
+
+	/// Set [`StartAuraParams::pre_seal_hook`].
+	pub fn pre_seal_hook(mut self, pre_seal_hook: PreSealHook<B>) -> Self {
+		self.params.pre_seal_hook = Some(pre_seal_hook);
+		self
+	}
+
+	/// Set [`StartAuraParams::offline_authorities`].
+	pub fn offline_authorities(mut self, offline_authorities: OfflineAuthorities<A>) -> Self {
+		self.params.offline_authorities = Some(offline_authorities);
+		self
+	}
+
+	/// Set [`StartAuraParams::engine_id`].
+	pub fn engine_id(mut self, engine_id: ConsensusEngineId) -> Self {
+		self.params.engine_id = engine_id;
+		self
+	}
+
+	/// Set [`StartAuraParams::authored_blocks`].
+	pub fn authored_blocks(mut self, authored_blocks: AuthoredBlocks<B::Hash>) -> Self {
+		self.params.authored_blocks = Some(authored_blocks);
+		self
+	}
+
+	/// Set [`StartAuraParams::time_source`].
+	pub fn time_source(mut self, time_source: TimeSource) -> Self {
+		self.params.time_source = Some(time_source);
+		self
+	}
+
+	/// Set [`StartAuraParams::inherent_retry`].
+	pub fn inherent_retry(mut self, inherent_retry: bool) -> Self {
+		self.params.inherent_retry = inherent_retry;
+		self
+	}
+
+	/// Set [`StartAuraParams::authored_block_origin`].
+	pub fn authored_block_origin(mut self, authored_block_origin: BlockOrigin) -> Self {
+		self.params.authored_block_origin = authored_block_origin;
+		self
+	}
+
+	/// Set [`StartAuraParams::max_extrinsics`].
+	pub fn max_extrinsics(mut self, max_extrinsics: usize) -> Self {
+		self.params.max_extrinsics = Some(max_extrinsics);
+		self
+	}
+
+	/// Set [`StartAuraParams::slot_tiebreak_fork_choice`].
+	pub fn slot_tiebreak_fork_choice(mut self, slot_tiebreak_fork_choice: bool) -> Self {
+		self.params.slot_tiebreak_fork_choice = slot_tiebreak_fork_choice;
+		self
+	}
+
+	/// Set [`StartAuraParams::retry_self_import`].
+	pub fn retry_self_import(mut self, retry_self_import: bool) -> Self {
+		self.params.retry_self_import = retry_self_import;
+		self
+	}
+
+	/// Set [`StartAuraParams::authorities_lookahead`].
+	pub fn authorities_lookahead(mut self, authorities_lookahead: u32) -> Self {
+		self.params.authorities_lookahead = Some(authorities_lookahead);
+		self
+	}
+
+	/// Set [`StartAuraParams::should_author`].
+	pub fn should_author(mut self, should_author: ShouldAuthor) -> Self {
+		self.params.should_author = Some(should_author);
+		self
+	}
+
+	/// Set [`StartAuraParams::include_authority_index`].
+	pub fn include_authority_index(mut self, include_authority_index: bool) -> Self {
+		self.params.include_authority_index = include_authority_index;
+		self
+	}
+
+	/// Set [`StartAuraParams::max_reorg_depth`].
+	pub fn max_reorg_depth(mut self, max_reorg_depth: u32) -> Self {
+		self.params.max_reorg_depth = Some(max_reorg_depth);
+		self
+	}
+
+	/// Set [`StartAuraParams::telemetry_level`].
+	pub fn telemetry_level(mut self, telemetry_level: TelemetryLevel) -> Self {
+		self.params.telemetry_level = telemetry_level;
+		self
+	}
+
+	/// Set [`StartAuraParams::skip_empty_blocks`].
+	pub fn skip_empty_blocks(mut self, skip_empty_blocks: SkipEmptyConfig) -> Self {
+		self.params.skip_empty_blocks = Some(skip_empty_blocks);
+		self
+	}
+
+	/// Set [`StartAuraParams::key_type`].
+	pub fn key_type(mut self, key_type: KeyTypeId) -> Self {
+		self.params.key_type = key_type;
+		self
+	}
+
+	/// Set [`StartAuraParams::max_propose_retries`].
+	pub fn max_propose_retries(mut self, max_propose_retries: u32) -> Self {
+		self.params.max_propose_retries = max_propose_retries;
+		self
+	}
+
+	/// Set [`StartAuraParams::clock_drift_threshold`].
+	pub fn clock_drift_threshold(mut self, clock_drift_threshold: u64) -> Self {
+		self.params.clock_drift_threshold = Some(clock_drift_threshold);
+		self
+	}
+
+	/// Build the parameters.
+	pub fn build(self) -> StartAuraParams<C, SC, I, PF, SO, L, CIDP, BS, CAW, N, A, B> {
+		self.params
+	}
+}
+
+/// Builder of [`BuildAuraWorkerParams`], with defaults for all the optional parameters.
+///
+/// The defaults are those of [`StartAuraParamsBuilder`], and the worker is neither controlled
+/// through an [`AuraControl`] nor reports changes of the slot duration.
+pub struct BuildAuraWorkerParamsBuilder<C, I, PF, SO, L, BS, N, A, B: BlockT> {
+	params: BuildAuraWorkerParams<C, I, PF, SO, L, BS, N, A, B>,
+}
+
+impl<C, I, PF, SO, L, BS, N, A, B: BlockT>
+	BuildAuraWorkerParamsBuilder<C, I, PF, SO, L, BS, N, A, B>
+{
+	/// Create a builder from the required parameters.
+	pub fn new(
+		client: Arc<C>,
+		block_import: I,
+		proposer_factory: PF,
+		sync_oracle: SO,
+		justification_sync_link: L,
+		keystore: SyncCryptoStorePtr,
+	) -> Self {
+		Self {
+			params: BuildAuraWorkerParams {
+				client,
+				block_import,
+				proposer_factory,
+				sync_oracle,
+				justification_sync_link,
+				force_authoring: false,
+				backoff_authoring_blocks: None,
+				keystore,
+				block_proposal_slot_portion: SlotProportion::new(2f32 / 3f32),
+				max_block_proposal_slot_portion: None,
+				telemetry: None,
+				compatibility_mode: CompatibilityMode::None,
+				prometheus_registry: None,
+				json_events: false,
+				authorities_preloader: None,
+				max_head_slot_lag: None,
+				skip_slots: None,
+				self_verify_before_import: false,
+				min_inter_block_time: None,
+				authorities_retry: false,
+				max_tx_per_lenient_block: None,
+				transaction_count_hint: None,
+				authorities_cache_capacity: None,
+				authority_weights: None,
+				control: None,
+				slot_lenience_type: SlotLenienceType::Exponential,
+				slot_duration_changes: None,
+				fork_choice: ForkChoiceStrategy::LongestChain,
+				secondary_slot_delay: None,
+				async_keystore: false,
+				deadline_adjuster: None,
+				pre_seal_hook: None,
+				offline_authorities: None,
+				engine_id: AURA_ENGINE_ID,
+				authored_blocks: None,
+				time_source: None,
+				authored_block_origin: BlockOrigin::Own,
+				max_extrinsics: None,
+				slot_tiebreak_fork_choice: false,
+				authority_provider: AuraApiAuthorities,
+				retry_self_import: false,
+				should_author: None,
+				include_authority_index: false,
+				max_reorg_depth: None,
+				telemetry_level: TelemetryLevel::Verbose,
+				skip_empty_blocks: None,
+				key_type: AURA,
+				max_propose_retries: 0,
+				clock_drift_threshold: None,
+			},
+		}
+	}
+
+	/// Set [`BuildAuraWorkerParams::force_authoring`].
+	pub fn force_authoring(mut self, force_authoring: bool) -> Self {
+		self.params.force_authoring = force_authoring;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::backoff_authoring_blocks`].
+	pub fn backoff_authoring_blocks(mut self, backoff_authoring_blocks: BS) -> Self {
+		self.params.backoff_authoring_blocks = Some(backoff_authoring_blocks);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::block_proposal_slot_portion`].
+	pub fn block_proposal_slot_portion(
+		mut self,
+		block_proposal_slot_portion: SlotProportion,
+	) -> Self {
+		self.params.block_proposal_slot_portion = block_proposal_slot_portion;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::max_block_proposal_slot_portion`].
+	pub fn max_block_proposal_slot_portion(
+		mut self,
+		max_block_proposal_slot_portion: SlotProportion,
+	) -> Self {
+		self.params.max_block_proposal_slot_portion = Some(max_block_proposal_slot_portion);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::telemetry`].
+	pub fn telemetry(mut self, telemetry: TelemetryHandle) -> Self {
+		self.params.telemetry = Some(telemetry);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::compatibility_mode`].
+	pub fn compatibility_mode(mut self, compatibility_mode: CompatibilityMode<N>) -> Self {
+		self.params.compatibility_mode = compatibility_mode;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::prometheus_registry`].
+	pub fn prometheus_registry(mut self, prometheus_registry: Registry) -> Self {
+		self.params.prometheus_registry = Some(prometheus_registry);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::json_events`].
+	pub fn json_events(mut self, json_events: bool) -> Self {
+		self.params.json_events = json_events;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::authorities_preloader`].
+	pub fn authorities_preloader(
+		mut self,
+		authorities_preloader: AuthoritiesPreloader<A, N>,
+	) -> Self {
+		self.params.authorities_preloader = Some(authorities_preloader);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::max_head_slot_lag`].
+	pub fn max_head_slot_lag(mut self, max_head_slot_lag: u64) -> Self {
+		self.params.max_head_slot_lag = Some(max_head_slot_lag);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::skip_slots`].
+	pub fn skip_slots(mut self, skip_slots: Box<dyn Fn(Slot) -> bool + Send + Sync>) -> Self {
+		self.params.skip_slots = Some(skip_slots);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::self_verify_before_import`].
+	pub fn self_verify_before_import(mut self, self_verify_before_import: bool) -> Self {
+		self.params.self_verify_before_import = self_verify_before_import;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::min_inter_block_time`].
+	pub fn min_inter_block_time(mut self, min_inter_block_time: Duration) -> Self {
+		self.params.min_inter_block_time = Some(min_inter_block_time);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::authorities_retry`].
+	pub fn authorities_retry(mut self, authorities_retry: bool) -> Self {
+		self.params.authorities_retry = authorities_retry;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::max_tx_per_lenient_block`].
+	pub fn max_tx_per_lenient_block(mut self, max_tx_per_lenient_block: usize) -> Self {
+		self.params.max_tx_per_lenient_block = Some(max_tx_per_lenient_block);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::transaction_count_hint`].
+	pub fn transaction_count_hint(mut self, transaction_count_hint: TransactionCountHint) -> Self {
+		self.params.transaction_count_hint = Some(transaction_count_hint);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::authorities_cache_capacity`].
+	pub fn authorities_cache_capacity(mut self, authorities_cache_capacity: usize) -> Self {
+		self.params.authorities_cache_capacity = Some(authorities_cache_capacity);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::authority_weights`].
+	pub fn authority_weights(mut self, authority_weights: AuthorityWeights) -> Self {
+		self.params.authority_weights = Some(authority_weights);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::control`].
+	pub fn control(mut self, control: AuraControl) -> Self {
+		self.params.control = Some(control);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::slot_lenience_type`].
+	pub fn slot_lenience_type(mut self, slot_lenience_type: SlotLenienceType) -> Self {
+		self.params.slot_lenience_type = slot_lenience_type;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::slot_duration_changes`].
+	pub fn slot_duration_changes(mut self, slot_duration_changes: SlotDurationChanges) -> Self {
+		self.params.slot_duration_changes = Some(slot_duration_changes);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::fork_choice`].
+	pub fn fork_choice(mut self, fork_choice: ForkChoiceStrategy) -> Self {
+		self.params.fork_choice = fork_choice;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::secondary_slot_delay`].
+	pub fn secondary_slot_delay(mut self, secondary_slot_delay: SlotProportion) -> Self {
+		self.params.secondary_slot_delay = Some(secondary_slot_delay);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::async_keystore`].
+	pub fn async_keystore(mut self, async_keystore: bool) -> Self {
+		self.params.async_keystore = async_keystore;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::deadline_adjuster`].
+	pub fn deadline_adjuster(mut self, deadline_adjuster: DeadlineAdjuster<B>) -> Self {
+		self.params.deadline_adjuster = Some(deadline_adjuster);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::pre_seal_hook`].
+	pub fn pre_seal_hook(mut self, pre_seal_hook: PreSealHook<B>) -> Self {
+		self.params.pre_seal_hook = Some(pre_seal_hook);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::offline_authorities`].
+	pub fn offline_authorities(mut self, offline_authorities: OfflineAuthorities<A>) -> Self {
+		self.params.offline_authorities = Some(offline_authorities);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::engine_id`].
+	pub fn engine_id(mut self, engine_id: ConsensusEngineId) -> Self {
+		self.params.engine_id = engine_id;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::authored_blocks`].
+	pub fn authored_blocks(mut self, authored_blocks: AuthoredBlocks<B::Hash>) -> Self {
+		self.params.authored_blocks = Some(authored_blocks);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::time_source`].
+	pub fn time_source(mut self, time_source: TimeSource) -> Self {
+		self.params.time_source = Some(time_source);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::authored_block_origin`].
+	pub fn authored_block_origin(mut self, authored_block_origin: BlockOrigin) -> Self {
+		self.params.authored_block_origin = authored_block_origin;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::max_extrinsics`].
+	pub fn max_extrinsics(mut self, max_extrinsics: usize) -> Self {
+		self.params.max_extrinsics = Some(max_extrinsics);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::slot_tiebreak_fork_choice`].
+	pub fn slot_tiebreak_fork_choice(mut self, slot_tiebreak_fork_choice: bool) -> Self {
+		self.params.slot_tiebreak_fork_choice = slot_tiebreak_fork_choice;
+		self
+	}
+
+
+	/// Set [`BuildAuraWorkerParams::retry_self_import`].
+	pub fn retry_self_import(mut self, retry_self_import: bool) -> Self {
+		self.params.retry_self_import = retry_self_import;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::should_author`].
+	pub fn should_author(mut self, should_author: ShouldAuthor) -> Self {
+		self.params.should_author = Some(should_author);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::include_authority_index`].
+	pub fn include_authority_index(mut self, include_authority_index: bool) -> Self {
+		self.params.include_authority_index = include_authority_index;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::max_reorg_depth`].
+	pub fn max_reorg_depth(mut self, max_reorg_depth: u32) -> Self {
+		self.params.max_reorg_depth = Some(max_reorg_depth);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::telemetry_level`].
+	pub fn telemetry_level(mut self, telemetry_level: TelemetryLevel) -> Self {
+		self.params.telemetry_level = telemetry_level;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::skip_empty_blocks`].
+	pub fn skip_empty_blocks(mut self, skip_empty_blocks: SkipEmptyConfig) -> Self {
+		self.params.skip_empty_blocks = Some(skip_empty_blocks);
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::key_type`].
+	pub fn key_type(mut self, key_type: KeyTypeId) -> Self {
+		self.params.key_type = key_type;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::max_propose_retries`].
+	pub fn max_propose_retries(mut self, max_propose_retries: u32) -> Self {
+		self.params.max_propose_retries = max_propose_retries;
+		self
+	}
+
+	/// Set [`BuildAuraWorkerParams::clock_drift_threshold`].
+	pub fn clock_drift_threshold(mut self, clock_drift_threshold: u64) -> Self {
+		self.params.clock_drift_threshold = Some(clock_drift_threshold);
+		self
+	}
+
+	/// Build the parameters.
+	pub fn build(self) -> BuildAuraWorkerParams<C, I, PF, SO, L, BS, N, A, B> {
+		self.params
+	}
+}
+
+/// Builder of [`ImportQueueParams`], with defaults for all the optional parameters.
+///
+/// The defaults are those of a plain Aura verifier: equivocations are checked, blocks are
+/// verified serially and none of the optional verifier features is enabled.
+pub struct ImportQueueParamsBuilder<'a, Block: BlockT, I, C, S, CAW, CIDP, E, A> {
+	params: ImportQueueParams<'a, Block, I, C, S, CAW, CIDP, E, A>,
+}
+
+impl<'a, Block: BlockT, I, C, S, CAW, CIDP, E, A>
+	ImportQueueParamsBuilder<'a, Block, I, C, S, CAW, CIDP, E, A>
+{
+	/// Create a builder from the required parameters.
+	pub fn new(
+		block_import: I,
+		client: Arc<C>,
+		create_inherent_data_providers: CIDP,
+		spawner: &'a S,
+		can_author_with: CAW,
+	) -> Self {
+		Self {
+			params: ImportQueueParams {
+				block_import,
+				justification_import: None,
+				client,
+				create_inherent_data_providers,
+				spawner,
+				registry: None,
+				can_author_with,
+				check_for_equivocation: CheckForEquivocation::Yes,
+				telemetry: None,
+				compatibility_mode: CompatibilityMode::None,
+				use_consensus_log_authorities: false,
+				handover_window: None,
+				check_timestamp_slot_consistency: false,
+				slot_duration_schedule: Vec::new(),
+				equivocation_sender: None,
+				authority_weights: None,
+				on_equivocation: None,
+				accept_secondary_slots: false,
+				equivocation_window_slots: sc_consensus_slots::MAX_SLOT_CAPACITY,
+				offline_authorities: None,
+				max_future_slots: u64::MAX,
+				engine_id: AURA_ENGINE_ID,
+				enforce_monotonic_slots: false,
+				verify_seals_only: false,
+				genesis_timestamp: 0,
+				verification_concurrency: 1,
+			},
+		}
+	}
+
+	/// Set [`ImportQueueParams::justification_import`].
+	pub fn justification_import(
+		mut self,
+		justification_import: BoxJustificationImport<Block>,
+	) -> Self {
+		self.params.justification_import = Some(justification_import);
+		self
+	}
+
+	/// Set [`ImportQueueParams::registry`].
+	pub fn registry(mut self, registry: &'a Registry) -> Self {
+		self.params.registry = Some(registry);
+		self
+	}
+
+	/// Set [`ImportQueueParams::check_for_equivocation`].
+	pub fn check_for_equivocation(mut self, check_for_equivocation: CheckForEquivocation) -> Self {
+		self.params.check_for_equivocation = check_for_equivocation;
+		self
+	}
+
+	/// Set [`ImportQueueParams::telemetry`].
+	pub fn telemetry(mut self, telemetry: TelemetryHandle) -> Self {
+		self.params.telemetry = Some(telemetry);
+		self
+	}
+
+	/// Set [`ImportQueueParams::compatibility_mode`].
+	pub fn compatibility_mode(
+		mut self,
+		compatibility_mode: CompatibilityMode<NumberFor<Block>>,
+	) -> Self {
+		self.params.compatibility_mode = compatibility_mode;
+		self
+	}
+
+	/// Set [`ImportQueueParams::use_consensus_log_authorities`].
+	pub fn use_consensus_log_authorities(mut self, use_consensus_log_authorities: bool) -> Self {
+		self.params.use_consensus_log_authorities = use_consensus_log_authorities;
+		self
+	}
+
+	/// Set [`ImportQueueParams::handover_window`].
+	pub fn handover_window(
+		mut self,
+		handover_window: (NumberFor<Block>, NumberFor<Block>),
+	) -> Self {
+		self.params.handover_window = Some(handover_window);
+		self
+	}
+
+	/// Set [`ImportQueueParams::check_timestamp_slot_consistency`].
+	pub fn check_timestamp_slot_consistency(
+		mut self,
+		check_timestamp_slot_consistency: bool,
+	) -> Self {
+		self.params.check_timestamp_slot_consistency = check_timestamp_slot_consistency;
+		self
+	}
+
+	/// Set [`ImportQueueParams::slot_duration_schedule`].
+	pub fn slot_duration_schedule(
+		mut self,
+		slot_duration_schedule: Vec<(NumberFor<Block>, SlotDuration)>,
+	) -> Self {
+		self.params.slot_duration_schedule = slot_duration_schedule;
+		self
+	}
+
+	/// Set [`ImportQueueParams::equivocation_sender`].
+	pub fn equivocation_sender(mut self, equivocation_sender: mpsc::Sender<E>) -> Self {
+		self.params.equivocation_sender = Some(equivocation_sender);
+		self
+	}
+
+	/// Set [`ImportQueueParams::authority_weights`].
+	pub fn authority_weights(mut self, authority_weights: AuthorityWeights) -> Self {
+		self.params.authority_weights = Some(authority_weights);
+		self
+	}
+
+	/// Set [`ImportQueueParams::on_equivocation`].
+	pub fn on_equivocation(
+		mut self,
+		on_equivocation: Box<dyn Fn(&Block::Header, &Block::Header) + Send + Sync>,
+	) -> Self {
+		self.params.on_equivocation = Some(on_equivocation);
+		self
+	}
+
+	/// Set [`ImportQueueParams::accept_secondary_slots`].
+	pub fn accept_secondary_slots(mut self, accept_secondary_slots: bool) -> Self {
+		self.params.accept_secondary_slots = accept_secondary_slots;
+		self
+	}
+
+	/// Set [`ImportQueueParams::equivocation_window_slots`].
+	pub fn equivocation_window_slots(mut self, equivocation_window_slots: u64) -> Self {
+		self.params.equivocation_window_slots = equivocation_window_slots;
+		self
+	}
+
+	/// Set [`ImportQueueParams::offline_authorities`].
+	pub fn offline_authorities(mut self, offline_authorities: OfflineAuthorities<A>) -> Self {
+		self.params.offline_authorities = Some(offline_authorities);
+		self
+	}
+
+	/// Set [`ImportQueueParams::max_future_slots`].
+	pub fn max_future_slots(mut self, max_future_slots: u64) -> Self {
+		self.params.max_future_slots = max_future_slots;
+		self
+	}
+
+	/// Set [`ImportQueueParams::engine_id`].
+	pub fn engine_id(mut self, engine_id: ConsensusEngineId) -> Self {
+		self.params.engine_id = engine_id;
+		self
+	}
+
+	/// Set [`ImportQueueParams::enforce_monotonic_slots`].
+	pub fn enforce_monotonic_slots(mut self, enforce_monotonic_slots: bool) -> Self {
+		self.params.enforce_monotonic_slots = enforce_monotonic_slots;
+		self
+	}
+
+	/// Set [`ImportQueueParams::verify_seals_only`].
+	pub fn verify_seals_only(mut self, verify_seals_only: bool) -> Self {
+		self.params.verify_seals_only = verify_seals_only;
+		self
+	}
+
+	/// Set [`ImportQueueParams::genesis_timestamp`].
+	pub fn genesis_timestamp(mut self, genesis_timestamp: u64) -> Self {
+		self.params.genesis_timestamp = genesis_timestamp;
+		self
+	}
+
+	/// Set [`ImportQueueParams::verification_concurrency`].
+	pub fn verification_concurrency(mut self, verification_concurrency: usize) -> Self {
+		self.params.verification_concurrency = verification_concurrency;
+		self
+	}
+
+	/// Build the parameters.
+	pub fn build(self) -> ImportQueueParams<'a, Block, I, C, S, CAW, CIDP, E, A> {
+		self.params
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sc_keystore::LocalKeystore;
+	use sp_consensus_aura::sr25519::AuthorityId;
+	use substrate_test_runtime_client::runtime::Block;
+
+	#[test]
+	fn builder_applies_defaults_and_overrides() {
+		type Builder =
+			StartAuraParamsBuilder<(), (), (), (), (), (), (), (), (), u64, AuthorityId, Block>;
+		let params = Builder::new(
+			SlotDuration::from_millis(6000),
+			Arc::new(()),
+			(),
+			(),
+			(),
+			(),
+			(),
+			(),
+			Arc::new(LocalKeystore::in_memory()),
+			(),
+		)
+		.force_authoring(true)
+		.max_head_slot_lag(4)
+		.build();
+
+		assert!(params.force_authoring);
+		assert_eq!(params.max_head_slot_lag, Some(4));
+		assert!(params.backoff_authoring_blocks.is_none());
+		assert!(matches!(params.compatibility_mode, CompatibilityMode::None));
+		assert!(matches!(params.fork_choice, ForkChoiceStrategy::LongestChain));
+		assert_eq!(params.engine_id, AURA_ENGINE_ID);
+	}
+
+	#[test]
+	fn worker_params_builder_applies_defaults_and_overrides() {
+		type Builder =
+			BuildAuraWorkerParamsBuilder<(), (), (), (), (), (), u64, AuthorityId, Block>;
+		let keystore = Arc::new(LocalKeystore::in_memory());
+		let params = Builder::new(Arc::new(()), (), (), (), (), keystore)
+			.max_propose_retries(2)
+			.build();
+
+		assert_eq!(params.max_propose_retries, 2);
+		assert!(!params.force_authoring);
+		assert!(params.control.is_none());
+		assert!(params.slot_duration_changes.is_none());
+		assert_eq!(params.key_type, AURA);
+		assert_eq!(params.engine_id, AURA_ENGINE_ID);
+	}
+
+	#[test]
+	fn import_queue_params_builder_applies_defaults_and_overrides() {
+		type Builder<'a> = ImportQueueParamsBuilder<'a, Block, (), (), (), (), (), (), AuthorityId>;
+		let params = Builder::new((), Arc::new(()), (), &(), ())
+			.verification_concurrency(4)
+			.build();
+
+		assert_eq!(params.verification_concurrency, 4);
+		assert!(matches!(params.check_for_equivocation, CheckForEquivocation::Yes));
+		assert!(params.justification_import.is_none());
+		assert!(params.registry.is_none());
+		assert_eq!(params.max_future_slots, u64::MAX);
+		assert_eq!(params.engine_id, AURA_ENGINE_ID);
+	}
+}
@@ -0,0 +1,229 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Retrying failed proposals within the slot.
+//!
+//! See [`BuildAuraWorkerParams::max_propose_retries`](crate::BuildAuraWorkerParams).
+
+use futures::prelude::*;
+use log::debug;
+use sp_consensus::{Proposal, Proposer};
+use sp_inherents::InherentData;
+use sp_runtime::{traits::Block as BlockT, Digest};
+use std::{
+	marker::PhantomData,
+	pin::Pin,
+	time::{Duration, Instant},
+};
+
+/// Creates a proposer on the same parent as the first one, for a retry.
+pub(crate) type CreateRetryProposer<B, P> = Box<
+	dyn FnMut() -> Pin<Box<dyn Future<Output = Result<P, <P as Proposer<B>>::Error>> + Send>>
+		+ Send,
+>;
+
+/// Proposer retrying a failed proposal with a new proposer, as long as the slot budget is not
+/// used up.
+///
+/// A proposer is consumed by proposing, so each retry creates its own. It is only created once
+/// an attempt failed, and the time taken to create it counts against the budget.
+pub(crate) struct RetryingProposer<B: BlockT, P: Proposer<B>> {
+	proposer: P,
+	create: CreateRetryProposer<B, P>,
+	retries: u32,
+	_block: PhantomData<fn() -> B>,
+}
+
+impl<B: BlockT, P: Proposer<B>> RetryingProposer<B, P> {
+	/// Propose with `proposer`, retrying up to `retries` times with proposers from `create` on
+	/// failure.
+	pub(crate) fn new(proposer: P, create: CreateRetryProposer<B, P>, retries: u32) -> Self {
+		Self { proposer, create, retries, _block: PhantomData }
+	}
+}
+
+impl<B, P> Proposer<B> for RetryingProposer<B, P>
+where
+	B: BlockT,
+	P: Proposer<B> + Send + 'static,
+{
+	type Error = P::Error;
+	type Transaction = P::Transaction;
+	type Proposal = Pin<
+		Box<
+			dyn Future<Output = Result<Proposal<B, P::Transaction, P::Proof>, P::Error>>
+				+ Send
+				+ 'static,
+		>,
+	>;
+	type ProofRecording = P::ProofRecording;
+	type Proof = P::Proof;
+
+	/// Each attempt is handed what is left of `max_duration`, so the attempts together never
+	/// take longer than the first one alone could.
+	fn propose(
+		self,
+		inherent_data: InherentData,
+		inherent_digests: Digest,
+		max_duration: Duration,
+		block_size_limit: Option<usize>,
+	) -> Self::Proposal {
+		async move {
+			let started = Instant::now();
+			let Self { mut proposer, mut create, mut retries, .. } = self;
+			loop {
+				let remaining = max_duration.saturating_sub(started.elapsed());
+				let error = match proposer
+					.propose(
+						inherent_data.clone(),
+						inherent_digests.clone(),
+						remaining,
+						block_size_limit,
+					)
+					.await
+				{
+					Err(error) if retries > 0 && started.elapsed() < max_duration => error,
+					proposal => return proposal,
+				};
+				debug!(
+					target: "aura",
+					"Proposing failed, retrying within {:?}: {:?}",
+					max_duration.saturating_sub(started.elapsed()),
+					error,
+				);
+				retries -= 1;
+				proposer = create().await?;
+			}
+		}
+		.boxed()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{executor::block_on, future};
+	use sp_consensus::DisableProofRecording;
+	use std::sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc,
+	};
+	use substrate_test_runtime_client::runtime::{Block, Header};
+
+	/// Proposer whose proposals fail while fewer than `failures` were made by any of them.
+	struct Flaky {
+		failures: usize,
+		calls: Arc<AtomicUsize>,
+		durations: Arc<parking_lot::Mutex<Vec<Duration>>>,
+	}
+
+	impl Proposer<Block> for Flaky {
+		type Error = sp_consensus::Error;
+		type Transaction = ();
+		type Proposal = future::Ready<Result<Proposal<Block, (), ()>, sp_consensus::Error>>;
+		type ProofRecording = DisableProofRecording;
+		type Proof = ();
+
+		fn propose(
+			self,
+			_: InherentData,
+			digest: Digest,
+			max_duration: Duration,
+			_: Option<usize>,
+		) -> Self::Proposal {
+			self.durations.lock().push(max_duration);
+			if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+				return future::ready(Err(sp_consensus::Error::ClientImport("flaky".into())))
+			}
+			let header =
+				Header::new(1, Default::default(), Default::default(), Default::default(), digest);
+			future::ready(Ok(Proposal {
+				block: Block { header, extrinsics: Vec::new() },
+				proof: (),
+				storage_changes: Default::default(),
+			}))
+		}
+	}
+
+	/// Propose with `retries` retries, each retry proposer taking `create_delay` to be created,
+	/// returning whether it succeeded, the durations the attempts were handed and how many retry
+	/// proposers were created.
+	fn propose(
+		failures: usize,
+		retries: u32,
+		create_delay: Duration,
+	) -> (bool, Vec<Duration>, usize) {
+		let calls = Arc::new(AtomicUsize::new(0));
+		let durations = Arc::new(parking_lot::Mutex::new(Vec::new()));
+		let created = Arc::new(AtomicUsize::new(0));
+		let flaky = {
+			let (calls, durations) = (calls.clone(), durations.clone());
+			move || Flaky { failures, calls: calls.clone(), durations: durations.clone() }
+		};
+		let create: CreateRetryProposer<Block, Flaky> = {
+			let (flaky, created) = (flaky.clone(), created.clone());
+			Box::new(move || {
+				created.fetch_add(1, Ordering::SeqCst);
+				std::thread::sleep(create_delay);
+				future::ready(Ok(flaky())).boxed()
+			})
+		};
+		let proposal = block_on(RetryingProposer::new(flaky(), create, retries).propose(
+			Default::default(),
+			Default::default(),
+			Duration::from_secs(1),
+			None,
+		));
+		let durations = durations.lock().clone();
+		(proposal.is_ok(), durations, created.load(Ordering::SeqCst))
+	}
+
+	#[test]
+	fn failed_proposals_are_retried_within_the_slot() {
+		// fails once, then succeeds
+		let (proposed, durations, created) = propose(1, 1, Duration::ZERO);
+		assert!(proposed);
+		assert_eq!(durations.len(), 2);
+		assert_eq!(durations[0], Duration::from_secs(1));
+		// the retry only gets what is left of the budget
+		assert!(durations[1] <= durations[0]);
+		assert_eq!(created, 1);
+
+		// no retries by default
+		assert!(!propose(1, 0, Duration::ZERO).0);
+		assert_eq!(propose(0, 0, Duration::ZERO), (true, vec![Duration::from_secs(1)], 0));
+		// retries are used up
+		let (proposed, durations, created) = propose(3, 2, Duration::ZERO);
+		assert!(!proposed);
+		assert_eq!(durations.len(), 3);
+		assert_eq!(created, 2);
+	}
+
+	#[test]
+	fn retry_proposers_are_only_created_after_a_failure_within_the_budget() {
+		// a successful proposal needs no retry proposer
+		assert_eq!(propose(0, 3, Duration::ZERO).2, 0);
+
+		// creating the retry proposer is charged against the budget
+		let delay = Duration::from_millis(50);
+		let (proposed, durations, created) = propose(1, 1, delay);
+		assert!(proposed);
+		assert_eq!(created, 1);
+		assert!(durations[1] <= Duration::from_secs(1) - delay);
+	}
+}
@@ -0,0 +1,487 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Verification of the seals of incoming blocks in parallel, ahead of the import queue.
+
+use crate::{
+	expected_slot_author, fetch_authorities,
+	import_queue::{check_seal, find_authorities_change},
+	is_secondary_slot, AuraApiAuthorities, AuthorityId, AuthorityWeights, CompatibilityMode,
+};
+use codec::Codec;
+use futures::{channel::mpsc, prelude::*};
+use log::{debug, error};
+use parking_lot::Mutex;
+use sc_consensus::import_queue::{DefaultImportQueue, ImportQueue, IncomingBlock, Link, Origin};
+use sp_api::ProvideRuntimeApi;
+use sp_blockchain::HeaderBackend;
+use sp_consensus::BlockOrigin;
+use sp_consensus_aura::AuraApi;
+use sp_core::crypto::Pair;
+use sp_runtime::{
+	generic::BlockId,
+	traits::{Block as BlockT, Header, NumberFor},
+	ConsensusEngineId, Justifications,
+};
+use std::{
+	collections::{HashMap, VecDeque},
+	fmt::Debug,
+	hash::Hash,
+	sync::Arc,
+	thread,
+};
+
+/// Maximum number of preverified seals kept until their blocks are verified.
+const MAX_PREVERIFIED_SEALS: usize = 4096;
+
+/// The import queue returned by [`import_queue`](crate::import_queue).
+pub type AuraImportQueue<B, C> = SealPreverifyingQueue<B, DefaultImportQueue<B, C>>;
+
+/// Seals verified ahead of the import queue, as the hash of the sealed block and its signer.
+///
+/// Entries are taken by the verifier, the oldest ones are dropped beyond
+/// [`MAX_PREVERIFIED_SEALS`].
+pub(crate) struct PreverifiedSeals<H, A> {
+	seals: Arc<Mutex<(HashMap<H, A>, VecDeque<H>)>>,
+}
+
+impl<H, A> Clone for PreverifiedSeals<H, A> {
+	fn clone(&self) -> Self {
+		Self { seals: self.seals.clone() }
+	}
+}
+
+impl<H: Hash + Eq + Clone, A> PreverifiedSeals<H, A> {
+	pub fn new() -> Self {
+		Self { seals: Arc::new(Mutex::new((HashMap::new(), VecDeque::new()))) }
+	}
+
+	fn insert(&self, hash: H, signer: A) {
+		let (seals, order) = &mut *self.seals.lock();
+		if seals.insert(hash.clone(), signer).is_none() {
+			order.push_back(hash);
+		}
+		while order.len() > MAX_PREVERIFIED_SEALS {
+			if let Some(oldest) = order.pop_front() {
+				seals.remove(&oldest);
+			}
+		}
+	}
+
+	/// Take the signer of the seal of the block with the given `hash`, if it was preverified.
+	pub fn take(&self, hash: &H) -> Option<A> {
+		let (seals, order) = &mut *self.seals.lock();
+		let signer = seals.remove(hash)?;
+		order.retain(|h| h != hash);
+		Some(signer)
+	}
+}
+
+/// Verify the seals of `headers`, each against the authorities sealing it, on up to
+/// `concurrency` threads.
+///
+/// Returns the hash and the signer of every header sealed by the author of its slot, in the
+/// order of `headers`.
+pub(crate) fn preverify_seals<B, P>(
+	mut headers: Vec<(B::Header, Arc<Vec<AuthorityId<P>>>)>,
+	weights: Option<&AuthorityWeights>,
+	compatibility_mode: &CompatibilityMode<NumberFor<B>>,
+	engine_id: ConsensusEngineId,
+	concurrency: usize,
+) -> Vec<(B::Hash, AuthorityId<P>)>
+where
+	B: BlockT,
+	P: Pair + 'static,
+	P::Signature: Codec,
+{
+	let chunk_size = (headers.len() + concurrency.max(1) - 1) / concurrency.max(1);
+	let mut chunks = Vec::new();
+	while !headers.is_empty() {
+		let rest = headers.split_off(chunk_size.min(headers.len()));
+		chunks.push(std::mem::replace(&mut headers, rest));
+	}
+
+	let checks = chunks
+		.into_iter()
+		.map(|chunk| {
+			let weights = weights.cloned();
+			let compatibility_mode = compatibility_mode.clone();
+			thread::spawn(move || {
+				chunk
+					.into_iter()
+					.filter_map(|(header, authorities)| {
+						let hash = header.hash();
+						let secondary = is_secondary_slot::<B>(&header);
+						let slot = check_seal::<B, P>(
+							header,
+							&authorities,
+							weights.as_ref(),
+							None,
+							&compatibility_mode,
+							engine_id,
+						)
+						.ok()?;
+						let signer = expected_slot_author::<P>(
+							slot,
+							&authorities,
+							weights.as_ref(),
+							None,
+							secondary,
+						)?;
+						Some((hash, signer.clone()))
+					})
+					.collect::<Vec<_>>()
+			})
+		})
+		.collect::<Vec<_>>();
+
+	checks
+		.into_iter()
+		.filter_map(|check| match check.join() {
+			Ok(verified) => Some(verified),
+			Err(_) => {
+				error!(target: "aura", "Seal preverification thread panicked");
+				None
+			},
+		})
+		.flatten()
+		.collect()
+}
+
+/// Finds the authorities sealing incoming blocks and preverifies their seals.
+pub(crate) struct SealPreverifier<B: BlockT, C, P: Pair> {
+	client: Arc<C>,
+	compatibility_mode: CompatibilityMode<NumberFor<B>>,
+	authority_weights: Option<AuthorityWeights>,
+	concurrency: usize,
+	engine_id: ConsensusEngineId,
+	seals: PreverifiedSeals<B::Hash, AuthorityId<P>>,
+	/// The authorities sealing the children of the last preverified block.
+	next_authorities: Option<(B::Hash, Arc<Vec<AuthorityId<P>>>)>,
+}
+
+impl<B, C, P> SealPreverifier<B, C, P>
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B> + HeaderBackend<B>,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+	P: Pair + 'static,
+	P::Public: Codec + Debug,
+	P::Signature: Codec,
+{
+	pub fn new(
+		client: Arc<C>,
+		compatibility_mode: CompatibilityMode<NumberFor<B>>,
+		authority_weights: Option<AuthorityWeights>,
+		concurrency: usize,
+		engine_id: ConsensusEngineId,
+		seals: PreverifiedSeals<B::Hash, AuthorityId<P>>,
+	) -> Self {
+		Self {
+			client,
+			compatibility_mode,
+			authority_weights,
+			concurrency,
+			engine_id,
+			seals,
+			next_authorities: None,
+		}
+	}
+
+	/// The authorities sealing the children of `parent_hash`, if they can be found.
+	///
+	/// The parent is either the last preverified block or already imported.
+	fn authorities_at(
+		&self,
+		parent_hash: B::Hash,
+		number: NumberFor<B>,
+	) -> Option<Arc<Vec<AuthorityId<P>>>> {
+		match self.next_authorities.as_ref() {
+			Some((hash, authorities)) if *hash == parent_hash => Some(authorities.clone()),
+			_ => {
+				self.client.header(BlockId::Hash(parent_hash)).ok().flatten()?;
+				fetch_authorities(
+					&*self.client,
+					&AuraApiAuthorities,
+					parent_hash,
+					number,
+					&self.compatibility_mode,
+				)
+				.map(Arc::new)
+				.ok()
+			},
+		}
+	}
+
+	/// Preverify the seals of `blocks`, noting the verified ones for the verifier.
+	///
+	/// Within the batch the authority set is followed through the
+	/// `ConsensusLog::AuthoritiesChange` digests. If that guess is wrong the seal does not match
+	/// the verifier's expected author, and the verifier checks it again.
+	pub fn preverify(&mut self, blocks: &[IncomingBlock<B>]) {
+		let mut headers = Vec::with_capacity(blocks.len());
+		for header in blocks.iter().filter_map(|block| block.header.as_ref()) {
+			let authorities = match self.authorities_at(*header.parent_hash(), *header.number()) {
+				Some(authorities) => authorities,
+				None => {
+					self.next_authorities = None;
+					continue
+				},
+			};
+
+			let next = find_authorities_change::<B, AuthorityId<P>>(header)
+				.map_or_else(|| authorities.clone(), Arc::new);
+			self.next_authorities = Some((header.hash(), next));
+
+			headers.push((header.clone(), authorities));
+		}
+
+		let checked = headers.len();
+		let verified = preverify_seals::<B, P>(
+			headers,
+			self.authority_weights.as_ref(),
+			&self.compatibility_mode,
+			self.engine_id,
+			self.concurrency,
+		);
+		debug!(
+			target: "aura",
+			"Preverified {} of {} seals of {} incoming blocks",
+			verified.len(),
+			checked,
+			blocks.len(),
+		);
+
+		for (hash, signer) in verified {
+			self.seals.insert(hash, signer);
+		}
+	}
+}
+
+enum QueueMessage<B: BlockT> {
+	Blocks(BlockOrigin, Vec<IncomingBlock<B>>),
+	Justifications(Origin, B::Hash, NumberFor<B>, Justifications),
+}
+
+/// Import queue preverifying the seals of incoming blocks in parallel before handing them to
+/// the wrapped queue.
+///
+/// Blocks and justifications reach the wrapped queue in the order they are received. Without a
+/// preverification worker, everything is handed over right away.
+pub struct SealPreverifyingQueue<B: BlockT, Q> {
+	inner: Arc<Mutex<Q>>,
+	sender: Option<mpsc::UnboundedSender<QueueMessage<B>>>,
+}
+
+impl<B: BlockT, Q: ImportQueue<B> + 'static> SealPreverifyingQueue<B, Q> {
+	/// Wrap `inner` without preverifying any seals.
+	pub fn new(inner: Q) -> Self {
+		Self { inner: Arc::new(Mutex::new(inner)), sender: None }
+	}
+
+	/// Wrap `inner`, preverifying the seals of incoming blocks with `preverifier`.
+	///
+	/// Returns the queue and the worker that has to be spawned on a blocking task.
+	pub(crate) fn with_preverifier<C, P>(
+		inner: Q,
+		mut preverifier: SealPreverifier<B, C, P>,
+	) -> (Self, impl Future<Output = ()> + Send)
+	where
+		C: ProvideRuntimeApi<B> + HeaderBackend<B> + Send + Sync + 'static,
+		C::Api: AuraApi<B, AuthorityId<P>>,
+		P: Pair + 'static,
+		P::Public: Codec + Debug,
+		P::Signature: Codec,
+	{
+		let (sender, mut receiver) = mpsc::unbounded();
+		let inner = Arc::new(Mutex::new(inner));
+
+		let queue = inner.clone();
+		let worker = async move {
+			while let Some(message) = receiver.next().await {
+				match message {
+					QueueMessage::Blocks(origin, blocks) => {
+						preverifier.preverify(&blocks);
+						queue.lock().import_blocks(origin, blocks);
+					},
+					QueueMessage::Justifications(who, hash, number, justifications) =>
+						queue.lock().import_justifications(who, hash, number, justifications),
+				}
+			}
+		};
+
+		(Self { inner, sender: Some(sender) }, worker)
+	}
+}
+
+impl<B: BlockT, Q: ImportQueue<B> + 'static> ImportQueue<B> for SealPreverifyingQueue<B, Q> {
+	fn import_blocks(&mut self, origin: BlockOrigin, blocks: Vec<IncomingBlock<B>>) {
+		match self.sender.as_ref() {
+			Some(sender) =>
+				if sender.unbounded_send(QueueMessage::Blocks(origin, blocks)).is_err() {
+					error!(target: "aura", "Seal preverification worker is gone, dropping blocks");
+				},
+			None => self.inner.lock().import_blocks(origin, blocks),
+		}
+	}
+
+	fn import_justifications(
+		&mut self,
+		who: Origin,
+		hash: B::Hash,
+		number: NumberFor<B>,
+		justifications: Justifications,
+	) {
+		match self.sender.as_ref() {
+			Some(sender) => {
+				let message = QueueMessage::Justifications(who, hash, number, justifications);
+				if sender.unbounded_send(message).is_err() {
+					error!(
+						target: "aura",
+						"Seal preverification worker is gone, dropping justifications",
+					);
+				}
+			},
+			None => self.inner.lock().import_justifications(who, hash, number, justifications),
+		}
+	}
+
+	fn poll_actions(&mut self, cx: &mut futures::task::Context, link: &mut dyn Link<B>) {
+		self.inner.lock().poll_actions(cx, link)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_consensus_aura::{
+		digests::CompatibleDigestItem,
+		sr25519::{AuthorityPair, AuthoritySignature},
+		AURA_ENGINE_ID,
+	};
+	use sp_core::H256;
+	use sp_runtime::{Digest, DigestItem};
+	use std::time::Instant;
+	use substrate_test_runtime_client::runtime::{Block, Header as TestHeader};
+
+	fn sealed_header(pair: &AuthorityPair, number: u64, slot: u64) -> TestHeader {
+		let pre_digest =
+			<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_pre_digest(slot.into());
+		let mut header = TestHeader::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Digest { logs: vec![pre_digest] },
+		);
+		let signature = pair.sign(header.hash().as_ref());
+		header
+			.digest_mut()
+			.push(<DigestItem as CompatibleDigestItem<AuthoritySignature>>::aura_seal(signature));
+		header
+	}
+
+	#[test]
+	fn preverified_seals_are_bounded_and_taken_once() {
+		let seals = PreverifiedSeals::<u64, u64>::new();
+		for hash in 0..MAX_PREVERIFIED_SEALS as u64 + 2 {
+			seals.insert(hash, hash * 2);
+		}
+
+		assert_eq!(seals.take(&0), None);
+		assert_eq!(seals.take(&1), None);
+		assert_eq!(seals.take(&2), Some(4));
+		assert_eq!(seals.take(&2), None);
+	}
+
+	/// Headers sealed alternately by Alice and Bob, where every fourth one is sealed by the
+	/// wrong author, along with the hash and the signer of each correctly sealed one.
+	fn headers(
+		count: u64,
+	) -> (
+		Vec<(TestHeader, Arc<Vec<AuthorityId<AuthorityPair>>>)>,
+		Vec<(H256, AuthorityId<AuthorityPair>)>,
+	) {
+		let alice = AuthorityPair::from_string("//Alice", None).expect("Valid seed");
+		let bob = AuthorityPair::from_string("//Bob", None).expect("Valid seed");
+		let authorities = Arc::new(vec![alice.public(), bob.public()]);
+
+		let mut headers = Vec::new();
+		let mut expected = Vec::new();
+		for n in 0..count {
+			let author = if n % 2 == 0 { &alice } else { &bob };
+			let pair = if n % 4 == 3 { &alice } else { author };
+			let header = sealed_header(pair, n + 1, n);
+			if n % 4 != 3 {
+				expected.push((header.hash(), author.public()));
+			}
+			headers.push((header, authorities.clone()));
+		}
+		(headers, expected)
+	}
+
+	#[test]
+	fn parallel_preverification_finds_the_same_seals() {
+		let (headers, expected) = headers(200);
+		let mode = CompatibilityMode::None;
+
+		let serial = preverify_seals::<Block, AuthorityPair>(
+			headers.clone(),
+			None,
+			&mode,
+			AURA_ENGINE_ID,
+			1,
+		);
+		let parallel =
+			preverify_seals::<Block, AuthorityPair>(headers, None, &mode, AURA_ENGINE_ID, 8);
+
+		assert_eq!(serial, expected);
+		assert_eq!(parallel, expected);
+	}
+
+	#[test]
+	#[ignore]
+	fn bench_parallel_preverification() {
+		let (headers, expected) = headers(2_000);
+		let mode = CompatibilityMode::None;
+
+		let started = Instant::now();
+		let serial = preverify_seals::<Block, AuthorityPair>(
+			headers.clone(),
+			None,
+			&mode,
+			AURA_ENGINE_ID,
+			1,
+		);
+		let serial_duration = started.elapsed();
+
+		let started = Instant::now();
+		let parallel =
+			preverify_seals::<Block, AuthorityPair>(headers, None, &mode, AURA_ENGINE_ID, 8);
+		let parallel_duration = started.elapsed();
+
+		assert_eq!(serial, expected);
+		assert_eq!(parallel, expected);
+		eprintln!(
+			"Preverified {} seals in {:?} serially and {:?} on 8 threads",
+			expected.len(),
+			serial_duration,
+			parallel_duration,
+		);
+	}
+}
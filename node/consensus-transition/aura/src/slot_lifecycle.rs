@@ -0,0 +1,281 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! A single record of everything the worker did in one authored slot.
+
+use log::debug;
+use parking_lot::Mutex;
+use sc_consensus::{BlockCheckParams, BlockImport, BlockImportParams, ImportResult, StateAction};
+use sc_telemetry::{telemetry, TelemetryHandle, CONSENSUS_INFO};
+use sp_blockchain::well_known_cache_keys::Id as CacheKeyId;
+use sp_consensus::BlockOrigin;
+use sp_consensus_slots::Slot;
+use sp_runtime::traits::Block as BlockT;
+use std::{
+	collections::HashMap,
+	time::{Duration, Instant},
+};
+
+/// The lifecycle of an authored slot, filled in across the worker's slot flow.
+///
+/// The worker completes the record up to sealing in `block_import_params` and hands it to
+/// [`SlotLifecycleImport`], which adds the import duration and emits it as the
+/// `aura.slot_lifecycle` telemetry event.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct SlotLifecycle {
+	/// The authored slot.
+	pub slot: Slot,
+	/// Index of the local authority in the authority set.
+	pub authority_index: Option<usize>,
+	/// Size of the authority set.
+	pub authorities_len: usize,
+	/// The time granted for proposing.
+	pub proposing_budget: Duration,
+	/// Time taken to create the proposer and propose the block.
+	pub propose_duration: Duration,
+	/// Time taken to sign the block.
+	pub sign_duration: Duration,
+}
+
+/// Block import of the worker, completing and emitting the [`SlotLifecycle`] of each authored
+/// block.
+///
+/// If `retry_import` is set, a failed import of an authored block is retried once before its
+/// slot ends. An import that fails for good is reported as the `aura.self_import_failed`
+/// telemetry event.
+pub(crate) struct SlotLifecycleImport<I> {
+	inner: I,
+	pending: Mutex<Option<(SlotLifecycle, Option<Instant>)>>,
+	telemetry: Option<TelemetryHandle>,
+	retry_import: bool,
+}
+
+impl<I> SlotLifecycleImport<I> {
+	pub fn new(inner: I, telemetry: Option<TelemetryHandle>, retry_import: bool) -> Self {
+		Self { inner, pending: Mutex::new(None), telemetry, retry_import }
+	}
+
+	/// Hand over the lifecycle of the block that is imported next, along with the end of its
+	/// slot, until which a failed import may be retried.
+	pub fn note_sealed(&self, lifecycle: SlotLifecycle, slot_ends_at: Option<Instant>) {
+		*self.pending.lock() = Some((lifecycle, slot_ends_at));
+	}
+}
+
+/// The parameters to import `block` once more after its import failed.
+///
+/// The block is executed anew, as the first attempt consumed its storage changes. `None` if
+/// `block` carries intermediates, which cannot be copied.
+fn reimport_params<B: BlockT, T>(
+	block: &BlockImportParams<B, T>,
+) -> Option<BlockImportParams<B, T>> {
+	if !block.intermediates.is_empty() {
+		return None
+	}
+
+	let mut params = BlockImportParams::new(block.origin, block.header.clone());
+	params.justifications = block.justifications.clone();
+	params.post_digests = block.post_digests.clone();
+	params.body = block.body.clone();
+	params.indexed_body = block.indexed_body.clone();
+	params.state_action = match &block.state_action {
+		StateAction::Skip => StateAction::Skip,
+		StateAction::ExecuteIfPossible => StateAction::ExecuteIfPossible,
+		_ => StateAction::Execute,
+	};
+	params.finalized = block.finalized;
+	params.auxiliary = block.auxiliary.clone();
+	params.fork_choice = block.fork_choice;
+	params.import_existing = block.import_existing;
+	params.post_hash = block.post_hash;
+	Some(params)
+}
+
+#[async_trait::async_trait]
+impl<B, I> BlockImport<B> for SlotLifecycleImport<I>
+where
+	B: BlockT,
+	I: BlockImport<B> + Send,
+	I::Transaction: Send + 'static,
+{
+	type Error = I::Error;
+	type Transaction = I::Transaction;
+
+	async fn check_block(
+		&mut self,
+		block: BlockCheckParams<B>,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(block).await
+	}
+
+	async fn import_block(
+		&mut self,
+		block: BlockImportParams<B, Self::Transaction>,
+		cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let lifecycle = match block.origin {
+			BlockOrigin::Own => self.pending.get_mut().take(),
+			_ => None,
+		};
+		let (lifecycle, slot_ends_at) = match lifecycle {
+			Some(pending) => pending,
+			None => return self.inner.import_block(block, cache).await,
+		};
+
+		let hash = block.post_hash();
+		let retry = match slot_ends_at {
+			Some(ends_at) if self.retry_import =>
+				reimport_params(&block).map(|block| (block, cache.clone(), ends_at)),
+			_ => None,
+		};
+
+		let started = Instant::now();
+		let (res, retried) = match (self.inner.import_block(block, cache).await, retry) {
+			(Err(e), Some((block, cache, ends_at))) if Instant::now() < ends_at => {
+				debug!(
+					target: "aura",
+					"Importing authored block {:?} failed, retrying: {}",
+					hash,
+					e,
+				);
+				(self.inner.import_block(block, cache).await, true)
+			},
+			(res, _) => (res, false),
+		};
+		let import_duration = started.elapsed();
+
+		debug!(
+			target: "aura",
+			"Slot {} lifecycle of {:?}: {:?}, imported in {:?}",
+			lifecycle.slot,
+			hash,
+			lifecycle,
+			import_duration,
+		);
+		telemetry!(
+			self.telemetry;
+			CONSENSUS_INFO;
+			"aura.slot_lifecycle";
+			"slot" => *lifecycle.slot,
+			"authority_index" => lifecycle.authority_index,
+			"authorities_len" => lifecycle.authorities_len,
+			"proposing_budget_ms" => lifecycle.proposing_budget.as_millis() as u64,
+			"propose_ms" => lifecycle.propose_duration.as_millis() as u64,
+			"sign_ms" => lifecycle.sign_duration.as_millis() as u64,
+			"import_ms" => import_duration.as_millis() as u64,
+			"imported" => res.is_ok(),
+			"hash" => ?hash,
+		);
+		if let Err(e) = &res {
+			telemetry!(
+				self.telemetry;
+				CONSENSUS_INFO;
+				"aura.self_import_failed";
+				"slot" => *lifecycle.slot,
+				"hash" => ?hash,
+				"retried" => retried,
+				"err" => ?e,
+			);
+		}
+
+		res
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use substrate_test_runtime_client::runtime::{Block, Header};
+
+	/// Block import failing the first `failures` imports.
+	struct FlakyImport {
+		failures: usize,
+		imports: usize,
+	}
+
+	#[async_trait::async_trait]
+	impl BlockImport<Block> for FlakyImport {
+		type Error = sp_consensus::Error;
+		type Transaction = ();
+
+		async fn check_block(
+			&mut self,
+			_: BlockCheckParams<Block>,
+		) -> Result<ImportResult, Self::Error> {
+			Ok(ImportResult::imported(false))
+		}
+
+		async fn import_block(
+			&mut self,
+			_: BlockImportParams<Block, ()>,
+			_: HashMap<CacheKeyId, Vec<u8>>,
+		) -> Result<ImportResult, Self::Error> {
+			self.imports += 1;
+			if self.imports <= self.failures {
+				return Err(sp_consensus::Error::ClientImport("database is locked".into()))
+			}
+			Ok(ImportResult::imported(true))
+		}
+	}
+
+	/// Import an authored block into a block import failing `failures` times, returning whether
+	/// it got imported and the number of import attempts.
+	fn import(
+		failures: usize,
+		retry_import: bool,
+		slot_ends_in: Option<Duration>,
+	) -> (bool, usize) {
+		let mut import =
+			SlotLifecycleImport::new(FlakyImport { failures, imports: 0 }, None, retry_import);
+		import.note_sealed(
+			SlotLifecycle {
+				slot: 1.into(),
+				authority_index: Some(0),
+				authorities_len: 1,
+				proposing_budget: Duration::from_secs(1),
+				propose_duration: Duration::ZERO,
+				sign_duration: Duration::ZERO,
+			},
+			slot_ends_in.map(|ends_in| Instant::now() + ends_in),
+		);
+
+		let header = Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		let block = BlockImportParams::new(BlockOrigin::Own, header);
+		let imported = block_on(import.import_block(block, HashMap::new())).is_ok();
+		(imported, import.inner.imports)
+	}
+
+	#[test]
+	fn failed_import_of_authored_block_is_retried_once_within_its_slot() {
+		let slot = Some(Duration::from_secs(60));
+
+		assert_eq!(import(1, true, slot), (true, 2));
+		assert_eq!(import(2, true, slot), (false, 2));
+		assert_eq!(import(0, true, slot), (true, 1));
+		assert_eq!(import(1, false, slot), (false, 1));
+		assert_eq!(import(1, true, Some(Duration::ZERO)), (false, 1));
+		assert_eq!(import(1, true, None), (false, 1));
+	}
+}
@@ -0,0 +1,96 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! `tracing` spans around claiming a slot and proposing its block.
+//!
+//! The spans are only opened with the `tracing-spans` feature, without it they do nothing. The
+//! `log` records of the worker are emitted within the spans, so tracing subscribers that bridge
+//! `log` attach them to the span of their slot.
+
+use futures::Future;
+use sp_consensus_slots::Slot;
+
+/// A span of the worker, a no-op without the `tracing-spans` feature.
+#[derive(Clone)]
+pub(crate) struct Span {
+	#[cfg(feature = "tracing-spans")]
+	inner: tracing::Span,
+}
+
+#[cfg(feature = "tracing-spans")]
+impl Span {
+	/// The `aura.claim_slot` span of claiming `slot`.
+	///
+	/// The authority index is recorded within the span through [`Self::record_authority_index`]
+	/// once the slot is claimed.
+	pub(crate) fn claim_slot(slot: Slot) -> Self {
+		let inner = tracing::info_span!(
+			target: "aura",
+			"aura.claim_slot",
+			slot = *slot,
+			authority_index = tracing::field::Empty,
+		);
+		Self { inner }
+	}
+
+	/// The `aura.propose` span of proposing and sealing a block in the claimed `slot`.
+	pub(crate) fn propose(slot: Option<Slot>, authority_index: Option<usize>) -> Self {
+		let inner = tracing::info_span!(
+			target: "aura",
+			"aura.propose",
+			slot = tracing::field::Empty,
+			authority_index = tracing::field::Empty,
+		);
+		if let Some(slot) = slot {
+			inner.record("slot", &*slot);
+		}
+		if let Some(authority_index) = authority_index {
+			inner.record("authority_index", &(authority_index as u64));
+		}
+		Self { inner }
+	}
+
+	/// Record the index of the authority that claimed the slot on the current span.
+	pub(crate) fn record_authority_index(authority_index: Option<usize>) {
+		if let Some(authority_index) = authority_index {
+			tracing::Span::current().record("authority_index", &(authority_index as u64));
+		}
+	}
+
+	/// Run `future` within the span.
+	pub(crate) fn instrument<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+		tracing::Instrument::instrument(future, self.inner)
+	}
+}
+
+#[cfg(not(feature = "tracing-spans"))]
+impl Span {
+	pub(crate) fn claim_slot(_slot: Slot) -> Self {
+		Self {}
+	}
+
+	pub(crate) fn propose(_slot: Option<Slot>, _authority_index: Option<usize>) -> Self {
+		Self {}
+	}
+
+	pub(crate) fn record_authority_index(_authority_index: Option<usize>) {}
+
+	pub(crate) fn instrument<F: Future>(self, future: F) -> impl Future<Output = F::Output> {
+		future
+	}
+}
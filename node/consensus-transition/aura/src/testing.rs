@@ -0,0 +1,184 @@
+// This file is part of Substrate.
+
+// Copyright (C) 2018-2022 Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Entry points for deterministic consensus tests, behind the `testing` feature.
+
+use crate::{build_aura_worker, AuthorityId, AuthorityProvider, BuildAuraWorkerParams, SlotTracker};
+use codec::{Decode, Encode};
+use sc_client_api::{backend::AuxStore, BlockOf};
+use sc_consensus::BlockImport;
+use sc_consensus_slots::{BackoffAuthoringBlocksStrategy, SlotInfo, SlotResult, SlotWorker};
+use sp_api::ProvideRuntimeApi;
+use sp_application_crypto::AppPublic;
+use sp_blockchain::{HeaderBackend, HeaderMetadata};
+use sp_consensus::{Environment, Proposer, SyncOracle};
+use sp_consensus_aura::AuraApi;
+use sp_core::crypto::Pair;
+use sp_runtime::traits::{Block as BlockT, Member, NumberFor};
+use std::hash::Hash;
+
+/// Build the aura worker like [`build_aura_worker`], but always author on top of `head`.
+///
+/// The chain head the worker is handed with each slot, i.e. the best block of the `SelectChain`
+/// it runs with, is replaced by `head`. This keeps tests from depending on the live chain
+/// selection.
+pub fn build_aura_worker_on_fixed_head<P, B, C, PF, I, SO, L, BS, Error, AP>(
+	params: BuildAuraWorkerParams<C, I, PF, SO, L, BS, NumberFor<B>, AuthorityId<P>, B, AP>,
+	head: B::Header,
+) -> (impl SlotWorker<B, <PF::Proposer as Proposer<B>>::Proof>, SlotTracker)
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ BlockOf
+		+ AuxStore
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B>
+		+ Send
+		+ Sync,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+	PF: Environment<B, Error = Error> + Send + Sync + 'static,
+	PF::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	<PF::Proposer as Proposer<B>>::Proof: Encode,
+	P: Pair + Send + Sync,
+	P::Public: AppPublic + Hash + Member + Encode + Decode,
+	P::Signature: TryFrom<Vec<u8>> + Hash + Member + Encode + Decode,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
+	SO: SyncOracle + Send + Sync + Clone,
+	L: sc_consensus::JustificationSyncLink<B>,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api> + Send + Sync + 'static,
+{
+	let (worker, slot_tracker) = build_aura_worker::<P, B, C, PF, I, SO, L, BS, Error, AP>(params);
+	(FixedHeadWorker { worker, head }, slot_tracker)
+}
+
+/// Build the aura worker like [`build_aura_worker`], but seal every block with the fixed
+/// `signature` instead of signing it with the keystore.
+///
+/// This makes authored blocks reproducible byte for byte across runs, e.g. to snapshot the
+/// encoded block authored in a slot or to fuzz the consensus. **Hazard:** the seals are not
+/// made by the slot author, so every verifier rejects the blocks, and the signature is not
+/// checked against the author when sealing either. `signature` has to decode as a signature of
+/// `P`, otherwise sealing fails. Only available in debug builds with the `testing` feature, so
+/// it cannot end up in a release build.
+#[cfg(debug_assertions)]
+pub fn build_aura_worker_with_deterministic_seal<P, B, C, PF, I, SO, L, BS, Error, AP>(
+	params: BuildAuraWorkerParams<C, I, PF, SO, L, BS, NumberFor<B>, AuthorityId<P>, B, AP>,
+	signature: Vec<u8>,
+) -> (impl SlotWorker<B, <PF::Proposer as Proposer<B>>::Proof>, SlotTracker)
+where
+	B: BlockT,
+	C: ProvideRuntimeApi<B>
+		+ BlockOf
+		+ AuxStore
+		+ HeaderBackend<B>
+		+ HeaderMetadata<B>
+		+ Send
+		+ Sync,
+	C::Api: AuraApi<B, AuthorityId<P>>,
+	PF: Environment<B, Error = Error> + Send + Sync + 'static,
+	PF::Proposer: Proposer<B, Error = Error, Transaction = sp_api::TransactionFor<C, B>>,
+	<PF::Proposer as Proposer<B>>::Proof: Encode,
+	P: Pair + Send + Sync,
+	P::Public: AppPublic + Hash + Member + Encode + Decode,
+	P::Signature: TryFrom<Vec<u8>> + Hash + Member + Encode + Decode,
+	I: BlockImport<B, Transaction = sp_api::TransactionFor<C, B>> + Send + Sync + 'static,
+	Error: std::error::Error + Send + From<sp_consensus::Error> + 'static,
+	SO: SyncOracle + Send + Sync + Clone,
+	L: sc_consensus::JustificationSyncLink<B>,
+	BS: BackoffAuthoringBlocksStrategy<NumberFor<B>> + Send + Sync + 'static,
+	AP: AuthorityProvider<B, AuthorityId<P>, C::Api> + Send + Sync + 'static,
+{
+	let (mut worker, slot_tracker) =
+		crate::new_aura_worker::<P, B, C, PF, I, SO, L, BS, Error, AP>(params);
+	worker.deterministic_signature = Some(signature);
+	(worker.into_slot_worker(), slot_tracker)
+}
+
+/// A slot worker authoring on top of `head` in every slot.
+struct FixedHeadWorker<W, H> {
+	worker: W,
+	head: H,
+}
+
+#[async_trait::async_trait]
+impl<B, Proof, W> SlotWorker<B, Proof> for FixedHeadWorker<W, B::Header>
+where
+	B: BlockT,
+	Proof: Send + 'static,
+	W: SlotWorker<B, Proof> + Send,
+{
+	async fn on_slot(&mut self, mut slot_info: SlotInfo<B>) -> Option<SlotResult<B, Proof>> {
+		slot_info.chain_head = self.head.clone();
+		self.worker.on_slot(slot_info).await
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use sp_runtime::traits::Header as _;
+	use std::time::{Duration, Instant};
+	use substrate_test_runtime_client::runtime::{Block, Header};
+
+	/// Slot worker recording the chain heads it is handed.
+	#[derive(Default)]
+	struct RecordingWorker {
+		heads: Vec<u64>,
+	}
+
+	#[async_trait::async_trait]
+	impl SlotWorker<Block, ()> for RecordingWorker {
+		async fn on_slot(&mut self, slot_info: SlotInfo<Block>) -> Option<SlotResult<Block, ()>> {
+			self.heads.push(*slot_info.chain_head.number());
+			None
+		}
+	}
+
+	fn header(number: u64) -> Header {
+		Header::new(
+			number,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		)
+	}
+
+	#[test]
+	fn fixed_head_replaces_the_selected_chain_head() {
+		let mut worker = FixedHeadWorker { worker: RecordingWorker::default(), head: header(7) };
+
+		for (slot, best) in [(1u64, 1), (2, 2), (3, 10)] {
+			let slot_info = SlotInfo::<Block> {
+				slot: slot.into(),
+				timestamp: Default::default(),
+				ends_at: Instant::now() + Duration::from_secs(3600),
+				inherent_data: Default::default(),
+				duration: Duration::from_millis(1000),
+				chain_head: header(best),
+				block_size_limit: None,
+			};
+			assert!(block_on(worker.on_slot(slot_info)).is_none());
+		}
+
+		assert_eq!(worker.worker.heads, vec![7, 7, 7]);
+	}
+}